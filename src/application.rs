@@ -23,13 +23,14 @@ use crate::{
     cache::Cache,
     client::{BackgroundTask, MpdWrapper},
     config::{APPLICATION_USER_AGENT, VERSION},
-    library::Library,
-    player::Player,
+    library::{Library, QueryBuilderDialog},
+    player::{AlarmsDialog, PartitionsDialog, Player, WorkspacesDialog},
     preferences::Preferences,
     utils::{settings_manager, tokio_runtime},
     EuphonicaWindow
 };
 use adw::subclass::prelude::*;
+use gettextrs::gettext;
 use gtk::{gio, glib};
 use std::{
     cell::{Cell, OnceCell, RefCell}, fs::create_dir_all, ops::ControlFlow, path::PathBuf, rc::Rc
@@ -165,6 +166,8 @@ mod imp {
                 obj.set_accels_for_action("app.quit", &["<primary>q"]);
                 obj.set_accels_for_action("app.fullscreen", &["F11"]);
                 obj.set_accels_for_action("app.refresh", &["F5"]);
+                obj.set_accels_for_action("app.undo", &["<primary>z"]);
+                obj.set_accels_for_action("app.redo", &["<primary><shift>z", "<primary>y"]);
 
                 self.library.get().unwrap().setup(
                     self.client.get().unwrap().clone(),
@@ -263,14 +266,45 @@ impl EuphonicaApplication {
         let preferences_action = gio::ActionEntry::builder("preferences")
             .activate(move |app: &Self, _, _| app.show_preferences())
             .build();
+        let partitions_action = gio::ActionEntry::builder("partitions")
+            .activate(move |app: &Self, _, _| app.show_partitions())
+            .build();
+        let query_builder_action = gio::ActionEntry::builder("query-builder")
+            .activate(move |app: &Self, _, _| app.show_query_builder())
+            .build();
+        let alarms_action = gio::ActionEntry::builder("alarms")
+            .activate(move |app: &Self, _, _| app.show_alarms())
+            .build();
+        let queue_workspaces_action = gio::ActionEntry::builder("queue-workspaces")
+            .activate(move |app: &Self, _, _| app.show_queue_workspaces())
+            .build();
+        let undo_action = gio::ActionEntry::builder("undo")
+            .activate(move |app: &Self, _, _| app.undo_edit(false))
+            .build();
+        let redo_action = gio::ActionEntry::builder("redo")
+            .activate(move |app: &Self, _, _| app.undo_edit(true))
+            .build();
         self.add_action_entries([
             toggle_fullscreen_action,
             refresh_action,
             update_db_action,
             quit_action,
             about_action,
-            preferences_action
+            preferences_action,
+            partitions_action,
+            query_builder_action,
+            alarms_action,
+            queue_workspaces_action,
+            undo_action,
+            redo_action
         ]);
+        // Stateful action for switching between saved connection profiles.
+        // MpdWrapper listens to the underlying key and reconnects.
+        self.add_action(
+            &settings_manager()
+                .child("client")
+                .create_action("mpd-active-profile")
+        );
     }
 
     fn toggle_fullscreen(&self) {
@@ -361,6 +395,80 @@ impl EuphonicaApplication {
         prefs.update();
     }
 
+    pub fn show_partitions(&self) {
+        let window = self.active_window().unwrap();
+        let dialog = PartitionsDialog::new(self.get_client());
+        dialog.present(Some(&window));
+    }
+
+    pub fn show_query_builder(&self) {
+        let window = self.active_window().unwrap();
+        let dialog = QueryBuilderDialog::new(
+            self.get_library(),
+            self.get_client().get_client_state(),
+            self.get_cache()
+        );
+        dialog.present(Some(&window));
+    }
+
+    pub fn show_alarms(&self) {
+        let window = self.active_window().unwrap();
+        let dialog = AlarmsDialog::new(self.get_client());
+        dialog.present(Some(&window));
+    }
+
+    pub fn show_queue_workspaces(&self) {
+        let window = self.active_window().unwrap();
+        let dialog = WorkspacesDialog::new(&self.get_player());
+        dialog.present(Some(&window));
+    }
+
+    /// Undo (or redo) the last queue or playlist edit.
+    fn undo_edit(&self, redo: bool) {
+        let window = self.active_window().and_downcast::<EuphonicaWindow>();
+        // Our accels take precedence over text fields' own, so hand the shortcut back
+        // to whichever one is focused.
+        if let Some(text) = window
+            .as_ref()
+            .and_then(|window| window.focus())
+            .and_downcast::<gtk::Text>()
+        {
+            let _ = text.activate_action(if redo { "text.redo" } else { "text.undo" }, None);
+            return;
+        }
+        let client = self.get_client();
+        let state = client.get_client_state();
+        if client.is_restoring_queue() {
+            if let Some(window) = window {
+                window.send_simple_toast(&gettext("Please wait until the queue has been restored"), 2);
+            }
+            return;
+        }
+        let had_step = if redo { state.can_redo() } else { state.can_undo() };
+        let res = if redo { client.redo() } else { client.undo() };
+        let Some(window) = window else {
+            return;
+        };
+        match res {
+            Some(label) => {
+                let title = if redo {
+                    gettext("Redone: {label}")
+                } else {
+                    gettext("Undone: {label}")
+                };
+                window.send_undo_toast(&title.replace("{label}", &label), !redo);
+            }
+            // The history is dropped in this case, as older steps may depend on this one.
+            None if had_step => {
+                window.send_simple_toast(&gettext("Unable to undo: the queue or playlist has changed"), 3);
+            }
+            None => {
+                let msg = if redo { gettext("Nothing to redo") } else { gettext("Nothing to undo") };
+                window.send_simple_toast(&msg, 2);
+            }
+        }
+    }
+
     /// Quit Euphonica. Useful for when run-in-background is true. Otherwise just close the window.
     pub fn quit_app(&self) {
         self.imp().hold_guard.take();
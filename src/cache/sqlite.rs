@@ -1,17 +1,18 @@
 extern crate bson;
 extern crate rusqlite;
 
-use std::io::Cursor;
+use std::{collections::HashMap, io::Cursor};
 
 use once_cell::sync::Lazy;
 use r2d2_sqlite::SqliteConnectionManager;
 use rusqlite::{params, Error as SqliteError, Result, Row};
-use time::OffsetDateTime;
+use time::{Date, OffsetDateTime};
 use glib::{ThreadPool, ThreadHandle};
 
 use crate::{
-    common::{AlbumInfo, ArtistInfo, SongInfo},
+    common::{Alarm, AlbumInfo, ArtistInfo, QueueSnapshot, SavedAlarm, SavedQueue, SmartPlaylist, SongInfo},
     meta_providers::models::{AlbumMeta, ArtistMeta, Lyrics, LyricsParseError},
+    scrobbler::Scrobble,
     utils::strip_filename_linux,
 };
 
@@ -37,7 +38,57 @@ static SQLITE_POOL: Lazy<r2d2::Pool<SqliteConnectionManager>> = Lazy::new(|| {
 
         println!("Local metadata DB version: {user_version}");
         match user_version {
-            3 => {break;},
+            8 => {break;},
+            7 => {
+                conn.execute_batch("create table if not exists `alarms` (
+    `id` INTEGER not null,
+    `data` VARCHAR not null,
+    `checked` DATETIME not null,
+    primary key(`id`)
+);
+pragma user_version = 8;
+").expect("Unable to migrate DB version 7 to 8");
+            },
+            6 => {
+                conn.execute_batch("create table if not exists `queue_snapshots` (
+    `id` INTEGER not null,
+    `name` VARCHAR null unique,
+    `data` VARCHAR not null,
+    `timestamp` DATETIME not null,
+    primary key(`id`)
+);
+create index if not exists `queue_snapshots_last` on `queue_snapshots` (`name`, `timestamp` desc);
+pragma user_version = 7;
+").expect("Unable to migrate DB version 6 to 7");
+            },
+            5 => {
+                // Listens recorded before this have no duration. These are filled in from
+                // the MPD library the first time listening statistics are computed.
+                conn.execute_batch("alter table songs_history add column duration INTEGER null;
+pragma user_version = 6;
+").expect("Unable to migrate DB version 5 to 6");
+            },
+            4 => {
+                conn.execute_batch("create table if not exists `scrobble_queue` (
+    `id` INTEGER not null,
+    `service` VARCHAR not null,
+    `data` VARCHAR not null,
+    primary key(`id`)
+);
+create index if not exists `scrobble_queue_service` on `scrobble_queue` (`service`, `id`);
+pragma user_version = 5;
+").expect("Unable to migrate DB version 4 to 5");
+            },
+            3 => {
+                conn.execute_batch("create table if not exists `smart_playlists` (
+    `name` VARCHAR not null unique,
+    `data` VARCHAR not null,
+    `last_modified` DATETIME not null,
+    primary key(`name`)
+);
+pragma user_version = 4;
+").expect("Unable to migrate DB version 3 to 4");
+            },
             2 => {
                 conn.execute_batch("alter table albums_history add column mbid varchar null;
 alter table albums_history add column artist varchar null;
@@ -173,6 +224,7 @@ create table if not exists `songs_history` (
     `id` INTEGER not null,
     `uri` VARCHAR not null,
     `timestamp` DATETIME not null,
+    `duration` INTEGER null,
     primary key(`id`)
 );
 create index if not exists `song_history_last` on `songs_history` (`uri`, `timestamp` desc);
@@ -207,8 +259,39 @@ create unique index if not exists `image_key` on `images` (
     `is_thumbnail`
 );
 
+create table if not exists `smart_playlists` (
+    `name` VARCHAR not null unique,
+    `data` VARCHAR not null,
+    `last_modified` DATETIME not null,
+    primary key(`name`)
+);
+
+create table if not exists `scrobble_queue` (
+    `id` INTEGER not null,
+    `service` VARCHAR not null,
+    `data` VARCHAR not null,
+    primary key(`id`)
+);
+create index if not exists `scrobble_queue_service` on `scrobble_queue` (`service`, `id`);
+
+create table if not exists `queue_snapshots` (
+    `id` INTEGER not null,
+    `name` VARCHAR null unique,
+    `data` VARCHAR not null,
+    `timestamp` DATETIME not null,
+    primary key(`id`)
+);
+create index if not exists `queue_snapshots_last` on `queue_snapshots` (`name`, `timestamp` desc);
+
+create table if not exists `alarms` (
+    `id` INTEGER not null,
+    `data` VARCHAR not null,
+    `checked` DATETIME not null,
+    primary key(`id`)
+);
+
 pragma journal_mode=WAL;
-pragma user_version = 3;
+pragma user_version = 8;
 end;
 ").expect("Unable to init metadata SQLite DB");
                     }
@@ -638,33 +721,90 @@ pub fn unregister_avatar_key(key: &str, is_thumbnail: bool) -> ThreadHandle<Resu
     unregister_image_key(key.to_owned(), Some("avatar"), is_thumbnail)
 }
 
-pub fn add_to_history(song: &SongInfo) -> Result<(), Error> {
-    let mut conn = SQLITE_POOL.get().unwrap();
-    let tx = conn.transaction().map_err(|e| Error::DbError(e))?;
-    let ts = OffsetDateTime::now_utc();
+fn insert_history(tx: &rusqlite::Transaction, song: &SongInfo, ts: &OffsetDateTime) -> Result<(), Error> {
     tx.execute(
-        "insert into songs_history (uri, timestamp) values (?1, ?2)",
-        params![&song.uri, &ts],
+        "insert into songs_history (uri, timestamp, duration) values (?1, ?2, ?3)",
+        params![&song.uri, ts, song.duration.map(|dur| dur.as_secs())],
     )
     .map_err(|e| Error::DbError(e))?;
     if let Some(album) = song.album.as_ref() {
         tx.execute(
             "insert into albums_history (title, mbid, artist, timestamp) values (?1, ?2, ?3, ?4)",
-            params![&album.title, album.mbid.as_ref(), album.albumartist.as_ref(), &ts],
+            params![&album.title, album.mbid.as_ref(), album.albumartist.as_ref(), ts],
         )
         .map_err(|e| Error::DbError(e))?;
     }
     for artist in song.artists.iter() {
         tx.execute(
             "insert into artists_history(name, timestamp) values (?1, ?2)",
-            params![&artist.name, &ts],
+            params![&artist.name, ts],
         )
         .map_err(|e| Error::DbError(e))?;
     }
+    Ok(())
+}
+
+pub fn add_to_history(song: &SongInfo) -> Result<(), Error> {
+    let mut conn = SQLITE_POOL.get().unwrap();
+    let tx = conn.transaction().map_err(|e| Error::DbError(e))?;
+    insert_history(&tx, song, &OffsetDateTime::now_utc())?;
     tx.commit().map_err(|e| Error::DbError(e))?;
     Ok(())
 }
 
+/// Add past listens to the history, such as those imported from another service.
+/// Listens of a song that overlap one already in the history (within the song's
+/// duration, or a minute if unknown) are assumed to be the same and skipped.
+/// Returns the number of listens added and skipped.
+pub fn import_history(listens: &[(SongInfo, OffsetDateTime)]) -> Result<(usize, usize), Error> {
+    let mut conn = SQLITE_POOL.get().unwrap();
+    let tx = conn.transaction().map_err(|e| Error::DbError(e))?;
+    let mut added: usize = 0;
+    let mut skipped: usize = 0;
+    for (song, ts) in listens.iter() {
+        let window = song.duration.map_or(0, |dur| dur.as_secs()).max(60);
+        let exists = tx
+            .query_row(
+                "
+select exists(
+    select 1 from songs_history
+    where uri = ?1 and abs(cast(strftime('%s', timestamp) as integer) - ?2) < ?3
+)",
+                params![&song.uri, ts.unix_timestamp(), window],
+                |r| r.get::<usize, bool>(0),
+            )
+            .map_err(|e| Error::DbError(e))?;
+        if exists {
+            skipped += 1;
+        } else {
+            insert_history(&tx, song, ts)?;
+            added += 1;
+        }
+    }
+    tx.commit().map_err(|e| Error::DbError(e))?;
+    Ok((added, skipped))
+}
+
+/// Get the whole song history in chronological order, along with listen times & song
+/// durations (in seconds) where known.
+pub fn get_history() -> Result<Vec<(String, OffsetDateTime, Option<u64>)>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare("select uri, timestamp, duration from songs_history order by timestamp asc")
+        .unwrap();
+    let res = query
+        .query_map([], |r| {
+            Ok((
+                r.get::<usize, String>(0)?,
+                r.get::<usize, OffsetDateTime>(1)?,
+                r.get::<usize, Option<u64>>(2)?,
+            ))
+        })
+        .map_err(|e| Error::DbError(e))?
+        .map(|r| r.unwrap());
+    return Ok(res.collect());
+}
+
 /// Get URIs of up to N last listened to songs.
 pub fn get_last_n_songs(n: u32) -> Result<Vec<(String, OffsetDateTime)>, Error> {
     let conn = SQLITE_POOL.get().unwrap();
@@ -735,3 +875,443 @@ pub fn clear_history() -> Result<(), Error> {
     tx.commit().map_err(|e| Error::DbError(e))?;
     Ok(())
 }
+
+/// Get the number of times each song has been played and when it was last played.
+pub fn get_play_stats() -> Result<HashMap<String, (u32, OffsetDateTime)>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare(
+            "
+select uri, count(*), max(timestamp) as last_played
+from songs_history
+group by uri",
+        )
+        .unwrap();
+    let res = query
+        .query_map([], |r| Ok((
+            r.get::<usize, String>(0)?,
+            (r.get::<usize, u32>(1)?, r.get::<usize, OffsetDateTime>(2)?)
+        )))
+        .map_err(|e| Error::DbError(e))?
+        .map(|r| r.unwrap());
+
+    return Ok(res.collect());
+}
+
+// Listening statistics. All functions below take an optional cut-off time, before
+// which listens are ignored. Listening time is approximated by the duration of the
+// songs listened to. Days and hours are in local time.
+
+/// Get (URI, play count, date first listened to)s of up to N most played songs.
+/// The first listen date is across the whole history, regardless of the cut-off.
+pub fn get_top_songs(since: Option<OffsetDateTime>, n: u32) -> Result<Vec<(String, u32, Date)>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare(
+            "
+select uri, count(*) as plays,
+    (select date(min(timestamp), 'localtime') from songs_history h where h.uri = songs_history.uri)
+from songs_history
+where ?1 is null or timestamp >= ?1
+group by uri order by plays desc, max(timestamp) desc limit ?2",
+        )
+        .unwrap();
+    let res = query
+        .query_map(params![since, n], |r| Ok((
+            r.get::<usize, String>(0)?,
+            r.get::<usize, u32>(1)?,
+            r.get::<usize, Date>(2)?
+        )))
+        .map_err(|e| Error::DbError(e))?
+        .map(|r| r.unwrap());
+
+    return Ok(res.collect());
+}
+
+/// Get (title, artist, mbid, play count, date first listened to)s of up to N most played albums.
+pub fn get_top_albums(
+    since: Option<OffsetDateTime>,
+    n: u32,
+) -> Result<Vec<(String, Option<String>, Option<String>, u32, Date)>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare(
+            "
+select title, artist, mbid, count(*) as plays,
+    (select date(min(timestamp), 'localtime') from albums_history h where h.title = albums_history.title)
+from albums_history
+where ?1 is null or timestamp >= ?1
+group by title order by plays desc, max(timestamp) desc limit ?2",
+        )
+        .unwrap();
+    let res = query
+        .query_map(params![since, n], |r| Ok((
+            r.get::<usize, String>(0)?,
+            r.get::<usize, Option<String>>(1)?,
+            r.get::<usize, Option<String>>(2)?,
+            r.get::<usize, u32>(3)?,
+            r.get::<usize, Date>(4)?
+        )))
+        .map_err(|e| Error::DbError(e))?
+        .map(|r| r.unwrap());
+
+    return Ok(res.collect());
+}
+
+/// Get (name, play count, date first listened to)s of up to N most played artists.
+pub fn get_top_artists(since: Option<OffsetDateTime>, n: u32) -> Result<Vec<(String, u32, Date)>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare(
+            "
+select name, count(*) as plays,
+    (select date(min(timestamp), 'localtime') from artists_history h where h.name = artists_history.name)
+from artists_history
+where ?1 is null or timestamp >= ?1
+group by name order by plays desc, max(timestamp) desc limit ?2",
+        )
+        .unwrap();
+    let res = query
+        .query_map(params![since, n], |r| Ok((
+            r.get::<usize, String>(0)?,
+            r.get::<usize, u32>(1)?,
+            r.get::<usize, Date>(2)?
+        )))
+        .map_err(|e| Error::DbError(e))?
+        .map(|r| r.unwrap());
+
+    return Ok(res.collect());
+}
+
+/// Get the total number of listens and listening time in seconds.
+pub fn get_listening_totals(since: Option<OffsetDateTime>) -> Result<(u32, u64), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.query_row(
+        "select count(*), coalesce(sum(duration), 0) from songs_history where ?1 is null or timestamp >= ?1",
+        params![since],
+        |r| Ok((r.get::<usize, u32>(0)?, r.get::<usize, u64>(1)?)),
+    )
+    .map_err(|e| Error::DbError(e))
+}
+
+/// Get listening time in seconds for each day with at least one listen.
+pub fn get_listening_time_by_day(since: Option<OffsetDateTime>) -> Result<Vec<(Date, u64)>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare(
+            "
+select date(timestamp, 'localtime') as day, coalesce(sum(duration), 0)
+from songs_history
+where ?1 is null or timestamp >= ?1
+group by day order by day",
+        )
+        .unwrap();
+    let res = query
+        .query_map(params![since], |r| Ok((r.get::<usize, Date>(0)?, r.get::<usize, u64>(1)?)))
+        .map_err(|e| Error::DbError(e))?
+        .map(|r| r.unwrap());
+
+    return Ok(res.collect());
+}
+
+/// Get listening time in seconds for each (weekday, hour) pair with at least one listen.
+/// Weekdays are numbered from Sunday (0) like in SQLite's strftime.
+pub fn get_listening_time_by_hour(since: Option<OffsetDateTime>) -> Result<Vec<(u8, u8, u64)>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare(
+            "
+select cast(strftime('%w', timestamp, 'localtime') as integer) as weekday,
+    cast(strftime('%H', timestamp, 'localtime') as integer) as hour,
+    coalesce(sum(duration), 0)
+from songs_history
+where ?1 is null or timestamp >= ?1
+group by weekday, hour",
+        )
+        .unwrap();
+    let res = query
+        .query_map(params![since], |r| Ok((
+            r.get::<usize, u8>(0)?,
+            r.get::<usize, u8>(1)?,
+            r.get::<usize, u64>(2)?
+        )))
+        .map_err(|e| Error::DbError(e))?
+        .map(|r| r.unwrap());
+
+    return Ok(res.collect());
+}
+
+/// Get all days on which something was listened to, in ascending order.
+pub fn get_listening_days() -> Result<Vec<Date>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare("select distinct date(timestamp, 'localtime') as day from songs_history order by day")
+        .unwrap();
+    let res = query
+        .query_map([], |r| Ok(r.get::<usize, Date>(0)?))
+        .map_err(|e| Error::DbError(e))?
+        .map(|r| r.unwrap());
+
+    return Ok(res.collect());
+}
+
+/// Get URIs of listened-to songs whose duration has not been recorded.
+pub fn get_history_uris_without_duration() -> Result<Vec<String>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare("select distinct uri from songs_history where duration is null")
+        .unwrap();
+    let res = query
+        .query_map([], |r| Ok(r.get::<usize, String>(0)?))
+        .map_err(|e| Error::DbError(e))?
+        .map(|r| r.unwrap());
+
+    return Ok(res.collect());
+}
+
+/// Fill in missing durations of past listens. Songs no longer in the library should be
+/// given a duration of zero so they won't be looked up again.
+pub fn set_history_durations(durations: &[(String, u64)]) -> Result<(), Error> {
+    let mut conn = SQLITE_POOL.get().unwrap();
+    let tx = conn.transaction().map_err(|e| Error::DbError(e))?;
+    for (uri, duration) in durations.iter() {
+        tx.execute(
+            "update songs_history set duration = ?2 where uri = ?1 and duration is null",
+            params![uri, duration],
+        )
+        .map_err(|e| Error::DbError(e))?;
+    }
+    tx.commit().map_err(|e| Error::DbError(e))?;
+    Ok(())
+}
+
+pub fn get_smart_playlists() -> Result<Vec<SmartPlaylist>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare("select data from smart_playlists order by name")
+        .unwrap();
+    let res = query
+        .query_map([], |r| r.get::<usize, String>(0))
+        .map_err(|e| Error::DbError(e))?
+        .map(|r| {
+            let data = r.map_err(|e| Error::DbError(e))?;
+            serde_json::from_str::<SmartPlaylist>(&data).map_err(|_| Error::DocToMetaError)
+        });
+
+    return res.collect();
+}
+
+/// Save a smart playlist, replacing the one with the same name. When renaming,
+/// also pass the old name so the old entry can be removed.
+pub fn write_smart_playlist(playlist: &SmartPlaylist, old_name: Option<&str>) -> Result<(), Error> {
+    let mut conn = SQLITE_POOL.get().unwrap();
+    let tx = conn.transaction().map_err(|e| Error::DbError(e))?;
+    if let Some(old_name) = old_name {
+        tx.execute("delete from smart_playlists where name = ?1", params![old_name])
+            .map_err(|e| Error::DbError(e))?;
+    }
+    tx.execute("delete from smart_playlists where name = ?1", params![&playlist.name])
+        .map_err(|e| Error::DbError(e))?;
+    tx.execute(
+        "insert into smart_playlists (name, data, last_modified) values (?1,?2,?3)",
+        params![
+            &playlist.name,
+            serde_json::to_string(playlist).map_err(|_| Error::MetaToDocError)?,
+            OffsetDateTime::now_utc()
+        ],
+    )
+    .map_err(|e| Error::DbError(e))?;
+    tx.commit().map_err(|e| Error::DbError(e))?;
+    Ok(())
+}
+
+pub fn delete_smart_playlist(name: &str) -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.execute("delete from smart_playlists where name = ?1", params![name])
+        .map_err(|e| Error::DbError(e))?;
+    Ok(())
+}
+
+/// Add a listen to the submission queue of a scrobbling service.
+pub fn queue_scrobble(service: &str, scrobble: &Scrobble) -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.execute(
+        "insert into scrobble_queue (service, data) values (?1, ?2)",
+        params![
+            service,
+            serde_json::to_string(scrobble).map_err(|_| Error::MetaToDocError)?
+        ],
+    )
+    .map_err(|e| Error::DbError(e))?;
+    Ok(())
+}
+
+/// Get up to N oldest queued listens of a scrobbling service, along with their queue IDs.
+pub fn get_queued_scrobbles(service: &str, n: u32) -> Result<Vec<(i64, Scrobble)>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare("select id, data from scrobble_queue where service = ?1 order by id limit ?2")
+        .unwrap();
+    let res = query
+        .query_map(params![service, n], |r| Ok((r.get::<usize, i64>(0)?, r.get::<usize, String>(1)?)))
+        .map_err(|e| Error::DbError(e))?
+        .map(|r| {
+            let (id, data) = r.map_err(|e| Error::DbError(e))?;
+            let scrobble = serde_json::from_str::<Scrobble>(&data).map_err(|_| Error::DocToMetaError)?;
+            Ok((id, scrobble))
+        });
+
+    return res.collect();
+}
+
+pub fn remove_queued_scrobbles(ids: &[i64]) -> Result<(), Error> {
+    let mut conn = SQLITE_POOL.get().unwrap();
+    let tx = conn.transaction().map_err(|e| Error::DbError(e))?;
+    for id in ids.iter() {
+        tx.execute("delete from scrobble_queue where id = ?1", params![id])
+            .map_err(|e| Error::DbError(e))?;
+    }
+    tx.commit().map_err(|e| Error::DbError(e))?;
+    Ok(())
+}
+
+/// Save an automatic queue snapshot, then drop all but the latest `keep` of them.
+pub fn add_queue_snapshot(snapshot: &QueueSnapshot, keep: u32) -> Result<(), Error> {
+    let mut conn = SQLITE_POOL.get().unwrap();
+    let tx = conn.transaction().map_err(|e| Error::DbError(e))?;
+    tx.execute(
+        "insert into queue_snapshots (name, data, timestamp) values (null, ?1, ?2)",
+        params![
+            serde_json::to_string(snapshot).map_err(|_| Error::MetaToDocError)?,
+            OffsetDateTime::now_utc()
+        ],
+    )
+    .map_err(|e| Error::DbError(e))?;
+    tx.execute(
+        "delete from queue_snapshots where name is null and id not in (
+select id from queue_snapshots where name is null order by timestamp desc, id desc limit ?1
+)",
+        params![keep],
+    )
+    .map_err(|e| Error::DbError(e))?;
+    tx.commit().map_err(|e| Error::DbError(e))?;
+    Ok(())
+}
+
+/// Save the queue as a named workspace, replacing the one with the same name.
+pub fn write_queue_workspace(name: &str, snapshot: &QueueSnapshot) -> Result<(), Error> {
+    let mut conn = SQLITE_POOL.get().unwrap();
+    let tx = conn.transaction().map_err(|e| Error::DbError(e))?;
+    tx.execute("delete from queue_snapshots where name = ?1", params![name])
+        .map_err(|e| Error::DbError(e))?;
+    tx.execute(
+        "insert into queue_snapshots (name, data, timestamp) values (?1, ?2, ?3)",
+        params![
+            name,
+            serde_json::to_string(snapshot).map_err(|_| Error::MetaToDocError)?,
+            OffsetDateTime::now_utc()
+        ],
+    )
+    .map_err(|e| Error::DbError(e))?;
+    tx.commit().map_err(|e| Error::DbError(e))?;
+    Ok(())
+}
+
+pub fn delete_queue_workspace(name: &str) -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.execute("delete from queue_snapshots where name = ?1", params![name])
+        .map_err(|e| Error::DbError(e))?;
+    Ok(())
+}
+
+/// Get all saved queues, workspaces first (by name) then automatic snapshots (latest first).
+pub fn get_saved_queues() -> Result<Vec<SavedQueue>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare(
+            "select id, name, data, timestamp from queue_snapshots
+order by name is null, name, timestamp desc, id desc",
+        )
+        .unwrap();
+    let res = query
+        .query_map([], |r| {
+            Ok((
+                r.get::<usize, i64>(0)?,
+                r.get::<usize, Option<String>>(1)?,
+                r.get::<usize, String>(2)?,
+                r.get::<usize, OffsetDateTime>(3)?,
+            ))
+        })
+        .map_err(|e| Error::DbError(e))?
+        .map(|r| {
+            let (id, name, data, saved) = r.map_err(|e| Error::DbError(e))?;
+            let snapshot = serde_json::from_str::<QueueSnapshot>(&data).map_err(|_| Error::DocToMetaError)?;
+            Ok(SavedQueue {
+                id,
+                name,
+                saved,
+                snapshot,
+            })
+        });
+
+    return res.collect();
+}
+
+pub fn get_alarms() -> Result<Vec<SavedAlarm>, Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    let mut query = conn
+        .prepare("select id, data, checked from alarms order by id")
+        .unwrap();
+    let res = query
+        .query_map([], |r| {
+            Ok((
+                r.get::<usize, i64>(0)?,
+                r.get::<usize, String>(1)?,
+                r.get::<usize, OffsetDateTime>(2)?,
+            ))
+        })
+        .map_err(|e| Error::DbError(e))?
+        .map(|r| {
+            let (id, data, checked) = r.map_err(|e| Error::DbError(e))?;
+            let alarm = serde_json::from_str::<Alarm>(&data).map_err(|_| Error::DocToMetaError)?;
+            Ok(SavedAlarm { id, checked, alarm })
+        });
+
+    return res.collect();
+}
+
+/// Add an alarm. Only times it is due at from now on count.
+pub fn add_alarm(alarm: &Alarm) -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.execute(
+        "insert into alarms (data, checked) values (?1, ?2)",
+        params![
+            serde_json::to_string(alarm).map_err(|_| Error::MetaToDocError)?,
+            OffsetDateTime::now_utc()
+        ],
+    )
+    .map_err(|e| Error::DbError(e))?;
+    Ok(())
+}
+
+/// Save changes to an alarm, along with up to when it has been checked.
+pub fn update_alarm(saved: &SavedAlarm) -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.execute(
+        "update alarms set data = ?2, checked = ?3 where id = ?1",
+        params![
+            saved.id,
+            serde_json::to_string(&saved.alarm).map_err(|_| Error::MetaToDocError)?,
+            saved.checked
+        ],
+    )
+    .map_err(|e| Error::DbError(e))?;
+    Ok(())
+}
+
+pub fn delete_alarm(id: i64) -> Result<(), Error> {
+    let conn = SQLITE_POOL.get().unwrap();
+    conn.execute("delete from alarms where id = ?1", params![id])
+        .map_err(|e| Error::DbError(e))?;
+    Ok(())
+}
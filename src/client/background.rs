@@ -1,8 +1,8 @@
-use std::{borrow::Cow, hash::BuildHasherDefault, num::NonZero, ops::Range, sync::Mutex};
+use std::{borrow::Cow, cell::RefCell, collections::{BTreeMap, BTreeSet, HashMap}, hash::BuildHasherDefault, num::NonZero, ops::Range, path::{Path, PathBuf}, sync::Mutex};
 
 use async_channel::{SendError, Sender};
 use gio::prelude::SettingsExt;
-use gtk::gdk;
+use gtk::{gdk, glib};
 use lru::LruCache;
 use nohash_hasher::NoHashHasher;
 use once_cell::sync::Lazy;
@@ -10,17 +10,42 @@ use time::OffsetDateTime;
 
 use mpd::{
     error::{Error as MpdError, ErrorCode},
-    search::{Operation as QueryOperation, Query, Term, Window}, Id,
+    search::{Operation as QueryOperation, Query, Term, Window}, EditAction, Id,
 };
-use rustc_hash::FxHashSet;
+use rustc_hash::{FxHashMap, FxHashSet};
 
-use crate::{cache::{get_new_image_paths, sqlite}, common::SongInfo, meta_providers::ProviderMessage, utils::{self, strip_filename_linux}};
+use crate::{
+    cache::{get_new_image_paths, sqlite},
+    common::{
+        playlist_check::{BrokenEntry, DuplicateEntry},
+        stats::{compute_streaks, local_today},
+        sticker_backup::{NamedStickers, SongStickers, KNOWN_STICKER_NAMES, STICKER_BACKUP_VERSION},
+        ListeningStats, PlaylistCheckReport, QueueSnapshot, SmartPlaylist, SongInfo, SongStats,
+        StatsRange, StickerBackup,
+        StickerRestoreReport, Stickers, Thumbs,
+    },
+    history::{self, matching, ExportedListen, ImportReport, ImportedListen},
+    meta_providers::{lastfm::LastfmWrapper, MetadataProvider, ProviderMessage},
+    playlist_file::{self, ExportOptions, PlaylistEntry, PlaylistImportReport},
+    utils::{self, strip_filename_linux},
+};
 
 use super::*;
 
 const BATCH_SIZE: usize = 128;
 const FETCH_LIMIT: usize = 10000000; // Fetch at most ten million songs at once (same
 // folder, same tag, etc)
+// Radio mode: how many candidates to take from each source
+const RADIO_SIMILAR_TRACKS: u32 = 50;
+const RADIO_SIMILAR_ARTISTS: u32 = 10;
+const RADIO_SONGS_PER_ARTIST: u32 = 20;
+const RADIO_SONGS_PER_GENRE: u32 = 500;
+// Length of each top songs/albums/artists list in listening statistics
+const STATS_TOP_N: u32 = 10;
+// How many songs with a similar title to consider when matching imported listens
+const HISTORY_MATCH_CANDIDATES: u32 = 50;
+// How many songs with the same file name to offer for relinking a broken playlist entry
+const RELINK_CANDIDATES: usize = 5;
 
 // Cache song infos so we can reuse them on queue updates.
 // Song IDs are u32s anyway, and I don't think there's any risk of a HashDoS attack
@@ -30,6 +55,12 @@ static QUEUED_SONG_CACHE: Lazy<Mutex<LruCache<u32, SongInfo, BuildHasherDefault<
         LruCache::with_hasher(NonZero::new(16384).unwrap(), BuildHasherDefault::default())
     ));
 
+// Stickers of recently shown songs by URI, so that song lists don't have to look them
+// up again on every queue change. Sticker changes made through us drop the affected
+// entries.
+static SONG_STICKER_CACHE: Lazy<Mutex<LruCache<String, Stickers>>> =
+    Lazy::new(|| Mutex::new(LruCache::new(NonZero::new(16384).unwrap())));
+
 pub fn update_mpd_database(
     client: &mut mpd::Client<stream::StreamWrapper>,
     sender_to_fg: &Sender<AsyncClientMessage>,
@@ -44,14 +75,30 @@ pub fn update_mpd_database(
     }
 }
 
+/// Drop all cached queued songs. Needed when queue IDs can no longer be trusted,
+/// such as after reconnecting to a restarted daemon.
+pub fn clear_queued_song_cache() {
+    QUEUED_SONG_CACHE.lock().unwrap().clear();
+}
+
+/// Forget the cached stickers of a song, such as after changing one of them.
+pub fn forget_song_stickers(uri: &str) {
+    SONG_STICKER_CACHE.lock().unwrap().pop(uri);
+}
+
+/// Forget the cached stickers of all songs. Needed when they might have changed
+/// without us knowing, such as after reconnecting or restoring a backup.
+pub fn clear_song_sticker_cache() {
+    SONG_STICKER_CACHE.lock().unwrap().clear();
+}
+
 pub fn get_current_queue(
     client: &mut mpd::Client<stream::StreamWrapper>,
     sender_to_fg: &Sender<AsyncClientMessage>,
 ) {
     // This command is only called upon connection so we should drop the entire cache
-    {
-        QUEUED_SONG_CACHE.lock().unwrap().clear();
-    }
+    clear_queued_song_cache();
+    clear_song_sticker_cache();
     let mut curr_len: usize = 0;
     let mut more: bool = true;
     while more && (curr_len) < FETCH_LIMIT {
@@ -59,10 +106,14 @@ pub fn get_current_queue(
             Window::from((curr_len as u32, (curr_len + BATCH_SIZE) as u32))
         ) {
             Ok(mut mpd_songs) => {
-                let songs: Vec<SongInfo> = mpd_songs
+                let mut songs: Vec<SongInfo> = mpd_songs
                     .iter_mut()
                     .map(|mpd_song| SongInfo::from(std::mem::take(mpd_song)))
                     .collect();
+                if let Err(mpd_error) = fetch_batch_stickers(client, &mut songs) {
+                    let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
+                    return;
+                }
                 if !songs.is_empty() {
                     // Cache
                     let mut cache = QUEUED_SONG_CACHE.lock().unwrap();
@@ -93,6 +144,7 @@ pub fn get_current_queue(
             }
         }
     }
+    let _ = sender_to_fg.send_blocking(AsyncClientMessage::QueueDownloaded);
 }
 
 pub fn get_queue_changes(
@@ -112,7 +164,7 @@ pub fn get_queue_changes(
                     // Map to songs.
                     // Use this background client to fetch cache misses to avoid blocking UI.
                     let mut cache = QUEUED_SONG_CACHE.lock().unwrap();
-                    let songs: Vec<SongInfo> = changes
+                    let mut songs: Vec<SongInfo> = changes
                         .into_iter()
                         .map(|change| {
                             if let Some(cached_song) = cache.get(&change.id.0) {
@@ -135,6 +187,11 @@ pub fn get_queue_changes(
                             }
                         })
                         .collect();
+                    drop(cache);
+                    if let Err(mpd_error) = fetch_batch_stickers(client, &mut songs) {
+                        let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
+                        return;
+                    }
                     let _ = sender_to_fg.send_blocking(AsyncClientMessage::QueueChangesReceived(
                         songs
                     ));
@@ -419,8 +476,10 @@ where
 fn fetch_songs_by_query<F>(
     client: &mut mpd::Client<stream::StreamWrapper>,
     query: &Query,
+    with_stickers: bool,
     respond: F,
-) where
+) -> Result<(), MpdError>
+where
     F: Fn(Vec<SongInfo>) -> Result<(), SendError<AsyncClientMessage>>,
 {
     let mut curr_len: usize = 0;
@@ -431,10 +490,13 @@ fn fetch_songs_by_query<F>(
             Window::from((curr_len as u32, (curr_len + BATCH_SIZE) as u32)),
         ) {
             Ok(mut mpd_songs) => {
-                let songs: Vec<SongInfo> = mpd_songs
+                let mut songs: Vec<SongInfo> = mpd_songs
                     .iter_mut()
                     .map(|mpd_song| SongInfo::from(std::mem::take(mpd_song)))
                     .collect();
+                if with_stickers {
+                    fetch_batch_stickers(client, &mut songs)?;
+                }
                 if !songs.is_empty() {
                     let _ = respond(songs);
                     curr_len += BATCH_SIZE;
@@ -442,11 +504,13 @@ fn fetch_songs_by_query<F>(
                     more = false;
                 }
             }
-            Err(e) => {
-                dbg!(e);
+            Err(mpd_error) => {
+                // Don't retry, as the query itself might be at fault
+                return Err(mpd_error);
             }
         }
     }
+    Ok(())
 }
 
 /// Fetch all albums, using AlbumArtist to further disambiguate same-named ones.
@@ -522,9 +586,12 @@ pub fn fetch_album_songs(
     sender_to_fg: &Sender<AsyncClientMessage>,
     tag: String,
 ) {
-    fetch_songs_by_query(
+    // Album views show play statistics, which are kept as myMPD-compatible stickers.
+    // These are looked up along with the other stickers of each batch.
+    let res = fetch_songs_by_query(
         client,
         Query::new().and(Term::Tag(Cow::Borrowed("album")), tag.clone()),
+        true,
         |songs| {
             sender_to_fg.send_blocking(AsyncClientMessage::AlbumSongInfoDownloaded(
                 tag.clone(),
@@ -532,6 +599,9 @@ pub fn fetch_album_songs(
             ))
         },
     );
+    if let Err(mpd_error) = res {
+        let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
+    }
 }
 
 pub fn fetch_artists(
@@ -616,7 +686,7 @@ pub fn fetch_recent_artists(
             }
             Err(MpdError::Io(_)) => {
                 // Connection error => attempt to reconnect
-                let _ = sender_to_fg.send_blocking(AsyncClientMessage::Connect);
+                let _ = sender_to_fg.send_blocking(AsyncClientMessage::ConnectionLost);
                 return;
             }
             _ => {}
@@ -633,20 +703,506 @@ pub fn fetch_songs_of_artist(
     sender_to_fg: &Sender<AsyncClientMessage>,
     name: String,
 ) {
-    fetch_songs_by_query(
+    if let Err(mpd_error) = fetch_songs_by_query(
         client,
         Query::new().and_with_op(
             Term::Tag(Cow::Borrowed("artist")),
             QueryOperation::Contains,
             name.clone(),
         ),
+        true,
         |songs| {
             sender_to_fg.send_blocking(AsyncClientMessage::ArtistSongInfoDownloaded(
                 name.clone(),
                 songs,
             ))
         },
-    );
+    ) {
+        let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
+    }
+}
+
+/// Fetch all songs matching a filter expression. Failures (most likely due to
+/// mistakes in the expression) are reported back with the expression itself.
+pub fn fetch_filter_songs(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    filter: FilterExpr,
+) {
+    let expression = filter.to_string();
+    if let Err(mpd_error) = fetch_songs_by_query(client, &filter.to_query(), false, |songs| {
+        sender_to_fg.send_blocking(AsyncClientMessage::FilterSongInfoDownloaded(
+            expression.clone(),
+            songs,
+        ))
+    }) {
+        let detail = match mpd_error {
+            MpdError::Server(server_error) => server_error.detail,
+            other => format!("{:?}", other),
+        };
+        let _ = sender_to_fg.send_blocking(AsyncClientMessage::FilterFailed(expression, detail));
+    }
+}
+
+// Tags looked into by the global search. MPD filters have no OR operator, so
+// each of these (plus the file path) gets its own search command and the
+// results are merged by URI.
+const SEARCH_TAGS: [&str; 6] = ["title", "artist", "album", "albumartist", "composer", "genre"];
+
+fn search_songs(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    term: &str,
+    limit: usize,
+) -> Result<(), MpdError> {
+    let mut already_found: FxHashSet<String> = FxHashSet::default();
+    let search_terms = SEARCH_TAGS
+        .iter()
+        .map(|tag| Term::Tag(Cow::Borrowed(*tag)))
+        .chain(std::iter::once(Term::File));
+    for search_term in search_terms {
+        let mut query = Query::new();
+        query.and_with_op(search_term, QueryOperation::Contains, term);
+        let mut curr_len: usize = 0;
+        while already_found.len() < limit {
+            let mpd_songs = client.search(
+                &query,
+                Window::from((curr_len as u32, (curr_len + BATCH_SIZE) as u32)),
+            )?;
+            let n_fetched = mpd_songs.len();
+            let remaining = limit - already_found.len();
+            let mut songs: Vec<SongInfo> = Vec::with_capacity(n_fetched.min(remaining));
+            for mpd_song in mpd_songs.into_iter() {
+                if songs.len() >= remaining {
+                    break;
+                }
+                if already_found.insert(mpd_song.file.clone()) {
+                    songs.push(SongInfo::from(mpd_song));
+                }
+            }
+            if !songs.is_empty() {
+                let _ = sender_to_fg.send_blocking(AsyncClientMessage::SearchSongInfoDownloaded(
+                    term.to_owned(),
+                    songs,
+                ));
+            }
+            if n_fetched < BATCH_SIZE {
+                break;
+            }
+            curr_len += BATCH_SIZE;
+        }
+    }
+    Ok(())
+}
+
+fn search_artists(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    term: &str,
+) -> Result<(), MpdError> {
+    // Same approach as fetch_artists, but only keeping parsed artists whose
+    // names contain the term (an artist tag might match thanks to a featured one).
+    let term_lower = term.to_lowercase();
+    let mut already_parsed: FxHashSet<String> = FxHashSet::default();
+    let grouped_vals = client.list(
+        &Term::Tag(Cow::Borrowed("artist")),
+        Query::new().and_with_op(
+            Term::Tag(Cow::Borrowed("artist")),
+            QueryOperation::Contains,
+            term,
+        ),
+        None,
+    )?;
+    if let Some((_, tags)) = grouped_vals.groups.first() {
+        for tag in tags.iter() {
+            let mut songs = client.find(
+                Query::new().and(Term::Tag(Cow::Borrowed("artist")), tag),
+                Window::from((0, 1)),
+            )?;
+            if !songs.is_empty() {
+                let first_song = SongInfo::from(std::mem::take(&mut songs[0]));
+                for artist in first_song.into_artist_infos().into_iter() {
+                    if artist.name.to_lowercase().contains(&term_lower)
+                        && already_parsed.insert(artist.name.clone())
+                    {
+                        let _ = sender_to_fg.send_blocking(
+                            AsyncClientMessage::SearchArtistDownloaded(term.to_owned(), artist),
+                        );
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Case-insensitively search the whole library for the given term. Albums and
+/// artists are matched by name while songs are matched by any of SEARCH_TAGS
+/// or their path. Results are streamed back tagged with the term so the UI
+/// can drop those of outdated searches.
+pub fn search_library(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    term: String,
+    song_limit: u32,
+) {
+    let res = fetch_albums_by_query(
+        client,
+        Query::new().and_with_op(
+            Term::Tag(Cow::Borrowed("album")),
+            QueryOperation::Contains,
+            term.clone(),
+        ),
+        |info| {
+            sender_to_fg.send_blocking(AsyncClientMessage::SearchAlbumDownloaded(
+                term.clone(),
+                info,
+            ))
+        },
+    )
+    .and_then(|_| search_artists(client, sender_to_fg, &term))
+    .and_then(|_| search_songs(client, sender_to_fg, &term, song_limit as usize));
+    if let Err(mpd_error) = res {
+        let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
+    }
+}
+
+/// Get the raw values of a sticker for all songs having it. Servers without
+/// sticker support simply yield nothing.
+fn fetch_raw_song_stickers(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    name: &str,
+) -> Result<HashMap<String, String>, MpdError> {
+    match client.find_sticker("song", "", name) {
+        Ok(pairs) => Ok(pairs.into_iter().collect()),
+        Err(MpdError::Server(_)) => Ok(HashMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Get the values of a numeric sticker for all songs having it.
+fn fetch_song_stickers(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    name: &str,
+) -> Result<HashMap<String, i8>, MpdError> {
+    Ok(fetch_raw_song_stickers(client, name)?
+        .into_iter()
+        .filter_map(|(uri, val)| val.trim().parse::<i8>().ok().map(|v| (uri, v)))
+        .collect())
+}
+
+/// Get the stickers of a batch of songs, such as for song rows with inline rating
+/// controls. Only the given songs are looked up, with a single `sticker list` each,
+/// as scanning the whole library for every batch would be far too slow. Songs looked
+/// up before are served from the song sticker cache. Servers without sticker support
+/// yield nothing.
+fn fetch_batch_stickers(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    songs: &mut [SongInfo],
+) -> Result<(), MpdError> {
+    let mut supported = true;
+    for song in songs.iter_mut() {
+        // Songs might come from the queued song cache with outdated values.
+        // Don't hold the lock while waiting for MPD, as the main thread uses it too.
+        if let Some(stickers) = SONG_STICKER_CACHE.lock().unwrap().get(&song.uri) {
+            song.stickers = stickers.clone();
+            continue;
+        }
+        song.stickers = Stickers::default();
+        if !supported {
+            continue;
+        }
+        match client.stickers_map("song", &song.uri) {
+            Ok(stickers) => {
+                for (name, val) in stickers.iter() {
+                    song.stickers.set(name, val);
+                }
+            }
+            Err(MpdError::Server(e)) if e.code == ErrorCode::UnknownCmd => {
+                supported = false;
+                continue;
+            }
+            // Not a database song (such as streams)
+            Err(MpdError::Server(_)) => {}
+            Err(e) => {
+                return Err(e);
+            }
+        }
+        SONG_STICKER_CACHE
+            .lock()
+            .unwrap()
+            .put(song.uri.clone(), song.stickers.clone());
+    }
+    Ok(())
+}
+
+/// Find songs whose numeric sticker has one of the given values using `sticker find`
+/// with the = operator, so that the server does the filtering. Returns None if the
+/// server doesn't support value comparisons.
+fn find_songs_with_sticker_values(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    name: &str,
+    values: impl Iterator<Item = i8>,
+) -> Result<Option<HashMap<String, i8>>, MpdError> {
+    let mut res: HashMap<String, i8> = HashMap::new();
+    for val in values {
+        match client.find_sticker_eq("song", "", name, &val.to_string()) {
+            Ok(uris) => res.extend(uris.into_iter().map(|uri| (uri, val))),
+            Err(MpdError::Server(_)) => return Ok(None),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(Some(res))
+}
+
+/// Candidates & stats of smart playlists whose only way of narrowing down the library
+/// is through stickers (such as the built-in Liked songs & Top rated collections),
+/// found server-side. Returns None if the playlist has an MPD filter or no sticker
+/// rules, or if the server can't compare sticker values, in which case the whole
+/// library has to be scanned instead.
+fn find_sticker_candidates(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    playlist: &SmartPlaylist,
+) -> Result<Option<(Vec<String>, SongStats)>, MpdError> {
+    if playlist.filter.is_some() || !(playlist.liked_only || playlist.min_rating.is_some()) {
+        return Ok(None);
+    }
+    let mut stats = SongStats::default();
+    let candidates: Vec<String>;
+    if playlist.liked_only {
+        let Some(likes) = find_songs_with_sticker_values(
+            client,
+            Stickers::LIKE_KEY,
+            std::iter::once(i8::from(Thumbs::Up)),
+        )?
+        else {
+            return Ok(None);
+        };
+        stats.likes = likes;
+        candidates = stats.likes.keys().cloned().collect();
+        if playlist.needs_ratings() {
+            stats.ratings = fetch_song_stickers(client, Stickers::RATING_KEY)?;
+        }
+    } else {
+        let min = playlist.min_rating.unwrap_or_default().clamp(0, 10);
+        let Some(ratings) = find_songs_with_sticker_values(client, Stickers::RATING_KEY, min..=10)?
+        else {
+            return Ok(None);
+        };
+        stats.ratings = ratings;
+        candidates = stats.ratings.keys().cloned().collect();
+        if playlist.needs_likes() {
+            stats.likes = fetch_song_stickers(client, Stickers::LIKE_KEY)?;
+        }
+    }
+    Ok(Some((candidates, stats)))
+}
+
+/// Evaluate a smart playlist. The MPD filter (if any) is applied server-side,
+/// after which the remaining rules are applied using stickers and local history.
+/// Results are sent back in one go, even if empty, so that views can tell
+/// evaluation has finished.
+pub fn evaluate_smart_playlist(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    playlist: SmartPlaylist,
+) {
+    let res = find_sticker_candidates(client, &playlist).and_then(|maybe_found| {
+        let Some((uris, mut stats)) = maybe_found else {
+            return Ok(None);
+        };
+        let uris: Vec<&str> = uris.iter().map(String::as_str).collect();
+        let songs = fetch_songs_by_uri(client, &uris)?;
+        stats.play_counts = sqlite::get_play_stats().expect("Sqlite DB error");
+        Ok(Some((songs, stats)))
+    });
+    match res {
+        Ok(Some((songs, stats))) => {
+            let songs = playlist.apply(songs, &stats);
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::SmartPlaylistSongInfoDownloaded(
+                playlist.name,
+                songs,
+            ));
+            return;
+        }
+        Ok(None) => {}
+        Err(mpd_error) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
+            return;
+        }
+    }
+    let query = match playlist.filter.as_ref() {
+        Some(filter) => filter.to_query(),
+        None => {
+            let mut query = Query::new();
+            query.and(Term::Base, "");
+            query
+        }
+    };
+    let candidates: RefCell<Vec<SongInfo>> = RefCell::new(Vec::new());
+    let res = fetch_songs_by_query(client, &query, false, |songs| {
+        candidates.borrow_mut().extend(songs);
+        Ok(())
+    })
+    .and_then(|_| {
+        let mut stats = SongStats::default();
+        if playlist.needs_ratings() {
+            stats.ratings = fetch_song_stickers(client, Stickers::RATING_KEY)?;
+        }
+        if playlist.needs_likes() {
+            stats.likes = fetch_song_stickers(client, Stickers::LIKE_KEY)?;
+        }
+        stats.play_counts = sqlite::get_play_stats().expect("Sqlite DB error");
+        Ok(stats)
+    });
+    match res {
+        Ok(stats) => {
+            let songs = playlist.apply(candidates.take(), &stats);
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::SmartPlaylistSongInfoDownloaded(
+                playlist.name,
+                songs,
+            ));
+        }
+        Err(mpd_error) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
+        }
+    }
+}
+
+fn find_uris(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    query: &Query,
+    limit: u32,
+) -> Result<Vec<String>, MpdError> {
+    client
+        .find(query, Window::from((0, limit)))
+        .map(|songs| songs.into_iter().map(|song| song.file).collect())
+}
+
+fn add_radio_candidates(scores: &mut HashMap<String, f64>, uris: Vec<String>, score: f64) {
+    for uri in uris.into_iter() {
+        *scores.entry(uri).or_insert(0.0) += score;
+    }
+}
+
+fn pick_radio_songs(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    seed: &SongInfo,
+    exclude: Vec<String>,
+    count: u32,
+    no_repeat_window: u32,
+) -> Result<Vec<String>, MpdError> {
+    // Songs found through several sources accumulate their scores.
+    let mut scores: HashMap<String, f64> = HashMap::new();
+    if let Some(artist) = seed.artist_tag.as_deref() {
+        let lastfm = LastfmWrapper::new();
+        // Earlier Last.fm results are more similar and as such worth more.
+        let tracks = lastfm.get_similar_tracks(artist, &seed.title, RADIO_SIMILAR_TRACKS);
+        let n_tracks = tracks.len() as f64;
+        for (idx, (track_artist, title)) in tracks.into_iter().enumerate() {
+            let uris = find_uris(
+                client,
+                Query::new()
+                    .and(Term::Tag(Cow::Borrowed("artist")), track_artist)
+                    .and(Term::Tag(Cow::Borrowed("title")), title),
+                1,
+            )?;
+            add_radio_candidates(&mut scores, uris, 4.0 - 2.0 * idx as f64 / n_tracks);
+        }
+        let artists = lastfm.get_similar_artists(artist, RADIO_SIMILAR_ARTISTS);
+        let n_artists = artists.len() as f64;
+        for (idx, similar_artist) in artists.into_iter().enumerate() {
+            let uris = find_uris(
+                client,
+                Query::new().and(Term::Tag(Cow::Borrowed("artist")), similar_artist),
+                RADIO_SONGS_PER_ARTIST,
+            )?;
+            add_radio_candidates(&mut scores, uris, 2.0 - idx as f64 / n_artists);
+        }
+        let uris = find_uris(
+            client,
+            Query::new().and(Term::Tag(Cow::Borrowed("artist")), artist),
+            RADIO_SONGS_PER_ARTIST,
+        )?;
+        add_radio_candidates(&mut scores, uris, 1.0);
+    }
+    // SongInfo doesn't keep genres, so read them from the raw tags.
+    let genres: Vec<String> = client
+        .find(Query::new().and(Term::File, &seed.uri), Window::from((0, 1)))?
+        .into_iter()
+        .flat_map(|song| song.tags.into_iter())
+        .filter_map(|(tag, val)| tag.eq_ignore_ascii_case("genre").then_some(val))
+        .collect();
+    for genre in genres.into_iter() {
+        let uris = find_uris(
+            client,
+            Query::new().and(Term::Tag(Cow::Borrowed("genre")), genre),
+            RADIO_SONGS_PER_GENRE,
+        )?;
+        add_radio_candidates(&mut scores, uris, 0.5);
+    }
+
+    let likes = fetch_song_stickers(client, Stickers::LIKE_KEY)?;
+    let ratings = fetch_song_stickers(client, Stickers::RATING_KEY)?;
+    let mut blocked: FxHashSet<String> = exclude.into_iter().collect();
+    blocked.insert(seed.uri.clone());
+    if no_repeat_window > 0 {
+        blocked.extend(
+            sqlite::get_last_n_songs(no_repeat_window)
+                .expect("Sqlite DB error")
+                .into_iter()
+                .map(|(uri, _)| uri),
+        );
+    }
+    let mut picks: Vec<(String, f64)> = scores
+        .into_iter()
+        .filter(|(uri, _)| !blocked.contains(uri) && likes.get(uri) != Some(&0))
+        .map(|(uri, mut score)| {
+            if likes.get(&uri) == Some(&2) {
+                score += 1.0;
+            }
+            if let Some(rating) = ratings.get(&uri) {
+                // 2.5 stars is neutral
+                score += (*rating as f64 - 5.0) / 5.0;
+            }
+            // Shake things up a little so that the same seed won't always yield
+            // the same songs.
+            score *= glib::random_double_range(0.5, 1.5);
+            (uri, score)
+        })
+        .collect();
+    picks.sort_by(|a, b| b.1.total_cmp(&a.1));
+    picks.truncate(count as usize);
+    Ok(picks.into_iter().map(|(uri, _)| uri).collect())
+}
+
+/// Radio mode: append up to `count` songs similar to the seed song.
+/// Candidates come from Last.fm's similar tracks & artists (if enabled), then songs
+/// sharing the seed's artist or genre. Likes and ratings push candidates up or down.
+/// Disliked songs, songs in `exclude` and the last `no_repeat_window` songs in the
+/// playback history are never picked.
+pub fn fill_radio(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    seed: SongInfo,
+    exclude: Vec<String>,
+    count: u32,
+    no_repeat_window: u32,
+) {
+    match pick_radio_songs(client, &seed, exclude, count, no_repeat_window) {
+        Ok(uris) => {
+            if uris.is_empty() {
+                println!("[Radio] No new songs similar to {}", &seed.uri);
+            }
+            add_multi(client, sender_to_fg, &uris, false, None, None);
+        }
+        Err(MpdError::Io(_)) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::ConnectionLost);
+        }
+        Err(mpd_error) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
+        }
+    }
 }
 
 pub fn fetch_folder_contents(
@@ -673,10 +1229,14 @@ pub fn fetch_playlist_songs(
     if client.version.1 < 24 {
         match client.playlist(&name, Option::<Range<u32>>::None) {
             Ok(mut mpd_songs) => {
-                let songs: Vec<SongInfo> = mpd_songs
+                let mut songs: Vec<SongInfo> = mpd_songs
                     .iter_mut()
                     .map(|mpd_song| SongInfo::from(std::mem::take(mpd_song)))
                     .collect();
+                if let Err(mpd_error) = fetch_batch_stickers(client, &mut songs) {
+                    let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
+                    return;
+                }
                 if !songs.is_empty() {
                     let _ = sender_to_fg.send_blocking(
                         AsyncClientMessage::PlaylistSongInfoDownloaded(name.clone(), songs),
@@ -694,10 +1254,14 @@ pub fn fetch_playlist_songs(
         while more && (curr_len as usize) < FETCH_LIMIT {
             match client.playlist(&name, Some(curr_len..(curr_len + BATCH_SIZE as u32))) {
                 Ok(mut mpd_songs) => {
-                    let songs: Vec<SongInfo> = mpd_songs
+                    let mut songs: Vec<SongInfo> = mpd_songs
                         .iter_mut()
                         .map(|mpd_song| SongInfo::from(std::mem::take(mpd_song)))
                         .collect();
+                    if let Err(mpd_error) = fetch_batch_stickers(client, &mut songs) {
+                        let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, None));
+                        return;
+                    }
                     more = songs.len() >= BATCH_SIZE as usize;
                     if !songs.is_empty() {
                         curr_len += songs.len() as u32;
@@ -780,6 +1344,769 @@ pub fn fetch_last_n_songs(
     }
 }
 
+/// Fetch songs that have a saved position, most recently played first.
+pub fn fetch_resumable_songs(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+) {
+    let res = fetch_raw_song_stickers(client, Stickers::ELAPSED_KEY).and_then(|positions| {
+        let last_played = fetch_raw_song_stickers(client, Stickers::LAST_PLAYED_KEY)?;
+        let mut uris: Vec<&str> = positions.keys().map(String::as_str).collect();
+        uris.sort_by_cached_key(|uri| {
+            std::cmp::Reverse(
+                last_played
+                    .get(*uri)
+                    .and_then(|val| val.trim().parse::<i64>().ok())
+                    .unwrap_or(0),
+            )
+        });
+        let mut songs = fetch_songs_by_uri(client, &uris)?;
+        for song in songs.iter_mut() {
+            if let Some(val) = positions.get(&song.uri) {
+                song.stickers.set_elapsed(val);
+            }
+        }
+        Ok(songs)
+    });
+    match res {
+        Ok(songs) => {
+            if !songs.is_empty() {
+                let _ = sender_to_fg
+                    .send_blocking(AsyncClientMessage::ResumableSongInfoDownloaded(songs));
+            }
+        }
+        Err(error) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(error, None));
+        }
+    }
+}
+
+/// Listens recorded by older versions have no duration. Look them up so they
+/// count towards listening time too.
+fn fill_history_durations(client: &mut mpd::Client<stream::StreamWrapper>) -> Result<(), MpdError> {
+    let uris = sqlite::get_history_uris_without_duration().expect("Sqlite DB error");
+    if uris.is_empty() {
+        return Ok(());
+    }
+    let songs = fetch_songs_by_uri(client, &uris.iter().map(String::as_str).collect::<Vec<&str>>())?;
+    let found: HashMap<&str, u64> = songs
+        .iter()
+        .map(|song| (song.uri.as_str(), song.duration.map_or(0, |dur| dur.as_secs())))
+        .collect();
+    let durations: Vec<(String, u64)> = uris
+        .iter()
+        .map(|uri| (uri.clone(), found.get(uri.as_str()).copied().unwrap_or(0)))
+        .collect();
+    sqlite::set_history_durations(&durations).expect("Sqlite DB error");
+    Ok(())
+}
+
+fn collect_listening_stats(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    range: StatsRange,
+) -> Result<ListeningStats, MpdError> {
+    fill_history_durations(client)?;
+    let since = range.since();
+    let mut stats = ListeningStats {
+        range,
+        ..Default::default()
+    };
+    (stats.total_plays, stats.total_time) =
+        sqlite::get_listening_totals(since).expect("Sqlite DB error");
+    (stats.current_streak, stats.longest_streak) = compute_streaks(
+        &sqlite::get_listening_days().expect("Sqlite DB error"),
+        local_today(),
+    );
+    stats.by_day = sqlite::get_listening_time_by_day(since).expect("Sqlite DB error");
+    for (weekday, hour, secs) in sqlite::get_listening_time_by_hour(since)
+        .expect("Sqlite DB error")
+        .into_iter()
+    {
+        // SQLite counts weekdays from Sunday
+        stats.by_hour[(weekday as usize + 6) % 7][hour as usize] = secs;
+    }
+
+    // Songs, albums & artists no longer in the library are left out
+    let top_songs = sqlite::get_top_songs(since, STATS_TOP_N).expect("Sqlite DB error");
+    let mut songs = fetch_songs_by_uri(
+        client,
+        &top_songs
+            .iter()
+            .map(|tup| tup.0.as_str())
+            .collect::<Vec<&str>>(),
+    )?;
+    for (uri, plays, first_listened) in top_songs.into_iter() {
+        if let Some(idx) = songs.iter().position(|song| song.uri == uri) {
+            stats.top_songs.push((songs.swap_remove(idx), plays, first_listened));
+        }
+    }
+
+    for (title, artist, mbid, plays, first_listened) in sqlite::get_top_albums(since, STATS_TOP_N)
+        .expect("Sqlite DB error")
+        .into_iter()
+    {
+        let mut query = Query::new();
+        query.and(Term::Tag(Cow::Borrowed("album")), title);
+        if let Some(artist) = artist {
+            query.and(Term::Tag(Cow::Borrowed("albumartist")), artist);
+        }
+        if let Some(mbid) = mbid {
+            query.and(Term::Tag(Cow::Borrowed("musicbrainz_albumid")), mbid);
+        }
+        if let Some(info) = client
+            .find(&query, Window::from((0, 1)))?
+            .into_iter()
+            .next()
+            .and_then(|song| SongInfo::from(song).into_album_info())
+        {
+            stats.top_albums.push((info, plays, first_listened));
+        }
+    }
+
+    for (name, plays, first_listened) in sqlite::get_top_artists(since, STATS_TOP_N)
+        .expect("Sqlite DB error")
+        .into_iter()
+    {
+        if let Some(info) = client
+            .find(
+                Query::new().and_with_op(
+                    Term::Tag(Cow::Borrowed("artist")),
+                    QueryOperation::Contains,
+                    &name,
+                ),
+                Window::from((0, 1)),
+            )?
+            .into_iter()
+            .next()
+            .and_then(|song| {
+                SongInfo::from(song)
+                    .into_artist_infos()
+                    .into_iter()
+                    .find(|artist| artist.name == name)
+            })
+        {
+            stats.top_artists.push((info, plays, first_listened));
+        }
+    }
+
+    Ok(stats)
+}
+
+pub fn fetch_listening_stats(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    range: StatsRange,
+) {
+    match collect_listening_stats(client, range) {
+        Ok(stats) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::ListeningStatsDownloaded(stats));
+        }
+        Err(error) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(error, None));
+        }
+    }
+}
+
+pub fn export_history(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    path: PathBuf,
+) {
+    let entries = sqlite::get_history().expect("Sqlite DB error");
+    let mut uris: Vec<&str> = entries.iter().map(|(uri, _, _)| uri.as_str()).collect();
+    uris.sort_unstable();
+    uris.dedup();
+    match fetch_songs_by_uri(client, &uris) {
+        Ok(songs) => {
+            let songs: HashMap<&str, &SongInfo> =
+                songs.iter().map(|song| (song.uri.as_str(), song)).collect();
+            let listens: Vec<ExportedListen> = entries
+                .iter()
+                .map(|(uri, ts, duration)| {
+                    ExportedListen::new(uri.clone(), *ts, *duration, songs.get(uri.as_str()).copied())
+                })
+                .collect();
+            let res = history::write_listens(&path, &listens)
+                .map(|_| listens.len())
+                .map_err(|e| e.to_string());
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::HistoryExported(res));
+        }
+        Err(error) => {
+            // Also report back so that the preferences dialog can leave its busy state
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::HistoryExported(Err(error.to_string())));
+        }
+    }
+}
+
+/// Find the library song an imported listen refers to, trying its URI, then its
+/// recording MBID, then the closest fuzzy match among songs with similar titles.
+fn match_listen(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    listen: &ImportedListen,
+) -> Result<Option<SongInfo>, MpdError> {
+    if let Some(uri) = listen.uri.as_ref() {
+        if let Some(song) = fetch_songs_by_uri(client, &[uri.as_str()])?.pop() {
+            return Ok(Some(song));
+        }
+    }
+    if let Some(mbid) = listen.recording_mbid.as_ref() {
+        let mut songs = client.find(
+            Query::new().and(Term::Tag(Cow::Borrowed("musicbrainz_trackid")), mbid.as_str()),
+            Window::from((0, 1)),
+        )?;
+        if !songs.is_empty() {
+            return Ok(Some(SongInfo::from(songs.remove(0))));
+        }
+    }
+    if listen.title.is_empty() {
+        return Ok(None);
+    }
+    let mut query = Query::new();
+    query.and_with_op(
+        Term::Tag(Cow::Borrowed("title")),
+        QueryOperation::Contains,
+        matching::search_term(&listen.title),
+    );
+    Ok(client
+        .search(&query, Window::from((0, HISTORY_MATCH_CANDIDATES)))?
+        .into_iter()
+        .map(SongInfo::from)
+        .filter_map(|song| matching::score(listen, &song).map(|score| (score, song)))
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, song)| song))
+}
+
+fn match_and_import_listens(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    listens: Vec<ImportedListen>,
+) -> Result<ImportReport, MpdError> {
+    // Exports repeat the same tracks many times over, so only look each one up once.
+    let mut matches: HashMap<(Option<String>, Option<String>, String, String), Option<SongInfo>> =
+        HashMap::new();
+    let mut unmatched: HashMap<(String, String), usize> = HashMap::new();
+    let mut to_add: Vec<(SongInfo, OffsetDateTime)> = Vec::with_capacity(listens.len());
+    for listen in listens.iter() {
+        let Ok(ts) = OffsetDateTime::from_unix_timestamp(listen.timestamp) else {
+            continue;
+        };
+        let key = (
+            listen.uri.clone(),
+            listen.recording_mbid.clone(),
+            matching::normalize(&listen.artist),
+            matching::normalize(&listen.title),
+        );
+        let song = match matches.get(&key) {
+            Some(song) => song.clone(),
+            None => {
+                let song = match_listen(client, listen)?;
+                matches.insert(key, song.clone());
+                song
+            }
+        };
+        if let Some(song) = song {
+            to_add.push((song, ts));
+        } else {
+            let title = if listen.title.is_empty() {
+                listen.uri.clone().unwrap_or_default()
+            } else {
+                listen.title.clone()
+            };
+            *unmatched.entry((listen.artist.clone(), title)).or_insert(0) += 1;
+        }
+    }
+    let (imported, duplicates) = sqlite::import_history(&to_add).expect("Sqlite DB error");
+    let mut unmatched: Vec<(String, String, usize)> = unmatched
+        .into_iter()
+        .map(|((artist, title), count)| (artist, title, count))
+        .collect();
+    unmatched.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| (&a.0, &a.1).cmp(&(&b.0, &b.1))));
+    Ok(ImportReport {
+        imported,
+        duplicates,
+        unmatched,
+    })
+}
+
+pub fn import_history(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    path: PathBuf,
+) {
+    let listens = match history::read_listens(&path) {
+        Ok(listens) => listens,
+        Err(msg) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::HistoryImported(Err(msg)));
+            return;
+        }
+    };
+    let res = match_and_import_listens(client, listens).map_err(|e| e.to_string());
+    let _ = sender_to_fg.send_blocking(AsyncClientMessage::HistoryImported(res));
+}
+
+/// Get all stickers of an object. Objects without any, or of a type the server
+/// doesn't support stickers for, simply yield nothing.
+fn fetch_all_stickers(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    typ: &str,
+    uri: &str,
+) -> Result<BTreeMap<String, String>, MpdError> {
+    match client.stickers_map(typ, uri) {
+        Ok(stickers) => Ok(stickers.into_iter().collect()),
+        Err(MpdError::Server(_)) => Ok(BTreeMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+/// Find all objects of the given type having any of the known stickers.
+fn find_stickered(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    typ: &str,
+) -> Result<BTreeSet<String>, MpdError> {
+    let mut res: BTreeSet<String> = BTreeSet::new();
+    for name in KNOWN_STICKER_NAMES.iter() {
+        match client.find_sticker(typ, "", name) {
+            Ok(pairs) => res.extend(pairs.into_iter().map(|(uri, _)| uri)),
+            Err(MpdError::Server(_)) => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(res)
+}
+
+fn collect_sticker_backup(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+) -> Result<StickerBackup, MpdError> {
+    let mut backup = StickerBackup {
+        version: STICKER_BACKUP_VERSION,
+        created_at: OffsetDateTime::now_utc().unix_timestamp(),
+        ..Default::default()
+    };
+    let uris = find_stickered(client, "song")?;
+    let songs = fetch_songs_by_uri(client, &uris.iter().map(String::as_str).collect::<Vec<&str>>())?;
+    let songs: HashMap<&str, &SongInfo> = songs.iter().map(|song| (song.uri.as_str(), song)).collect();
+    for uri in uris.iter() {
+        let stickers = fetch_all_stickers(client, "song", uri)?;
+        if !stickers.is_empty() {
+            backup.songs.push(SongStickers::new(uri.clone(), songs.get(uri.as_str()).copied(), stickers));
+        }
+    }
+    // Album stickers need MPD 0.24+. Older servers will just return nothing.
+    for name in find_stickered(client, "album")?.into_iter() {
+        let stickers = fetch_all_stickers(client, "album", &name)?;
+        if !stickers.is_empty() {
+            backup.albums.push(NamedStickers { name, stickers });
+        }
+    }
+    let playlists = match client.playlists() {
+        Ok(playlists) => playlists,
+        Err(MpdError::Server(_)) => Vec::new(),
+        Err(e) => return Err(e),
+    };
+    for playlist in playlists.into_iter() {
+        let stickers = fetch_all_stickers(client, "playlist", &playlist.name)?;
+        if !stickers.is_empty() {
+            backup.playlists.push(NamedStickers {
+                name: playlist.name,
+                stickers,
+            });
+        }
+    }
+    Ok(backup)
+}
+
+pub fn backup_stickers(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    path: PathBuf,
+) {
+    let res = collect_sticker_backup(client)
+        .map_err(|e| e.to_string())
+        .and_then(|backup| {
+            let file = std::fs::File::create(&path).map_err(|e| e.to_string())?;
+            serde_json::to_writer_pretty(std::io::BufWriter::new(file), &backup)
+                .map_err(|e| e.to_string())?;
+            Ok(backup.n_stickers())
+        });
+    let _ = sender_to_fg.send_blocking(AsyncClientMessage::StickersBackedUp(res));
+}
+
+/// Find where a backed up song is in the library now: at the same URI, else by its
+/// MBID, else by exact title, artist & album tags (closest duration wins).
+fn locate_song(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    entry: &SongStickers,
+) -> Result<Option<String>, MpdError> {
+    if !client
+        .find(Query::new().and(Term::File, entry.uri.as_str()), Window::from((0, 1)))?
+        .is_empty()
+    {
+        return Ok(Some(entry.uri.clone()));
+    }
+    if let Some(mbid) = entry.mbid.as_ref() {
+        let mut songs = client.find(
+            Query::new().and(Term::Tag(Cow::Borrowed("musicbrainz_trackid")), mbid.as_str()),
+            Window::from((0, 1)),
+        )?;
+        if !songs.is_empty() {
+            return Ok(Some(songs.remove(0).file));
+        }
+    }
+    let (Some(title), Some(artist)) = (entry.title.as_ref(), entry.artist.as_ref()) else {
+        return Ok(None);
+    };
+    let mut query = Query::new();
+    query
+        .and(Term::Tag(Cow::Borrowed("title")), title.as_str())
+        .and(Term::Tag(Cow::Borrowed("artist")), artist.as_str());
+    if let Some(album) = entry.album.as_ref() {
+        query.and(Term::Tag(Cow::Borrowed("album")), album.as_str());
+    }
+    Ok(client
+        .find(&query, Window::from((0, BATCH_SIZE as u32)))?
+        .into_iter()
+        .map(SongInfo::from)
+        .min_by_key(|song| match (entry.duration, song.duration) {
+            (Some(secs), Some(dur)) => secs.abs_diff(dur.as_secs()),
+            _ => 0,
+        })
+        .map(|song| song.uri))
+}
+
+fn apply_sticker_backup(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    backup: &StickerBackup,
+    dry_run: bool,
+) -> Result<StickerRestoreReport, MpdError> {
+    let mut report = StickerRestoreReport {
+        dry_run,
+        ..Default::default()
+    };
+    let mut to_write: Vec<(&str, String, &BTreeMap<String, String>)> = Vec::new();
+    for entry in backup.songs.iter() {
+        match locate_song(client, entry)? {
+            Some(uri) => {
+                if uri == entry.uri {
+                    report.songs_kept += 1;
+                } else {
+                    report.songs_remapped.push((entry.uri.clone(), uri.clone()));
+                }
+                to_write.push(("song", uri, &entry.stickers));
+            }
+            None => report.songs_missing.push(entry.uri.clone()),
+        }
+    }
+    for album in backup.albums.iter() {
+        let exists = !client
+            .find(
+                Query::new().and(Term::Tag(Cow::Borrowed("album")), album.name.as_str()),
+                Window::from((0, 1)),
+            )?
+            .is_empty();
+        if exists {
+            report.albums_restored += 1;
+            to_write.push(("album", album.name.clone(), &album.stickers));
+        } else {
+            report.albums_missing.push(album.name.clone());
+        }
+    }
+    let playlists: FxHashSet<String> = match client.playlists() {
+        Ok(playlists) => playlists.into_iter().map(|playlist| playlist.name).collect(),
+        Err(MpdError::Server(_)) => FxHashSet::default(),
+        Err(e) => return Err(e),
+    };
+    for playlist in backup.playlists.iter() {
+        if playlists.contains(&playlist.name) {
+            report.playlists_restored += 1;
+            to_write.push(("playlist", playlist.name.clone(), &playlist.stickers));
+        } else {
+            report.playlists_missing.push(playlist.name.clone());
+        }
+    }
+    report.n_stickers = to_write.iter().map(|(_, _, stickers)| stickers.len()).sum();
+    if dry_run {
+        return Ok(report);
+    }
+    for (typ, uri, stickers) in to_write.into_iter() {
+        for (name, value) in stickers.iter() {
+            match client.set_sticker(typ, &uri, name, value) {
+                Ok(_) => {}
+                // Servers older than 0.24 reject album & playlist stickers
+                Err(MpdError::Server(_)) if typ != "song" => {
+                    report.n_stickers -= 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    Ok(report)
+}
+
+pub fn restore_stickers(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    path: PathBuf,
+    dry_run: bool,
+) {
+    let res = std::fs::read_to_string(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|text| serde_json::from_str::<StickerBackup>(&text).map_err(|e| e.to_string()))
+        .and_then(|backup| {
+            if backup.version > STICKER_BACKUP_VERSION {
+                return Err("Backup was made by a newer version of Euphonica".to_owned());
+            }
+            apply_sticker_backup(client, &backup, dry_run).map_err(|e| e.to_string())
+        });
+    if !dry_run {
+        clear_song_sticker_cache();
+    }
+    let _ = sender_to_fg.send_blocking(AsyncClientMessage::StickersRestored(res));
+}
+
+/// Local path of MPD's music directory as set in the preferences, if any.
+fn music_directory() -> Option<PathBuf> {
+    let dir = utils::settings_manager()
+        .child("client")
+        .string("mpd-music-directory");
+    Some(PathBuf::from(dir.as_str())).filter(|dir| !dir.as_os_str().is_empty())
+}
+
+fn fetch_all_queue_songs(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+) -> Result<Vec<SongInfo>, MpdError> {
+    let mut res: Vec<SongInfo> = Vec::new();
+    loop {
+        match client.queue(Window::from((
+            res.len() as u32,
+            (res.len() + BATCH_SIZE) as u32,
+        ))) {
+            Ok(songs) if !songs.is_empty() => res.extend(songs.into_iter().map(SongInfo::from)),
+            Ok(_) => break,
+            // Past the end of the queue
+            Err(MpdError::Server(e)) if e.code == ErrorCode::Argument => break,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(res)
+}
+
+pub fn export_playlist_file(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    name: Option<String>,
+    path: PathBuf,
+    options: ExportOptions,
+) {
+    let songs = match name.as_ref() {
+        Some(name) => client
+            .playlist(name, Option::<Range<u32>>::None)
+            .map(|songs| songs.into_iter().map(SongInfo::from).collect()),
+        None => fetch_all_queue_songs(client),
+    };
+    let res = songs.map_err(|e| e.to_string()).and_then(|songs| {
+        playlist_file::write_playlist(
+            &path,
+            name.as_deref().unwrap_or("Queue"),
+            &songs,
+            options,
+            music_directory().as_deref(),
+        )
+        .map(|_| songs.len())
+        .map_err(|e| e.to_string())
+    });
+    let _ = sender_to_fg.send_blocking(AsyncClientMessage::PlaylistFileExported(res));
+}
+
+/// Find the MPD URI of a playlist file entry, along with whether it was found by path
+/// (as opposed to by tags). Remote URLs are kept as-is.
+fn resolve_playlist_entry(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    entry: &PlaylistEntry,
+    playlist_path: &Path,
+    music_dir: Option<&Path>,
+) -> Result<Option<(String, bool)>, MpdError> {
+    for uri in playlist_file::uri_candidates(entry, playlist_path, music_dir).into_iter() {
+        if playlist_file::is_url(&uri) || !fetch_songs_by_uri(client, &[uri.as_str()])?.is_empty() {
+            return Ok(Some((uri, true)));
+        }
+    }
+    let Some((artist, title)) = playlist_file::search_tags(entry) else {
+        return Ok(None);
+    };
+    let listen = ImportedListen {
+        artist,
+        title,
+        album: entry.album.clone(),
+        duration: entry.duration,
+        ..Default::default()
+    };
+    Ok(match_listen(client, &listen)?.map(|song| (song.uri, false)))
+}
+
+fn import_playlist_entries(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    path: &Path,
+    entries: Vec<PlaylistEntry>,
+) -> Result<PlaylistImportReport, MpdError> {
+    let music_dir = music_directory();
+    let mut report = PlaylistImportReport::default();
+    let mut uris: Vec<String> = Vec::with_capacity(entries.len());
+    for entry in entries.iter() {
+        match resolve_playlist_entry(client, entry, path, music_dir.as_deref())? {
+            Some((uri, by_path)) => {
+                if by_path {
+                    report.by_path += 1;
+                } else {
+                    report.by_tags += 1;
+                }
+                uris.push(uri);
+            }
+            None => report.unmatched.push(entry.location.clone()),
+        }
+    }
+    if uris.is_empty() {
+        return Ok(report);
+    }
+    // Never overwrite existing playlists
+    let existing: FxHashSet<String> = client
+        .playlists()?
+        .into_iter()
+        .map(|playlist| playlist.name)
+        .collect();
+    let stem = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "Imported playlist".to_owned());
+    report.name = stem.clone();
+    let mut n: u32 = 2;
+    while existing.contains(&report.name) {
+        report.name = format!("{stem} ({n})");
+        n += 1;
+    }
+    {
+        let edits: Vec<EditAction> = uris
+            .iter()
+            .map(|uri| EditAction::Add(Cow::Borrowed(report.name.as_str()), Cow::Borrowed(uri.as_str()), None))
+            .collect();
+        client.pl_edit(&edits)?;
+    }
+    Ok(report)
+}
+
+pub fn import_playlist_file(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    path: PathBuf,
+) {
+    let res = playlist_file::read_playlist(&path).and_then(|entries| {
+        import_playlist_entries(client, &path, entries).map_err(|e| e.to_string())
+    });
+    let _ = sender_to_fg.send_blocking(AsyncClientMessage::PlaylistFileImported(res));
+}
+
+/// Library songs a broken playlist entry might have pointed to. Reorganising a library
+/// usually keeps file names, so those go first, followed by the best MBID or tag match.
+fn relink_candidates(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    song: &SongInfo,
+) -> Result<Vec<SongInfo>, MpdError> {
+    let path = Path::new(&song.uri);
+    let mut res: Vec<SongInfo> = Vec::new();
+    if let Some(file_name) = path.file_name().and_then(|name| name.to_str()) {
+        let file_name = file_name.to_lowercase();
+        res.extend(
+            client
+                .search(
+                    Query::new().and(Term::File, file_name.as_str()),
+                    Window::from((0, HISTORY_MATCH_CANDIDATES)),
+                )?
+                .into_iter()
+                .map(SongInfo::from)
+                .filter(|candidate| {
+                    Path::new(&candidate.uri)
+                        .file_name()
+                        .is_some_and(|name| name.to_string_lossy().to_lowercase() == file_name)
+                })
+                .take(RELINK_CANDIDATES),
+        );
+    }
+    // Songs missing from the database come with whatever tags the playlist kept, which
+    // usually means none, in which case their title is just the file name.
+    let stem = path.file_stem().and_then(|stem| stem.to_str());
+    let entry = PlaylistEntry {
+        location: song.uri.clone(),
+        title: Some(song.title.clone()).filter(|title| Some(title.as_str()) != stem),
+        artist: song.artist_tag.clone(),
+        album: song.album.as_ref().map(|album| album.title.clone()),
+        duration: song.duration.map(|dur| dur.as_secs()),
+    };
+    let recording_mbid = song.get_mbid().map(str::to_owned);
+    if let Some((artist, title)) = playlist_file::search_tags(&entry) {
+        let listen = ImportedListen {
+            artist,
+            title,
+            album: entry.album,
+            recording_mbid,
+            duration: entry.duration,
+            ..Default::default()
+        };
+        if let Some(found) = match_listen(client, &listen)? {
+            if !res.iter().any(|candidate| candidate.uri == found.uri) {
+                res.push(found);
+            }
+        }
+    }
+    Ok(res)
+}
+
+fn check_playlist_entries(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    name: String,
+) -> Result<PlaylistCheckReport, MpdError> {
+    let songs: Vec<SongInfo> = client
+        .playlist(&name, Option::<Range<u32>>::None)?
+        .into_iter()
+        .map(SongInfo::from)
+        .collect();
+    let mut report = PlaylistCheckReport {
+        name,
+        uris: songs.iter().map(|song| song.uri.clone()).collect(),
+        ..Default::default()
+    };
+    let mut first_pos: FxHashMap<String, u32> = FxHashMap::default();
+    for (pos, song) in songs.into_iter().enumerate() {
+        let pos = pos as u32;
+        if let Some(first) = first_pos.get(&song.uri) {
+            report.duplicates.push(DuplicateEntry {
+                pos,
+                first_pos: *first,
+                song,
+            });
+            continue;
+        }
+        first_pos.insert(song.uri.clone(), pos);
+        // Streams are never in the database. Otherwise MPD still lists songs it no longer
+        // has, only without tags, so look each one up to be sure.
+        if playlist_file::is_url(&song.uri) || !fetch_songs_by_uri(client, &[song.uri.as_str()])?.is_empty() {
+            continue;
+        }
+        report.broken.push(BrokenEntry {
+            pos,
+            candidates: relink_candidates(client, &song)?,
+            uri: song.uri,
+        });
+    }
+    Ok(report)
+}
+
+pub fn check_playlist(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    name: String,
+) {
+    let res = check_playlist_entries(client, name).map_err(|e| e.to_string());
+    let _ = sender_to_fg.send_blocking(AsyncClientMessage::PlaylistChecked(res));
+}
+
 pub fn play_at(
     client: &mut mpd::Client<stream::StreamWrapper>,
     id_or_pos: u32,
@@ -792,6 +2119,29 @@ pub fn play_at(
     }
 }
 
+/// Rebuild a saved queue after the current one has been cleared.
+pub fn restore_queue(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    snapshot: QueueSnapshot,
+) {
+    add_multi(client, sender_to_fg, &snapshot.uris, false, None, None);
+    if let Some(pos) = snapshot.pos {
+        // Seeking starts playback, so pause again if we weren't playing back then.
+        let res = client.seek(pos, snapshot.elapsed).and_then(|_| {
+            if snapshot.playing {
+                Ok(())
+            } else {
+                client.pause(true)
+            }
+        });
+        if let Err(e) = res {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(e, None));
+        }
+    }
+    let _ = sender_to_fg.send_blocking(AsyncClientMessage::QueueRestored);
+}
+
 pub fn find_add(
     client: &mut mpd::Client<stream::StreamWrapper>,
     sender_to_fg: &Sender<AsyncClientMessage>,
@@ -858,7 +2208,47 @@ pub fn add_multi(
             let _ = sender_to_fg.send_blocking(AsyncClientMessage::Queuing(false));
         }
         Err(MpdError::Io(_)) => {
-            let _ = sender_to_fg.send_blocking(AsyncClientMessage::Connect);
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::ConnectionLost);
+        }
+        Err(mpd_error) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, Some(ClientError::Queuing)));
+        }
+    }
+}
+
+pub fn add_up_next(
+    client: &mut mpd::Client<stream::StreamWrapper>,
+    sender_to_fg: &Sender<AsyncClientMessage>,
+    uris: &[String],
+    pos: u32,
+    first: bool,
+) {
+    if uris.is_empty() {
+        return;
+    }
+    let _ = sender_to_fg.send_blocking(AsyncClientMessage::Queuing(true));
+    // Insert one by one as we need the ID of each new song to raise its priority.
+    let mut ids: Vec<u32> = Vec::with_capacity(uris.len());
+    let mut res: Result<(), MpdError> = Ok(());
+    for (offset, uri) in uris.iter().enumerate() {
+        match client.insert(uri, pos as usize + offset) {
+            Ok(id) => ids.push(id as u32),
+            Err(e) => {
+                res = Err(e);
+                break;
+            }
+        }
+    }
+    // Report those that made it in even if the rest failed.
+    if !ids.is_empty() {
+        let _ = sender_to_fg.send_blocking(AsyncClientMessage::UpNextQueued(ids, first));
+    }
+    match res {
+        Ok(()) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::Queuing(false));
+        }
+        Err(MpdError::Io(_)) => {
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::ConnectionLost);
         }
         Err(mpd_error) => {
             let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, Some(ClientError::Queuing)));
@@ -886,7 +2276,7 @@ pub fn load_playlist(
             let _ = sender_to_fg.send_blocking(AsyncClientMessage::Queuing(false));
         }
         Err(MpdError::Io(_)) => {
-            let _ = sender_to_fg.send_blocking(AsyncClientMessage::Connect);
+            let _ = sender_to_fg.send_blocking(AsyncClientMessage::ConnectionLost);
         }
         Err(mpd_error) => {
             let _ = sender_to_fg.send_blocking(AsyncClientMessage::BackgroundError(mpd_error, Some(ClientError::Queuing)));
@@ -1,24 +1,40 @@
 mod stream;
 mod background;
+mod undo;
 pub mod state;
 pub mod wrapper;
 pub mod password;
+pub mod profile;
+
+use std::path::PathBuf;
 
 use mpd::{lsinfo::LsInfoEntry, Query, Subsystem, error::Error as MpdError};
 pub use state::{ClientState, ConnectionState, ClientError};
 pub use wrapper::MpdWrapper;
 
-use crate::common::{AlbumInfo, ArtistInfo, SongInfo};
+use crate::{
+    common::{
+        AlbumInfo, ArtistInfo, FilterExpr, ListeningStats, PlaylistCheckReport, QueueSnapshot,
+        SmartPlaylist, SongInfo, StatsRange, StickerRestoreReport,
+    },
+    history::ImportReport,
+    playlist_file::{ExportOptions, PlaylistImportReport},
+};
 
 // Messages to be sent from child thread or synchronous methods
 enum AsyncClientMessage {
     Connect, // Host and port are always read from gsettings
     Disconnect,
+    ConnectionLost, // An established connection has dropped. Starts automatic reconnection.
+    Reconnect(u64), // Scheduled automatic reconnection attempt, tagged with its generation
     Status(usize), // Number of pending background tasks
     Idle(Vec<Subsystem>), // Will only be sent from the child thread
     QueueSongsDownloaded(Vec<SongInfo>),
+    QueueDownloaded, // All batches of the current queue have been sent
     QueueChangesReceived(Vec<SongInfo>),
     Queuing(bool),  // Set queuing state
+    QueueRestored, // A saved queue has been queued back (see BackgroundTask::RestoreQueue)
+    UpNextQueued(Vec<u32>, bool), // Queue IDs of newly added up-next songs & whether they go first
     AlbumBasicInfoDownloaded(AlbumInfo), // Return new album to be added to the list model (as SongInfo of a random song in it).
     RecentAlbumDownloaded(AlbumInfo),
     AlbumSongInfoDownloaded(String, Vec<SongInfo>), // Return songs in the album with the given tag (batched)
@@ -29,6 +45,23 @@ enum AsyncClientMessage {
     FolderContentsDownloaded(String, Vec<LsInfoEntry>),
     PlaylistSongInfoDownloaded(String, Vec<SongInfo>),
     RecentSongInfoDownloaded(Vec<SongInfo>),
+    ResumableSongInfoDownloaded(Vec<SongInfo>), // Songs with a saved position (elapsed sticker)
+    ListeningStatsDownloaded(ListeningStats),
+    HistoryExported(Result<usize, String>), // Number of exported listens or error detail
+    HistoryImported(Result<ImportReport, String>),
+    StickersBackedUp(Result<usize, String>), // Number of backed up stickers or error detail
+    StickersRestored(Result<StickerRestoreReport, String>),
+    PlaylistFileExported(Result<usize, String>), // Number of exported songs or error detail
+    PlaylistFileImported(Result<PlaylistImportReport, String>),
+    PlaylistChecked(Result<PlaylistCheckReport, String>),
+    // Global search results, tagged with the search term they belong to
+    SearchSongInfoDownloaded(String, Vec<SongInfo>),
+    SearchAlbumDownloaded(String, AlbumInfo),
+    SearchArtistDownloaded(String, ArtistInfo),
+    // Songs matching a filter expression, tagged with its textual form
+    FilterSongInfoDownloaded(String, Vec<SongInfo>),
+    FilterFailed(String, String), // Expression & error detail
+    SmartPlaylistSongInfoDownloaded(String, Vec<SongInfo>), // All songs of the smart playlist with the given name
     DBUpdated,
     // Generic background error, with an optional Euphonica-specific hint
     BackgroundError(MpdError, Option<ClientError>)
@@ -43,6 +76,10 @@ pub enum BackgroundTask {
     QueueUris(Vec<String>, bool, Option<u32>, Option<u32>),
     QueueQuery(Query<'static>, Option<u32>),  // Optional position to start playing from
     QueuePlaylist(String, Option<u32>),
+    RestoreQueue(QueueSnapshot), // Queue the saved songs, then seek to the saved position
+    // Insert songs at the given queue position for the up-next list, which will be
+    // placed before (true) or after (false) the existing up-next songs.
+    QueueUpNext(Vec<String>, u32, bool),
     DownloadFolderCover(AlbumInfo),
     DownloadEmbeddedCover(SongInfo),
     FetchQueue,  // Full fetch
@@ -57,4 +94,19 @@ pub enum BackgroundTask {
     FetchArtistAlbums(String), // Get all albums of an artist with given name
     FetchPlaylistSongs(String), // Get songs of playlist with given name
     FetchRecentSongs(u32), // Get last n songs
+    FetchResumableSongs, // Get songs with a saved position
+    FetchListeningStats(StatsRange), // Aggregate the listening history over the given range
+    ExportHistory(PathBuf), // Format is decided by the file extension
+    ImportHistory(PathBuf),
+    BackupStickers(PathBuf),
+    RestoreStickers(PathBuf, bool), // Backup file & whether this is a dry run
+    // Stored playlist name (None for the queue), destination file & options.
+    // Format is decided by the file extension.
+    ExportPlaylistFile(Option<String>, PathBuf, ExportOptions),
+    ImportPlaylistFile(PathBuf), // Saved as a new stored playlist named after the file
+    CheckPlaylist(String), // Find broken & duplicate entries in the stored playlist with given name
+    Search(String, u32), // Search the whole library for a term, returning at most n songs
+    FetchFilterSongs(FilterExpr), // Get all songs matching a filter expression
+    EvaluateSmartPlaylist(SmartPlaylist),
+    FillRadio(SongInfo, Vec<String>, u32, u32), // Seed song, URIs to avoid, number of songs to add & no-repeat window
 }
@@ -6,35 +6,53 @@ use crate::config::APPLICATION_ID;
 pub fn get_mpd_password_schema() -> Schema {
     let mut attributes = HashMap::new();
     attributes.insert("type", SchemaAttributeType::String);
+    attributes.insert("profile", SchemaAttributeType::String);
 
     Schema::new(APPLICATION_ID, SchemaFlags::NONE, attributes)
 }
 
-pub async fn get_mpd_password() -> Result<Option<String>, String> {
-    let schema = get_mpd_password_schema();
+// Passwords of named connection profiles live in their own slots. The unnamed
+// slot (empty profile name) keeps the original attributes so passwords saved by
+// older versions are still found. A distinct type is needed for profile slots as
+// lookups match on subsets of attributes.
+fn get_attributes(profile: &str) -> HashMap<&str, &str> {
     let mut attributes = HashMap::new();
-    attributes.insert("type", "mpd");
+    if profile.is_empty() {
+        attributes.insert("type", "mpd");
+    } else {
+        attributes.insert("type", "mpd-profile");
+        attributes.insert("profile", profile);
+    }
+    attributes
+}
+
+pub async fn get_mpd_password(profile: &str) -> Result<Option<String>, String> {
+    let schema = get_mpd_password_schema();
 
     libsecret::password_lookup_future(
         Some(&schema),
-        attributes
+        get_attributes(profile)
     )
         .await
         .map(|op| op.map(|gs| gs.as_str().to_owned()))
         .map_err(|ge| format!("{:?}", ge))
 }
 
-pub async fn set_mpd_password(maybe_password: Option<&str>) -> Result<(), String> {
+pub async fn set_mpd_password(profile: &str, maybe_password: Option<&str>) -> Result<(), String> {
     let schema = get_mpd_password_schema();
-    let mut attributes = HashMap::new();
-    attributes.insert("type", "mpd");
+    let attributes = get_attributes(profile);
 
     if let Some(password) = maybe_password {
+        let label = if profile.is_empty() {
+            "Euphonica MPD password".to_owned()
+        } else {
+            format!("Euphonica MPD password ({})", profile)
+        };
         libsecret::password_store_future(
             Some(&schema),
             attributes,
             None,
-            "Euphonica MPD password",
+            &label,
             password
         )
             .await
@@ -0,0 +1,154 @@
+use gtk::{gio, gio::prelude::*, glib::prelude::*};
+
+use crate::utils;
+
+// Saved connection profiles. The "live" connection keys of the client schema
+// (mpd-host, mpd-port, etc.) always hold the values of the active profile, so
+// MpdWrapper and the visualiser keep reading them as before. Switching profiles
+// simply means copying a saved profile into those keys and reconnecting.
+// Passwords are not stored here but in a per-profile libsecret slot
+// (see client::password).
+
+// (name, use-unix-socket, unix-socket, host, port, visualizer-pcm-source)
+type ProfileTuple = (String, bool, String, String, u32, String);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectionProfile {
+    pub name: String,
+    pub use_unix_socket: bool,
+    pub unix_socket: String,
+    pub host: String,
+    pub port: u32,
+    pub viz_source: String, // nick of io.github.htkhiem.Euphonica.pcmsource
+}
+
+impl From<ProfileTuple> for ConnectionProfile {
+    fn from(tuple: ProfileTuple) -> Self {
+        let (name, use_unix_socket, unix_socket, host, port, viz_source) = tuple;
+        Self {
+            name,
+            use_unix_socket,
+            unix_socket,
+            host,
+            port,
+            viz_source,
+        }
+    }
+}
+
+impl ConnectionProfile {
+    /// Snapshot the current connection settings under the given name.
+    pub fn from_current(name: &str) -> Self {
+        let conn = utils::settings_manager().child("client");
+        Self {
+            name: name.to_owned(),
+            use_unix_socket: conn.boolean("mpd-use-unix-socket"),
+            unix_socket: conn.string("mpd-unix-socket").to_string(),
+            host: conn.string("mpd-host").to_string(),
+            port: conn.uint("mpd-port"),
+            viz_source: conn.string("mpd-visualizer-pcm-source").to_string(),
+        }
+    }
+
+    fn to_tuple(&self) -> ProfileTuple {
+        (
+            self.name.clone(),
+            self.use_unix_socket,
+            self.unix_socket.clone(),
+            self.host.clone(),
+            self.port,
+            self.viz_source.clone(),
+        )
+    }
+
+    fn write_to(&self, conn: &gio::Settings) {
+        let _ = conn.set_boolean("mpd-use-unix-socket", self.use_unix_socket);
+        let _ = conn.set_string("mpd-unix-socket", &self.unix_socket);
+        let _ = conn.set_string("mpd-host", &self.host);
+        let _ = conn.set_uint("mpd-port", self.port);
+        if conn
+            .set_string("mpd-visualizer-pcm-source", &self.viz_source)
+            .is_err()
+        {
+            println!(
+                "Profile {} has an invalid visualiser source: {}",
+                &self.name, &self.viz_source
+            );
+        }
+    }
+
+    /// Human-readable address of this profile, for use as subtitles.
+    pub fn get_address(&self) -> String {
+        if self.use_unix_socket {
+            self.unix_socket.clone()
+        } else {
+            format!("{}:{}", &self.host, self.port)
+        }
+    }
+}
+
+pub fn get_profiles() -> Vec<ConnectionProfile> {
+    utils::settings_manager()
+        .child("client")
+        .value("mpd-profiles")
+        .get::<Vec<ProfileTuple>>()
+        .unwrap_or_default()
+        .into_iter()
+        .map(ConnectionProfile::from)
+        .collect()
+}
+
+pub fn get_profile(name: &str) -> Option<ConnectionProfile> {
+    get_profiles().into_iter().find(|p| p.name == name)
+}
+
+fn set_profiles(profiles: &[ConnectionProfile]) {
+    let tuples: Vec<ProfileTuple> = profiles.iter().map(ConnectionProfile::to_tuple).collect();
+    utils::settings_manager()
+        .child("client")
+        .set_value("mpd-profiles", &tuples.to_variant())
+        .expect("Unable to save connection profiles");
+}
+
+/// Add a new profile or overwrite an existing one with the same name.
+pub fn save_profile(profile: ConnectionProfile) {
+    let mut profiles = get_profiles();
+    if let Some(existing) = profiles.iter_mut().find(|p| p.name == profile.name) {
+        *existing = profile;
+    } else {
+        profiles.push(profile);
+    }
+    set_profiles(&profiles);
+}
+
+/// Remove a profile. The current connection settings are kept as-is even if
+/// the removed profile was the active one. Its password slot should be cleared
+/// separately with set_mpd_password(name, None).
+pub fn delete_profile(name: &str) {
+    let mut profiles = get_profiles();
+    profiles.retain(|p| p.name != name);
+    set_profiles(&profiles);
+    let conn = utils::settings_manager().child("client");
+    if conn.string("mpd-active-profile").as_str() == name {
+        let _ = conn.set_string("mpd-active-profile", "");
+    }
+}
+
+/// Copy the given profile into the live connection keys. Returns false if
+/// no such profile exists. Does not reconnect by itself.
+pub fn apply_profile(name: &str) -> bool {
+    if let Some(profile) = get_profile(name) {
+        profile.write_to(&utils::settings_manager().child("client"));
+        true
+    } else {
+        false
+    }
+}
+
+/// Name of the active profile. Empty if none.
+pub fn get_active_profile_name() -> String {
+    utils::settings_manager()
+        .child("client")
+        .string("mpd-active-profile")
+        .to_string()
+}
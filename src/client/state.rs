@@ -20,6 +20,7 @@ pub enum ConnectionState {
     PasswordNotAvailable, // No password was provided but we need one
     CredentialStoreError, // Internal error
     WrongPassword,   // The provided password does not match any of the configured passwords
+    Reconnecting,    // Lost an established connection and retrying in the background
     Connected,
 }
 
@@ -40,7 +41,7 @@ pub enum ClientError {
 }
 
 mod imp {
-    use glib::{ParamSpec, ParamSpecBoolean, ParamSpecEnum, ParamSpecUInt64};
+    use glib::{ParamSpec, ParamSpecBoolean, ParamSpecEnum, ParamSpecUInt, ParamSpecUInt64};
 
     use super::*;
     use once_cell::sync::Lazy;
@@ -53,6 +54,10 @@ mod imp {
         pub supports_playlists: Cell<bool>,
         pub queuing: Cell<bool>,
         pub stickers_support_level: Cell<StickersSupportLevel>,
+        // Number of the current automatic reconnection attempt. 0 when not reconnecting.
+        pub reconnect_attempt: Cell<u32>,
+        pub can_undo: Cell<bool>,
+        pub can_redo: Cell<bool>,
     }
 
     #[glib::object_subclass]
@@ -66,7 +71,10 @@ mod imp {
                 n_tasks: Cell::new(0),
                 stickers_support_level: Cell::default(),
                 supports_playlists: Cell::new(true),
-                queuing: Cell::new(false)
+                queuing: Cell::new(false),
+                reconnect_attempt: Cell::new(0),
+                can_undo: Cell::new(false),
+                can_redo: Cell::new(false)
             }
         }
     }
@@ -88,6 +96,15 @@ mod imp {
                     ParamSpecEnum::builder::<ConnectionState>("connection-state")
                         .read_only()
                         .build(),
+                    ParamSpecUInt::builder("reconnect-attempt")
+                        .read_only()
+                        .build(),
+                    ParamSpecBoolean::builder("can-undo")
+                        .read_only()
+                        .build(),
+                    ParamSpecBoolean::builder("can-redo")
+                        .read_only()
+                        .build(),
                 ]
             });
             PROPERTIES.as_ref()
@@ -101,6 +118,9 @@ mod imp {
                 "stickers-support-level" => obj.get_stickers_support_level().to_value(),
                 "supports-playlists" => obj.supports_playlists().to_value(),
                 "is-queuing" => self.queuing.get().to_value(),
+                "reconnect-attempt" => self.reconnect_attempt.get().to_value(),
+                "can-undo" => self.can_undo.get().to_value(),
+                "can-redo" => self.can_redo.get().to_value(),
                 _ => unimplemented!(),
             }
         }
@@ -119,6 +139,12 @@ mod imp {
                             BoxedAnyObject::static_type(), // Vec<PosIdChange> (diff only)
                         ])
                         .build(),
+                    Signal::builder("up-next-queued")
+                        .param_types([
+                            BoxedAnyObject::static_type(), // Vec<u32> of queue IDs
+                            bool::static_type(),           // whether they go first
+                        ])
+                        .build(),
                     Signal::builder("album-art-downloaded")
                         .param_types([
                             String::static_type(),         // folder URI
@@ -179,6 +205,88 @@ mod imp {
                             BoxedAnyObject::static_type(), // Vec<Song>
                         ])
                         .build(),
+                    Signal::builder("resumable-songs-downloaded")
+                        .param_types([
+                            BoxedAnyObject::static_type(), // Vec<Song>
+                        ])
+                        .build(),
+                    Signal::builder("listening-stats-downloaded")
+                        .param_types([
+                            BoxedAnyObject::static_type(), // ListeningStats
+                        ])
+                        .build(),
+                    Signal::builder("history-exported")
+                        .param_types([
+                            BoxedAnyObject::static_type(), // Result<usize, String>
+                        ])
+                        .build(),
+                    Signal::builder("history-imported")
+                        .param_types([
+                            BoxedAnyObject::static_type(), // Result<ImportReport, String>
+                        ])
+                        .build(),
+                    Signal::builder("stickers-backed-up")
+                        .param_types([
+                            BoxedAnyObject::static_type(), // Result<usize, String>
+                        ])
+                        .build(),
+                    Signal::builder("stickers-restored")
+                        .param_types([
+                            BoxedAnyObject::static_type(), // Result<StickerRestoreReport, String>
+                        ])
+                        .build(),
+                    Signal::builder("playlist-file-exported")
+                        .param_types([
+                            BoxedAnyObject::static_type(), // Result<usize, String>
+                        ])
+                        .build(),
+                    Signal::builder("playlist-file-imported")
+                        .param_types([
+                            BoxedAnyObject::static_type(), // Result<PlaylistImportReport, String>
+                        ])
+                        .build(),
+                    Signal::builder("playlist-checked")
+                        .param_types([
+                            BoxedAnyObject::static_type(), // Result<PlaylistCheckReport, String>
+                        ])
+                        .build(),
+                    // An undoable queue or playlist edit was made. Carries its label.
+                    Signal::builder("edit-recorded")
+                        .param_types([String::static_type()])
+                        .build(),
+                    // Global search results. The first parameter is the search term
+                    // these results are for.
+                    Signal::builder("search-songs-downloaded")
+                        .param_types([
+                            String::static_type(),
+                            BoxedAnyObject::static_type(), // Vec<Song>
+                        ])
+                        .build(),
+                    Signal::builder("search-album-downloaded")
+                        .param_types([String::static_type(), Album::static_type()])
+                        .build(),
+                    Signal::builder("search-artist-downloaded")
+                        .param_types([String::static_type(), Artist::static_type()])
+                        .build(),
+                    // Songs matching a filter expression (keyed by its textual form)
+                    Signal::builder("filter-songs-downloaded")
+                        .param_types([
+                            String::static_type(),
+                            BoxedAnyObject::static_type(), // Vec<Song>
+                        ])
+                        .build(),
+                    Signal::builder("filter-failed")
+                        .param_types([
+                            String::static_type(), // expression
+                            String::static_type(), // error detail
+                        ])
+                        .build(),
+                    Signal::builder("smart-playlist-songs-downloaded")
+                        .param_types([
+                            String::static_type(), // smart playlist name
+                            BoxedAnyObject::static_type(), // Vec<Song>
+                        ])
+                        .build(),
                     Signal::builder("client-error")
                         .param_types([
                             ClientError::static_type()
@@ -188,7 +296,8 @@ mod imp {
                         .param_types([
                             BoxedAnyObject::static_type(), // Vec<Song>
                         ])
-                        .build()
+                        .build(),
+                    Signal::builder("queue-downloaded").build()
                 ]
             })
         }
@@ -217,6 +326,17 @@ impl ClientState {
         }
     }
 
+    pub fn get_reconnect_attempt(&self) -> u32 {
+        self.imp().reconnect_attempt.get()
+    }
+
+    pub fn set_reconnect_attempt(&self, attempt: u32) {
+        let old = self.imp().reconnect_attempt.replace(attempt);
+        if old != attempt {
+            self.notify("reconnect-attempt");
+        }
+    }
+
     // Convenience emit wrappers
     pub fn emit_result<T: ToValue>(&self, signal_name: &str, val: T) {
         self.emit_by_name::<()>(signal_name, &[&val])
@@ -274,4 +394,26 @@ impl ClientState {
             self.notify("is-queuing");
         }
     }
+
+    pub fn can_undo(&self) -> bool {
+        self.imp().can_undo.get()
+    }
+
+    pub fn set_can_undo(&self, new: bool) {
+        let old = self.imp().can_undo.replace(new);
+        if old != new {
+            self.notify("can-undo");
+        }
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.imp().can_redo.get()
+    }
+
+    pub fn set_can_redo(&self, new: bool) {
+        let old = self.imp().can_redo.replace(new);
+        if old != new {
+            self.notify("can-redo");
+        }
+    }
 }
@@ -0,0 +1,245 @@
+use std::{borrow::Cow, collections::VecDeque, ops::Range};
+
+use mpd::{
+    client::Client,
+    error::{Error as MpdError, ErrorCode},
+    proto::Proto,
+    search::Window,
+    EditAction, State,
+};
+
+use crate::common::{queue_moves, QueueMove};
+
+use super::stream::StreamWrapper;
+
+// Undo history of queue & stored playlist edits made through this client. Each step
+// holds the edit that reverts a change rather than the change itself, so undoing is just
+// applying that edit, which in turn yields the edit to redo it with (see MpdWrapper).
+// Edits made by other clients are not tracked, so positions are trusted as-is.
+
+// Older steps are dropped beyond this many.
+const MAX_UNDO_STEPS: usize = 50;
+
+#[derive(Debug, Clone)]
+pub enum Edit {
+    /// Add a song at a queue position.
+    QueueInsert(u32, String),
+    /// Remove the song at a queue position.
+    QueueDelete(u32),
+    /// Swap the songs at two queue positions.
+    QueueSwap(u32, u32),
+    /// Add songs at queue positions, in ascending order.
+    QueueInsertMany(Vec<(u32, String)>),
+    /// Remove the songs at the given sorted queue positions.
+    QueueDeleteMany(Vec<u32>),
+    /// Move ranges of songs around the queue, in order.
+    QueueMoves(Vec<QueueMove>),
+    /// Replace the whole queue, optionally playing from the given position.
+    QueueReplace(Vec<String>, Option<u32>),
+    /// Set the contents of a stored playlist, or delete it if None.
+    Playlist(String, Option<Vec<String>>),
+}
+
+#[derive(Debug, Clone)]
+pub struct UndoStep {
+    /// Describes the change this step reverts, such as "Queue cleared".
+    pub label: String,
+    pub revert: Edit,
+}
+
+#[derive(Debug, Default)]
+pub struct EditHistory {
+    undo: VecDeque<UndoStep>,
+    redo: Vec<UndoStep>,
+}
+
+impl EditHistory {
+    /// Record a new change. Anything previously undone can no longer be redone.
+    pub fn push(&mut self, step: UndoStep) {
+        self.redo.clear();
+        self.push_undo(step);
+    }
+
+    /// Record a redone change without touching the redo stack.
+    pub fn push_undo(&mut self, step: UndoStep) {
+        self.undo.push_back(step);
+        if self.undo.len() > MAX_UNDO_STEPS {
+            self.undo.pop_front();
+        }
+    }
+
+    pub fn push_redo(&mut self, step: UndoStep) {
+        self.redo.push(step);
+    }
+
+    pub fn pop_undo(&mut self) -> Option<UndoStep> {
+        self.undo.pop_back()
+    }
+
+    pub fn pop_redo(&mut self) -> Option<UndoStep> {
+        self.redo.pop()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+/// Send the given commands as a single command list, so that MPD applies them in one go.
+fn command_list(
+    client: &mut Client<StreamWrapper>,
+    commands: &[(&str, String, Option<u32>)],
+) -> Result<(), MpdError> {
+    client.run_command("command_list_begin", ())?;
+    for (command, range, to) in commands.iter() {
+        match to {
+            Some(to) => client.run_command(command, (range.as_str(), *to))?,
+            None => client.run_command(command, range.as_str())?,
+        }
+    }
+    client.run_command("command_list_end", ())?;
+    client.expect_ok()
+}
+
+/// Edit that restores the queue to its current state, including the playing song.
+pub fn snapshot_queue(client: &mut Client<StreamWrapper>) -> Result<Edit, MpdError> {
+    let status = client.status()?;
+    let uris: Vec<String> = if status.queue_len > 0 {
+        client
+            .queue(Window::from((0, status.queue_len)))?
+            .into_iter()
+            .map(|song| song.file)
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let playing = status
+        .song
+        .filter(|_| status.state == State::Play)
+        .map(|place| place.pos);
+    Ok(Edit::QueueReplace(uris, playing))
+}
+
+/// Edit that restores a stored playlist to its current contents, or deletes it if
+/// it doesn't exist yet.
+pub fn snapshot_playlist(client: &mut Client<StreamWrapper>, name: &str) -> Result<Edit, MpdError> {
+    match client.playlist(name, Option::<Range<u32>>::None) {
+        Ok(songs) => Ok(Edit::Playlist(
+            name.to_owned(),
+            Some(songs.into_iter().map(|song| song.file).collect()),
+        )),
+        Err(MpdError::Server(e)) if e.code == ErrorCode::NoExist => {
+            Ok(Edit::Playlist(name.to_owned(), None))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Apply an edit, returning the edit that reverts it. None if there was nothing to do.
+pub fn apply(client: &mut Client<StreamWrapper>, edit: Edit) -> Result<Option<Edit>, MpdError> {
+    match edit {
+        Edit::QueueInsert(pos, uri) => {
+            client.insert(&uri, pos as usize)?;
+            Ok(Some(Edit::QueueDelete(pos)))
+        }
+        Edit::QueueDelete(pos) => {
+            let Some(song) = client.songs(pos)?.pop() else {
+                return Ok(None);
+            };
+            client.delete(pos)?;
+            Ok(Some(Edit::QueueInsert(pos, song.file)))
+        }
+        Edit::QueueSwap(a, b) => {
+            client.swap(a, b)?;
+            Ok(Some(Edit::QueueSwap(a, b)))
+        }
+        Edit::QueueInsertMany(songs) => {
+            for (pos, uri) in songs.iter() {
+                client.insert(uri, *pos as usize)?;
+            }
+            Ok(Some(Edit::QueueDeleteMany(
+                songs.into_iter().map(|(pos, _)| pos).collect(),
+            )))
+        }
+        Edit::QueueDeleteMany(positions) => {
+            let (Some(first), Some(last)) = (positions.first(), positions.last()) else {
+                return Ok(None);
+            };
+            let first = *first;
+            let songs = client.queue(Window::from((first, last + 1)))?;
+            let removed: Vec<(u32, String)> = positions
+                .iter()
+                .filter_map(|pos| {
+                    songs
+                        .get((pos - first) as usize)
+                        .map(|song| (*pos, song.file.clone()))
+                })
+                .collect();
+            // Delete from the back so that earlier positions stay valid.
+            let commands: Vec<(&str, String, Option<u32>)> = queue_moves::to_ranges(&positions)
+                .into_iter()
+                .rev()
+                .map(|range| ("delete", format!("{}:{}", range.start, range.end), None))
+                .collect();
+            command_list(client, &commands)?;
+            Ok(Some(Edit::QueueInsertMany(removed)))
+        }
+        Edit::QueueMoves(moves) => {
+            if moves.is_empty() {
+                return Ok(None);
+            }
+            let commands: Vec<(&str, String, Option<u32>)> = moves
+                .iter()
+                .map(|m| ("move", format!("{}:{}", m.start, m.end), Some(m.to)))
+                .collect();
+            command_list(client, &commands)?;
+            Ok(Some(Edit::QueueMoves(
+                moves.iter().rev().map(QueueMove::inverse).collect(),
+            )))
+        }
+        Edit::QueueReplace(uris, playing) => {
+            let revert = snapshot_queue(client)?;
+            client.clear()?;
+            if !uris.is_empty() {
+                client.push_multiple(&uris)?;
+            }
+            if let Some(pos) = playing {
+                client.switch(pos)?;
+            }
+            Ok(Some(revert))
+        }
+        Edit::Playlist(name, uris) => {
+            let revert = snapshot_playlist(client, &name)?;
+            let exists = matches!(&revert, Edit::Playlist(_, Some(_)));
+            match uris {
+                None => {
+                    if exists {
+                        client.pl_remove(&name)?;
+                    }
+                }
+                Some(uris) => {
+                    let mut edits: Vec<EditAction> = Vec::with_capacity(uris.len() + 1);
+                    if exists {
+                        edits.push(EditAction::Clear(Cow::Borrowed(name.as_str())));
+                    }
+                    edits.extend(uris.iter().map(|uri| {
+                        EditAction::Add(Cow::Borrowed(name.as_str()), Cow::Borrowed(uri.as_str()), None)
+                    }));
+                    if !edits.is_empty() {
+                        client.pl_edit(&edits)?;
+                    }
+                }
+            }
+            Ok(Some(revert))
+        }
+    }
+}
@@ -1,5 +1,6 @@
 use async_channel::{Receiver, Sender};
 use futures::executor;
+use gettextrs::{gettext, ngettext};
 use glib::clone;
 use gtk::{gio, glib};
 use gtk::{gio::prelude::*, glib::BoxedAnyObject};
@@ -9,23 +10,27 @@ use mpd::{
     client::Client,
     error::{Error as MpdError, ErrorCode as MpdErrorCode},
     lsinfo::LsInfoEntry,
+    proto::Proto,
+    search::Window,
     song::Id,
-    Channel, EditAction, Idle, Output, SaveMode, Subsystem,
+    Channel, EditAction, Idle, Output, SaveMode, State, Subsystem,
 };
 
 use std::net::TcpStream;
 use std::os::unix::net::UnixStream;
 use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use std::{
     cell::{Cell, RefCell},
     rc::Rc
 };
 use uuid::Uuid;
 
-use crate::common::Stickers;
+use crate::common::{Stickers, Thumbs};
 use crate::{
-    common::{Album, AlbumInfo, Artist, INode, Song, SongInfo},
+    cache::sqlite,
+    common::{Album, AlbumInfo, Artist, INode, QueueMove, QueueSnapshot, Song, SongInfo},
     meta_providers::ProviderMessage,
     player::PlaybackFlow,
     utils,
@@ -35,9 +40,28 @@ use super::{AsyncClientMessage, BackgroundTask};
 use super::state::{ClientState, ConnectionState, StickersSupportLevel};
 use super::stream::StreamWrapper;
 use super::password::get_mpd_password;
+use super::profile;
 use super::background;
+use super::undo::{self, Edit, EditHistory, UndoStep};
 use super::ClientError;
 
+pub const DEFAULT_PARTITION: &str = "default";
+
+// Automatic reconnection backoff. The nth attempt waits for a random duration between
+// half of and the full min(RECONNECT_BASE_DELAY_S * 2^(n-1), RECONNECT_MAX_DELAY_S)
+// seconds, such that several clients reconnecting to a restarted daemon won't hit it
+// at the exact same time.
+const RECONNECT_BASE_DELAY_S: f64 = 1.0;
+const RECONNECT_MAX_DELAY_S: f64 = 60.0;
+
+fn get_reconnect_delay(attempt: u32) -> Duration {
+    let ceiling = (RECONNECT_BASE_DELAY_S * 2f64.powi(attempt.saturating_sub(1).min(16) as i32))
+        .min(RECONNECT_MAX_DELAY_S);
+    // Reuse the UUID crate's RNG for jitter
+    let jitter = (Uuid::new_v4().as_u128() % 1000) as f64 / 1000.0;
+    Duration::from_secs_f64(ceiling * (0.5 + 0.5 * jitter))
+}
+
 // Thin wrapper around the blocking mpd::Client. It contains two separate client
 // objects connected to the same address. One lives on the main thread along
 // with the GUI and takes care of sending user commands to the daemon, while the
@@ -89,6 +113,14 @@ use super::ClientError;
 // incidentally, disconnecting the main thread's client will send an idle message,
 // unblocking the child thread and allowing it to check the flag.
 
+// When an established connection drops (daemon restarted, network hiccup, etc.),
+// we don't go through the usual Connecting state, as that would clear all views.
+// Instead, both clients are closed and the state is set to Reconnecting while
+// attempts are scheduled with exponential backoff. Once one succeeds, views are
+// silently brought up to date by replaying the same handlers as idle updates.
+// Each scheduled attempt carries a generation number. Manual connections bump
+// it, making all in-flight attempts obsolete.
+
 #[derive(Debug)]
 pub struct MpdWrapper {
     // Corresponding sender, for cloning into child thread.
@@ -118,7 +150,15 @@ pub struct MpdWrapper {
     // expected_queue version, we are out of sync and must perform a refresh
     // using the old logic. Else do nothing.
     queue_version: Cell<u32>,
-    expected_queue_version: Cell<u32>
+    expected_queue_version: Cell<u32>,
+    reconnect_generation: Cell<u64>,
+    // Queue & playlist edits made through this client, for undoing.
+    edit_history: RefCell<EditHistory>,
+    // Set while an undone or redone queue is being queued back in the background.
+    // Edits can't be undone meanwhile as the queue isn't what they expect yet.
+    restoring_queue: Cell<bool>,
+    // Kept around so that our profile-switching handler stays connected.
+    conn_settings: gio::Settings
 }
 
 impl MpdWrapper {
@@ -139,11 +179,16 @@ impl MpdWrapper {
             pending_idle: Arc::new(AtomicBool::new(false)),
             meta_sender,
             queue_version: Cell::new(0),
-            expected_queue_version: Cell::new(0)
+            expected_queue_version: Cell::new(0),
+            reconnect_generation: Cell::new(0),
+            edit_history: RefCell::new(EditHistory::default()),
+            restoring_queue: Cell::new(false),
+            conn_settings: utils::settings_manager().child("client")
         });
 
         // For future noob self: these are shallow
         wrapper.clone().setup_channel(receiver);
+        wrapper.clone().setup_profile_switching();
         wrapper
     }
 
@@ -213,6 +258,11 @@ impl MpdWrapper {
                     let _ = sender_to_fg.send_blocking(AsyncClientMessage::Disconnect);
                     return;
                 }
+            // The main client has already validated this partition.
+            let partition = conn.string("mpd-partition");
+            if partition.as_str() != DEFAULT_PARTITION {
+                let _ = client.partition(partition.as_str());
+            }
 
             'outer: loop {
                 let skip_to_idle = pending_idle.load(Ordering::Relaxed);
@@ -296,6 +346,45 @@ impl MpdWrapper {
                         BackgroundTask::FetchRecentSongs(count) => {
                             background::fetch_last_n_songs(&mut client, &sender_to_fg, count);
                         }
+                        BackgroundTask::FetchResumableSongs => {
+                            background::fetch_resumable_songs(&mut client, &sender_to_fg);
+                        }
+                        BackgroundTask::FetchListeningStats(range) => {
+                            background::fetch_listening_stats(&mut client, &sender_to_fg, range);
+                        }
+                        BackgroundTask::ExportHistory(path) => {
+                            background::export_history(&mut client, &sender_to_fg, path);
+                        }
+                        BackgroundTask::ImportHistory(path) => {
+                            background::import_history(&mut client, &sender_to_fg, path);
+                        }
+                        BackgroundTask::BackupStickers(path) => {
+                            background::backup_stickers(&mut client, &sender_to_fg, path);
+                        }
+                        BackgroundTask::RestoreStickers(path, dry_run) => {
+                            background::restore_stickers(&mut client, &sender_to_fg, path, dry_run);
+                        }
+                        BackgroundTask::ExportPlaylistFile(name, path, options) => {
+                            background::export_playlist_file(&mut client, &sender_to_fg, name, path, options);
+                        }
+                        BackgroundTask::ImportPlaylistFile(path) => {
+                            background::import_playlist_file(&mut client, &sender_to_fg, path);
+                        }
+                        BackgroundTask::CheckPlaylist(name) => {
+                            background::check_playlist(&mut client, &sender_to_fg, name);
+                        }
+                        BackgroundTask::Search(term, song_limit) => {
+                            background::search_library(&mut client, &sender_to_fg, term, song_limit);
+                        }
+                        BackgroundTask::FetchFilterSongs(filter) => {
+                            background::fetch_filter_songs(&mut client, &sender_to_fg, filter);
+                        }
+                        BackgroundTask::EvaluateSmartPlaylist(playlist) => {
+                            background::evaluate_smart_playlist(&mut client, &sender_to_fg, playlist);
+                        }
+                        BackgroundTask::FillRadio(seed, exclude, count, no_repeat_window) => {
+                            background::fill_radio(&mut client, &sender_to_fg, seed, exclude, count, no_repeat_window);
+                        }
                         BackgroundTask::QueueUris(uris, recursive, play_from, insert_pos) => {
                             background::add_multi(&mut client, &sender_to_fg, &uris, recursive, play_from, insert_pos);
                         }
@@ -305,6 +394,12 @@ impl MpdWrapper {
                         BackgroundTask::QueuePlaylist(name, play_from) => {
                             background::load_playlist(&mut client, &sender_to_fg, &name, play_from);
                         }
+                        BackgroundTask::RestoreQueue(snapshot) => {
+                            background::restore_queue(&mut client, &sender_to_fg, snapshot);
+                        }
+                        BackgroundTask::QueueUpNext(uris, pos, first) => {
+                            background::add_up_next(&mut client, &sender_to_fg, &uris, pos, first);
+                        }
                     }
                 } else {
                     // If not, go into idle mode
@@ -323,6 +418,15 @@ impl MpdWrapper {
                                             let _ = client.close();
                                             break 'outer;
                                         }
+                                        // Main client has switched partitions. Follow it.
+                                        "PARTITION" => {
+                                            let partition = conn.string("mpd-partition");
+                                            if let Err(e) = client.partition(partition.as_str()) {
+                                                let _ = sender_to_fg.send_blocking(
+                                                    AsyncClientMessage::BackgroundError(e, None)
+                                                );
+                                            }
+                                        }
                                         _ => {}
                                     }
                                 }
@@ -334,7 +438,7 @@ impl MpdWrapper {
                         println!(
                             "Child thread encountered a client error while idling. Stopping..."
                         );
-                        let _ = sender_to_fg.send_blocking(AsyncClientMessage::Disconnect);
+                        let _ = sender_to_fg.send_blocking(AsyncClientMessage::ConnectionLost);
                         break 'outer;
                     }
                 }
@@ -374,21 +478,49 @@ impl MpdWrapper {
             self,
             async move {
                 loop {
+                    let mut ping_failed = false;
                     if let Some(client) = this.main_client.borrow_mut().as_mut() {
-                        let res = client.ping();
-                        if res.is_err() {
+                        if client.ping().is_err() {
                             println!("[KeepAlive] [FATAL] Could not ping mpd. The connection might have already timed out, or the daemon might have crashed.");
-                            break;
+                            ping_failed = true;
                         }
                     }
                     else {
                         println!("[KeepAlive] There is no client currently running. Won't ping.");
                     }
+                    if ping_failed {
+                        let _ = this.main_sender.send_blocking(AsyncClientMessage::ConnectionLost);
+                    }
                     glib::timeout_future_seconds(ping_interval).await;
                 }
             }));
     }
 
+    fn setup_profile_switching(self: Rc<Self>) {
+        // Switching profiles (from the header switcher or Preferences) only changes
+        // this key. Copy the selected profile into the live connection keys, then
+        // reconnect both clients using them.
+        self.conn_settings.connect_changed(
+            Some("mpd-active-profile"),
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |settings, _| {
+                    let name = settings.string("mpd-active-profile");
+                    if name.is_empty() {
+                        return;
+                    }
+                    if profile::apply_profile(name.as_str()) {
+                        println!("Switching to connection profile {}", name.as_str());
+                        this.queue_connect();
+                    } else {
+                        println!("Connection profile {} does not exist", name.as_str());
+                    }
+                }
+            ),
+        );
+    }
+
     async fn respond(&self, request: AsyncClientMessage, recently_connected: bool) -> glib::ControlFlow {
         // println!("Received MpdMessage {:?}", request);
         match request {
@@ -398,10 +530,20 @@ impl MpdWrapper {
                 }
             }
             AsyncClientMessage::Disconnect => self.disconnect_async().await,
+            AsyncClientMessage::ConnectionLost => self.on_connection_lost().await,
+            AsyncClientMessage::Reconnect(generation) => self.reconnect_async(generation).await,
             AsyncClientMessage::Idle(changes) => self.handle_idle_changes(changes).await,
             AsyncClientMessage::QueueSongsDownloaded(songs) => {
                 self.on_songs_downloaded("queue-songs-downloaded", None, songs)
             }
+            AsyncClientMessage::QueueRestored => {
+                if self.restoring_queue.replace(false) {
+                    self.sync_edit_history();
+                }
+            }
+            AsyncClientMessage::QueueDownloaded => {
+                self.state.emit_by_name::<()>("queue-downloaded", &[]);
+            }
             AsyncClientMessage::QueueChangesReceived(changes) => {
                 self.state.emit_boxed_result("queue-changed", changes);
             }
@@ -439,9 +581,90 @@ impl MpdWrapper {
             AsyncClientMessage::Status(n_tasks) => self.state.set_n_background_tasks(n_tasks as u64),
             AsyncClientMessage::RecentSongInfoDownloaded(songs) => self
                 .on_songs_downloaded("recent-songs-downloaded", None, songs),
+            AsyncClientMessage::ResumableSongInfoDownloaded(songs) => self
+                .on_songs_downloaded("resumable-songs-downloaded", None, songs),
+            AsyncClientMessage::ListeningStatsDownloaded(stats) => {
+                self.state.emit_by_name::<()>(
+                    "listening-stats-downloaded",
+                    &[&BoxedAnyObject::new(stats)]
+                );
+            }
+            AsyncClientMessage::HistoryExported(res) => {
+                self.state.emit_by_name::<()>(
+                    "history-exported",
+                    &[&BoxedAnyObject::new(res)]
+                );
+            }
+            AsyncClientMessage::HistoryImported(res) => {
+                self.state.emit_by_name::<()>(
+                    "history-imported",
+                    &[&BoxedAnyObject::new(res)]
+                );
+            }
+            AsyncClientMessage::StickersBackedUp(res) => {
+                self.state.emit_by_name::<()>(
+                    "stickers-backed-up",
+                    &[&BoxedAnyObject::new(res)]
+                );
+            }
+            AsyncClientMessage::StickersRestored(res) => {
+                self.state.emit_by_name::<()>(
+                    "stickers-restored",
+                    &[&BoxedAnyObject::new(res)]
+                );
+            }
+            AsyncClientMessage::PlaylistFileExported(res) => {
+                self.state.emit_by_name::<()>(
+                    "playlist-file-exported",
+                    &[&BoxedAnyObject::new(res)]
+                );
+            }
+            AsyncClientMessage::PlaylistFileImported(res) => {
+                self.state.emit_by_name::<()>(
+                    "playlist-file-imported",
+                    &[&BoxedAnyObject::new(res)]
+                );
+            }
+            AsyncClientMessage::PlaylistChecked(res) => {
+                self.state.emit_by_name::<()>(
+                    "playlist-checked",
+                    &[&BoxedAnyObject::new(res)]
+                );
+            }
+            AsyncClientMessage::SearchSongInfoDownloaded(term, songs) => {
+                self.on_songs_downloaded("search-songs-downloaded", Some(term), songs)
+            }
+            AsyncClientMessage::SearchAlbumDownloaded(term, info) => {
+                self.on_album_downloaded("search-album-downloaded", Some(&term), info)
+            }
+            AsyncClientMessage::SearchArtistDownloaded(term, info) => self
+                .state
+                .emit_by_name::<()>("search-artist-downloaded", &[&term, &Artist::from(info)]),
+            AsyncClientMessage::FilterSongInfoDownloaded(expression, songs) => {
+                self.on_songs_downloaded("filter-songs-downloaded", Some(expression), songs)
+            }
+            AsyncClientMessage::FilterFailed(expression, detail) => self
+                .state
+                .emit_by_name::<()>("filter-failed", &[&expression, &detail]),
+            AsyncClientMessage::SmartPlaylistSongInfoDownloaded(name, songs) => {
+                // Emitted even when empty to signal that evaluation has finished
+                self.state.emit_by_name::<()>(
+                    "smart-playlist-songs-downloaded",
+                    &[
+                        &name,
+                        &BoxedAnyObject::new(songs.into_iter().map(Song::from).collect::<Vec<Song>>()),
+                    ]
+                );
+            }
             AsyncClientMessage::Queuing(block) => {
                 self.state.set_queuing(block);
             }
+            AsyncClientMessage::UpNextQueued(ids, first) => {
+                self.state.emit_by_name::<()>(
+                    "up-next-queued",
+                    &[&BoxedAnyObject::new(ids), &first]
+                );
+            }
             AsyncClientMessage::BackgroundError(error, or) => {
                 self.handle_common_mpd_error(&error, or);
             }
@@ -477,7 +700,7 @@ impl MpdWrapper {
                     // These errors can only happen after we've successfully connected both clients, so
                     // we should attempt a reconnection.
                     println!("[Warning] Lost child client. Reconnecting...");
-                    let _ = self.main_sender.send_blocking(AsyncClientMessage::Connect);
+                    let _ = self.main_sender.send_blocking(AsyncClientMessage::ConnectionLost);
                 }
             if let Some(client) = self.main_client.borrow_mut().as_mut() {
                 // Wake background thread
@@ -497,8 +720,9 @@ impl MpdWrapper {
             .expect("Cannot call reconnection asynchronously");
     }
 
-    async fn disconnect_async(&self) {
-        if let Some(mut main_client) = self.main_client.borrow_mut().take() {
+    async fn close_clients(&self) {
+        let main_client = self.main_client.borrow_mut().take();
+        if let Some(mut main_client) = main_client {
             println!("Closing existing clients");
             // Stop child thread by sending a "STOP" message through mpd itself
             let _ = main_client.sendmessage(self.bg_channel.clone(), "STOP");
@@ -510,20 +734,131 @@ impl MpdWrapper {
             let _ = handle.await;
             println!("Stopped all clients successfully.");
         }
+    }
+
+    async fn disconnect_async(&self) {
+        self.close_clients().await;
         self.state
             .set_connection_state(ConnectionState::NotConnected);
     }
 
     pub async fn connect_async(&self) {
+        // Manual connections supersede any scheduled automatic attempt.
+        self.cancel_reconnection();
         // Close current clients
         self.disconnect_async().await;
-        self.state.set_queuing(false);
         self.queue_version.set(0);
         self.expected_queue_version.set(0);
-
-        let conn = utils::settings_manager().child("client");
+        // Positions recorded against the old server (or state) mean nothing now.
+        // Any restore still underway went down with the old child client.
+        self.restoring_queue.set(false);
+        self.clear_edit_history();
 
         self.state.set_connection_state(ConnectionState::Connecting);
+        let new_state = self.try_connect().await;
+        self.state.set_connection_state(new_state);
+    }
+
+    async fn on_connection_lost(&self) {
+        // Several sources (ping loop, child thread, failed commands) might report the
+        // same drop. Only the first one while connected counts.
+        if self.state.get_connection_state() != ConnectionState::Connected {
+            return;
+        }
+        println!("Lost connection to MPD");
+        if self.conn_settings.boolean("mpd-auto-reconnect") {
+            self.close_clients().await;
+            self.schedule_reconnection();
+        } else {
+            self.connect_async().await;
+        }
+    }
+
+    fn schedule_reconnection(&self) {
+        let attempt = self.state.get_reconnect_attempt() + 1;
+        self.state.set_reconnect_attempt(attempt);
+        self.state.set_connection_state(ConnectionState::Reconnecting);
+        let delay = get_reconnect_delay(attempt);
+        println!("Reconnection attempt {} in {:.1}s", attempt, delay.as_secs_f64());
+        let sender = self.main_sender.clone();
+        let generation = self.reconnect_generation.get();
+        glib::timeout_add_local_once(delay, move || {
+            let _ = sender.send_blocking(AsyncClientMessage::Reconnect(generation));
+        });
+    }
+
+    fn cancel_reconnection(&self) {
+        self.reconnect_generation.set(self.reconnect_generation.get().wrapping_add(1));
+        self.state.set_reconnect_attempt(0);
+    }
+
+    async fn reconnect_async(&self, generation: u64) {
+        if generation != self.reconnect_generation.get() {
+            // Superseded by a manual connection
+            return;
+        }
+        match self.try_connect().await {
+            ConnectionState::Connected => {
+                println!("Reconnected after {} attempt(s)", self.state.get_reconnect_attempt());
+                self.cancel_reconnection();
+                self.state.set_connection_state(ConnectionState::Connected);
+                self.resync();
+            }
+            ConnectionState::ConnectionRefused | ConnectionState::SocketNotFound => {
+                // Daemon is probably still restarting
+                self.schedule_reconnection();
+            }
+            other => {
+                // Authentication problems won't go away by retrying.
+                self.cancel_reconnection();
+                self.state.set_connection_state(other);
+            }
+        }
+    }
+
+    /// Silently bring the queue and player state up to date after they might have
+    /// changed without us receiving idle updates, such as after reconnecting or
+    /// switching partitions.
+    fn resync(&self) {
+        // Song IDs might have been reassigned (daemon restarted or different partition).
+        background::clear_queued_song_cache();
+        // If the queue has been fetched before, diff the whole of it against our local
+        // copy instead of refetching, such that the queue view keeps its scroll position.
+        // Queue versions restart when the daemon does, so diff against version 0.
+        if self.queue_version.get() > 0 {
+            let res = self.main_client.borrow_mut().as_mut().map(|client| client.status());
+            match res {
+                Some(Ok(status)) => {
+                    self.queue_version.set(status.queue_version);
+                    self.expected_queue_version.set(status.queue_version);
+                    self.queue_background(
+                        BackgroundTask::FetchQueueChanges(0, status.queue_len),
+                        true
+                    );
+                }
+                Some(Err(err)) => {
+                    self.handle_common_mpd_error(&err, None);
+                }
+                None => {}
+            }
+        }
+        // Everything else is refreshed by the same handlers as regular idle updates.
+        for subsystem in [
+            Subsystem::Player,
+            Subsystem::Mixer,
+            Subsystem::Options,
+            Subsystem::Output,
+            Subsystem::Playlist,
+        ] {
+            self.state.emit_boxed_result("idle", subsystem);
+        }
+    }
+
+    /// Open both clients using the live connection settings. Does not touch the
+    /// connection state, but returns the one it should be set to.
+    async fn try_connect(&self) -> ConnectionState {
+        self.state.set_queuing(false);
+        let conn = utils::settings_manager().child("client");
         let handle: gio::JoinHandle<Result<mpd::Client<StreamWrapper>, MpdError>>;
         let use_unix_socket = conn.boolean("mpd-use-unix-socket");
         if use_unix_socket {
@@ -564,7 +899,7 @@ impl MpdWrapper {
                 // If there is a password configured, use it to authenticate.
                 let mut password_access_failed = false;
                 let client_password: Option<String>;
-                match get_mpd_password().await {
+                match get_mpd_password(conn.string("mpd-active-profile").as_str()).await {
                     Ok(maybe_password) => {
                         match maybe_password {
                             Some(password) => {
@@ -573,13 +908,10 @@ impl MpdWrapper {
                                 if let Err(MpdError::Server(se)) = password_res {
                                     let _ = client.close();
                                     if se.code == MpdErrorCode::Password {
-                                        self.state
-                                            .set_connection_state(ConnectionState::WrongPassword);
+                                        return ConnectionState::WrongPassword;
                                     } else {
-                                        self.state
-                                            .set_connection_state(ConnectionState::NotConnected);
+                                        return ConnectionState::NotConnected;
                                     }
-                                    return;
                                 }
                             }
                             None => {
@@ -596,34 +928,45 @@ impl MpdWrapper {
                     }
                 }
                 // Doubles as a litmus test to see if we are authenticated.
-                if let Err(MpdError::Server(se)) = client.subscribe(self.bg_channel.clone()) {
-                    if se.code == MpdErrorCode::Permission {
-                        self.state.set_connection_state(
-                            if password_access_failed {
-                                ConnectionState::CredentialStoreError
-                            } else if client_password.is_none() {
-                                ConnectionState::PasswordNotAvailable
-                            } else {
-                                ConnectionState::Unauthenticated
+                match client.subscribe(self.bg_channel.clone()) {
+                    Err(MpdError::Server(se)) if se.code == MpdErrorCode::Permission => {
+                        let _ = client.close();
+                        if password_access_failed {
+                            ConnectionState::CredentialStoreError
+                        } else if client_password.is_none() {
+                            ConnectionState::PasswordNotAvailable
+                        } else {
+                            ConnectionState::Unauthenticated
+                        }
+                    }
+                    Err(e) => {
+                        let _ = dbg!(e);
+                        let _ = client.close();
+                        ConnectionState::NotConnected
+                    }
+                    Ok(_) => {
+                        // Follow the selected partition, falling back to the default one
+                        // if it no longer exists.
+                        let partition = conn.string("mpd-partition");
+                        if partition.as_str() != DEFAULT_PARTITION {
+                            if let Err(e) = client.partition(partition.as_str()) {
+                                println!("Cannot switch to partition {}: {:?}", partition.as_str(), e);
+                                let _ = conn.set_string("mpd-partition", DEFAULT_PARTITION);
                             }
-                        );
+                        }
+                        self.main_client.replace(Some(client));
+                        self.start_bg_thread(client_password);
+                        ConnectionState::Connected
                     }
-                } else {
-                    self.main_client.replace(Some(client));
-                    self.start_bg_thread(client_password);
-                    self.state.set_connection_state(ConnectionState::Connected);
                 }
             }
             e => {
                 let _ = dbg!(e);
-                self.state
-                    .set_connection_state(
-                        if use_unix_socket {
-                            ConnectionState::SocketNotFound
-                        } else {
-                            ConnectionState::ConnectionRefused
-                        }
-                    );
+                if use_unix_socket {
+                    ConnectionState::SocketNotFound
+                } else {
+                    ConnectionState::ConnectionRefused
+                }
             }
         }
     }
@@ -638,7 +981,7 @@ impl MpdWrapper {
         let mut handled = true;
         match *e {
             MpdError::Io(_) => {
-                let _ = self.main_sender.send_blocking(AsyncClientMessage::Connect);
+                let _ = self.main_sender.send_blocking(AsyncClientMessage::ConnectionLost);
             }
             _ => {
                 handled = false;
@@ -686,6 +1029,117 @@ impl MpdWrapper {
         }
     }
 
+    /// Name of the partition both clients are currently in.
+    pub fn get_partition(&self) -> String {
+        self.conn_settings.string("mpd-partition").to_string()
+    }
+
+    pub fn get_partitions(&self) -> Option<Vec<String>> {
+        if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            self.handle_get_error(client.listpartitions())
+        } else {
+            None
+        }
+    }
+
+    /// Get outputs belonging to each partition. The main client briefly visits
+    /// each partition to list them, then returns to the current one.
+    pub fn get_partition_outputs(&self) -> Option<Vec<(String, Vec<Output>)>> {
+        let current = self.get_partition();
+        let res: Option<Result<Vec<(String, Vec<Output>)>, MpdError>>;
+        if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            let mut fetch = || -> Result<Vec<(String, Vec<Output>)>, MpdError> {
+                let mut partitions = Vec::new();
+                for name in client.listpartitions()?.into_iter() {
+                    client.partition(name.as_str())?;
+                    // Outputs moved to another partition are left behind as dummies.
+                    let outputs: Vec<Output> = client
+                        .outputs()?
+                        .into_iter()
+                        .filter(|output| output.plugin != "dummy")
+                        .collect();
+                    partitions.push((name, outputs));
+                }
+                Ok(partitions)
+            };
+            let fetched = fetch();
+            res = Some(fetched.and_then(|partitions| {
+                client.partition(current.as_str()).map(|_| partitions)
+            }));
+        } else {
+            res = None;
+        }
+        res.and_then(|res| self.handle_get_error(res))
+    }
+
+    fn handle_partition_result(&self, res: Result<(), MpdError>) -> Result<(), Option<MpdError>> {
+        match res {
+            Ok(()) => {
+                self.force_idle();
+                Ok(())
+            }
+            Err(e) => {
+                self.handle_common_mpd_error(&e, None);
+                Err(Some(e))
+            }
+        }
+    }
+
+    pub fn switch_partition(&self, name: &str) -> Result<(), Option<MpdError>> {
+        let res = if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            client.partition(name)
+        } else {
+            return Err(None);
+        };
+        let res = res.and_then(|_| {
+            // Remember the partition before waking the child client up, as it reads it
+            // from there. The client must not be borrowed meanwhile, since settings
+            // handlers might call back into us.
+            let _ = self.conn_settings.set_string("mpd-partition", name);
+            // Make the child client follow us.
+            if let Some(client) = self.main_client.borrow_mut().as_mut() {
+                client.sendmessage(self.bg_channel.clone(), "PARTITION")
+            } else {
+                Ok(())
+            }
+        });
+        let res = self.handle_partition_result(res);
+        if res.is_ok() {
+            // The new partition has its own queue, playback state and outputs.
+            self.clear_edit_history();
+            self.resync();
+        }
+        res
+    }
+
+    pub fn new_partition(&self, name: &str) -> Result<(), Option<MpdError>> {
+        let res = if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            client.newpartition(name)
+        } else {
+            return Err(None);
+        };
+        self.handle_partition_result(res)
+    }
+
+    pub fn delete_partition(&self, name: &str) -> Result<(), Option<MpdError>> {
+        let res = if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            client.delpartition(name)
+        } else {
+            return Err(None);
+        };
+        self.handle_partition_result(res)
+    }
+
+    /// Move the output with the given name into the current partition.
+    pub fn move_output(&self, output_name: &str) -> Result<(), Option<MpdError>> {
+        let res = if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            client.moveoutput(output_name)
+        } else {
+            return Err(None);
+        };
+        self.handle_partition_result(res)
+    }
+
     pub fn get_volume(&self) -> Option<i8> {
         if let Some(client) = self.main_client.borrow_mut().as_mut() {
             self.handle_get_error(client.getvol())
@@ -753,7 +1207,12 @@ impl MpdWrapper {
         let min_lvl = if typ == "song" { StickersSupportLevel::SongsOnly } else { StickersSupportLevel::All };
         if let (true, Some(client)) = (self.state.get_stickers_support_level() >= min_lvl, self.main_client.borrow_mut().as_mut()) {
             match client.set_sticker(typ, uri, name, value) {
-                Ok(()) => {self.force_idle();},
+                Ok(()) => {
+                    if typ == "song" {
+                        background::forget_song_stickers(uri);
+                    }
+                    self.force_idle();
+                },
                 Err(error) => {
                     if let MpdError::Server(server_err) = error {
                         self.handle_sticker_server_error(server_err);
@@ -767,9 +1226,14 @@ impl MpdWrapper {
 
     pub fn delete_sticker(&self, typ: &str, uri: &str, name: &str) {
         let min_lvl = if typ == "song" { StickersSupportLevel::SongsOnly } else { StickersSupportLevel::All };
-        if let (true, Some(client)) = (self.state.get_stickers_support_level() > min_lvl, self.main_client.borrow_mut().as_mut()) {
+        if let (true, Some(client)) = (self.state.get_stickers_support_level() >= min_lvl, self.main_client.borrow_mut().as_mut()) {
             match client.delete_sticker(typ, uri, name) {
-                Ok(()) => {self.force_idle();},
+                Ok(()) => {
+                    if typ == "song" {
+                        background::forget_song_stickers(uri);
+                    }
+                    self.force_idle();
+                },
                 Err(error) => {
                     if let MpdError::Server(server_err) = error {
                         self.handle_sticker_server_error(server_err);
@@ -781,6 +1245,24 @@ impl MpdWrapper {
         }
     }
 
+    /// Set or clear the myMPD-compatible rating sticker (0-10) of a song.
+    pub fn set_song_rating(&self, uri: &str, score: Option<i8>) {
+        if let Some(score) = score {
+            self.set_sticker("song", uri, Stickers::RATING_KEY, &score.to_string());
+        } else {
+            self.delete_sticker("song", uri, Stickers::RATING_KEY);
+        }
+    }
+
+    /// Set the myMPD-compatible like sticker of a song. Neutral removes it altogether.
+    pub fn set_song_like(&self, uri: &str, status: Thumbs) {
+        if status != Thumbs::Sideways {
+            self.set_sticker("song", uri, Stickers::LIKE_KEY, &i8::from(status).to_string());
+        } else {
+            self.delete_sticker("song", uri, Stickers::LIKE_KEY);
+        }
+    }
+
     fn handle_playlist_error(&self, err: &ServerError) {
         if err.detail.contains("disabled") {
             self.state.set_supports_playlists(false);
@@ -882,24 +1364,29 @@ impl MpdWrapper {
         }
     }
 
-    pub fn edit_playlist(&self, actions: &[EditAction]) -> Result<(), Option<MpdError>> {
-        if let Some(client) = self.main_client.borrow_mut().as_mut() {
-            match client.pl_edit(actions) {
-                Ok(()) => {
-                    self.force_idle();
-                    Ok(())
-                },
-                Err(e) => {
-                    if let MpdError::Server(server_err) = &e {
-                        self.handle_playlist_error(server_err);
-                    } else {
-                        self.handle_common_mpd_error(&e, None);
-                    }
-                    return Err(Some(e));
+    /// Run a batch of edits on the named playlist. All actions must target it.
+    pub fn edit_playlist(&self, name: &str, actions: &[EditAction]) -> Result<(), Option<MpdError>> {
+        let res = if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            // Snapshot first so that the whole batch can be undone in one go.
+            undo::snapshot_playlist(client, name)
+                .and_then(|revert| client.pl_edit(actions).map(|_| revert))
+        } else {
+            return Err(None);
+        };
+        match res {
+            Ok(revert) => {
+                self.record_edit(gettext("Playlist \"{name}\" edited").replace("{name}", &name), revert, true);
+                self.force_idle();
+                Ok(())
+            },
+            Err(e) => {
+                if let MpdError::Server(server_err) = &e {
+                    self.handle_playlist_error(server_err);
+                } else {
+                    self.handle_common_mpd_error(&e, None);
                 }
+                Err(Some(e))
             }
-        } else {
-            Err(None)
         }
     }
 
@@ -1012,6 +1499,25 @@ impl MpdWrapper {
         }
     }
 
+    /// Have MPD stop once the current song ends, then turn single mode back off by
+    /// itself. Returns false without doing anything if the daemon predates 0.21,
+    /// which introduced single oneshot.
+    pub fn single_oneshot(&self) -> bool {
+        if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            if client.version.1 < 21 {
+                return false;
+            }
+            let res = client
+                .run_command("single", "oneshot")
+                .and_then(|_| client.expect_ok());
+            let ok = res.is_ok();
+            self.handle_set_error(res);
+            ok
+        } else {
+            false
+        }
+    }
+
     pub fn set_crossfade(&self, fade: f64) {
         if let Some(client) = self.main_client.borrow_mut().as_mut() {
             self.handle_set_error(client.crossfade(fade as i64));
@@ -1083,35 +1589,236 @@ impl MpdWrapper {
         }
     }
 
+    /// Apply an edit with the main client, returning the edit that reverts it.
+    /// Errors are handled here.
+    fn apply_edit(&self, edit: Edit) -> Option<Edit> {
+        // Queuing a whole queue's worth of songs can take a while, so only clear the
+        // queue here & leave adding them back to the background client.
+        let (edit, restore) = match edit {
+            Edit::QueueReplace(uris, pos) if !uris.is_empty() => (
+                Edit::QueueReplace(Vec::new(), None),
+                Some(QueueSnapshot {
+                    uris,
+                    pos,
+                    elapsed: 0.0,
+                    playing: pos.is_some(),
+                }),
+            ),
+            edit => (edit, None),
+        };
+        let res = if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            undo::apply(client, edit)
+        } else {
+            return None;
+        };
+        match res {
+            Ok(revert) => {
+                self.force_idle();
+                if let Some(snapshot) = restore {
+                    self.restoring_queue.set(true);
+                    self.sync_edit_history();
+                    self.queue_background(BackgroundTask::RestoreQueue(snapshot), true);
+                }
+                revert
+            }
+            Err(e) => {
+                self.handle_common_mpd_error(&e, None);
+                None
+            }
+        }
+    }
+
+    /// Remember how to revert a change we just made. Only changes worth a toast are
+    /// announced; small ones like single moves can still be undone via shortcuts.
+    fn record_edit(&self, label: String, revert: Edit, announce: bool) {
+        self.edit_history.borrow_mut().push(UndoStep {
+            label: label.clone(),
+            revert,
+        });
+        self.sync_edit_history();
+        if announce {
+            self.state.emit_by_name::<()>("edit-recorded", &[&label]);
+        }
+    }
+
+    fn clear_edit_history(&self) {
+        self.edit_history.borrow_mut().clear();
+        self.sync_edit_history();
+    }
+
+    fn sync_edit_history(&self) {
+        let history = self.edit_history.borrow();
+        let restoring = self.restoring_queue.get();
+        self.state.set_can_undo(!restoring && history.can_undo());
+        self.state.set_can_redo(!restoring && history.can_redo());
+    }
+
+    /// Whether undoing & redoing are on hold until the queue has been restored.
+    pub fn is_restoring_queue(&self) -> bool {
+        self.restoring_queue.get()
+    }
+
+    /// Revert the last recorded edit. Returns its label if successful.
+    pub fn undo(&self) -> Option<String> {
+        if self.restoring_queue.get() {
+            return None;
+        }
+        let step = self.edit_history.borrow_mut().pop_undo()?;
+        let res = self.apply_edit(step.revert);
+        match res {
+            Some(redo) => {
+                self.edit_history.borrow_mut().push_redo(UndoStep {
+                    label: step.label.clone(),
+                    revert: redo,
+                });
+                self.sync_edit_history();
+                Some(step.label)
+            }
+            None => {
+                // Older steps probably depend on this one, so don't try them either.
+                self.clear_edit_history();
+                None
+            }
+        }
+    }
+
+    /// Re-apply the last undone edit. Returns its label if successful.
+    pub fn redo(&self) -> Option<String> {
+        if self.restoring_queue.get() {
+            return None;
+        }
+        let step = self.edit_history.borrow_mut().pop_redo()?;
+        let res = self.apply_edit(step.revert);
+        match res {
+            Some(revert) => {
+                self.edit_history.borrow_mut().push_undo(UndoStep {
+                    label: step.label.clone(),
+                    revert,
+                });
+                self.sync_edit_history();
+                Some(step.label)
+            }
+            None => {
+                self.clear_edit_history();
+                None
+            }
+        }
+    }
+
     pub fn swap(&self, id1: u32, id2: u32, is_id: bool) {
-        if let Some(client) = self.main_client.borrow_mut().as_mut() {
-            let res = if is_id {
-                client
-                    .swap(Id(id1), Id(id2))
-            } else {
-                client.swap(id1, id2)
-            };
-            self.handle_set_error(res);
+        if is_id {
+            if let Some(client) = self.main_client.borrow_mut().as_mut() {
+                self.handle_set_error(client.swap(Id(id1), Id(id2)));
+            }
+        } else if let Some(revert) = self.apply_edit(Edit::QueueSwap(id1, id2)) {
+            self.record_edit(gettext("Queue reordered"), revert, false);
         }
     }
 
     pub fn delete_at(&self, id_or_pos: u32, is_id: bool) {
+        if is_id {
+            if let Some(client) = self.main_client.borrow_mut().as_mut() {
+                self.handle_set_error(client.delete(Id(id_or_pos)));
+            }
+        } else if let Some(revert) = self.apply_edit(Edit::QueueDelete(id_or_pos)) {
+            self.record_edit(gettext("Song removed from queue"), revert, true);
+        }
+    }
+
+    /// Move ranges of queue positions around, in a single command list.
+    pub fn move_ranges(&self, moves: Vec<QueueMove>, label: &str, announce: bool) {
+        if let Some(revert) = self.apply_edit(Edit::QueueMoves(moves)) {
+            self.record_edit(label.to_owned(), revert, announce);
+        }
+    }
+
+    /// Remove the songs at the given sorted queue positions, in a single command list.
+    pub fn delete_positions(&self, positions: Vec<u32>) {
+        let n_songs = positions.len();
+        if let Some(revert) = self.apply_edit(Edit::QueueDeleteMany(positions)) {
+            self.record_edit(
+                ngettext("{n} song removed from queue", "{n} songs removed from queue", n_songs as u32)
+                    .replace("{n}", &n_songs.to_string()),
+                revert,
+                true,
+            );
+        }
+    }
+
+    /// Set the priorities of queued songs by their IDs, in a single command list.
+    /// Songs with higher priorities are played first in random mode. 0 is the default.
+    pub fn set_priorities(&self, prios: &[(u32, u8)]) {
+        if prios.is_empty() {
+            return;
+        }
         if let Some(client) = self.main_client.borrow_mut().as_mut() {
-            let res = if is_id {
-                client
-                    .delete(Id(id_or_pos))
-            } else {
-                client
-                    .delete(id_or_pos)
-            };
+            let res = client
+                .run_command("command_list_begin", ())
+                .and_then(|_| {
+                    prios
+                        .iter()
+                        .try_for_each(|(id, prio)| client.run_command("prioid", (*prio as u32, *id)))
+                })
+                .and_then(|_| client.run_command("command_list_end", ()))
+                .and_then(|_| client.expect_ok());
             self.handle_set_error(res);
         }
     }
 
-    pub fn clear_queue(&self) {
-        if let Some(client) = self.main_client.borrow_mut().as_mut() {
-            self.handle_set_error(client.clear());
+    /// Current queue contents & playback position.
+    pub fn get_queue_snapshot(&self) -> Option<QueueSnapshot> {
+        let res = if let Some(client) = self.main_client.borrow_mut().as_mut() {
+            client.status().and_then(|status| {
+                let uris = if status.queue_len > 0 {
+                    client
+                        .queue(Window::from((0, status.queue_len)))?
+                        .into_iter()
+                        .map(|song| song.file)
+                        .collect()
+                } else {
+                    Vec::new()
+                };
+                Ok(QueueSnapshot {
+                    uris,
+                    pos: status.song.map(|place| place.pos),
+                    elapsed: status.elapsed.map_or(0.0, |dur| dur.as_secs_f64()),
+                    playing: status.state == State::Play,
+                })
+            })
+        } else {
+            return None;
+        };
+        self.handle_get_error(res)
+    }
+
+    fn clear_queue_as(&self, label: &str) {
+        // Keep the old queue around for longer than the undo history does.
+        if let Some(snapshot) = self.get_queue_snapshot().filter(|snapshot| !snapshot.is_empty()) {
+            let keep = utils::settings_manager()
+                .child("player")
+                .uint("queue-snapshot-limit");
+            if keep > 0 {
+                if let Err(e) = sqlite::add_queue_snapshot(&snapshot, keep) {
+                    println!("Unable to save queue snapshot: {e:?}");
+                }
+            }
         }
+        if let Some(revert) = self.apply_edit(Edit::QueueReplace(Vec::new(), None)) {
+            // Nothing to restore if the queue was already empty
+            if !matches!(&revert, Edit::QueueReplace(old, _) if old.is_empty()) {
+                self.record_edit(label.to_owned(), revert, true);
+            }
+        }
+    }
+
+    pub fn clear_queue(&self) {
+        self.clear_queue_as(&gettext("Queue cleared"));
+    }
+
+    /// Clear the queue ahead of queuing something else in its place. Undoing this
+    /// brings back the old queue rather than an empty one.
+    pub fn clear_queue_for_replace(&self) {
+        self.clear_queue_as(&gettext("Queue replaced"));
     }
 
     pub fn register_local_queue_changes(&self, n_changes: u32) {
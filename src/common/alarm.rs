@@ -0,0 +1,107 @@
+use gtk::glib;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+// Scheduled playback, kept in the local SQLite DB. Alarms go off in local time while
+// Euphonica is running, including in the background. Each one remembers up to when its
+// occurrences have been dealt with, so that those that went by while Euphonica was
+// not running can be reported the next time it starts.
+
+// TODO: translatable
+pub const WEEKDAY_NAMES: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlarmSource {
+    /// Name of a stored playlist
+    Playlist(String),
+    /// URL of a stream, such as an internet radio station
+    Stream(String),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Alarm {
+    pub hour: u8,
+    pub minute: u8,
+    /// Days of the week to go off on, as a bitmask with Monday as the lowest bit.
+    /// Alarms without any go off once, then get disabled.
+    pub days: u8,
+    pub source: AlarmSource,
+    /// Volume to start playing at, in percent.
+    pub start_volume: u8,
+    /// Volume to ramp up to, in percent.
+    pub end_volume: u8,
+    /// Seconds taken to get from the start to the end volume.
+    pub ramp_secs: u32,
+    pub enabled: bool,
+}
+
+impl Alarm {
+    pub fn is_one_off(&self) -> bool {
+        self.days == 0
+    }
+
+    /// Whether this alarm goes off on the given day of the week, numbered like
+    /// glib::DateTime::day_of_week (1 is Monday).
+    pub fn goes_off_on(&self, day_of_week: i32) -> bool {
+        self.is_one_off() || self.days & (1 << (day_of_week - 1)) != 0
+    }
+
+    /// Latest time (as a Unix timestamp) this alarm was due at within (after, until].
+    pub fn last_due(&self, after: i64, until: i64) -> Option<i64> {
+        let until_dt = glib::DateTime::from_unix_local(until).ok()?;
+        let today = glib::DateTime::from_local(
+            until_dt.year(),
+            until_dt.month(),
+            until_dt.day_of_month(),
+            self.hour as i32,
+            self.minute as i32,
+            0.0,
+        )
+        .ok()?;
+        // Every day of the week comes up within the last 8 days, counting today even
+        // if its time has yet to come.
+        (0..8)
+            .filter_map(|n| today.add_days(-n).ok())
+            .filter(|dt| self.goes_off_on(dt.day_of_week()))
+            .map(|dt| dt.to_unix())
+            .find(|ts| *ts > after && *ts <= until)
+    }
+
+    pub fn time_desc(&self) -> String {
+        format!("{:02}:{:02}", self.hour, self.minute)
+    }
+
+    // TODO: translatable
+    pub fn days_desc(&self) -> String {
+        match self.days & 0x7f {
+            0 => "Once".to_owned(),
+            0x7f => "Every day".to_owned(),
+            0x1f => "Weekdays".to_owned(),
+            0x60 => "Weekends".to_owned(),
+            days => WEEKDAY_NAMES
+                .iter()
+                .enumerate()
+                .filter(|(idx, _)| days & (1 << idx) != 0)
+                .map(|(_, name)| *name)
+                .collect::<Vec<&str>>()
+                .join(", "),
+        }
+    }
+
+    pub fn source_desc(&self) -> &str {
+        match &self.source {
+            AlarmSource::Playlist(name) => name,
+            AlarmSource::Stream(url) => url,
+        }
+    }
+}
+
+/// An alarm as stored in the DB.
+#[derive(Debug, Clone)]
+pub struct SavedAlarm {
+    pub id: i64,
+    /// Times this alarm was due at up to this point have been dealt with, by either
+    /// going off or being reported as missed.
+    pub checked: OffsetDateTime,
+    pub alarm: Alarm,
+}
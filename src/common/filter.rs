@@ -0,0 +1,268 @@
+use std::fmt;
+
+use gtk::{gio::prelude::*, glib::prelude::*};
+use mpd::Query;
+use serde::{Deserialize, Serialize};
+
+use crate::utils;
+
+// Filter expressions as understood by MPD 0.21+ (find, search, findadd, list...).
+// The mpd::Query builder can only AND together simple tag comparisons, so we
+// keep our own tree here and hand its textual form to Query::raw instead. See
+// https://mpd.readthedocs.io/en/latest/protocol.html#filters
+//
+// Trees are serialised as JSON when saved.
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TagOperator {
+    Equal,
+    NotEqual,
+    Contains,
+    StartsWith,
+    Regex,
+    NotRegex,
+}
+
+impl TagOperator {
+    pub const ALL: [TagOperator; 6] = [
+        Self::Equal,
+        Self::NotEqual,
+        Self::Contains,
+        Self::StartsWith,
+        Self::Regex,
+        Self::NotRegex,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Equal => "==",
+            Self::NotEqual => "!=",
+            Self::Contains => "contains",
+            Self::StartsWith => "starts_with",
+            Self::Regex => "=~",
+            Self::NotRegex => "!~",
+        }
+    }
+
+    /// Human-readable name for use in dropdowns.
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Equal => "is",
+            Self::NotEqual => "is not",
+            Self::Contains => "contains",
+            Self::StartsWith => "starts with",
+            Self::Regex => "matches regex",
+            Self::NotRegex => "does not match regex",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum FilterExpr {
+    /// Compare a tag. Besides actual tags, "any" matches any tag value, while
+    /// "file" and "base" match the song path.
+    Tag {
+        tag: String,
+        op: TagOperator,
+        value: String,
+    },
+    /// Songs modified after the given time, either an ISO 8601 timestamp or
+    /// seconds since the Unix epoch.
+    ModifiedSince(String),
+    /// Songs added to the database after the given time (MPD 0.24+). Same
+    /// format as ModifiedSince.
+    AddedSince(String),
+    /// Compare the audio format, such as "44100:16:2". When masked, "*" can be
+    /// used in place of any of its components.
+    AudioFormat { format: String, masked: bool },
+    Not(Box<FilterExpr>),
+    And(Vec<FilterExpr>),
+}
+
+impl Default for FilterExpr {
+    fn default() -> Self {
+        Self::And(Vec::new())
+    }
+}
+
+/// Quote a value as an MPD filter string literal.
+fn quote(value: &str) -> String {
+    let mut res = String::with_capacity(value.len() + 2);
+    res.push('"');
+    for c in value.chars() {
+        if matches!(c, '"' | '\'' | '\\') {
+            res.push('\\');
+        }
+        res.push(c);
+    }
+    res.push('"');
+    res
+}
+
+/// Whether the given string is usable as a point in time for modified-since
+/// and added-since.
+fn is_valid_timestamp(value: &str) -> bool {
+    value.parse::<i64>().is_ok() || chrono::DateTime::parse_from_rfc3339(value).is_ok()
+}
+
+impl FilterExpr {
+    pub fn new_tag(tag: &str, op: TagOperator, value: &str) -> Self {
+        Self::Tag {
+            tag: tag.to_owned(),
+            op,
+            value: value.to_owned(),
+        }
+    }
+
+    /// Check the tree for mistakes MPD would reject, returning a human-readable
+    /// description of the first one found.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::Tag { tag, op, value } => {
+                if tag.trim().is_empty() {
+                    return Err("A condition is missing its tag".to_owned());
+                }
+                if matches!(op, TagOperator::Regex | TagOperator::NotRegex) {
+                    // MPD uses PCRE, whose syntax is mostly a superset of ours.
+                    if let Err(e) = regex::Regex::new(value) {
+                        return Err(format!("Invalid regular expression: {e}"));
+                    }
+                }
+                Ok(())
+            }
+            Self::ModifiedSince(since) | Self::AddedSince(since) => {
+                if is_valid_timestamp(since.trim()) {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "\"{since}\" is neither an ISO 8601 timestamp nor a Unix time"
+                    ))
+                }
+            }
+            Self::AudioFormat { format, .. } => {
+                if format.split(':').count() == 3 {
+                    Ok(())
+                } else {
+                    Err(format!(
+                        "\"{format}\" is not an audio format (samplerate:bits:channels)"
+                    ))
+                }
+            }
+            Self::Not(inner) => inner.validate(),
+            Self::And(children) => {
+                if children.is_empty() {
+                    return Err("Groups must contain at least one condition".to_owned());
+                }
+                children.iter().try_for_each(FilterExpr::validate)
+            }
+        }
+    }
+
+    /// Get a node of the tree. The path consists of indices into And nodes'
+    /// children, passing through Not nodes transparently.
+    pub fn node_at_mut(&mut self, path: &[usize]) -> Option<&mut FilterExpr> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        match self {
+            Self::Not(inner) => inner.node_at_mut(path),
+            Self::And(children) => children.get_mut(path[0])?.node_at_mut(&path[1..]),
+            _ => None,
+        }
+    }
+
+    /// Wrap into a Query that can be passed to the usual client methods
+    /// (find, findadd, etc.).
+    pub fn to_query(&self) -> Query<'static> {
+        Query::raw(self.to_string())
+    }
+}
+
+impl fmt::Display for FilterExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // base only does prefix matching on folder paths and takes no operator
+            Self::Tag { tag, value, .. } if tag.trim() == "base" => {
+                write!(f, "(base {})", quote(value))
+            }
+            Self::Tag { tag, op, value } => {
+                write!(f, "({} {} {})", tag.trim(), op.as_str(), quote(value))
+            }
+            Self::ModifiedSince(since) => write!(f, "(modified-since {})", quote(since.trim())),
+            Self::AddedSince(since) => write!(f, "(added-since {})", quote(since.trim())),
+            Self::AudioFormat { format, masked } => write!(
+                f,
+                "(AudioFormat {} {})",
+                if *masked { "=~" } else { "==" },
+                quote(format.trim())
+            ),
+            Self::Not(inner) => write!(f, "(!{inner})"),
+            Self::And(children) => {
+                // MPD doesn't accept redundant parentheses around a lone expression
+                if children.len() == 1 {
+                    return write!(f, "{}", children[0]);
+                }
+                write!(f, "(")?;
+                for (idx, child) in children.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, " AND ")?;
+                    }
+                    write!(f, "{child}")?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+// Saved queries live in the library schema as (name, JSON tree) pairs.
+
+pub fn get_saved_queries() -> Vec<(String, FilterExpr)> {
+    utils::settings_manager()
+        .child("library")
+        .value("saved-queries")
+        .get::<Vec<(String, String)>>()
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|(name, json)| match serde_json::from_str::<FilterExpr>(&json) {
+            Ok(expr) => Some((name, expr)),
+            Err(e) => {
+                println!("Skipping unreadable saved query {name}: {e}");
+                None
+            }
+        })
+        .collect()
+}
+
+fn set_saved_queries(queries: &[(String, FilterExpr)]) {
+    let tuples: Vec<(String, String)> = queries
+        .iter()
+        .map(|(name, expr)| {
+            (
+                name.clone(),
+                serde_json::to_string(expr).expect("Unable to serialise query"),
+            )
+        })
+        .collect();
+    utils::settings_manager()
+        .child("library")
+        .set_value("saved-queries", &tuples.to_variant())
+        .expect("Unable to save queries");
+}
+
+/// Add a new saved query or overwrite an existing one with the same name.
+pub fn save_query(name: &str, expr: &FilterExpr) {
+    let mut queries = get_saved_queries();
+    if let Some(existing) = queries.iter_mut().find(|(n, _)| n == name) {
+        existing.1 = expr.clone();
+    } else {
+        queries.push((name.to_owned(), expr.clone()));
+    }
+    set_saved_queries(&queries);
+}
+
+pub fn delete_saved_query(name: &str) {
+    let mut queries = get_saved_queries();
+    queries.retain(|(n, _)| n != name);
+    set_saved_queries(&queries);
+}
@@ -0,0 +1,133 @@
+use gtk::{glib, prelude::*, subclass::prelude::*};
+use std::cell::Cell;
+
+use super::sticker::Thumbs;
+
+const LIKED: &'static str = "heart-filled-symbolic";
+const NEUTRAL: &'static str = "heart-outline-symbolic";
+const DISLIKED: &'static str = "heart-broken-symbolic";
+
+mod imp {
+    use std::sync::OnceLock;
+
+    use super::*;
+    use glib::{clone, subclass::Signal, Properties};
+
+    /// Like toggle. Clicking likes or un-likes, while right-clicking dislikes or
+    /// un-dislikes. Values follow the like sticker (0 = dislike, 1 = neutral, 2 = like).
+    #[derive(Default, Properties)]
+    #[properties(wrapper_type = super::LikeButton)]
+    pub struct LikeButton {
+        #[property(get, set = Self::set_value)]
+        pub value: Cell<i8>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for LikeButton {
+        const NAME: &'static str = "EuphonicaLikeButton";
+        type Type = super::LikeButton;
+        type ParentType = gtk::Button;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for LikeButton {
+        fn constructed(&self) {
+            self.parent_constructed();
+            let obj = self.obj();
+            obj.add_css_class("flat");
+            obj.add_css_class("like-button");
+            obj.set_valign(gtk::Align::Center);
+            self.value.set(i8::from(Thumbs::Sideways));
+            self.update_icon();
+
+            let secondary_ctl = gtk::GestureClick::new();
+            secondary_ctl.set_button(gtk::gdk::BUTTON_SECONDARY);
+            secondary_ctl.connect_released(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, _, _, _| {
+                    this.toggle(Thumbs::Down);
+                }
+            ));
+            obj.add_controller(secondary_ctl);
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| {
+                vec![
+                    // Only emitted for user-initiated changes
+                    Signal::builder("changed")
+                        .build()
+                ]
+            })
+        }
+    }
+
+    impl WidgetImpl for LikeButton {}
+
+    impl ButtonImpl for LikeButton {
+        fn clicked(&self) {
+            self.toggle(Thumbs::Up);
+        }
+    }
+
+    impl LikeButton {
+        fn set_value(&self, new: i8) {
+            let old = self.value.replace(new);
+            if old != new {
+                self.update_icon();
+                self.obj().notify("value");
+            }
+        }
+
+        fn toggle(&self, status: Thumbs) {
+            let new = if self.obj().thumbs() == status {
+                Thumbs::Sideways
+            } else {
+                status
+            };
+            self.obj().set_value(i8::from(new));
+            self.obj().emit_by_name::<()>("changed", &[]);
+        }
+
+        fn update_icon(&self) {
+            let obj = self.obj();
+            match obj.thumbs() {
+                Thumbs::Up => {
+                    obj.set_icon_name(LIKED);
+                    obj.set_tooltip_text(Some("Liked (click to unlike)"));
+                    obj.add_css_class("liked");
+                }
+                Thumbs::Sideways => {
+                    obj.set_icon_name(NEUTRAL);
+                    obj.set_tooltip_text(Some("Like (right-click to dislike)"));
+                    obj.remove_css_class("liked");
+                }
+                Thumbs::Down => {
+                    obj.set_icon_name(DISLIKED);
+                    obj.set_tooltip_text(Some("Disliked (right-click to undo)"));
+                    obj.remove_css_class("liked");
+                }
+            }
+        }
+    }
+}
+
+glib::wrapper! {
+    pub struct LikeButton(ObjectSubclass<imp::LikeButton>)
+    @extends gtk::Button, gtk::Widget,
+    @implements gtk::Accessible, gtk::Actionable, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl Default for LikeButton {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl LikeButton {
+    pub fn thumbs(&self) -> Thumbs {
+        Thumbs::try_from(self.value()).unwrap_or_default()
+    }
+}
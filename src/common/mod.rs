@@ -1,21 +1,39 @@
+pub mod alarm;
 pub mod album;
 pub mod artist;
 pub mod blend_mode;
+pub mod filter;
 pub mod inode;
+pub mod like_button;
 pub mod marquee;
 pub mod rating;
 pub mod paintables;
+pub mod playlist_check;
+pub mod queue_moves;
+pub mod queue_snapshot;
+pub mod smart_playlist;
 pub mod song;
+pub mod stats;
 pub mod sticker;
+pub mod sticker_backup;
 pub mod theme_selector;
 
-pub use sticker::Stickers;
+pub use sticker::{Stickers, Thumbs};
+pub use alarm::{Alarm, AlarmSource, SavedAlarm};
 pub use album::{Album, AlbumInfo};
+pub use filter::{FilterExpr, TagOperator};
 pub use artist::{artists_to_string, parse_mb_artist_tag, Artist, ArtistInfo};
 pub use inode::{INode, INodeType};
+pub use like_button::LikeButton;
 pub use marquee::Marquee;
+pub use playlist_check::{PlaylistCheckReport, PlaylistFix};
+pub use queue_moves::QueueMove;
+pub use queue_snapshot::{QueueSnapshot, SavedQueue};
 pub use rating::Rating;
+pub use smart_playlist::{SmartPlaylist, SmartSort, SongStats};
 pub use song::{QualityGrade, Song, SongInfo};
+pub use stats::{ListeningStats, StatsRange};
+pub use sticker_backup::{StickerBackup, StickerRestoreReport};
 pub use theme_selector::ThemeSelector;
 
 
@@ -0,0 +1,60 @@
+use rustc_hash::FxHashMap;
+
+use super::SongInfo;
+
+// Maintenance of stored playlists. MPD keeps playlist entries as plain URIs, so
+// songs that have since been moved or deleted leave broken entries behind. These are
+// found by looking each URI up in the database, and may be relinked to library songs
+// with the same file name, MusicBrainz ID or tags. Repeated URIs are flagged too.
+
+/// A playlist entry whose song is no longer in the database.
+#[derive(Debug, Clone)]
+pub struct BrokenEntry {
+    pub pos: u32,
+    pub uri: String,
+    /// Library songs this entry might have pointed to, most likely first.
+    pub candidates: Vec<SongInfo>,
+}
+
+/// A later copy of a song already in the playlist.
+#[derive(Debug, Clone)]
+pub struct DuplicateEntry {
+    pub pos: u32,
+    pub first_pos: u32,
+    pub song: SongInfo,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PlaylistCheckReport {
+    pub name: String,
+    /// Contents of the playlist at the time of the check, in order.
+    pub uris: Vec<String>,
+    pub broken: Vec<BrokenEntry>,
+    pub duplicates: Vec<DuplicateEntry>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlaylistFix {
+    Relink(String), // URI of the song to point to instead
+    Remove,
+}
+
+impl PlaylistCheckReport {
+    pub fn is_clean(&self) -> bool {
+        self.broken.is_empty() && self.duplicates.is_empty()
+    }
+
+    /// Contents of the playlist after applying the given fixes, keyed by position.
+    /// Entries without a fix are kept as-is.
+    pub fn fixed_uris(&self, fixes: &FxHashMap<u32, PlaylistFix>) -> Vec<String> {
+        self.uris
+            .iter()
+            .enumerate()
+            .filter_map(|(pos, uri)| match fixes.get(&(pos as u32)) {
+                Some(PlaylistFix::Relink(new_uri)) => Some(new_uri.clone()),
+                Some(PlaylistFix::Remove) => None,
+                None => Some(uri.clone()),
+            })
+            .collect()
+    }
+}
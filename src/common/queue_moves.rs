@@ -0,0 +1,343 @@
+use std::{cmp::Ordering, ops::Range};
+
+use gtk::glib;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+use super::Song;
+
+// Reordering of the queue. Changes are worked out here as a list of MPD "move"
+// commands on ranges of songs, so that they can be sent as a single command list
+// and replayed on our local copy of the queue. Whole-queue transforms are expressed
+// as the target order of current positions.
+
+/// Move the songs in the given range of queue positions so that the first one
+/// ends up at `to`. Like MPD's own "move", `to` is counted after taking the range out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QueueMove {
+    pub start: u32,
+    pub end: u32,
+    pub to: u32,
+}
+
+impl QueueMove {
+    pub fn n_songs(&self) -> u32 {
+        self.end - self.start
+    }
+
+    /// The move that puts the songs back where they were.
+    pub fn inverse(&self) -> Self {
+        Self {
+            start: self.to,
+            end: self.to + self.n_songs(),
+            to: self.start,
+        }
+    }
+
+    /// Apply this move to a local copy of the queue.
+    pub fn apply<T>(&self, items: &mut Vec<T>) {
+        let block: Vec<T> = items
+            .drain(self.start as usize..self.end as usize)
+            .collect();
+        let to = self.to as usize;
+        items.splice(to..to, block);
+    }
+}
+
+/// Split sorted, deduplicated queue positions into runs of consecutive ones.
+pub fn to_ranges(positions: &[u32]) -> Vec<Range<u32>> {
+    let mut res: Vec<Range<u32>> = Vec::new();
+    for pos in positions.iter().copied() {
+        match res.last_mut() {
+            Some(range) if range.end == pos => range.end += 1,
+            _ => res.push(pos..pos + 1),
+        }
+    }
+    res
+}
+
+/// Moves that gather the songs at the given sorted positions, in their current order,
+/// right before the song currently at position `to` (or at the end if `to` is the queue
+/// length). Each run of consecutive songs takes a single move.
+pub fn gather_moves(positions: &[u32], to: u32, len: u32) -> Vec<QueueMove> {
+    let moving: FxHashSet<u32> = positions.iter().copied().collect();
+    // Songs are tracked by their original positions as they get shuffled around.
+    let mut sim: Vec<u32> = (0..len).collect();
+    let find = |sim: &[u32], orig: u32| sim.iter().position(|pos| *pos == orig);
+    // The first song staying put at or after the drop point. Everything goes before it.
+    let anchor = (to..len).find(|pos| !moving.contains(pos));
+    let mut res: Vec<QueueMove> = Vec::new();
+    // Original position of the last song of the previous run. Each run goes after it.
+    let mut prev_last: Option<u32> = None;
+    for range in to_ranges(positions) {
+        let Some(start) = find(&sim, range.start) else {
+            continue;
+        };
+        let n = range.len();
+        let block: Vec<u32> = sim.drain(start..start + n).collect();
+        let target = match prev_last {
+            Some(last) => find(&sim, last).map_or(sim.len(), |idx| idx + 1),
+            None => anchor.and_then(|pos| find(&sim, pos)).unwrap_or(sim.len()),
+        };
+        sim.splice(target..target, block);
+        if target != start {
+            res.push(QueueMove {
+                start: start as u32,
+                end: (start + n) as u32,
+                to: target as u32,
+            });
+        }
+        prev_last = Some(range.end - 1);
+    }
+    res
+}
+
+/// Moves that rearrange the queue into the given order of current positions. Songs on
+/// the longest run already in the right relative order stay put, while the rest are moved
+/// in after their new predecessors, a range at a time where they were already adjacent.
+pub fn permutation_moves(order: &[u32]) -> Vec<QueueMove> {
+    let keep = longest_increasing(order);
+    let mut sim: Vec<u32> = (0..order.len() as u32).collect();
+    let find = |sim: &[u32], orig: u32| sim.iter().position(|pos| *pos == orig);
+    let mut res: Vec<QueueMove> = Vec::new();
+    let mut idx = 0;
+    while idx < order.len() {
+        if keep.contains(&order[idx]) {
+            idx += 1;
+            continue;
+        }
+        // Take along following songs that also need moving & are right after this one.
+        let mut end = idx + 1;
+        while end < order.len() && !keep.contains(&order[end]) && order[end] == order[end - 1] + 1 {
+            end += 1;
+        }
+        let Some(start) = find(&sim, order[idx]) else {
+            break;
+        };
+        let n = end - idx;
+        let block: Vec<u32> = sim.drain(start..start + n).collect();
+        let target = if idx == 0 {
+            0
+        } else {
+            find(&sim, order[idx - 1]).map_or(sim.len(), |pos| pos + 1)
+        };
+        sim.splice(target..target, block);
+        if target != start {
+            res.push(QueueMove {
+                start: start as u32,
+                end: (start + n) as u32,
+                to: target as u32,
+            });
+        }
+        idx = end;
+    }
+    res
+}
+
+/// Values making up a longest increasing subsequence.
+fn longest_increasing(values: &[u32]) -> FxHashSet<u32> {
+    // Index into values of the smallest tail of an increasing run of each length,
+    // and the predecessor of each value on its run.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev: Vec<Option<usize>> = vec![None; values.len()];
+    for (idx, value) in values.iter().enumerate() {
+        let len = tails.partition_point(|tail| values[*tail] < *value);
+        prev[idx] = len.checked_sub(1).map(|len| tails[len]);
+        if len == tails.len() {
+            tails.push(idx);
+        } else {
+            tails[len] = idx;
+        }
+    }
+    let mut res = FxHashSet::default();
+    let mut curr = tails.last().copied();
+    while let Some(idx) = curr {
+        res.insert(values[idx]);
+        curr = prev[idx];
+    }
+    res
+}
+
+fn album_key(song: &Song) -> Option<(&str, &str)> {
+    let album = song.get_album()?;
+    Some((
+        album.albumsort.as_deref().unwrap_or(&album.title),
+        album.albumartist.as_deref().unwrap_or_default(),
+    ))
+}
+
+fn track_order(a: &Song, b: &Song) -> Ordering {
+    a.get_disc()
+        .cmp(&b.get_disc())
+        .then_with(|| a.get_track().cmp(&b.get_track()))
+}
+
+/// Queue sorted by album, then by disc & track. Songs without an album go last, in
+/// their current order.
+pub fn album_order(songs: &[Song]) -> Vec<u32> {
+    let mut order: Vec<u32> = (0..songs.len() as u32).collect();
+    order.sort_by(|a, b| {
+        let (a, b) = (&songs[*a as usize], &songs[*b as usize]);
+        match (album_key(a), album_key(b)) {
+            (Some(key_a), Some(key_b)) => key_a.cmp(&key_b).then_with(|| track_order(a, b)),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    });
+    order
+}
+
+/// Queue with its albums in random order, each one kept together in disc & track
+/// order. Songs without an album are shuffled in on their own.
+pub fn album_shuffle_order(songs: &[Song]) -> Vec<u32> {
+    let mut groups: Vec<Vec<u32>> = Vec::new();
+    let mut group_of: FxHashMap<(&str, &str), usize> = FxHashMap::default();
+    for (pos, song) in songs.iter().enumerate() {
+        match album_key(song) {
+            Some(key) => {
+                let idx = *group_of.entry(key).or_insert_with(|| {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                });
+                groups[idx].push(pos as u32);
+            }
+            None => groups.push(vec![pos as u32]),
+        }
+    }
+    for group in groups.iter_mut() {
+        group.sort_by(|a, b| track_order(&songs[*a as usize], &songs[*b as usize]));
+    }
+    // Fisher-Yates
+    for i in (1..groups.len()).rev() {
+        let j = glib::random_int_range(0, i as i32 + 1) as usize;
+        groups.swap(i, j);
+    }
+    groups.into_iter().flatten().collect()
+}
+
+/// Sorted positions of songs that are repeats of an earlier one. The copy at
+/// `keep` (such as the current song) is kept over any others.
+pub fn duplicate_positions(songs: &[Song], keep: Option<u32>) -> Vec<u32> {
+    let mut kept: FxHashMap<&str, u32> = FxHashMap::default();
+    if let Some(pos) = keep {
+        if let Some(song) = songs.get(pos as usize) {
+            kept.insert(song.get_uri(), pos);
+        }
+    }
+    songs
+        .iter()
+        .enumerate()
+        .filter_map(|(pos, song)| {
+            let pos = pos as u32;
+            let first = *kept.entry(song.get_uri()).or_insert(pos);
+            (first != pos).then_some(pos)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply_all(moves: &[QueueMove], len: u32) -> Vec<u32> {
+        let mut items: Vec<u32> = (0..len).collect();
+        for m in moves.iter() {
+            assert!(m.end <= len && m.to + m.n_songs() <= len, "move out of bounds: {m:?}");
+            m.apply(&mut items);
+        }
+        items
+    }
+
+    /// Every permutation of 0..len, in lexicographic order.
+    fn permutations(len: u32) -> Vec<Vec<u32>> {
+        if len == 0 {
+            return vec![Vec::new()];
+        }
+        let mut res = Vec::new();
+        for first in 0..len {
+            for rest in permutations(len - 1) {
+                let mut perm = vec![first];
+                perm.extend(rest.into_iter().map(|pos| if pos >= first { pos + 1 } else { pos }));
+                res.push(perm);
+            }
+        }
+        res
+    }
+
+    /// Deterministic Fisher-Yates shuffle of 0..len.
+    fn shuffled(len: u32, seed: u64) -> Vec<u32> {
+        let mut state = seed;
+        let mut order: Vec<u32> = (0..len).collect();
+        for i in (1..order.len()).rev() {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            order.swap(i, (state >> 33) as usize % (i + 1));
+        }
+        order
+    }
+
+    #[test]
+    fn ranges_split_on_gaps() {
+        assert_eq!(to_ranges(&[]), Vec::<Range<u32>>::new());
+        assert_eq!(to_ranges(&[0, 1, 2, 5, 7, 8]), vec![0..3, 5..6, 7..9]);
+    }
+
+    #[test]
+    fn gather_reaches_target_order() {
+        let len = 7;
+        for mask in 0u32..(1 << len) {
+            let positions: Vec<u32> = (0..len).filter(|pos| mask & (1 << pos) != 0).collect();
+            for to in 0..=len {
+                // Songs staying put keep their order, with the gathered ones right
+                // before the first of them at or after the drop point.
+                let staying: Vec<u32> = (0..len).filter(|pos| !positions.contains(pos)).collect();
+                let split = staying.iter().position(|pos| *pos >= to).unwrap_or(staying.len());
+                let mut expected: Vec<u32> = staying[..split].to_vec();
+                expected.extend_from_slice(&positions);
+                expected.extend_from_slice(&staying[split..]);
+
+                let moves = gather_moves(&positions, to, len);
+                assert_eq!(
+                    apply_all(&moves, len),
+                    expected,
+                    "gathering {positions:?} at {to}: {moves:?}"
+                );
+                assert!(moves.len() <= to_ranges(&positions).len());
+            }
+        }
+    }
+
+    #[test]
+    fn permutation_reaches_target_order() {
+        for len in 0..=6 {
+            for order in permutations(len) {
+                let moves = permutation_moves(&order);
+                assert_eq!(apply_all(&moves, len), order, "moves: {moves:?}");
+            }
+        }
+        for seed in 0..50 {
+            let order = shuffled(200, seed);
+            let moves = permutation_moves(&order);
+            assert_eq!(apply_all(&moves, 200), order);
+        }
+    }
+
+    #[test]
+    fn sorted_order_needs_no_moves() {
+        let order: Vec<u32> = (0..10).collect();
+        assert!(permutation_moves(&order).is_empty());
+        assert!(gather_moves(&[3, 4, 5], 3, 10).is_empty());
+    }
+
+    #[test]
+    fn inverse_moves_restore_order() {
+        for seed in 0..20 {
+            let order = shuffled(50, seed);
+            let moves = permutation_moves(&order);
+            let mut items = apply_all(&moves, 50);
+            for m in moves.iter().rev() {
+                m.inverse().apply(&mut items);
+            }
+            assert_eq!(items, (0..50).collect::<Vec<u32>>());
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+// Saved queues, kept in the local SQLite DB. Automatic snapshots are taken before
+// the queue is cleared or replaced, and only the most recent few are kept. Named
+// ones are "workspaces" that are kept until deleted and can be switched between.
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct QueueSnapshot {
+    pub uris: Vec<String>,
+    /// Queue position of the current song, if any.
+    pub pos: Option<u32>,
+    /// Seconds into the current song.
+    pub elapsed: f64,
+    pub playing: bool,
+}
+
+impl QueueSnapshot {
+    pub fn is_empty(&self) -> bool {
+        self.uris.is_empty()
+    }
+}
+
+/// A queue snapshot as stored in the DB.
+#[derive(Debug, Clone)]
+pub struct SavedQueue {
+    pub id: i64,
+    /// Only workspaces are named.
+    pub name: Option<String>,
+    pub saved: OffsetDateTime,
+    pub snapshot: QueueSnapshot,
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+
+use gtk::glib;
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+
+use super::{FilterExpr, SongInfo};
+
+// Smart playlists are kept in the local SQLite DB instead of on the MPD server.
+// Each one narrows down the songs matching an (optional) MPD filter expression
+// using data MPD can't filter by: stickers and our own playback history.
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SmartSort {
+    #[default]
+    Random,
+    Title,
+    Artist,
+    Album,
+    LastPlayed,
+    PlayCount,
+    Rating,
+}
+
+impl SmartSort {
+    pub const ALL: [SmartSort; 7] = [
+        Self::Random,
+        Self::Title,
+        Self::Artist,
+        Self::Album,
+        Self::LastPlayed,
+        Self::PlayCount,
+        Self::Rating,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Random => "Random",
+            Self::Title => "Title",
+            Self::Artist => "Artist",
+            Self::Album => "Album",
+            Self::LastPlayed => "Last played",
+            Self::PlayCount => "Play count",
+            Self::Rating => "Rating",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SmartPlaylist {
+    pub name: String,
+    /// None means the whole library.
+    pub filter: Option<FilterExpr>,
+    /// On the 0-10 scale of the rating sticker. Unrated songs never pass.
+    pub min_rating: Option<i8>,
+    pub liked_only: bool,
+    pub exclude_disliked: bool,
+    pub min_play_count: Option<u32>,
+    /// Only songs played within this many days.
+    pub played_within_days: Option<u32>,
+    /// Only songs not played for at least this many days (or never).
+    pub not_played_for_days: Option<u32>,
+    pub limit: Option<u32>,
+    pub sort: SmartSort,
+    pub descending: bool,
+    /// Built-in collections are not stored in the DB and can't be edited.
+    #[serde(skip)]
+    pub builtin: bool,
+}
+
+/// Local data about songs that MPD itself cannot filter by, keyed by URI.
+#[derive(Debug, Default)]
+pub struct SongStats {
+    pub ratings: HashMap<String, i8>,
+    pub likes: HashMap<String, i8>,  // Same values as Thumbs
+    pub play_counts: HashMap<String, (u32, OffsetDateTime)>,  // Count & last played
+}
+
+impl SmartPlaylist {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    /// Songs with the like sticker set to thumbs up.
+    pub fn liked_songs() -> Self {
+        Self {
+            name: "Liked songs".to_owned(),
+            liked_only: true,
+            sort: SmartSort::Artist,
+            builtin: true,
+            ..Default::default()
+        }
+    }
+
+    /// Songs rated 4 stars or more, best first.
+    pub fn top_rated() -> Self {
+        Self {
+            name: "Top rated".to_owned(),
+            min_rating: Some(8),
+            sort: SmartSort::Rating,
+            descending: true,
+            builtin: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn needs_ratings(&self) -> bool {
+        self.min_rating.is_some() || self.sort == SmartSort::Rating
+    }
+
+    pub fn needs_likes(&self) -> bool {
+        self.liked_only || self.exclude_disliked
+    }
+
+    /// Short human-readable summary of the rules.
+    pub fn describe(&self) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        if self.filter.is_some() {
+            parts.push("Filtered".to_owned());
+        }
+        if let Some(rating) = self.min_rating {
+            parts.push(format!("rated {:.1}+ stars", rating as f32 / 2.0));
+        }
+        if self.liked_only {
+            parts.push("liked".to_owned());
+        } else if self.exclude_disliked {
+            parts.push("not disliked".to_owned());
+        }
+        if let Some(count) = self.min_play_count {
+            parts.push(format!("played {count}+ times"));
+        }
+        if let Some(days) = self.played_within_days {
+            parts.push(format!("played in the last {days} days"));
+        }
+        if let Some(days) = self.not_played_for_days {
+            parts.push(format!("not played for {days} days"));
+        }
+        if let Some(limit) = self.limit {
+            parts.push(format!("at most {limit} songs"));
+        }
+        if parts.is_empty() {
+            return "Whole library".to_owned();
+        }
+        let mut res = parts.join(", ");
+        if let Some(first) = res.get_mut(0..1) {
+            first.make_ascii_uppercase();
+        }
+        res
+    }
+
+    /// Narrow down, sort and truncate the songs matched by the MPD filter.
+    /// Matching songs get their last_played field filled in along the way.
+    pub fn apply(&self, songs: Vec<SongInfo>, stats: &SongStats) -> Vec<SongInfo> {
+        let now = OffsetDateTime::now_utc();
+        let play_count = |uri: &str| stats.play_counts.get(uri).map_or(0, |s| s.0);
+        let rating = |uri: &str| stats.ratings.get(uri).copied();
+        let mut res: Vec<SongInfo> = songs
+            .into_iter()
+            .filter(|song| {
+                let uri = song.uri.as_str();
+                if let Some(min) = self.min_rating {
+                    if rating(uri).is_none_or(|r| r < min) {
+                        return false;
+                    }
+                }
+                let like = stats.likes.get(uri).copied().unwrap_or(1);
+                if (self.liked_only && like != 2) || (self.exclude_disliked && like == 0) {
+                    return false;
+                }
+                if let Some(min) = self.min_play_count {
+                    if play_count(uri) < min {
+                        return false;
+                    }
+                }
+                let last_played = stats.play_counts.get(uri).map(|s| s.1);
+                if let Some(days) = self.played_within_days {
+                    if last_played.is_none_or(|ts| now - ts > Duration::days(days as i64)) {
+                        return false;
+                    }
+                }
+                if let Some(days) = self.not_played_for_days {
+                    if last_played.is_some_and(|ts| now - ts < Duration::days(days as i64)) {
+                        return false;
+                    }
+                }
+                true
+            })
+            .map(|mut song| {
+                song.last_played = stats.play_counts.get(&song.uri).map(|s| s.1);
+                song
+            })
+            .collect();
+
+        match self.sort {
+            SmartSort::Random => {
+                // Fisher-Yates
+                for i in (1..res.len()).rev() {
+                    let j = glib::random_int_range(0, i as i32 + 1) as usize;
+                    res.swap(i, j);
+                }
+            }
+            SmartSort::Title => res.sort_by(|a, b| a.title.cmp(&b.title)),
+            SmartSort::Artist => res.sort_by(|a, b| a.artist_tag.cmp(&b.artist_tag)),
+            SmartSort::Album => res.sort_by(|a, b| {
+                a.album
+                    .as_ref()
+                    .map(|album| &album.title)
+                    .cmp(&b.album.as_ref().map(|album| &album.title))
+                    // Files in an album folder are usually named by track number
+                    .then_with(|| a.uri.cmp(&b.uri))
+            }),
+            SmartSort::LastPlayed => res.sort_by_key(|song| song.last_played),
+            SmartSort::PlayCount => res.sort_by_key(|song| play_count(&song.uri)),
+            SmartSort::Rating => res.sort_by_key(|song| rating(&song.uri)),
+        }
+        if self.descending && self.sort != SmartSort::Random {
+            res.reverse();
+        }
+        if let Some(limit) = self.limit {
+            res.truncate(limit as usize);
+        }
+        res
+    }
+}
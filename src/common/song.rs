@@ -11,9 +11,12 @@ use std::{
 };
 use time::{Date, Month, OffsetDateTime};
 
-use crate::cache::{get_image_cache_path, sqlite};
+use crate::{
+    cache::{get_image_cache_path, sqlite},
+    utils::format_secs_as_duration,
+};
 
-use super::{artists_to_string, parse_mb_artist_tag, AlbumInfo, ArtistInfo};
+use super::{artists_to_string, parse_mb_artist_tag, AlbumInfo, ArtistInfo, Stickers, Thumbs};
 
 // Mostly for eyecandy
 #[derive(Clone, Copy, Debug, glib::Enum, PartialEq, Default)]
@@ -86,6 +89,7 @@ pub struct SongInfo {
     pub duration: Option<Duration>, // Default to 0 if somehow the option in mpd's Song is None
     pub queue_id: Option<u32>,
     pub queue_pos: Option<u32>,  // Only set once at creation. Subsequent updates are kept in the Song GObject.
+    pub queue_prio: u8, // MPD priority of the queued song. Only used to restore the up-next list.
     // range: Option<Range>,
     pub album: Option<AlbumInfo>,
     track: Cell<i64>,
@@ -99,7 +103,9 @@ pub struct SongInfo {
     // MusicBrainz stuff
     mbid: Option<String>,
     last_modified: Option<String>,
-    pub last_played: Option<OffsetDateTime>
+    pub last_played: Option<OffsetDateTime>,
+    // Only filled in by views that display play statistics
+    pub stickers: Stickers
 }
 
 impl SongInfo {
@@ -114,6 +120,10 @@ impl SongInfo {
     pub fn into_artist_infos(self) -> Vec<ArtistInfo> {
         self.artists
     }
+
+    pub fn get_mbid(&self) -> Option<&str> {
+        self.mbid.as_deref()
+    }
 }
 
 impl Default for SongInfo {
@@ -126,6 +136,7 @@ impl Default for SongInfo {
             duration: None,
             queue_id: None,
             queue_pos: None,
+            queue_prio: 0,
             album: None,
             track: Cell::new(-1), // negative values indicate no track index
             disc: Cell::new(-1),
@@ -133,7 +144,8 @@ impl Default for SongInfo {
             quality_grade: QualityGrade::Unknown,
             mbid: None,
             last_modified: None,
-            last_played: None
+            last_played: None,
+            stickers: Stickers::default()
         }
     }
 }
@@ -141,7 +153,7 @@ impl Default for SongInfo {
 mod imp {
     use super::*;
     use glib::{
-        ParamSpec, ParamSpecBoolean, ParamSpecInt64, ParamSpecObject, ParamSpecString, ParamSpecUInt, ParamSpecUInt64
+        ParamSpec, ParamSpecBoolean, ParamSpecChar, ParamSpecInt64, ParamSpecObject, ParamSpecString, ParamSpecUInt, ParamSpecUInt64
     };
     use once_cell::sync::Lazy;
 
@@ -154,7 +166,11 @@ mod imp {
     #[derive(Debug)]
     pub struct Song {
         pub info: OnceCell<SongInfo>,
-        pub is_playing: Cell<bool>
+        pub is_playing: Cell<bool>,
+        // Editable from song rows, so kept outside of the info struct.
+        // Initialised from its stickers.
+        pub rating: Cell<Option<i8>>,
+        pub like: Cell<Thumbs>
     }
 
     #[glib::object_subclass]
@@ -165,7 +181,9 @@ mod imp {
         fn new() -> Self {
             Self {
                 info: OnceCell::new(),
-                is_playing: Cell::new(false)
+                is_playing: Cell::new(false),
+                rating: Cell::new(None),
+                like: Cell::new(Thumbs::Sideways)
             }
         }
     }
@@ -204,6 +222,16 @@ mod imp {
                     ParamSpecString::builder("last-played-desc")
                         .read_only()
                         .build(),
+                    ParamSpecString::builder("play-stats-desc")
+                        .read_only()
+                        .build(),
+                    ParamSpecString::builder("resume-desc")
+                        .read_only()
+                        .build(),
+                    // -1 means unrated
+                    ParamSpecChar::builder("rating").build(),
+                    // Same values as the like sticker
+                    ParamSpecChar::builder("like").build(),
                 ]
             });
             PROPERTIES.as_ref()
@@ -231,16 +259,30 @@ mod imp {
                 "quality-grade" => obj.get_quality_grade().to_icon_name().to_value(),
                 "last-modified" => obj.get_last_modified().to_value(),
                 "last-played-desc" => obj.get_last_played_desc().to_value(),
+                "play-stats-desc" => obj.get_play_stats_desc().to_value(),
+                "resume-desc" => obj.get_resume_desc().to_value(),
+                "rating" => obj.get_rating().unwrap_or(-1).to_value(),
+                "like" => i8::from(obj.get_like()).to_value(),
                 _ => unimplemented!(),
             }
         }
 
-        // fn set_property(&self, _id: usize, value: &glib::Value, pspec: &ParamSpec) {
-        //     let obj = self.obj();
-        //     match pspec.name() {
-        //         _ => unimplemented!(),
-        //     }
-        // }
+        fn set_property(&self, _id: usize, value: &glib::Value, pspec: &ParamSpec) {
+            let obj = self.obj();
+            match pspec.name() {
+                "rating" => {
+                    if let Ok(r) = value.get::<i8>() {
+                        obj.set_rating(if r >= 0 { Some(r) } else { None });
+                    }
+                }
+                "like" => {
+                    if let Ok(Ok(status)) = value.get::<i8>().map(Thumbs::try_from) {
+                        obj.set_like(status);
+                    }
+                }
+                _ => unimplemented!(),
+            }
+        }
     }
 }
 
@@ -316,6 +358,55 @@ impl Song {
         }
     }
 
+    /// Play & skip counts from the stickers DB, if any.
+    pub fn get_play_stats_desc(&self) -> Option<String> {
+        // TODO: translations
+        let stickers = &self.get_info().stickers;
+        let plays = stickers.play_count.unwrap_or(0);
+        let skips = stickers.skip_count.unwrap_or(0);
+        let plays_desc = if plays == 1 { "1 play".to_owned() } else { format!("{plays} plays") };
+        match skips {
+            0 if plays == 0 => None,
+            0 => Some(plays_desc),
+            1 => Some(format!("{plays_desc}, 1 skip")),
+            _ => Some(format!("{plays_desc}, {skips} skips"))
+        }
+    }
+
+    /// Saved position from the elapsed sticker, if any.
+    pub fn get_resume_desc(&self) -> Option<String> {
+        // TODO: translations
+        self.get_info().stickers.elapsed.map(|secs| {
+            format!(
+                "stopped at {} of {}",
+                format_secs_as_duration(secs as f64),
+                format_secs_as_duration(self.get_duration() as f64)
+            )
+        })
+    }
+
+    pub fn get_rating(&self) -> Option<i8> {
+        self.imp().rating.get()
+    }
+
+    pub fn set_rating(&self, new: Option<i8>) {
+        let old = self.imp().rating.replace(new);
+        if old != new {
+            self.notify("rating");
+        }
+    }
+
+    pub fn get_like(&self) -> Thumbs {
+        self.imp().like.get()
+    }
+
+    pub fn set_like(&self, new: Thumbs) {
+        let old = self.imp().like.replace(new);
+        if old != new {
+            self.notify("like");
+        }
+    }
+
     pub fn get_last_played(&self) -> Option<OffsetDateTime> {
         self.get_info().last_played.clone()
     }
@@ -481,6 +572,7 @@ impl From<mpd::song::Song> for SongInfo {
             duration: song.duration,
             queue_id: None,
             queue_pos: None,
+            queue_prio: 0,
             album: None,
             track: Cell::new(-1),
             disc: Cell::new(-1),
@@ -488,12 +580,14 @@ impl From<mpd::song::Song> for SongInfo {
             quality_grade: QualityGrade::Unknown,
             mbid: None,
             last_modified: song.last_mod,
-            last_played: None
+            last_played: None,
+            stickers: Stickers::default()
         };
 
         if let Some(place) = song.place {
             let _ = res.queue_id.replace(place.id.0);
             let _ = res.queue_pos.replace(place.pos);
+            res.queue_prio = place.prio;
         }
 
         // Search tags vector for additional fields we can use.
@@ -646,6 +740,8 @@ impl From<mpd::song::Song> for Song {
 impl From<SongInfo> for Song {
     fn from(info: SongInfo) -> Self {
         let res = glib::Object::new::<Self>();
+        res.imp().rating.set(info.stickers.rating);
+        res.imp().like.set(info.stickers.like);
         let _ = res.imp().info.set(info);
         res
     }
@@ -0,0 +1,98 @@
+use gtk::glib;
+use time::{Date, Duration, Month, OffsetDateTime};
+
+use super::{AlbumInfo, ArtistInfo, SongInfo};
+
+/// Time ranges the statistics page can be restricted to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StatsRange {
+    Week,
+    #[default]
+    Month,
+    Year,
+    AllTime,
+}
+
+impl StatsRange {
+    /// In the same order as the range dropdown of the statistics page.
+    pub fn from_index(idx: u32) -> Self {
+        match idx {
+            0 => Self::Week,
+            1 => Self::Month,
+            2 => Self::Year,
+            _ => Self::AllTime,
+        }
+    }
+
+    /// Listens before this time are ignored.
+    pub fn since(&self) -> Option<OffsetDateTime> {
+        let now = OffsetDateTime::now_utc();
+        match self {
+            Self::Week => Some(now - Duration::days(7)),
+            Self::Month => Some(now - Duration::days(30)),
+            Self::Year => Some(now - Duration::days(365)),
+            Self::AllTime => None,
+        }
+    }
+
+    /// How many days to show in the per-day heatmap. Capped at a year so that the
+    /// all-time calendar stays readable.
+    pub fn calendar_days(&self) -> i64 {
+        match self {
+            Self::Week => 7,
+            Self::Month => 30,
+            Self::Year | Self::AllTime => 365,
+        }
+    }
+}
+
+/// Everything shown on the statistics page. Listening times are in seconds.
+#[derive(Debug, Default)]
+pub struct ListeningStats {
+    pub range: StatsRange,
+    pub total_plays: u32,
+    pub total_time: u64,
+    pub current_streak: u32,
+    pub longest_streak: u32,
+    // Along with play count within the range & first listen date
+    pub top_songs: Vec<(SongInfo, u32, Date)>,
+    pub top_albums: Vec<(AlbumInfo, u32, Date)>,
+    pub top_artists: Vec<(ArtistInfo, u32, Date)>,
+    // Only days with at least one listen
+    pub by_day: Vec<(Date, u64)>,
+    // Indexed by weekday (Monday first), then hour
+    pub by_hour: [[u64; 24]; 7],
+}
+
+pub fn local_today() -> Date {
+    let now = glib::DateTime::now_local().expect("Unable to get local time");
+    Date::from_calendar_date(
+        now.year(),
+        Month::try_from(now.month() as u8).unwrap(),
+        now.day_of_month() as u8,
+    )
+    .expect("Invalid local date")
+}
+
+/// Compute the current & longest streaks of consecutive days with at least one listen,
+/// given such days in ascending order. The current streak is kept alive until the
+/// end of the day after the last listen.
+pub fn compute_streaks(days: &[Date], today: Date) -> (u32, u32) {
+    let mut longest: u32 = 0;
+    let mut run: u32 = 0;
+    let mut prev: Option<Date> = None;
+    for day in days.iter() {
+        run = if prev.and_then(|prev| prev.next_day()) == Some(*day) {
+            run + 1
+        } else {
+            1
+        };
+        longest = longest.max(run);
+        prev = Some(*day);
+    }
+    let current = match prev {
+        Some(last) if last == today || last.next_day() == Some(today) => run,
+        _ => 0,
+    };
+    (current, longest)
+}
@@ -21,9 +21,35 @@ impl TryFrom<i8> for Thumbs {
     }
 }
 
+impl From<Thumbs> for i8 {
+    fn from(value: Thumbs) -> Self {
+        match value {
+            Thumbs::Down => 0,
+            Thumbs::Sideways => 1,
+            Thumbs::Up => 2
+        }
+    }
+}
+
+/// Songs shorter than this (in seconds) never count towards play & skip statistics.
+const MIN_COUNTED_DURATION: u64 = 10;
+/// A song counts as played after half of it or this many seconds have been played,
+/// whichever comes first. Scrobbling uses the same rule.
+const MAX_REQUIRED_PLAYTIME: f64 = 240.0;
+/// Changing tracks before a song counts as played is a skip, but only if it has been
+/// playing for at least this many seconds. Like myMPD, this avoids counting quick
+/// jumps through the queue.
+const MIN_SKIP_ELAPSED: f64 = 10.0;
+
+/// How long (in seconds) a song of the given duration has to be played for to count
+/// as played.
+pub fn required_playtime(duration: u64) -> f64 {
+    (duration as f64 / 2.0).min(MAX_REQUIRED_PLAYTIME)
+}
+
 // Our sticker schema
 // Largely follows myMPD's schema
-#[derive(Default, Debug)]
+#[derive(Default, Debug, Clone)]
 pub struct Stickers {
     pub rating: Option<i8>,
     pub like: Thumbs, // 0 = dislike, 1 = neutral, 2 = like
@@ -43,6 +69,32 @@ impl Stickers {
     pub const PLAY_COUNT_KEY: &'static str = "playCount";
     pub const SKIP_COUNT_KEY: &'static str = "skipCount";
 
+    /// Whether a song of the given duration has been played for long enough to count
+    /// towards its play count (myMPD rules).
+    pub fn is_played(duration: u64, elapsed: f64) -> bool {
+        duration >= MIN_COUNTED_DURATION && elapsed >= required_playtime(duration)
+    }
+
+    /// Whether moving on from a song that has not been counted as played at the given
+    /// position should count as a skip (myMPD rules).
+    pub fn is_skipped(duration: u64, elapsed: f64) -> bool {
+        duration >= MIN_COUNTED_DURATION && elapsed >= MIN_SKIP_ELAPSED
+    }
+
+    /// Set a sticker by its name. Unknown names are ignored.
+    pub fn set(&mut self, name: &str, val: &str) {
+        match name {
+            Self::RATING_KEY => self.set_rating(val),
+            Self::LIKE_KEY => self.set_like(val),
+            Self::ELAPSED_KEY => self.set_elapsed(val),
+            Self::LAST_PLAYED_KEY => self.set_last_played(val),
+            Self::LAST_SKIPPED_KEY => self.set_last_skipped(val),
+            Self::PLAY_COUNT_KEY => self.set_play_count(val),
+            Self::SKIP_COUNT_KEY => self.set_skip_count(val),
+            _ => {}
+        }
+    }
+
     pub fn set_rating(&mut self, val: &str) {
         if let Ok(rating) = val.trim().parse::<i8>() {
             self.rating = Some(rating);
@@ -0,0 +1,100 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{SongInfo, Stickers};
+
+// Portable backups of the MPD sticker DB. Song stickers are keyed by URI, but each
+// entry also carries the song's MusicBrainz ID & main tags so that it can still be
+// found after the music folder has been reorganised or moved to another server.
+// Album (tag) and playlist stickers are keyed by name, as MPD does.
+
+pub const STICKER_BACKUP_VERSION: u32 = 1;
+
+/// Sticker names to look for on every object. MPD before 0.24 can't list all names in
+/// use, so songs are discovered by these names and then have all of their stickers
+/// backed up, including ones set by other clients.
+pub const KNOWN_STICKER_NAMES: [&str; 7] = [
+    Stickers::RATING_KEY,
+    Stickers::LIKE_KEY,
+    Stickers::ELAPSED_KEY,
+    Stickers::LAST_PLAYED_KEY,
+    Stickers::LAST_SKIPPED_KEY,
+    Stickers::PLAY_COUNT_KEY,
+    Stickers::SKIP_COUNT_KEY,
+];
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SongStickers {
+    pub uri: String,
+    pub mbid: Option<String>,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: Option<u64>, // In seconds
+    pub stickers: BTreeMap<String, String>,
+}
+
+impl SongStickers {
+    /// Songs no longer in the library are backed up with their URI only.
+    pub fn new(uri: String, song: Option<&SongInfo>, stickers: BTreeMap<String, String>) -> Self {
+        Self {
+            uri,
+            mbid: song.and_then(|song| song.get_mbid().map(str::to_owned)),
+            title: song.map(|song| song.title.clone()),
+            artist: song.and_then(|song| song.artist_tag.clone()),
+            album: song.and_then(|song| song.album.as_ref().map(|album| album.title.clone())),
+            duration: song.and_then(|song| song.duration.map(|dur| dur.as_secs())),
+            stickers,
+        }
+    }
+}
+
+/// Stickers of an album or playlist.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NamedStickers {
+    pub name: String,
+    pub stickers: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StickerBackup {
+    pub version: u32,
+    pub created_at: i64, // UNIX timestamp
+    pub songs: Vec<SongStickers>,
+    pub albums: Vec<NamedStickers>,
+    pub playlists: Vec<NamedStickers>,
+}
+
+impl StickerBackup {
+    pub fn n_stickers(&self) -> usize {
+        self.songs
+            .iter()
+            .map(|song| song.stickers.len())
+            .sum::<usize>()
+            + self
+                .albums
+                .iter()
+                .chain(self.playlists.iter())
+                .map(|obj| obj.stickers.len())
+                .sum::<usize>()
+    }
+}
+
+/// What restoring a backup did, or would do in a dry run.
+#[derive(Debug, Default)]
+pub struct StickerRestoreReport {
+    pub dry_run: bool,
+    pub n_stickers: usize, // Written, or to be written
+    pub songs_kept: usize, // Found at the same URI
+    // Old & new URIs of songs found elsewhere by MBID or tags
+    pub songs_remapped: Vec<(String, String)>,
+    pub songs_missing: Vec<String>,
+    pub albums_restored: usize,
+    pub albums_missing: Vec<String>,
+    pub playlists_restored: usize,
+    pub playlists_missing: Vec<String>,
+}
@@ -0,0 +1,63 @@
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+};
+
+// Just enough of RFC 4180 for history files, so as not to pull in another dependency.
+
+fn escape(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+pub fn write_record<W: Write, S: AsRef<str>>(writer: &mut W, fields: &[S]) -> io::Result<()> {
+    let line = fields
+        .iter()
+        .map(|field| escape(field.as_ref()))
+        .collect::<Vec<Cow<str>>>()
+        .join(",");
+    writer.write_all(line.as_bytes())?;
+    writer.write_all(b"\r\n")
+}
+
+/// Split text into records of fields. Quoted fields may contain commas, escaped
+/// quotes and line breaks. Blank lines are skipped.
+pub fn parse(text: &str) -> Vec<Vec<String>> {
+    let mut records: Vec<Vec<String>> = Vec::new();
+    let mut record: Vec<String> = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            if c != '"' {
+                field.push(c);
+            } else if chars.peek() == Some(&'"') {
+                field.push('"');
+                chars.next();
+            } else {
+                in_quotes = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    records.retain(|record| !(record.len() == 1 && record[0].trim().is_empty()));
+    records
+}
@@ -0,0 +1,241 @@
+use std::path::Path;
+
+use serde_json::{Map, Value};
+use time::{Date, Month};
+
+use super::{ExportedListen, HistoryFormat, ImportedListen, csv};
+
+const MONTHS: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+fn str_of(value: Option<&Value>) -> Option<String> {
+    value?
+        .as_str()
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(str::to_owned)
+}
+
+/// Last.fm wraps artist & album names in objects, with the name itself under "#text"
+/// or "name" depending on the API method used to export them.
+fn text_of(value: Option<&Value>) -> Option<String> {
+    match value? {
+        Value::Object(obj) => str_of(obj.get("#text")).or_else(|| str_of(obj.get("name"))),
+        other => str_of(Some(other)),
+    }
+}
+
+fn int_of(value: Option<&Value>) -> Option<i64> {
+    match value? {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.trim().parse().ok(),
+        _ => None,
+    }
+}
+
+/// UNIX timestamps, either as numbers or strings, or RFC 3339 date-times.
+fn timestamp_of(value: Option<&Value>) -> Option<i64> {
+    int_of(value).or_else(|| {
+        chrono::DateTime::parse_from_rfc3339(value?.as_str()?)
+            .ok()
+            .map(|dt| dt.timestamp())
+    })
+}
+
+/// Parse UTC dates like "31 Jan 2021, 12:34" as found in Last.fm exports.
+fn parse_lastfm_date(text: &str) -> Option<i64> {
+    let text = text.replace(',', " ");
+    let mut parts = text.split_whitespace();
+    let day: u8 = parts.next()?.parse().ok()?;
+    let month_name = parts.next()?.to_lowercase();
+    let month = MONTHS
+        .iter()
+        .position(|month| month_name.starts_with(month))?;
+    let year: i32 = parts.next()?.parse().ok()?;
+    let (hour, minute) = parts.next()?.split_once(':')?;
+    Some(
+        Date::from_calendar_date(year, Month::try_from(month as u8 + 1).ok()?, day)
+            .ok()?
+            .with_hms(hour.parse().ok()?, minute.parse().ok()?, 0)
+            .ok()?
+            .assume_utc()
+            .unix_timestamp(),
+    )
+}
+
+fn own_listen(value: &Value) -> Option<ImportedListen> {
+    let listen: ExportedListen = serde_json::from_value(value.clone()).ok()?;
+    Some(ImportedListen {
+        timestamp: listen.listened_at,
+        uri: Some(listen.uri).filter(|uri| !uri.is_empty()),
+        artist: listen.artist.unwrap_or_default(),
+        title: listen.title.unwrap_or_default(),
+        album: listen.album,
+        recording_mbid: listen.recording_mbid,
+        duration: listen.duration,
+    })
+}
+
+/// A listen from a ListenBrainz dump or API response. The recording MBID might be
+/// given by the submitting client or filled in later by ListenBrainz's own mapping.
+fn listenbrainz_listen(listened_at: Option<&Value>, meta: &Value) -> Option<ImportedListen> {
+    let info = meta.get("additional_info");
+    let mapping = meta.get("mbid_mapping");
+    Some(ImportedListen {
+        timestamp: timestamp_of(listened_at)?,
+        uri: None,
+        artist: str_of(meta.get("artist_name"))?,
+        title: str_of(meta.get("track_name"))?,
+        album: str_of(meta.get("release_name")),
+        recording_mbid: info
+            .and_then(|info| str_of(info.get("recording_mbid")))
+            .or_else(|| mapping.and_then(|mapping| str_of(mapping.get("recording_mbid")))),
+        duration: info
+            .and_then(|info| {
+                int_of(info.get("duration_ms"))
+                    .map(|ms| ms / 1000)
+                    .or_else(|| int_of(info.get("duration")))
+            })
+            .and_then(|secs| u64::try_from(secs).ok()),
+    })
+}
+
+/// A scrobble from a Last.fm export, in the user.getRecentTracks format.
+fn lastfm_scrobble(obj: &Map<String, Value>) -> Option<ImportedListen> {
+    // The currently playing track has no date yet
+    let date = obj.get("date")?;
+    Some(ImportedListen {
+        timestamp: timestamp_of(date.get("uts")).or_else(|| {
+            date.get("#text")
+                .unwrap_or(date)
+                .as_str()
+                .and_then(parse_lastfm_date)
+        })?,
+        uri: None,
+        artist: text_of(obj.get("artist"))?,
+        title: str_of(obj.get("name"))?,
+        album: text_of(obj.get("album")),
+        recording_mbid: str_of(obj.get("mbid")),
+        duration: None,
+    })
+}
+
+fn collect_json(value: &Value, res: &mut Vec<ImportedListen>) {
+    match value {
+        Value::Array(items) => {
+            for item in items.iter() {
+                collect_json(item, res);
+            }
+        }
+        Value::Object(obj) => {
+            if let Some(meta) = obj.get("track_metadata") {
+                res.extend(listenbrainz_listen(obj.get("listened_at"), meta));
+            } else if let Some(listens) = obj.get("payload").and_then(|p| p.get("listens")) {
+                collect_json(listens, res);
+            } else if let Some(tracks) = obj.get("recenttracks").and_then(|rt| rt.get("track")) {
+                collect_json(tracks, res);
+            } else if let Some(tracks) = obj.get("track").filter(|tracks| tracks.is_array()) {
+                collect_json(tracks, res);
+            } else if obj.contains_key("listened_at") {
+                res.extend(own_listen(value));
+            } else if obj.contains_key("name") && obj.contains_key("artist") {
+                res.extend(lastfm_scrobble(obj));
+            }
+        }
+        _ => {}
+    }
+}
+
+fn read_csv(text: &str, res: &mut Vec<ImportedListen>) {
+    let records = csv::parse(text);
+    let Some(first) = records.first() else {
+        return;
+    };
+    let header: Vec<String> = first
+        .iter()
+        .map(|name| name.trim().to_lowercase())
+        .collect();
+    let col = |names: &[&str]| {
+        header
+            .iter()
+            .position(|name| names.contains(&name.as_str()))
+    };
+    let (Some(artist_col), Some(title_col)) = (
+        col(&["artist", "artist_name"]),
+        col(&["title", "track", "track_name", "name"]),
+    ) else {
+        // Headerless exports as made by the popular lastfm-to-csv tool:
+        // artist, album, title, date
+        for record in records.iter().filter(|record| record.len() >= 4) {
+            let Some(timestamp) = parse_lastfm_date(&record[3]) else {
+                continue;
+            };
+            res.push(ImportedListen {
+                timestamp,
+                uri: None,
+                artist: record[0].trim().to_owned(),
+                title: record[2].trim().to_owned(),
+                album: Some(record[1].trim().to_owned()).filter(|album| !album.is_empty()),
+                recording_mbid: None,
+                duration: None,
+            });
+        }
+        return;
+    };
+    let album_col = col(&["album", "release_name"]);
+    let mbid_col = col(&["recording_mbid", "track_mbid", "mbid"]);
+    let uri_col = col(&["uri"]);
+    let duration_col = col(&["duration"]);
+    let timestamp_col = col(&["listened_at", "uts", "timestamp"]);
+    let date_col = col(&["utc_time", "date"]);
+    for record in records.iter().skip(1) {
+        let get = |col: Option<usize>| {
+            col.and_then(|col| record.get(col))
+                .map(|field| field.trim())
+                .filter(|field| !field.is_empty())
+        };
+        let Some(timestamp) = get(timestamp_col)
+            .and_then(|ts| ts.parse::<i64>().ok())
+            .or_else(|| get(date_col).and_then(parse_lastfm_date))
+        else {
+            continue;
+        };
+        res.push(ImportedListen {
+            timestamp,
+            uri: get(uri_col).map(str::to_owned),
+            artist: get(Some(artist_col)).unwrap_or_default().to_owned(),
+            title: get(Some(title_col)).unwrap_or_default().to_owned(),
+            album: get(album_col).map(str::to_owned),
+            recording_mbid: get(mbid_col).map(str::to_owned),
+            duration: get(duration_col).and_then(|secs| secs.parse().ok()),
+        });
+    }
+}
+
+/// Read listens from a file in any of the supported formats. Entries that can't be
+/// understood are skipped.
+pub fn read_listens(path: &Path) -> Result<Vec<ImportedListen>, String> {
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let text = text.trim_start_matches('\u{feff}');
+    let mut res: Vec<ImportedListen> = Vec::new();
+    match HistoryFormat::from_path(path) {
+        HistoryFormat::Csv => read_csv(text, &mut res),
+        HistoryFormat::Json => match serde_json::from_str::<Value>(text) {
+            Ok(value) => collect_json(&value, &mut res),
+            Err(_) => {
+                // JSON Lines, one listen per line
+                for line in text.lines().filter(|line| !line.trim().is_empty()) {
+                    let value = serde_json::from_str::<Value>(line).map_err(|e| e.to_string())?;
+                    collect_json(&value, &mut res);
+                }
+            }
+        },
+    }
+    res.retain(|listen| listen.uri.is_some() || !listen.title.is_empty());
+    if res.is_empty() {
+        Err("No listens found in file".to_owned())
+    } else {
+        Ok(res)
+    }
+}
@@ -0,0 +1,134 @@
+use crate::common::SongInfo;
+
+use super::ImportedListen;
+
+/// Minimum similarity of both titles and artists for a fuzzy match.
+const MIN_SIMILARITY: f64 = 0.8;
+/// Maximum difference in seconds between durations for a fuzzy match, when both are known.
+const MAX_DURATION_DIFF: u64 = 10;
+/// Words after a " - " in titles that mark a version of the same recording, such as
+/// in "Song - 2011 Remaster".
+const VERSION_MARKERS: [&str; 8] = [
+    "remaster", "version", "edit", "live", "mono", "stereo", "mix", "demo",
+];
+
+fn fold(text: &str, strip_brackets: bool) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut depth: u32 = 0;
+    for c in text.chars() {
+        match c {
+            '(' | '[' | '{' if strip_brackets => depth += 1,
+            ')' | ']' | '}' if strip_brackets => depth = depth.saturating_sub(1),
+            _ if depth > 0 => {}
+            c if c.is_alphanumeric() => res.extend(c.to_lowercase()),
+            // Apostrophes are often left out or replaced by typographic ones
+            '\'' | '’' => {}
+            _ => res.push(' '),
+        }
+    }
+    res.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Drop version suffixes such as " - Remastered 2011".
+fn strip_version(text: &str) -> &str {
+    match text.rsplit_once(" - ") {
+        Some((head, tail))
+            if VERSION_MARKERS
+                .iter()
+                .any(|marker| tail.to_lowercase().contains(marker)) =>
+        {
+            head
+        }
+        _ => text,
+    }
+}
+
+/// Lowercase, drop version suffixes & bracketed parts such as "(feat. X)" or
+/// "[Live]", ignore punctuation and collapse whitespace. Titles made of bracketed
+/// parts only are kept as-is apart from the rest.
+pub fn normalize(text: &str) -> String {
+    let stripped = fold(strip_version(text), true);
+    if stripped.is_empty() {
+        fold(text, false)
+    } else {
+        stripped
+    }
+}
+
+fn levenshtein(a: &[char], b: &[char]) -> usize {
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    let mut curr: Vec<usize> = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        curr[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == cb { 0 } else { 1 };
+            curr[j + 1] = (prev[j] + cost).min(prev[j + 1] + 1).min(curr[j] + 1);
+        }
+        std::mem::swap(&mut prev, &mut curr);
+    }
+    prev[b.len()]
+}
+
+/// Similarity between two normalized strings, from 0 (nothing in common) to 1 (equal).
+pub fn similarity(a: &str, b: &str) -> f64 {
+    if a == b {
+        return 1.0;
+    }
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let max_len = a.len().max(b.len());
+    if max_len == 0 {
+        return 1.0;
+    }
+    1.0 - levenshtein(&a, &b) as f64 / max_len as f64
+}
+
+/// Term to search the library with when looking for candidates: the title without
+/// versions or bracketed parts.
+pub fn search_term(title: &str) -> String {
+    let head = strip_version(title);
+    let head = head.split(['(', '[', '{']).next().unwrap_or(head).trim();
+    if head.is_empty() {
+        title.trim().to_owned()
+    } else {
+        head.to_owned()
+    }
+}
+
+/// How well a song in the library matches an imported listen, from 0 to 1, or None
+/// if it doesn't match at all.
+pub fn score(listen: &ImportedListen, song: &SongInfo) -> Option<f64> {
+    let title = similarity(&normalize(&listen.title), &normalize(&song.title));
+    if title < MIN_SIMILARITY {
+        return None;
+    }
+    // Compare against both the full artist tag and each individual artist, since the
+    // other side might have kept only the main one.
+    let listen_artist = normalize(&listen.artist);
+    let artist = song
+        .artist_tag
+        .iter()
+        .map(String::as_str)
+        .chain(song.artists.iter().map(|artist| artist.name.as_str()))
+        .map(|name| similarity(&listen_artist, &normalize(name)))
+        .fold(0.0, f64::max);
+    if artist < MIN_SIMILARITY {
+        return None;
+    }
+    let duration = match (listen.duration, song.duration) {
+        (Some(secs), Some(dur)) => {
+            let diff = secs.abs_diff(dur.as_secs());
+            if diff > MAX_DURATION_DIFF {
+                return None;
+            }
+            1.0 - diff as f64 / (MAX_DURATION_DIFF + 1) as f64
+        }
+        _ => 0.5,
+    };
+    // Prefer songs from the same album when there are several versions
+    let album = match (listen.album.as_ref(), song.album.as_ref()) {
+        (Some(a), Some(b)) => similarity(&normalize(a), &normalize(&b.title)),
+        _ => 0.5,
+    };
+    Some(0.4 * title + 0.4 * artist + 0.1 * duration + 0.1 * album)
+}
@@ -0,0 +1,177 @@
+mod csv;
+mod import;
+pub mod matching;
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use time::{OffsetDateTime, format_description::well_known::Rfc3339};
+
+use crate::common::SongInfo;
+
+pub use import::read_listens;
+
+// Listening history import & export. Exports contain one entry per listen, with the
+// song's tags resolved from the MPD library at the time of exporting so that they stay
+// meaningful elsewhere. Imports accept these exports as well as Last.fm scrobble exports
+// and ListenBrainz listen dumps. Imported listens are matched to songs in the library
+// by URI first, then by MusicBrainz recording ID, then by fuzzy artist, title & duration
+// matching (see the matching module).
+
+/// Columns of CSV exports, in the same order as ExportedListen's fields.
+const CSV_HEADER: [&str; 11] = [
+    "listened_at",
+    "time",
+    "uri",
+    "title",
+    "artist",
+    "album",
+    "album_artist",
+    "duration",
+    "recording_mbid",
+    "release_mbid",
+    "artist_mbids",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryFormat {
+    Json,
+    Csv,
+}
+
+impl HistoryFormat {
+    /// Guess from the file extension. Anything that isn't CSV is treated as JSON
+    /// (or JSON Lines, which ListenBrainz dumps use).
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => Self::Csv,
+            _ => Self::Json,
+        }
+    }
+}
+
+/// A single listen as written to exports.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ExportedListen {
+    pub listened_at: i64, // UNIX timestamp
+    #[serde(default)]
+    pub time: String, // Same as above, in RFC 3339 for humans
+    pub uri: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub duration: Option<u64>, // In seconds
+    pub recording_mbid: Option<String>,
+    pub release_mbid: Option<String>,
+    #[serde(default)]
+    pub artist_mbids: Vec<String>,
+}
+
+impl ExportedListen {
+    /// Songs no longer in the library are exported with their URI only.
+    pub fn new(
+        uri: String,
+        timestamp: OffsetDateTime,
+        duration: Option<u64>,
+        song: Option<&SongInfo>,
+    ) -> Self {
+        let mut res = Self {
+            listened_at: timestamp.unix_timestamp(),
+            time: timestamp.format(&Rfc3339).unwrap_or_default(),
+            uri,
+            duration: duration.filter(|secs| *secs > 0),
+            ..Default::default()
+        };
+        if let Some(song) = song {
+            res.title = Some(song.title.clone());
+            res.artist = song.artist_tag.clone().or_else(|| {
+                (!song.artists.is_empty()).then(|| {
+                    song.artists
+                        .iter()
+                        .map(|artist| artist.name.as_str())
+                        .collect::<Vec<&str>>()
+                        .join(", ")
+                })
+            });
+            if let Some(album) = song.album.as_ref() {
+                res.album = Some(album.title.clone());
+                res.album_artist = album.albumartist.clone();
+                res.release_mbid = album.mbid.clone();
+            }
+            res.duration = res.duration.or(song.duration.map(|dur| dur.as_secs()));
+            res.recording_mbid = song.get_mbid().map(str::to_owned);
+            res.artist_mbids = song
+                .artists
+                .iter()
+                .filter_map(|artist| artist.mbid.clone())
+                .collect();
+        }
+        res
+    }
+
+    fn to_record(&self) -> [String; 11] {
+        [
+            self.listened_at.to_string(),
+            self.time.clone(),
+            self.uri.clone(),
+            self.title.clone().unwrap_or_default(),
+            self.artist.clone().unwrap_or_default(),
+            self.album.clone().unwrap_or_default(),
+            self.album_artist.clone().unwrap_or_default(),
+            self.duration
+                .map(|secs| secs.to_string())
+                .unwrap_or_default(),
+            self.recording_mbid.clone().unwrap_or_default(),
+            self.release_mbid.clone().unwrap_or_default(),
+            self.artist_mbids.join(";"),
+        ]
+    }
+}
+
+/// Write listens to the given file, in the format implied by its extension.
+pub fn write_listens(path: &Path, listens: &[ExportedListen]) -> io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    match HistoryFormat::from_path(path) {
+        HistoryFormat::Json => serde_json::to_writer_pretty(&mut writer, listens)?,
+        HistoryFormat::Csv => {
+            csv::write_record(&mut writer, &CSV_HEADER)?;
+            for listen in listens.iter() {
+                csv::write_record(&mut writer, &listen.to_record())?;
+            }
+        }
+    }
+    writer.flush()
+}
+
+/// A past listen read from a file, yet to be matched against the library.
+#[derive(Debug, Clone, Default)]
+pub struct ImportedListen {
+    pub timestamp: i64,      // UNIX timestamp
+    pub uri: Option<String>, // Only available from our own exports
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    pub recording_mbid: Option<String>,
+    pub duration: Option<u64>, // In seconds
+}
+
+/// Outcome of a history import.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+    // Listens already in the history
+    pub duplicates: usize,
+    // Artist, title & listen count of tracks not found in the library, most listened first
+    pub unmatched: Vec<(String, String, usize)>,
+}
+
+impl ImportReport {
+    pub fn n_unmatched(&self) -> usize {
+        self.unmatched.iter().map(|(_, _, count)| count).sum()
+    }
+}
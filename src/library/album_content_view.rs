@@ -294,28 +294,20 @@ mod imp {
                     #[strong]
                     obj,
                     move |_, _, _| {
-                        if let (_, Some(library)) = (
-                            obj.imp().album.borrow().as_ref(),
-                            obj.get_library()
-                        ) {
-                            let store = &obj.imp().song_list;
-                            if obj.imp().selecting_all.get() {
-                                let mut songs: Vec<Song> = Vec::with_capacity(store.n_items() as usize);
-                                for i in 0..store.n_items() {
-                                    songs.push(store.item(i).and_downcast::<Song>().unwrap());
-                                }
-                                library.insert_songs_next(&songs);
-                            } else {
-                                // Get list of selected songs
-                                let sel = &obj.imp().sel_model.selection();
-                                let mut songs: Vec<Song> = Vec::with_capacity(sel.size() as usize);
-                                let (iter, first_idx) = BitsetIter::init_first(sel).unwrap();
-                                songs.push(store.item(first_idx).and_downcast::<Song>().unwrap());
-                                iter.for_each(|idx| {
-                                    songs.push(store.item(idx).and_downcast::<Song>().unwrap())
-                                });
-                                library.insert_songs_next(&songs);
-                            }
+                        if let Some(library) = obj.get_library() {
+                            library.insert_songs_next(&obj.imp().selected_songs());
+                        }
+                    }
+                ))
+                .build();
+
+            let action_add_up_next = ActionEntry::builder("add-up-next")
+                .activate(clone!(
+                    #[strong]
+                    obj,
+                    move |_, _, _| {
+                        if let Some(library) = obj.get_library() {
+                            library.add_songs_to_up_next(&obj.imp().selected_songs());
                         }
                     }
                 ))
@@ -329,6 +321,7 @@ mod imp {
                 action_refetch_metadata,
                 action_clear_album_art,
                 action_insert_queue,
+                action_add_up_next,
             ]);
             self.obj().insert_action_group("album-content-view", Some(&actions));
         }
@@ -337,6 +330,28 @@ mod imp {
     impl WidgetImpl for AlbumContentView {}
 
     impl AlbumContentView {
+        /// Selected songs, or all of them if none are selected.
+        pub fn selected_songs(&self) -> Vec<Song> {
+            let store = &self.song_list;
+            if self.selecting_all.get() {
+                let mut songs: Vec<Song> = Vec::with_capacity(store.n_items() as usize);
+                for i in 0..store.n_items() {
+                    songs.push(store.item(i).and_downcast::<Song>().unwrap());
+                }
+                songs
+            } else {
+                // Get list of selected songs
+                let sel = &self.sel_model.selection();
+                let mut songs: Vec<Song> = Vec::with_capacity(sel.size() as usize);
+                let (iter, first_idx) = BitsetIter::init_first(sel).unwrap();
+                songs.push(store.item(first_idx).and_downcast::<Song>().unwrap());
+                iter.for_each(|idx| {
+                    songs.push(store.item(idx).and_downcast::<Song>().unwrap())
+                });
+                songs
+            }
+        }
+
         pub fn on_selection_changed(&self) {
             let sel_model = &self.sel_model;
             // TODO: this can be slow, might consider redesigning
@@ -347,6 +362,7 @@ mod imp {
                 self.queue_split_button_content.set_label("Queue all");
                 let queue_split_menu = Menu::new();
                 queue_split_menu.append(Some("Queue all next"), Some("album-content-view.insert-queue"));
+                queue_split_menu.append(Some("Add all to up next"), Some("album-content-view.add-up-next"));
                 self.queue_split_button.set_menu_model(Some(&queue_split_menu));
             } else {
                 // TODO: l10n
@@ -357,6 +373,7 @@ mod imp {
                     .set_label(format!("Queue {}", n_sel).as_str());
                 let queue_split_menu = Menu::new();
                 queue_split_menu.append(Some(format!("Queue {} next", n_sel).as_str()), Some("album-content-view.insert-queue"));
+                queue_split_menu.append(Some(format!("Add {} to up next", n_sel).as_str()), Some("album-content-view.add-up-next"));
                 self.queue_split_button.set_menu_model(Some(&queue_split_menu));
             }
         }
@@ -1,8 +1,8 @@
-use glib::{clone, closure, Object, SignalHandlerId};
+use glib::{clone, closure, closure_local, Object, SignalHandlerId};
 use gtk::{glib, prelude::*, subclass::prelude::*, CompositeTemplate, Label};
 use std::cell::{OnceCell, RefCell};
 
-use crate::{common::Song, utils::format_secs_as_duration};
+use crate::{common::{LikeButton, Rating, Song}, utils::format_secs_as_duration};
 
 use super::Library;
 
@@ -31,9 +31,19 @@ mod imp {
         pub artist_name: TemplateChild<Label>,
         #[template_child]
         pub duration: TemplateChild<Label>,
+        #[template_child]
+        pub play_stats_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub play_stats: TemplateChild<Label>,
+        #[template_child]
+        pub rating: TemplateChild<Rating>,
+        #[template_child]
+        pub like: TemplateChild<LikeButton>,
         // For unbinding the queue buttons when not bound to a song (i.e. being recycled)
         pub replace_queue_id: RefCell<Option<SignalHandlerId>>,
         pub append_queue_id: RefCell<Option<SignalHandlerId>>,
+        pub rating_id: RefCell<Option<SignalHandlerId>>,
+        pub like_id: RefCell<Option<SignalHandlerId>>,
         pub library: OnceCell<Library>,
     }
 
@@ -66,6 +76,7 @@ mod imp {
                     ParamSpecString::builder("duration").build(),
                     // ParamSpecInt64::builder("disc").build(),
                     ParamSpecString::builder("quality-grade").build(),
+                    ParamSpecString::builder("play-stats").build(),
                 ]
             });
             PROPERTIES.as_ref()
@@ -87,6 +98,7 @@ mod imp {
                 "duration" => self.duration.label().to_value(),
                 // "disc" => self.disc.get_label().to_value(),
                 "quality-grade" => self.quality_grade.icon_name().to_value(),
+                "play-stats" => self.play_stats.label().to_value(),
                 _ => unimplemented!(),
             }
         }
@@ -132,6 +144,12 @@ mod imp {
                         self.quality_grade.set_visible(false);
                     }
                 }
+                "play-stats" => {
+                    // Play & skip counts from the stickers DB. Hidden if never played.
+                    let desc = value.get::<&str>().unwrap_or("");
+                    self.play_stats.set_label(desc);
+                    self.play_stats_box.set_visible(!desc.is_empty());
+                }
                 _ => unimplemented!(),
             }
         }
@@ -182,6 +200,18 @@ impl AlbumSongRow {
         item.property_expression("item")
             .chain_property::<Song>("quality-grade")
             .bind(self, "quality-grade", gtk::Widget::NONE);
+
+        item.property_expression("item")
+            .chain_property::<Song>("play-stats-desc")
+            .bind(self, "play-stats", gtk::Widget::NONE);
+
+        item.property_expression("item")
+            .chain_property::<Song>("rating")
+            .bind(&self.imp().rating.get(), "value", gtk::Widget::NONE);
+
+        item.property_expression("item")
+            .chain_property::<Song>("like")
+            .bind(&self.imp().like.get(), "value", gtk::Widget::NONE);
     }
 
     pub fn bind(&self, song: &Song) {
@@ -223,6 +253,41 @@ impl AlbumSongRow {
             // Unbind old ID
             self.imp().append_queue.disconnect(old_id);
         }
+        if let Some(old_id) = self.imp().rating_id.replace(Some(self.imp().rating.connect_closure(
+            "changed",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                song,
+                move |rating: Rating| {
+                    if let Some(library) = this.imp().library.get() {
+                        let val = rating.value();
+                        library.rate_song(&song, if val > 0 { Some(val) } else { None });
+                    }
+                }
+            ),
+        ))) {
+            self.imp().rating.disconnect(old_id);
+        }
+        if let Some(old_id) = self.imp().like_id.replace(Some(self.imp().like.connect_closure(
+            "changed",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                song,
+                move |like: LikeButton| {
+                    if let Some(library) = this.imp().library.get() {
+                        library.like_song(&song, like.thumbs());
+                    }
+                }
+            ),
+        ))) {
+            self.imp().like.disconnect(old_id);
+        }
     }
 
     pub fn unbind(&self) {
@@ -232,5 +297,11 @@ impl AlbumSongRow {
         if let Some(id) = self.imp().append_queue_id.borrow_mut().take() {
             self.imp().append_queue.disconnect(id);
         }
+        if let Some(id) = self.imp().rating_id.borrow_mut().take() {
+            self.imp().rating.disconnect(id);
+        }
+        if let Some(id) = self.imp().like_id.borrow_mut().take() {
+            self.imp().like.disconnect(id);
+        }
     }
 }
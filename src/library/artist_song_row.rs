@@ -7,7 +7,7 @@ use std::{
 
 use crate::{
     cache::{placeholders::ALBUMART_THUMBNAIL_PLACEHOLDER, Cache, CacheState},
-    common::{CoverSource, Song, SongInfo},
+    common::{CoverSource, LikeButton, Rating, Song, SongInfo},
     utils::{format_secs_as_duration, strip_filename_linux},
 };
 
@@ -38,6 +38,10 @@ mod imp {
         pub album_name: TemplateChild<gtk::Label>,
         #[template_child]
         pub duration: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub rating: TemplateChild<Rating>,
+        #[template_child]
+        pub like: TemplateChild<LikeButton>,
         pub thumbnail_signal_ids: RefCell<Option<(SignalHandlerId, SignalHandlerId)>>,
         pub library: OnceCell<Library>,
         pub song: RefCell<Option<Song>>,
@@ -179,6 +183,14 @@ impl ArtistSongRow {
             .chain_property::<Song>("quality-grade")
             .bind(self, "quality-grade", gtk::Widget::NONE);
 
+        item.property_expression("item")
+            .chain_property::<Song>("rating")
+            .bind(&self.imp().rating.get(), "value", gtk::Widget::NONE);
+
+        item.property_expression("item")
+            .chain_property::<Song>("like")
+            .bind(&self.imp().like.get(), "value", gtk::Widget::NONE);
+
         let _ = self.imp().thumbnail_signal_ids.replace(Some((
             cache_state.connect_closure(
                 "album-art-downloaded",
@@ -252,6 +264,35 @@ impl ArtistSongRow {
                 }
             }
         ));
+
+        self.imp().rating.connect_closure(
+            "changed",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |rating: Rating| {
+                    if let (Some(library), Some(song)) = (this.imp().library.get(), this.imp().song.borrow().as_ref()) {
+                        let val = rating.value();
+                        library.rate_song(song, if val > 0 { Some(val) } else { None });
+                    }
+                }
+            ),
+        );
+
+        self.imp().like.connect_closure(
+            "changed",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |like: LikeButton| {
+                    if let (Some(library), Some(song)) = (this.imp().library.get(), this.imp().song.borrow().as_ref()) {
+                        library.like_song(song, like.thumbs());
+                    }
+                }
+            ),
+        );
     }
 
     fn clear_thumbnail(&self) {
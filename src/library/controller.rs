@@ -1,13 +1,18 @@
 use crate::{
     cache::{sqlite, Cache},
     client::{BackgroundTask, ClientState, MpdWrapper},
-    common::{Album, Artist, INode, Song, Stickers}, 
+    common::{
+        Album, Artist, FilterExpr, INode, PlaylistCheckReport, PlaylistFix, SmartPlaylist, Song,
+        StatsRange, Stickers, Thumbs,
+    },
     utils::settings_manager,
     player::Player,
+    playlist_file::ExportOptions,
 };
 use glib::{closure_local, subclass::Signal};
 use gtk::{gio, glib, prelude::*};
-use std::{borrow::Cow, cell::OnceCell, rc::Rc, sync::OnceLock, vec::Vec};
+use rustc_hash::FxHashMap;
+use std::{borrow::Cow, cell::OnceCell, path::PathBuf, rc::Rc, sync::OnceLock, vec::Vec};
 
 use adw::subclass::prelude::*;
 
@@ -25,6 +30,8 @@ mod imp {
     pub struct Library {
         pub client: OnceCell<Rc<MpdWrapper>>,
         pub recent_songs: gio::ListStore,
+        // Songs with a saved position, for the continue listening shelf
+        pub resumable_songs: gio::ListStore,
         // Album/Artist retrieval routine:
         // 1. Library places a background task to fetch albums.
         // 3. Background client gets list of unique album tags
@@ -36,6 +43,9 @@ mod imp {
         // append to the list store.
         pub playlists: gio::ListStore,
         pub playlists_initialized: Cell<bool>,
+        // Smart playlists live in the local DB and are not tied to any server
+        pub smart_playlists: gio::ListStore,
+        pub smart_playlists_initialized: Cell<bool>,
         pub albums: gio::ListStore,
         pub albums_initialized: Cell<bool>,
         pub recent_albums: gio::ListStore,
@@ -43,6 +53,13 @@ mod imp {
         pub artists_initialized: Cell<bool>,
         pub recent_artists: gio::ListStore,
 
+        // Global search results. Results arriving for any other term than the
+        // current one belong to outdated searches and are dropped.
+        pub search_term: RefCell<String>,
+        pub search_songs: gio::ListStore,
+        pub search_albums: gio::ListStore,
+        pub search_artists: gio::ListStore,
+
         // Folder view
         // Files and folders
         pub folder_history: RefCell<Vec<String>>,
@@ -62,14 +79,21 @@ mod imp {
         fn new() -> Self {
             Self {
                 recent_songs: gio::ListStore::new::<Song>(),
+                resumable_songs: gio::ListStore::new::<Song>(),
                 playlists: gio::ListStore::new::<INode>(),
                 playlists_initialized: Cell::new(false),
+                smart_playlists: gio::ListStore::new::<glib::BoxedAnyObject>(),
+                smart_playlists_initialized: Cell::new(false),
                 albums: gio::ListStore::new::<Album>(),
                 albums_initialized: Cell::new(false),
                 recent_albums: gio::ListStore::new::<Album>(),
                 artists: gio::ListStore::new::<Artist>(),
                 artists_initialized: Cell::new(false),
                 recent_artists: gio::ListStore::new::<Artist>(),
+                search_term: RefCell::new(String::new()),
+                search_songs: gio::ListStore::new::<Song>(),
+                search_albums: gio::ListStore::new::<Album>(),
+                search_artists: gio::ListStore::new::<Artist>(),
                 client: OnceCell::new(),
                 cache: OnceCell::new(),
                 player: OnceCell::new(),
@@ -218,16 +242,77 @@ impl Library {
                 }
             ),
         );
+
+        client_state.connect_closure(
+            "resumable-songs-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, entries: glib::BoxedAnyObject| {
+                    this.imp().resumable_songs.remove_all();
+                    this.imp()
+                        .resumable_songs
+                        .extend_from_slice(entries.borrow::<Vec<Song>>().as_ref());
+                }
+            ),
+        );
+
+        client_state.connect_closure(
+            "search-songs-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, term: String, songs: glib::BoxedAnyObject| {
+                    if term == *this.imp().search_term.borrow() {
+                        this.imp()
+                            .search_songs
+                            .extend_from_slice(songs.borrow::<Vec<Song>>().as_ref());
+                    }
+                }
+            ),
+        );
+
+        client_state.connect_closure(
+            "search-album-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, term: String, album: Album| {
+                    if term == *this.imp().search_term.borrow() {
+                        this.imp().search_albums.append(&album);
+                    }
+                }
+            ),
+        );
+
+        client_state.connect_closure(
+            "search-artist-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, term: String, artist: Artist| {
+                    if term == *this.imp().search_term.borrow() {
+                        this.imp().search_artists.append(&artist);
+                    }
+                }
+            ),
+        );
     }
 
     pub fn clear(&self) {
         self.imp().recent_songs.remove_all();
+        self.imp().resumable_songs.remove_all();
         self.imp().albums.remove_all();
         self.imp().albums_initialized.set(false);
         self.imp().recent_albums.remove_all();
         self.imp().artists.remove_all();
         self.imp().artists_initialized.set(false);
         self.imp().recent_artists.remove_all();
+        self.clear_search();
         self.imp().playlists.remove_all();
         self.imp().playlists_initialized.set(false);
         self.imp().folder_inodes.remove_all();
@@ -273,7 +358,7 @@ impl Library {
     pub fn queue_songs(&self, songs: &[Song], replace: bool, play: bool) {
         // TODO: support executing this atomically as a command list
         if replace {
-            self.client().clear_queue();
+            self.client().clear_queue_for_replace();
         }
         self.client().queue_background(
             BackgroundTask::QueueUris(
@@ -293,32 +378,27 @@ impl Library {
         );
     }
 
+    /// Play songs right after the current one, ahead of any other up-next songs.
+    /// Also works in random mode, as they are given raised priorities.
     pub fn insert_songs_next(&self, songs: &[Song]) {
-        let pos = if let Some(current_pos) = self.player().queue_pos() {
-            // Insert after the position of the current song
-            current_pos + 1
-        } else {
-            // If no current song, insert at the start of the queue
-            0
-        };
-        self.client().queue_background(
-            BackgroundTask::QueueUris(
-                songs
-                    .iter()
-                    .map(|s| s.get_uri().to_owned())
-                    .collect::<Vec<String>>(),
-                false,
-                None,
-                Some(pos),
-            ),
+        self.player().queue_up_next(
+            songs.iter().map(|s| s.get_uri().to_owned()).collect(),
             true
         );
     }
 
+    /// Add songs to the end of the up-next list.
+    pub fn add_songs_to_up_next(&self, songs: &[Song]) {
+        self.player().queue_up_next(
+            songs.iter().map(|s| s.get_uri().to_owned()).collect(),
+            false
+        );
+    }
+
     /// Queue all songs in a given album by track order.
     pub fn queue_album(&self, album: Album, replace: bool, play: bool, play_from: Option<u32>) {
         if replace {
-            self.client().clear_queue();
+            self.client().clear_queue_for_replace();
         }
         let mut query = Query::new();
         query.and(
@@ -353,10 +433,21 @@ impl Library {
         }
     }
 
+    /// Rate a song on the 0-10 scale of the rating sticker, or clear its rating.
+    pub fn rate_song(&self, song: &Song, score: Option<i8>) {
+        song.set_rating(score);
+        self.client().set_song_rating(song.get_uri(), score);
+    }
+
+    pub fn like_song(&self, song: &Song, status: Thumbs) {
+        song.set_like(status);
+        self.client().set_song_like(song.get_uri(), status);
+    }
+
     /// Queue all songs of an artist. TODO: allow specifying order.
     pub fn queue_artist(&self, artist: Artist, use_albumartist: bool, replace: bool, play: bool) {
         if replace {
-            self.client().clear_queue();
+            self.client().clear_queue_for_replace();
         }
         let mut query = Query::new();
         query.and_with_op(
@@ -459,7 +550,7 @@ impl Library {
     /// Queue a song or folder (when recursive == true) for playback.
     pub fn queue_uri(&self, uri: &str, replace: bool, play: bool, recursive: bool) {
         if replace {
-            self.client().clear_queue();
+            self.client().clear_queue_for_replace();
         }
         self.client().queue_background(
             BackgroundTask::QueueUris(
@@ -492,6 +583,10 @@ impl Library {
         self.imp().recent_songs.clone()
     }
 
+    pub fn resumable_songs(&self) -> gio::ListStore {
+        self.imp().resumable_songs.clone()
+    }
+
     pub fn clear_recent_songs(&self) {
         self.imp().recent_songs.remove_all();  // Will make Recent View switch to the empty StatusPage
         sqlite::clear_history().expect("Unable to clear history");
@@ -531,7 +626,7 @@ impl Library {
     /// Queue a playlist for playback.
     pub fn queue_playlist(&self, name: &str, replace: bool, play: bool) {
         if replace {
-            self.client().clear_queue();
+            self.client().clear_queue_for_replace();
         }
         self.client().queue_background(
             BackgroundTask::QueuePlaylist(
@@ -554,6 +649,41 @@ impl Library {
         self.client().delete_playlist(name)
     }
 
+    pub fn export_playlist_file(&self, name: String, path: PathBuf, options: ExportOptions) {
+        self.client().queue_background(
+            BackgroundTask::ExportPlaylistFile(Some(name), path, options),
+            true,
+        );
+    }
+
+    /// Look for broken & duplicate entries in a stored playlist. Results are reported
+    /// through the client state's "playlist-checked" signal.
+    pub fn check_playlist(&self, name: &str) {
+        self.client()
+            .queue_background(BackgroundTask::CheckPlaylist(name.to_owned()), true);
+    }
+
+    /// Rewrite a checked playlist with the given fixes in a single command list.
+    pub fn fix_playlist(
+        &self,
+        report: &PlaylistCheckReport,
+        fixes: &FxHashMap<u32, PlaylistFix>,
+    ) -> Result<(), Option<MpdError>> {
+        let uris = report.fixed_uris(fixes);
+        let mut edits: Vec<EditAction> = Vec::with_capacity(uris.len() + 1);
+        edits.push(EditAction::Clear(Cow::Borrowed(report.name.as_str())));
+        edits.extend(uris.iter().map(|uri| {
+            EditAction::Add(Cow::Borrowed(report.name.as_str()), Cow::Borrowed(uri.as_str()), None)
+        }));
+        self.client().edit_playlist(&report.name, &edits)
+    }
+
+    /// Import a playlist file as a new MPD playlist. Results are reported
+    /// through the client state's "playlist-file-imported" signal.
+    pub fn import_playlist_file(&self, path: PathBuf) {
+        self.client().queue_background(BackgroundTask::ImportPlaylistFile(path), true);
+    }
+
     pub fn add_songs_to_playlist(
         &self,
         playlist_name: &str,
@@ -571,7 +701,68 @@ impl Library {
                 None,
             ));
         });
-        self.client().edit_playlist(&edits)
+        self.client().edit_playlist(playlist_name, &edits)
+    }
+
+    /// Get a reference to the local smart playlists store. Items are
+    /// BoxedAnyObjects holding SmartPlaylists.
+    pub fn smart_playlists(&self) -> gio::ListStore {
+        self.imp().smart_playlists.clone()
+    }
+
+    pub fn init_smart_playlists(&self) {
+        if !self.imp().smart_playlists_initialized.get() {
+            self.reload_smart_playlists();
+        }
+    }
+
+    fn reload_smart_playlists(&self) {
+        let saved = sqlite::get_smart_playlists().unwrap_or_else(|e| {
+            println!("Unable to load smart playlists: {e:?}");
+            Vec::new()
+        });
+        // Built-in collections go first
+        let playlists: Vec<glib::BoxedAnyObject> = [SmartPlaylist::liked_songs(), SmartPlaylist::top_rated()]
+            .into_iter()
+            .chain(saved)
+            .map(glib::BoxedAnyObject::new)
+            .collect();
+        self.imp().smart_playlists.remove_all();
+        self.imp().smart_playlists.extend_from_slice(&playlists);
+        self.imp().smart_playlists_initialized.set(true);
+    }
+
+    /// Save a new or edited smart playlist. When editing, old_name is the name
+    /// it had before (in case it has been renamed).
+    pub fn save_smart_playlist(&self, playlist: &SmartPlaylist, old_name: Option<&str>) {
+        sqlite::write_smart_playlist(playlist, old_name).expect("Unable to save smart playlist");
+        self.reload_smart_playlists();
+    }
+
+    pub fn delete_smart_playlist(&self, name: &str) {
+        sqlite::delete_smart_playlist(name).expect("Unable to delete smart playlist");
+        self.reload_smart_playlists();
+    }
+
+    /// Evaluate a smart playlist. Results come back via the
+    /// smart-playlist-songs-downloaded signal of ClientState.
+    pub fn evaluate_smart_playlist(&self, playlist: &SmartPlaylist) {
+        self.client()
+            .queue_background(BackgroundTask::EvaluateSmartPlaylist(playlist.clone()), true);
+    }
+
+    /// Save the given songs (usually the result of a smart playlist) as a
+    /// regular MPD playlist, overwriting any existing one with the same name.
+    pub fn freeze_songs_as_playlist(&self, name: &str, songs: &[Song]) -> Result<(), Option<MpdError>> {
+        let exists = self.imp().playlists.find_with_equal_func(|obj| {
+            obj.downcast_ref::<INode>().unwrap().get_name() == Some(name)
+        }).is_some();
+        // playlistclear fails on nonexistent playlists while playlistadd creates them
+        self.add_songs_to_playlist(
+            name,
+            songs,
+            if exists { SaveMode::Replace } else { SaveMode::Append }
+        )
     }
 
     pub fn get_folder_contents(&self) {
@@ -627,10 +818,84 @@ impl Library {
         self.cache().clear_artist_avatar(tag);
     }
 
+    /// Get a reference to the local global search song results store
+    pub fn search_songs(&self) -> gio::ListStore {
+        self.imp().search_songs.clone()
+    }
+
+    /// Get a reference to the local global search album results store
+    pub fn search_albums(&self) -> gio::ListStore {
+        self.imp().search_albums.clone()
+    }
+
+    /// Get a reference to the local global search artist results store
+    pub fn search_artists(&self) -> gio::ListStore {
+        self.imp().search_artists.clone()
+    }
+
+    pub fn clear_search(&self) {
+        self.imp().search_term.replace(String::new());
+        self.imp().search_songs.remove_all();
+        self.imp().search_albums.remove_all();
+        self.imp().search_artists.remove_all();
+    }
+
+    /// Search the whole library on the server side. Results of any previous
+    /// search still being streamed in will be ignored.
+    pub fn search(&self, term: &str) {
+        self.clear_search();
+        if term.is_empty() {
+            return;
+        }
+        self.imp().search_term.replace(term.to_owned());
+        let settings = settings_manager().child("library");
+        self.client().queue_background(
+            BackgroundTask::Search(term.to_owned(), settings.uint("n-search-songs")),
+            true,
+        );
+    }
+
+    /// Fetch all songs matching an advanced filter. Results are tagged with the
+    /// filter's textual form so that callers can discard stale ones.
+    pub fn preview_filter(&self, filter: &FilterExpr) {
+        self.client()
+            .queue_background(BackgroundTask::FetchFilterSongs(filter.clone()), true);
+    }
+
+    /// Queue all songs matching an advanced filter.
+    pub fn queue_filter(&self, filter: &FilterExpr, replace: bool, play: bool) {
+        if replace {
+            self.client().clear_queue_for_replace();
+        }
+        self.client().queue_background(
+            BackgroundTask::QueueQuery(
+                filter.to_query(),
+                if replace && play {
+                    Some(0)
+                } else {
+                    None
+                }
+            ),
+            true
+        );
+    }
+
     pub fn fetch_recent_songs(&self) {
         self.imp().recent_songs.remove_all();
         let settings = settings_manager().child("library");
         self.client()
             .queue_background(BackgroundTask::FetchRecentSongs(settings.uint("n-recent-songs")), true);
     }
+
+    pub fn fetch_resumable_songs(&self) {
+        self.imp().resumable_songs.remove_all();
+        self.client()
+            .queue_background(BackgroundTask::FetchResumableSongs, true);
+    }
+
+    /// Results are reported back via ClientState's listening-stats-downloaded signal.
+    pub fn fetch_listening_stats(&self, range: StatsRange) {
+        self.client()
+            .queue_background(BackgroundTask::FetchListeningStats(range), true);
+    }
 }
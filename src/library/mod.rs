@@ -1,5 +1,9 @@
 mod recent_view;
 mod recent_song_row;
+mod stats_view;
+
+mod search_view;
+mod query_builder;
 
 mod album_cell;
 mod album_content_view;
@@ -17,6 +21,8 @@ mod folder_view;
 
 mod playlist_content_view;
 mod playlist_view;
+mod smart_playlist_content_view;
+mod smart_playlist_editor;
 
 // Common stuff shared between views
 mod add_to_playlist;
@@ -26,6 +32,9 @@ mod generic_row;
 mod controller;
 
 pub use recent_view::RecentView;
+pub use stats_view::StatsView;
+pub use search_view::SearchView;
+pub use query_builder::QueryBuilderDialog;
 
 use album_cell::AlbumCell;
 pub use album_content_view::AlbumContentView;
@@ -42,5 +51,7 @@ pub use folder_view::FolderView;
 pub use playlist_content_view::PlaylistContentView;
 pub use playlist_song_row::PlaylistSongRow;
 pub use playlist_view::PlaylistView;
+use smart_playlist_content_view::SmartPlaylistContentView;
 
+pub use add_to_playlist::AddToPlaylistButton;
 pub use controller::Library;
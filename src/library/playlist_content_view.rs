@@ -1,3 +1,4 @@
+use adw::prelude::*;
 use adw::subclass::prelude::*;
 use glib::{clone, closure_local, signal::SignalHandlerId, Binding};
 use gtk::{gio, glib, prelude::*, BitsetIter, CompositeTemplate, ListItem, SignalListItemFactory};
@@ -7,12 +8,14 @@ use std::{
 };
 
 use mpd::error::{Error as MpdError, ErrorCode as MpdErrorCode, ServerError};
+use rustc_hash::FxHashMap;
 
 use super::{Library, PlaylistSongRow};
 use crate::{
     cache::Cache,
     client::ClientState,
-    common::{INode, Song},
+    common::{INode, PlaylistCheckReport, PlaylistFix, Song},
+    playlist_file,
     utils::format_secs_as_duration,
     window::EuphonicaWindow,
 };
@@ -134,6 +137,10 @@ mod imp {
         #[template_child]
         pub sel_none: TemplateChild<gtk::Button>,
 
+        #[template_child]
+        pub check_playlist: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub export_file: TemplateChild<gtk::Button>,
         #[template_child]
         pub rename_menu_btn: TemplateChild<gtk::MenuButton>,
         #[template_child]
@@ -197,6 +204,8 @@ mod imp {
                 edit_apply: TemplateChild::default(),
                 sel_all: TemplateChild::default(),
                 sel_none: TemplateChild::default(),
+                check_playlist: TemplateChild::default(),
+                export_file: TemplateChild::default(),
                 rename_menu_btn: TemplateChild::default(),
                 delete_menu_btn: TemplateChild::default(),
                 rename: TemplateChild::default(),
@@ -359,6 +368,10 @@ mod imp {
             }
             self.editing_song_list.extend_from_slice(&songs);
 
+            // Checking would rewrite the playlist from under us
+            self.is_editing.set(true);
+            self.check_playlist.set_sensitive(false);
+
             // Everything is now in place; start fading
             self.action_row.set_visible_child_name("edit-mode");
             self.content_stack.set_visible_child_name("edit-mode");
@@ -393,6 +406,8 @@ mod imp {
                 self.edit_undo.set_sensitive(false);
                 self.edit_redo.set_sensitive(false);
             }
+            self.is_editing.set(false);
+            self.check_playlist.set_sensitive(true);
             // Just fade back, no need to clear the list (won't lag us
             // since we're not rendering it)
             self.action_row.set_visible_child_name("queue-mode");
@@ -576,6 +591,77 @@ impl PlaylistContentView {
             }
         ));
 
+        self.imp().check_playlist.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |btn| {
+                if let Some(name) = this
+                    .imp()
+                    .playlist
+                    .borrow()
+                    .as_ref()
+                    .and_then(|playlist| playlist.get_name())
+                {
+                    // Until the results come back
+                    btn.set_sensitive(false);
+                    this.imp().library.get().unwrap().check_playlist(name);
+                }
+            }
+        ));
+        client_state.connect_closure(
+            "playlist-checked",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, boxed: glib::BoxedAnyObject| {
+                    let imp = this.imp();
+                    imp.check_playlist.set_sensitive(!imp.is_editing.get());
+                    match boxed.borrow::<Result<PlaylistCheckReport, String>>().as_ref() {
+                        Ok(report) => {
+                            // Ignore results for playlists we've since navigated away from
+                            if this.current_playlist().is_some_and(|playlist| {
+                                playlist.get_name() == Some(report.name.as_str())
+                            }) {
+                                this.show_check_report(report.clone());
+                            }
+                        }
+                        Err(e) => {
+                            imp.window
+                                .get()
+                                .unwrap()
+                                .show_dialog("Check Failed", &format!("Unable to check this playlist: {e}"));
+                        }
+                    }
+                }
+            ),
+        );
+
+        self.imp().export_file.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                let Some(name) = this
+                    .imp()
+                    .playlist
+                    .borrow()
+                    .as_ref()
+                    .and_then(|playlist| playlist.get_name().map(str::to_owned))
+                else {
+                    return;
+                };
+                glib::spawn_future_local(clone!(
+                    #[weak]
+                    this,
+                    async move {
+                        if let Some((path, options)) = playlist_file::choose_export_target(&this, &name).await {
+                            this.imp().library.get().unwrap().export_playlist_file(name, path, options);
+                        }
+                    }
+                ));
+            }
+        ));
+
         let rename_btn = self.imp().rename.get();
         let new_name = self.imp().new_name.get();
         let delete_btn = self.imp().delete.get();
@@ -847,6 +933,122 @@ impl PlaylistContentView {
         ));
     }
 
+    /// Let the user pick a fix for each broken or duplicate entry, then apply them all at once.
+    fn show_check_report(&self, report: PlaylistCheckReport) {
+        let window = self.imp().window.get().unwrap();
+        if report.is_clean() {
+            window.send_simple_toast("No missing songs or duplicates found", 3);
+            return;
+        }
+        let list = gtk::ListBox::builder()
+            .selection_mode(gtk::SelectionMode::None)
+            .css_classes(["boxed-list"])
+            .build();
+        // Broken entries can be kept, removed or relinked to one of their candidates.
+        let mut broken_rows: Vec<(u32, adw::ComboRow, Vec<String>)> = Vec::with_capacity(report.broken.len());
+        for entry in report.broken.iter() {
+            let mut options: Vec<String> = vec!["Keep".to_owned(), "Remove".to_owned()];
+            options.extend(entry.candidates.iter().map(|song| match song.artist_tag.as_ref() {
+                Some(artist) => format!("Relink to {} – {}", song.title, artist),
+                None => format!("Relink to {}", song.title),
+            }));
+            let row = adw::ComboRow::builder()
+                .title(glib::markup_escape_text(
+                    entry.uri.rsplit('/').next().unwrap_or(&entry.uri),
+                ))
+                .subtitle(glib::markup_escape_text(&entry.uri))
+                .model(&gtk::StringList::new(
+                    &options.iter().map(String::as_str).collect::<Vec<&str>>(),
+                ))
+                .selected(if entry.candidates.is_empty() { 1 } else { 2 })
+                .build();
+            list.append(&row);
+            broken_rows.push((
+                entry.pos,
+                row,
+                entry.candidates.iter().map(|song| song.uri.clone()).collect(),
+            ));
+        }
+        let mut duplicate_rows: Vec<(u32, adw::SwitchRow)> = Vec::with_capacity(report.duplicates.len());
+        for entry in report.duplicates.iter() {
+            let row = adw::SwitchRow::builder()
+                .title(glib::markup_escape_text(&entry.song.title))
+                .subtitle(format!(
+                    "Duplicate at #{} of #{}. Remove?",
+                    entry.pos + 1,
+                    entry.first_pos + 1
+                ))
+                .active(true)
+                .build();
+            list.append(&row);
+            duplicate_rows.push((entry.pos, row));
+        }
+
+        // TODO: translatable
+        let diag = adw::AlertDialog::builder()
+            .heading("Check Playlist")
+            .body(format!(
+                "Found {} missing song(s) and {} duplicate(s). Choose how to fix each of them.",
+                report.broken.len(),
+                report.duplicates.len()
+            ))
+            .extra_child(
+                &gtk::ScrolledWindow::builder()
+                    .hscrollbar_policy(gtk::PolicyType::Never)
+                    .propagate_natural_height(true)
+                    .max_content_height(300)
+                    .child(&list)
+                    .build(),
+            )
+            .build();
+        diag.add_response("cancel", "_Cancel");
+        diag.add_response("apply", "_Apply");
+        diag.set_response_appearance("apply", adw::ResponseAppearance::Suggested);
+        diag.choose(
+            window,
+            Option::<gio::Cancellable>::None.as_ref(),
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |resp| {
+                    if resp.as_str() != "apply" {
+                        return;
+                    }
+                    let mut fixes: FxHashMap<u32, PlaylistFix> = FxHashMap::default();
+                    for (pos, row, candidates) in broken_rows.iter() {
+                        match row.selected() {
+                            0 => {}
+                            1 => {
+                                fixes.insert(*pos, PlaylistFix::Remove);
+                            }
+                            idx => {
+                                if let Some(uri) = candidates.get(idx as usize - 2) {
+                                    fixes.insert(*pos, PlaylistFix::Relink(uri.clone()));
+                                }
+                            }
+                        }
+                    }
+                    for (pos, row) in duplicate_rows.iter() {
+                        if row.is_active() {
+                            fixes.insert(*pos, PlaylistFix::Remove);
+                        }
+                    }
+                    if fixes.is_empty() {
+                        return;
+                    }
+                    // The playlist view reloads us upon the resulting idle signal.
+                    if let Err(Some(e)) = this.imp().library.get().unwrap().fix_playlist(&report, &fixes) {
+                        this.imp()
+                            .window
+                            .get()
+                            .unwrap()
+                            .show_dialog("Check Failed", &format!("Unable to fix this playlist: {e}"));
+                    }
+                }
+            ),
+        );
+    }
+
     pub fn current_playlist(&self) -> Option<INode> {
         self.imp().playlist.borrow().clone()
     }
@@ -9,12 +9,13 @@ use std::{cell::Cell, cmp::Ordering, ops::Deref, rc::Rc};
 use glib::clone;
 use mpd::Subsystem;
 
-use super::{generic_row::GenericRow, Library};
+use super::{generic_row::GenericRow, smart_playlist_editor::SmartPlaylistEditor, Library, SmartPlaylistContentView};
 use crate::{
     cache::Cache,
     client::{ClientState, ConnectionState},
-    common::INode,
-    utils::{g_cmp_str_options, g_search_substr, settings_manager},
+    common::{INode, SmartPlaylist},
+    playlist_file::{self, PlaylistImportReport},
+    utils::{self, g_cmp_str_options, g_search_substr, settings_manager},
     window::EuphonicaWindow,
 };
 
@@ -24,7 +25,7 @@ mod imp {
 
     use glib::{subclass::Signal, Properties};
 
-    use crate::library::PlaylistContentView;
+    use crate::library::{PlaylistContentView, SmartPlaylistContentView};
 
     use super::*;
 
@@ -51,13 +52,24 @@ mod imp {
         #[template_child]
         pub search_entry: TemplateChild<gtk::SearchEntry>,
 
+        #[template_child]
+        pub import_btn: TemplateChild<gtk::Button>,
+
         // Content
         #[template_child]
+        pub new_smart_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub smart_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
         pub list_view: TemplateChild<gtk::ListView>,
         #[template_child]
         pub content_page: TemplateChild<adw::NavigationPage>,
         #[template_child]
         pub content_view: TemplateChild<PlaylistContentView>,
+        #[template_child]
+        pub smart_content_page: TemplateChild<adw::NavigationPage>,
+        #[template_child]
+        pub smart_content_view: TemplateChild<SmartPlaylistContentView>,
 
         // Search & filter models
         pub search_filter: gtk::CustomFilter,
@@ -85,10 +97,15 @@ mod imp {
                 search_btn: TemplateChild::default(),
                 search_bar: TemplateChild::default(),
                 search_entry: TemplateChild::default(),
+                import_btn: TemplateChild::default(),
                 // Content
+                new_smart_btn: TemplateChild::default(),
+                smart_list: TemplateChild::default(),
                 list_view: TemplateChild::default(),
                 content_page: TemplateChild::default(),
                 content_view: TemplateChild::default(),
+                smart_content_page: TemplateChild::default(),
+                smart_content_view: TemplateChild::default(),
                 // Search & filter models
                 search_filter: gtk::CustomFilter::default(),
                 sorter: gtk::CustomSorter::default(),
@@ -193,10 +210,26 @@ impl PlaylistView {
         window: EuphonicaWindow,
     ) {
         let content_view = self.imp().content_view.get();
-        content_view.setup(library.clone(), client_state.clone(), cache.clone(), window);
+        content_view.setup(library.clone(), client_state.clone(), cache.clone(), window.clone());
         self.imp().content_page.connect_hidden(move |_| {
             content_view.unbind(true);
         });
+        let smart_content_view = self.imp().smart_content_view.get();
+        smart_content_view.setup(library.clone(), client_state.clone(), cache.clone());
+        smart_content_view.connect_closure(
+            "deleted",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: SmartPlaylistContentView| {
+                    this.pop();
+                }
+            ),
+        );
+        self.imp().smart_content_page.connect_hidden(move |_| {
+            smart_content_view.unbind();
+        });
         self.imp()
             .library
             .set(library.clone())
@@ -204,6 +237,8 @@ impl PlaylistView {
         self.setup_sort();
         self.setup_search();
         self.setup_listview();
+        self.setup_smart_list();
+        self.setup_import(&client_state, window);
 
         client_state.connect_notify_local(
             Some("connection-state"),
@@ -267,6 +302,78 @@ impl PlaylistView {
         );
     }
 
+    fn setup_import(&self, client_state: &ClientState, window: EuphonicaWindow) {
+        self.imp().import_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |btn| {
+                glib::spawn_future_local(clone!(
+                    #[weak]
+                    this,
+                    #[weak]
+                    btn,
+                    async move {
+                        if let Some(path) = playlist_file::choose_import_source(&this).await {
+                            // One import at a time
+                            btn.set_sensitive(false);
+                            this.imp().library.get().unwrap().import_playlist_file(path);
+                        }
+                    }
+                ));
+            }
+        ));
+
+        client_state.connect_closure(
+            "playlist-file-imported",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                window,
+                move |_: ClientState, boxed: glib::BoxedAnyObject| {
+                    this.imp().import_btn.set_sensitive(true);
+                    match boxed.borrow::<Result<PlaylistImportReport, String>>().as_ref() {
+                        Ok(report) => this.show_import_report(report),
+                        Err(e) => {
+                            window.send_simple_toast(&format!("Unable to import playlist: {e}"), 3);
+                        }
+                    }
+                }
+            ),
+        );
+    }
+
+    fn show_import_report(&self, report: &PlaylistImportReport) {
+        let n_unmatched = report.unmatched.len();
+        // TODO: translatable
+        let body = if report.n_imported() == 0 {
+            "None of the songs in this playlist could be found in your library.".to_owned()
+        } else {
+            format!(
+                "Saved as \"{}\": {} song(s) found by path and {} by tags. {} song(s) could not be found in your library.",
+                &report.name, report.by_path, report.by_tags, n_unmatched
+            )
+        };
+        let diag = adw::AlertDialog::builder()
+            .heading("Playlist Imported")
+            .body(body)
+            .build();
+        diag.add_response("close", "_Close");
+        if n_unmatched > 0 {
+            diag.set_extra_child(Some(&utils::report_list(
+                report
+                    .unmatched
+                    .iter()
+                    .map(|location| {
+                        let file_name = location.rsplit('/').next().unwrap_or(location);
+                        (file_name, location.as_str(), String::new())
+                    }),
+            )));
+        }
+        diag.present(Some(self));
+    }
+
     fn setup_sort(&self) {
         // Setup sort widget & actions
         let settings = settings_manager();
@@ -457,6 +564,88 @@ impl PlaylistView {
             .init_playlist(inode.get_name().unwrap());
     }
 
+    pub fn on_smart_playlist_clicked(&self, playlist: SmartPlaylist) {
+        self.imp().smart_content_view.bind(playlist);
+        if self.imp().nav_view.visible_page_tag().is_none_or(|tag| tag.as_str() != "smart-content") {
+            self.imp().nav_view.push_by_tag("smart-content");
+        }
+    }
+
+    fn setup_smart_list(&self) {
+        let library = self.imp().library.get().unwrap();
+        library.init_smart_playlists();
+        let smart_playlists = library.smart_playlists();
+        self.imp().smart_list.bind_model(Some(&smart_playlists), |obj| {
+            let playlist = obj
+                .downcast_ref::<glib::BoxedAnyObject>()
+                .unwrap()
+                .borrow::<SmartPlaylist>();
+            let row = adw::ActionRow::builder()
+                .title(glib::markup_escape_text(&playlist.name))
+                .subtitle(playlist.describe())
+                .activatable(true)
+                .build();
+            row.add_prefix(&gtk::Image::from_icon_name(if !playlist.builtin {
+                "playlist-symbolic"
+            } else if playlist.liked_only {
+                "heart-filled-symbolic"
+            } else {
+                "star-large-symbolic"
+            }));
+            row.add_suffix(&gtk::Image::from_icon_name("right-symbolic"));
+            row.into()
+        });
+        self.imp().smart_list.connect_row_activated(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            smart_playlists,
+            move |_, row| {
+                if let Some(obj) = smart_playlists
+                    .item(row.index() as u32)
+                    .and_downcast::<glib::BoxedAnyObject>()
+                {
+                    let playlist = obj.borrow::<SmartPlaylist>().clone();
+                    this.on_smart_playlist_clicked(playlist);
+                }
+            }
+        ));
+
+        self.imp().new_smart_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                let library = this.imp().library.get().unwrap();
+                let editor = SmartPlaylistEditor::new(library.clone(), None);
+                editor.connect_closure(
+                    "saved",
+                    false,
+                    closure_local!(
+                        #[weak]
+                        this,
+                        move |_: SmartPlaylistEditor, name: String| {
+                            // Open the newly-created smart playlist
+                            let maybe_playlist = this
+                                .imp()
+                                .library
+                                .get()
+                                .unwrap()
+                                .smart_playlists()
+                                .iter::<glib::BoxedAnyObject>()
+                                .flatten()
+                                .map(|obj| obj.borrow::<SmartPlaylist>().clone())
+                                .find(|playlist| playlist.name == name);
+                            if let Some(playlist) = maybe_playlist {
+                                this.on_smart_playlist_clicked(playlist);
+                            }
+                        }
+                    ),
+                );
+                editor.present(Some(&this));
+            }
+        ));
+    }
+
     fn setup_listview(&self) {
         let library = self.imp().library.get().unwrap();
         // client_state.connect_closure(
@@ -0,0 +1,691 @@
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    rc::Rc,
+};
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gtk::{gio, glib, CompositeTemplate};
+
+use glib::{clone, closure_local};
+
+use super::{add_to_playlist::AddToPlaylistButton, recent_song_row::RecentSongRow, Library};
+use crate::{
+    cache::Cache,
+    client::ClientState,
+    common::{
+        filter::{delete_saved_query, get_saved_queries, save_query},
+        FilterExpr, Song, TagOperator,
+    },
+    utils::settings_manager,
+};
+
+// Tags offered by the condition editor. "any" matches any tag while "file" and
+// "base" match the path of the song and of its containing folder respectively.
+const TAGS: [&str; 20] = [
+    "any",
+    "title",
+    "artist",
+    "album",
+    "albumartist",
+    "composer",
+    "performer",
+    "conductor",
+    "genre",
+    "date",
+    "originaldate",
+    "track",
+    "disc",
+    "label",
+    "comment",
+    "work",
+    "grouping",
+    "musicbrainz_albumid",
+    "file",
+    "base",
+];
+
+// Kinds of nodes, in the order they appear in the editor's dropdowns
+const KINDS: [&str; 5] = ["Tag", "Modified since", "Added since", "Audio format", "Group"];
+
+fn kind_of(expr: &FilterExpr) -> u32 {
+    match expr {
+        FilterExpr::Tag { .. } => 0,
+        FilterExpr::ModifiedSince(_) => 1,
+        FilterExpr::AddedSince(_) => 2,
+        FilterExpr::AudioFormat { .. } => 3,
+        FilterExpr::Not(inner) => kind_of(inner),
+        FilterExpr::And(_) => 4,
+    }
+}
+
+fn new_of_kind(kind: u32) -> FilterExpr {
+    match kind {
+        1 => FilterExpr::ModifiedSince(String::new()),
+        2 => FilterExpr::AddedSince(String::new()),
+        3 => FilterExpr::AudioFormat {
+            format: String::new(),
+            masked: true,
+        },
+        4 => FilterExpr::And(vec![FilterExpr::new_tag("any", TagOperator::Contains, "")]),
+        _ => FilterExpr::new_tag("any", TagOperator::Contains, ""),
+    }
+}
+
+/// Skip past a NOT, if any.
+fn unwrap_not(expr: &mut FilterExpr) -> &mut FilterExpr {
+    match expr {
+        FilterExpr::Not(inner) => inner.as_mut(),
+        other => other,
+    }
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/io/github/htkhiem/Euphonica/gtk/library/query-builder.ui")]
+    pub struct QueryBuilderDialog {
+        #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        pub delete_saved: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub saved_queries: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub query_name: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub editor_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub expression: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub preview_count: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub preview: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub preview_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub add_to_playlist: TemplateChild<AddToPlaylistButton>,
+        #[template_child]
+        pub append_queue: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub replace_queue: TemplateChild<gtk::Button>,
+
+        // Root of the tree being edited. Always an And node.
+        pub filter: RefCell<FilterExpr>,
+        pub saved_names: RefCell<Vec<String>>,
+        // Set while repopulating the saved query picker to avoid loading queries
+        pub updating: Cell<bool>,
+        // Textual form of the previewed filter, used to drop stale results
+        pub preview_expression: RefCell<String>,
+        pub preview_songs: OnceCell<gio::ListStore>,
+        pub library: OnceCell<Library>,
+        pub signal_ids: RefCell<Vec<glib::SignalHandlerId>>,
+        pub client_state: OnceCell<ClientState>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for QueryBuilderDialog {
+        const NAME: &'static str = "EuphonicaQueryBuilderDialog";
+        type Type = super::QueryBuilderDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for QueryBuilderDialog {
+        fn dispose(&self) {
+            if let Some(client_state) = self.client_state.get() {
+                for id in self.signal_ids.take().into_iter() {
+                    client_state.disconnect(id);
+                }
+            }
+        }
+    }
+    impl WidgetImpl for QueryBuilderDialog {}
+    impl AdwDialogImpl for QueryBuilderDialog {}
+}
+
+glib::wrapper! {
+    pub struct QueryBuilderDialog(ObjectSubclass<imp::QueryBuilderDialog>)
+        @extends adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::ShortcutManager;
+}
+
+impl Default for QueryBuilderDialog {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl QueryBuilderDialog {
+    pub fn new(library: Library, client_state: ClientState, cache: Rc<Cache>) -> Self {
+        let res = Self::default();
+        let _ = res.imp().library.set(library);
+        res.setup(client_state, cache);
+        res.imp()
+            .filter
+            .replace(FilterExpr::And(vec![new_of_kind(0)]));
+        res.update_saved_queries(None);
+        res.rebuild_editor();
+        res
+    }
+
+    fn library(&self) -> &Library {
+        self.imp().library.get().unwrap()
+    }
+
+    fn setup(&self, client_state: ClientState, cache: Rc<Cache>) {
+        let imp = self.imp();
+        let preview_songs = gio::ListStore::new::<Song>();
+        // Only show the first few matches. Actions apply to all of them anyway.
+        let shown_songs = gtk::SliceListModel::new(
+            Some(preview_songs.clone()),
+            0,
+            settings_manager().child("library").uint("n-search-songs"),
+        );
+        let library = self.library().clone();
+        imp.preview_list.bind_model(Some(&shown_songs), move |obj| {
+            RecentSongRow::new(library.clone(), obj.downcast_ref::<Song>().unwrap(), cache.clone())
+                .into()
+        });
+        preview_songs
+            .bind_property("n-items", &imp.preview_count.get(), "label")
+            .transform_to(|_, n: u32| Some(format!("Matching songs ({n})")))
+            .build();
+        preview_songs
+            .bind_property("n-items", &imp.add_to_playlist.get(), "sensitive")
+            .transform_to(|_, n: u32| Some(n > 0))
+            .build();
+        imp.add_to_playlist.setup(
+            self.library().clone(),
+            gtk::MultiSelection::new(Some(preview_songs.clone())),
+        );
+        let _ = imp.preview_songs.set(preview_songs);
+
+        imp.signal_ids.replace(vec![
+            client_state.connect_closure(
+                "filter-songs-downloaded",
+                false,
+                closure_local!(
+                    #[weak(rename_to = this)]
+                    self,
+                    move |_: ClientState, expression: String, songs: glib::BoxedAnyObject| {
+                        if expression == *this.imp().preview_expression.borrow() {
+                            this.imp()
+                                .preview_songs
+                                .get()
+                                .unwrap()
+                                .extend_from_slice(songs.borrow::<Vec<Song>>().as_ref());
+                        }
+                    }
+                ),
+            ),
+            client_state.connect_closure(
+                "filter-failed",
+                false,
+                closure_local!(
+                    #[weak(rename_to = this)]
+                    self,
+                    move |_: ClientState, expression: String, detail: String| {
+                        if expression == *this.imp().preview_expression.borrow() {
+                            this.show_toast(&detail);
+                        }
+                    }
+                ),
+            ),
+        ]);
+        let _ = imp.client_state.set(client_state);
+
+        imp.preview.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.preview();
+            }
+        ));
+
+        imp.replace_queue.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                if let Some(filter) = this.get_valid_filter() {
+                    this.library().queue_filter(&filter, true, true);
+                    this.show_toast("Queue replaced");
+                }
+            }
+        ));
+
+        imp.append_queue.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                if let Some(filter) = this.get_valid_filter() {
+                    this.library().queue_filter(&filter, false, false);
+                    this.show_toast("Added to queue");
+                }
+            }
+        ));
+
+        imp.saved_queries.connect_selected_notify(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |row| {
+                if this.imp().updating.get() {
+                    return;
+                }
+                this.on_saved_query_selected(row.selected());
+            }
+        ));
+
+        imp.query_name.connect_apply(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |entry| {
+                let name = entry.text().trim().to_owned();
+                if name.is_empty() {
+                    return;
+                }
+                if let Some(filter) = this.get_valid_filter() {
+                    save_query(&name, &filter);
+                    this.update_saved_queries(Some(&name));
+                    this.show_toast("Query saved");
+                }
+            }
+        ));
+
+        imp.delete_saved.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                let selected = this.imp().saved_queries.selected() as usize;
+                // First entry is the unsaved placeholder
+                if selected == 0 {
+                    return;
+                }
+                let maybe_name = this.imp().saved_names.borrow().get(selected - 1).cloned();
+                if let Some(name) = maybe_name {
+                    delete_saved_query(&name);
+                    this.update_saved_queries(None);
+                }
+            }
+        ));
+    }
+
+    fn show_toast(&self, msg: &str) {
+        self.imp()
+            .toast_overlay
+            .add_toast(adw::Toast::builder().title(msg).timeout(3).build());
+    }
+
+    /// Repopulate the saved query picker, optionally selecting one by name.
+    fn update_saved_queries(&self, select: Option<&str>) {
+        let imp = self.imp();
+        let names: Vec<String> = get_saved_queries().into_iter().map(|(name, _)| name).collect();
+        let mut labels: Vec<&str> = Vec::with_capacity(names.len() + 1);
+        labels.push("(unsaved)");
+        labels.extend(names.iter().map(String::as_str));
+        imp.updating.set(true);
+        imp.saved_queries
+            .set_model(Some(&gtk::StringList::new(&labels)));
+        let selected = select
+            .and_then(|name| names.iter().position(|n| n == name))
+            .map_or(0, |idx| idx + 1);
+        imp.saved_queries.set_selected(selected as u32);
+        imp.delete_saved.set_sensitive(selected > 0);
+        imp.updating.set(false);
+        imp.saved_names.replace(names);
+    }
+
+    fn on_saved_query_selected(&self, idx: u32) {
+        let imp = self.imp();
+        imp.delete_saved.set_sensitive(idx > 0);
+        if idx == 0 {
+            return;
+        }
+        let Some(name) = imp.saved_names.borrow().get(idx as usize - 1).cloned() else {
+            return;
+        };
+        if let Some((_, filter)) = get_saved_queries().into_iter().find(|(n, _)| n == &name) {
+            // Keep the root an And node so that conditions can be added to it
+            let filter = match filter {
+                FilterExpr::And(children) => FilterExpr::And(children),
+                other => FilterExpr::And(vec![other]),
+            };
+            imp.filter.replace(filter);
+            imp.query_name.set_text(&name);
+            self.rebuild_editor();
+            self.preview();
+        }
+    }
+
+    /// Get the current filter if it is valid, else show why it is not.
+    fn get_valid_filter(&self) -> Option<FilterExpr> {
+        let filter = self.imp().filter.borrow().clone();
+        match filter.validate() {
+            Ok(()) => Some(filter),
+            Err(msg) => {
+                self.show_toast(&msg);
+                None
+            }
+        }
+    }
+
+    fn preview(&self) {
+        if let Some(filter) = self.get_valid_filter() {
+            self.imp().preview_songs.get().unwrap().remove_all();
+            self.imp().preview_expression.replace(filter.to_string());
+            self.library().preview_filter(&filter);
+        }
+    }
+
+    /// Update the textual form of the filter. Called on every edit.
+    fn on_filter_changed(&self) {
+        let imp = self.imp();
+        let filter = imp.filter.borrow();
+        match filter.validate() {
+            Ok(()) => {
+                imp.expression.set_label(&filter.to_string());
+                imp.expression.remove_css_class("error");
+            }
+            Err(msg) => {
+                imp.expression.set_label(&msg);
+                imp.expression.add_css_class("error");
+            }
+        }
+    }
+
+    /// Apply an edit to the node at the given path. If the structure of the
+    /// tree changes, the editor must be rebuilt afterwards.
+    fn edit_node<F: FnOnce(&mut FilterExpr)>(&self, path: &[usize], f: F) {
+        if let Some(node) = self.imp().filter.borrow_mut().node_at_mut(path) {
+            f(node);
+        }
+        self.on_filter_changed();
+    }
+
+    fn rebuild_editor(&self) {
+        let editor_box = self.imp().editor_box.get();
+        while let Some(child) = editor_box.first_child() {
+            editor_box.remove(&child);
+        }
+        let filter = self.imp().filter.borrow().clone();
+        editor_box.append(&self.build_group_children(&filter, &[]));
+        self.on_filter_changed();
+    }
+
+    fn build_group_children(&self, group: &FilterExpr, path: &[usize]) -> gtk::Widget {
+        let container = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(6)
+            .build();
+        if let FilterExpr::And(children) = group {
+            for (idx, child) in children.iter().enumerate() {
+                let mut child_path = path.to_vec();
+                child_path.push(idx);
+                container.append(&self.build_node(child, child_path));
+            }
+        }
+        let add_btn = gtk::Button::builder()
+            .label("Add condition")
+            .halign(gtk::Align::Start)
+            .build();
+        add_btn.add_css_class("flat");
+        let path = path.to_vec();
+        add_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.edit_node(&path, |node| {
+                    if let FilterExpr::And(children) = unwrap_not(node) {
+                        children.push(new_of_kind(0));
+                    }
+                });
+                this.rebuild_editor();
+            }
+        ));
+        container.append(&add_btn);
+        container.upcast()
+    }
+
+    fn build_node(&self, node: &FilterExpr, path: Vec<usize>) -> gtk::Widget {
+        let header = gtk::Box::builder().spacing(6).build();
+
+        let not_btn = gtk::ToggleButton::builder()
+            .label("NOT")
+            .tooltip_text("Invert this condition")
+            .active(matches!(node, FilterExpr::Not(_)))
+            .build();
+        not_btn.connect_toggled(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            path,
+            move |btn| {
+                let negate = btn.is_active();
+                this.edit_node(&path, |node| {
+                    let current = std::mem::take(node);
+                    *node = match current {
+                        FilterExpr::Not(inner) if !negate => *inner,
+                        FilterExpr::Not(inner) => FilterExpr::Not(inner),
+                        other if negate => FilterExpr::Not(Box::new(other)),
+                        other => other,
+                    };
+                });
+            }
+        ));
+        header.append(&not_btn);
+
+        let kind = gtk::DropDown::from_strings(&KINDS);
+        kind.set_selected(kind_of(node));
+        kind.connect_selected_notify(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            path,
+            move |dropdown| {
+                let new_kind = dropdown.selected();
+                this.edit_node(&path, |node| {
+                    let replacement = new_of_kind(new_kind);
+                    match node {
+                        FilterExpr::Not(inner) => **inner = replacement,
+                        other => *other = replacement,
+                    }
+                });
+                // Defer as we're still inside one of the widgets to be destroyed
+                glib::idle_add_local_once(clone!(
+                    #[weak]
+                    this,
+                    move || this.rebuild_editor()
+                ));
+            }
+        ));
+        header.append(&kind);
+
+        let inner = match node {
+            FilterExpr::Not(inner) => inner.as_ref(),
+            other => other,
+        };
+        let res: gtk::Widget = match inner {
+            FilterExpr::And(_) => {
+                let frame = gtk::Box::builder()
+                    .orientation(gtk::Orientation::Vertical)
+                    .spacing(6)
+                    .build();
+                frame.add_css_class("card");
+                header.set_margin_start(6);
+                header.set_margin_end(6);
+                header.set_margin_top(6);
+                frame.append(&header);
+                let children = self.build_group_children(inner, &path);
+                children.set_margin_start(24);
+                children.set_margin_end(6);
+                children.set_margin_bottom(6);
+                frame.append(&children);
+                frame.upcast()
+            }
+            FilterExpr::Tag { tag, op, value } => {
+                let tag_dropdown = gtk::DropDown::from_strings(&TAGS);
+                tag_dropdown.set_enable_search(true);
+                tag_dropdown.set_selected(
+                    TAGS.iter().position(|t| t == tag).unwrap_or(0) as u32,
+                );
+                tag_dropdown.connect_selected_notify(clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    #[strong]
+                    path,
+                    move |dropdown| {
+                        let new_tag = TAGS[dropdown.selected() as usize];
+                        this.edit_node(&path, |node| {
+                            if let FilterExpr::Tag { tag, .. } = unwrap_not(node) {
+                                *tag = new_tag.to_owned();
+                            }
+                        });
+                    }
+                ));
+                header.append(&tag_dropdown);
+
+                let labels: Vec<&str> = TagOperator::ALL.iter().map(TagOperator::label).collect();
+                let op_dropdown = gtk::DropDown::from_strings(&labels);
+                op_dropdown.set_selected(
+                    TagOperator::ALL.iter().position(|o| o == op).unwrap_or(0) as u32,
+                );
+                op_dropdown.connect_selected_notify(clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    #[strong]
+                    path,
+                    move |dropdown| {
+                        let new_op = TagOperator::ALL[dropdown.selected() as usize];
+                        this.edit_node(&path, |node| {
+                            if let FilterExpr::Tag { op, .. } = unwrap_not(node) {
+                                *op = new_op;
+                            }
+                        });
+                    }
+                ));
+                header.append(&op_dropdown);
+
+                let entry = gtk::Entry::builder().text(value).hexpand(true).build();
+                entry.connect_changed(clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    #[strong]
+                    path,
+                    move |entry| {
+                        let text = entry.text().to_string();
+                        this.edit_node(&path, |node| {
+                            if let FilterExpr::Tag { value, .. } = unwrap_not(node) {
+                                *value = text;
+                            }
+                        });
+                    }
+                ));
+                header.append(&entry);
+                header.clone().upcast()
+            }
+            FilterExpr::ModifiedSince(since) | FilterExpr::AddedSince(since) => {
+                let entry = gtk::Entry::builder()
+                    .text(since)
+                    .placeholder_text("2024-01-31T00:00:00Z or a Unix time")
+                    .hexpand(true)
+                    .build();
+                entry.connect_changed(clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    #[strong]
+                    path,
+                    move |entry| {
+                        let text = entry.text().to_string();
+                        this.edit_node(&path, |node| match unwrap_not(node) {
+                            FilterExpr::ModifiedSince(since) | FilterExpr::AddedSince(since) => {
+                                *since = text;
+                            }
+                            _ => {}
+                        });
+                    }
+                ));
+                header.append(&entry);
+                header.clone().upcast()
+            }
+            FilterExpr::AudioFormat { format, masked } => {
+                let masked_btn = gtk::CheckButton::builder()
+                    .label("Wildcards")
+                    .tooltip_text("Allow using * in place of any component")
+                    .active(*masked)
+                    .build();
+                masked_btn.connect_toggled(clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    #[strong]
+                    path,
+                    move |btn| {
+                        let active = btn.is_active();
+                        this.edit_node(&path, |node| {
+                            if let FilterExpr::AudioFormat { masked, .. } = unwrap_not(node) {
+                                *masked = active;
+                            }
+                        });
+                    }
+                ));
+                header.append(&masked_btn);
+
+                let entry = gtk::Entry::builder()
+                    .text(format)
+                    .placeholder_text("samplerate:bits:channels, such as 44100:16:2")
+                    .hexpand(true)
+                    .build();
+                entry.connect_changed(clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    #[strong]
+                    path,
+                    move |entry| {
+                        let text = entry.text().to_string();
+                        this.edit_node(&path, |node| {
+                            if let FilterExpr::AudioFormat { format, .. } = unwrap_not(node) {
+                                *format = text;
+                            }
+                        });
+                    }
+                ));
+                header.append(&entry);
+                header.clone().upcast()
+            }
+            FilterExpr::Not(_) => unreachable!(),
+        };
+
+        let remove_btn = gtk::Button::builder()
+            .icon_name("user-trash-symbolic")
+            .tooltip_text("Remove")
+            .valign(gtk::Align::Center)
+            .build();
+        remove_btn.add_css_class("flat");
+        remove_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            path,
+            move |_| {
+                if let Some((idx, parent_path)) = path.split_last() {
+                    this.edit_node(parent_path, |parent| {
+                        if let FilterExpr::And(children) = unwrap_not(parent) {
+                            children.remove(*idx);
+                        }
+                    });
+                    this.rebuild_editor();
+                }
+            }
+        ));
+        header.append(&remove_btn);
+
+        res
+    }
+}
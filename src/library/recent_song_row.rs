@@ -40,6 +40,8 @@ mod imp {
         #[template_child]
         pub artist_name: TemplateChild<gtk::Label>,
         #[template_child]
+        pub last_played_box: TemplateChild<gtk::Box>,
+        #[template_child]
         pub last_played: TemplateChild<gtk::Label>,
         pub thumbnail_signal_ids: RefCell<Option<(SignalHandlerId, SignalHandlerId)>>,
         pub library: OnceCell<Library>,
@@ -122,9 +124,10 @@ mod imp {
                     }
                 }
                 "last-played" => {
-                    if let Ok(desc) = value.get::<&str>() {
-                        self.last_played.set_label(desc);
-                    }
+                    // Also used for songs that were never played (such as search results)
+                    let desc = value.get::<&str>().unwrap_or("");
+                    self.last_played.set_label(desc);
+                    self.last_played_box.set_visible(!desc.is_empty());
                 }
                 _ => unimplemented!(),
             }
@@ -155,12 +158,20 @@ glib::wrapper! {
 impl RecentSongRow {
     pub fn new(library: Library, item: &Song, cache: Rc<Cache>) -> Self {
         let res: Self = Object::builder().build();
-        res.setup(library, item, cache);
+        res.setup(library, item, cache, "last-played-desc");
+        res
+    }
+
+    /// For the continue listening shelf: show the saved position instead of when the
+    /// song was last played.
+    pub fn new_resumable(library: Library, item: &Song, cache: Rc<Cache>) -> Self {
+        let res: Self = Object::builder().build();
+        res.setup(library, item, cache, "resume-desc");
         res
     }
 
     #[inline(always)]
-    pub fn setup(&self, library: Library, item: &Song, cache: Rc<Cache>) {
+    pub fn setup(&self, library: Library, item: &Song, cache: Rc<Cache>, desc_property: &str) {
         let cache_state = cache.get_cache_state();
         self.imp()
            .cache
@@ -190,7 +201,7 @@ impl RecentSongRow {
             }))
             .bind(self, "artist", gtk::Widget::NONE);
 
-        item.property_expression("last-played-desc")
+        item.property_expression(desc_property)
             .bind(self, "last-played", gtk::Widget::NONE);
 
         item.property_expression("quality-grade")
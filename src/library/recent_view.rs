@@ -39,6 +39,16 @@ mod imp {
         #[template_child]
         pub stack: TemplateChild<gtk::Stack>,
 
+        // Continue listening shelf
+        #[template_child]
+        pub resumable_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub collapse_resumable: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub resumable_revealer: TemplateChild<gtk::Revealer>,
+        #[template_child]
+        pub resumable_list: TemplateChild<gtk::ListBox>,
+
         // Albums row
         #[template_child]
         pub collapse_albums: TemplateChild<gtk::ToggleButton>,
@@ -124,6 +134,24 @@ mod imp {
                 }
             ));
 
+            self.collapse_resumable
+                .bind_property("active", &self.resumable_revealer.get(), "reveal-child")
+                .invert_boolean()
+                .sync_create()
+                .build();
+
+            self.resumable_revealer
+                .bind_property("child-revealed", &self.collapse_resumable.get(), "icon-name")
+                .transform_to(|_, is_revealed| {
+                    if is_revealed {
+                        Some("up-symbolic".to_value())
+                    } else {
+                        Some("down-symbolic".to_value())
+                    }
+                })
+                .sync_create()
+                .build();
+
             self.collapse_albums
                 .bind_property("active", &self.album_revealer.get(), "reveal-child")
                 .invert_boolean()
@@ -232,6 +260,7 @@ impl RecentView {
             ),
         );
 
+        self.setup_resumable_list(cache.clone());
         self.setup_album_row(window, cache.clone());
         self.setup_artist_row(window, cache.clone());
         self.setup_song_list(cache);
@@ -243,9 +272,48 @@ impl RecentView {
             library.fetch_recent_albums();
             library.fetch_recent_artists();
             library.fetch_recent_songs();
+            library.fetch_resumable_songs();
         }
     }
 
+    /// Songs with a saved position might not have made it into the history yet
+    /// (for example when stopped early), so show content if either list has items.
+    fn update_stack(&self) {
+        let library = self.imp().library.get().unwrap();
+        let has_content = library.recent_songs().n_items() > 0
+            || library.resumable_songs().n_items() > 0;
+        self.imp()
+            .stack
+            .set_visible_child_name(if has_content { "content" } else { "empty" });
+    }
+
+    fn setup_resumable_list(&self, cache: Rc<Cache>) {
+        let library = self.imp().library.get().unwrap().clone();
+        let song_list = library.resumable_songs();
+
+        // Hide the whole shelf when there is nothing to continue
+        song_list
+            .bind_property("n-items", &self.imp().resumable_box.get(), "visible")
+            .transform_to(|_, n: u32| Some((n > 0).to_value()))
+            .sync_create()
+            .build();
+        song_list.connect_items_changed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_, _, _, _| {
+                this.update_stack();
+            }
+        ));
+
+        self.imp().resumable_list.bind_model(
+            Some(&song_list),
+            move |obj| {
+                let row = RecentSongRow::new_resumable(library.clone(), obj.downcast_ref::<Song>().unwrap(), cache.clone());
+                row.into()
+            }
+        );
+    }
+
     fn setup_album_row(&self, window: &EuphonicaWindow, cache: Rc<Cache>) {
         let album_list = self.imp().library.get().unwrap().recent_albums();
 
@@ -447,22 +515,14 @@ impl RecentView {
         let library = self.imp().library.get().unwrap().clone();
         let song_list = library.recent_songs();
 
-        song_list
-            .bind_property(
-                "n-items",
-                &self.imp().stack.get(),
-                "visible-child-name",
-            )
-            .transform_to(|_, val: u32| {
-                if val > 0 {
-                    Some("content".to_value())
-                } else {
-                    Some("empty".to_value())
-                }
+        song_list.connect_items_changed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_, _, _, _| {
+                this.update_stack();
             }
-        )
-         .sync_create()
-         .build();
+        ));
+        self.update_stack();
 
         self.imp().song_list.bind_model(
             Some(&song_list),
@@ -0,0 +1,470 @@
+use std::rc::Rc;
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gtk::{
+    gio,
+    glib::{self},
+    CompositeTemplate, ListItem, SignalListItemFactory, SingleSelection,
+};
+
+use glib::{clone, Properties};
+
+use super::{AlbumCell, ArtistCell, Library};
+use crate::{
+    cache::Cache,
+    common::{marquee::MarqueeWrapMode, Album, Artist, INode, Song},
+    library::recent_song_row::RecentSongRow,
+    utils::{g_search_substr, settings_manager, LazyInit},
+    window::EuphonicaWindow,
+};
+
+mod imp {
+    use std::{
+        cell::{Cell, OnceCell},
+        sync::OnceLock,
+    };
+
+    use glib::subclass::Signal;
+
+    use super::*;
+
+    #[derive(Debug, CompositeTemplate, Properties, Default)]
+    #[properties(wrapper_type = super::SearchView)]
+    #[template(resource = "/io/github/htkhiem/Euphonica/gtk/library/search-view.ui")]
+    pub struct SearchView {
+        #[template_child]
+        pub show_sidebar: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub search_entry: TemplateChild<gtk::SearchEntry>,
+        #[template_child]
+        pub stack: TemplateChild<gtk::Stack>,
+
+        #[template_child]
+        pub album_section: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub album_row: TemplateChild<gtk::GridView>,
+        #[template_child]
+        pub artist_section: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub artist_row: TemplateChild<gtk::GridView>,
+        #[template_child]
+        pub playlist_section: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub playlist_list: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub song_section: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub song_list: TemplateChild<gtk::ListBox>,
+
+        // Playlists are few enough to be filtered locally
+        pub playlist_filter: gtk::CustomFilter,
+        pub playlist_results: OnceCell<gtk::FilterListModel>,
+
+        pub library: OnceCell<Library>,
+
+        #[property(get, set)]
+        pub collapsed: Cell<bool>,
+
+        pub initialized: Cell<bool>, // Only start searching once connected
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SearchView {
+        const NAME: &'static str = "EuphonicaSearchView";
+        type Type = super::SearchView;
+        type ParentType = gtk::Widget;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+            klass.set_layout_manager_type::<gtk::BinLayout>();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for SearchView {
+        fn dispose(&self) {
+            while let Some(child) = self.obj().first_child() {
+                child.unparent();
+            }
+        }
+
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            self.obj()
+                .bind_property("collapsed", &self.show_sidebar.get(), "visible")
+                .sync_create()
+                .build();
+
+            self.show_sidebar.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    this.obj().emit_by_name::<()>("show-sidebar-clicked", &[]);
+                }
+            ));
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| vec![Signal::builder("show-sidebar-clicked").build()])
+        }
+    }
+
+    impl WidgetImpl for SearchView {}
+}
+
+glib::wrapper! {
+    pub struct SearchView(ObjectSubclass<imp::SearchView>)
+        @extends gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl Default for SearchView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SearchView {
+    pub fn new() -> Self {
+        let res: Self = glib::Object::new();
+
+        res
+    }
+
+    pub fn setup(&self, library: Library, cache: Rc<Cache>, window: &EuphonicaWindow) {
+        self.imp()
+            .library
+            .set(library.clone())
+            .expect("Cannot init SearchView with Library");
+
+        self.setup_album_row(window, cache.clone());
+        self.setup_artist_row(window, cache.clone());
+        self.setup_playlist_list(window);
+        self.setup_song_list(cache);
+
+        let search_entry = self.imp().search_entry.get();
+        search_entry.connect_search_changed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.search();
+            }
+        ));
+        search_entry.connect_stop_search(move |entry| {
+            entry.set_text("");
+        });
+
+        self.update_stack();
+    }
+
+    /// Focus the search entry, for use by keyboard shortcuts.
+    pub fn focus_entry(&self) {
+        self.imp().search_entry.grab_focus();
+    }
+
+    fn search_term(&self) -> String {
+        self.imp().search_entry.text().trim().to_owned()
+    }
+
+    fn search(&self) {
+        // Playlists are matched locally, so they can be filtered even while not
+        // connected.
+        self.imp()
+            .playlist_filter
+            .changed(gtk::FilterChange::Different);
+        if self.imp().initialized.get() {
+            self.imp().library.get().unwrap().search(&self.search_term());
+        }
+        self.update_stack();
+    }
+
+    fn update_stack(&self) {
+        let imp = self.imp();
+        let (Some(library), Some(playlist_results)) =
+            (imp.library.get(), imp.playlist_results.get())
+        else {
+            return;
+        };
+        let page = if self.search_term().is_empty() {
+            "empty"
+        } else if library.search_albums().n_items() > 0
+            || library.search_artists().n_items() > 0
+            || library.search_songs().n_items() > 0
+            || playlist_results.n_items() > 0
+        {
+            "content"
+        } else {
+            "no-results"
+        };
+        imp.stack.set_visible_child_name(page);
+    }
+
+    /// Show a result section only when it has something in it.
+    fn bind_section_visibility(&self, model: &impl IsA<gio::ListModel>, section: &gtk::Box) {
+        model
+            .bind_property("n-items", section, "visible")
+            .transform_to(|_, n: u32| Some(n > 0))
+            .sync_create()
+            .build();
+        model.connect_items_changed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_, _, _, _| {
+                this.update_stack();
+            }
+        ));
+    }
+
+    fn setup_album_row(&self, window: &EuphonicaWindow, cache: Rc<Cache>) {
+        let album_list = self.imp().library.get().unwrap().search_albums();
+        self.bind_section_visibility(&album_list, &self.imp().album_section.get());
+
+        let sel_model = SingleSelection::new(Some(album_list));
+        self.imp().album_row.set_model(Some(&sel_model));
+
+        // Set up factory
+        let factory = SignalListItemFactory::new();
+        let adj = self.imp().album_row.hadjustment().unwrap();
+
+        // Reset scroll position every time a new item is created, such that a new
+        // search always starts from the beginning of the row.
+        factory.connect_setup(clone!(
+            #[weak]
+            cache,
+            #[weak]
+            adj,
+            move |_, list_item| {
+                let item = list_item
+                    .downcast_ref::<ListItem>()
+                    .expect("Needs to be ListItem");
+                let album_cell = AlbumCell::new(&item, cache, Some(MarqueeWrapMode::Scroll));
+                item.set_child(Some(&album_cell));
+                adj.set_value(0.0);
+            }
+        ));
+
+        factory.connect_bind(move |_, list_item| {
+            let item: Album = list_item
+                .downcast_ref::<ListItem>()
+                .expect("Needs to be ListItem")
+                .item()
+                .and_downcast::<Album>()
+                .expect("The item has to be a common::Album.");
+
+            let child: AlbumCell = list_item
+                .downcast_ref::<ListItem>()
+                .expect("Needs to be ListItem")
+                .child()
+                .and_downcast::<AlbumCell>()
+                .expect("The child has to be an `AlbumCell`.");
+            child.bind(&item);
+        });
+
+        factory.connect_unbind(move |_, list_item| {
+            let child: AlbumCell = list_item
+                .downcast_ref::<ListItem>()
+                .expect("Needs to be ListItem")
+                .child()
+                .and_downcast::<AlbumCell>()
+                .expect("The child has to be an `AlbumCell`.");
+            child.unbind();
+        });
+
+        self.imp().album_row.set_factory(Some(&factory));
+
+        self.imp().album_row.connect_activate(clone!(
+            #[weak]
+            window,
+            move |grid_view, position| {
+                let model = grid_view.model().expect("The model has to exist.");
+                let album = model
+                    .item(position)
+                    .and_downcast::<Album>()
+                    .expect("The item has to be a `common::Album`.");
+                window.goto_album(&album);
+            }
+        ));
+    }
+
+    fn setup_artist_row(&self, window: &EuphonicaWindow, cache: Rc<Cache>) {
+        let artist_list = self.imp().library.get().unwrap().search_artists();
+        self.bind_section_visibility(&artist_list, &self.imp().artist_section.get());
+
+        let sel_model = SingleSelection::new(Some(artist_list));
+        self.imp().artist_row.set_model(Some(&sel_model));
+
+        // Set up factory
+        let factory = SignalListItemFactory::new();
+        let adj = self.imp().artist_row.hadjustment().unwrap();
+
+        factory.connect_setup(clone!(
+            #[weak]
+            cache,
+            #[weak]
+            adj,
+            move |_, list_item| {
+                let item = list_item
+                    .downcast_ref::<ListItem>()
+                    .expect("Needs to be ListItem");
+                let artist_cell = ArtistCell::new(&item, cache);
+                item.set_child(Some(&artist_cell));
+                adj.set_value(0.0);
+            }
+        ));
+
+        factory.connect_bind(move |_, list_item| {
+            let item: Artist = list_item
+                .downcast_ref::<ListItem>()
+                .expect("Needs to be ListItem")
+                .item()
+                .and_downcast::<Artist>()
+                .expect("The item has to be a common::Artist.");
+
+            let child: ArtistCell = list_item
+                .downcast_ref::<ListItem>()
+                .expect("Needs to be ListItem")
+                .child()
+                .and_downcast::<ArtistCell>()
+                .expect("The child has to be an `ArtistCell`.");
+            child.bind(&item);
+        });
+
+        factory.connect_unbind(move |_, list_item| {
+            let child: ArtistCell = list_item
+                .downcast_ref::<ListItem>()
+                .expect("Needs to be ListItem")
+                .child()
+                .and_downcast::<ArtistCell>()
+                .expect("The child has to be an `ArtistCell`.");
+            child.unbind();
+        });
+
+        self.imp().artist_row.set_factory(Some(&factory));
+
+        self.imp().artist_row.connect_activate(clone!(
+            #[weak]
+            window,
+            move |grid_view, position| {
+                let model = grid_view.model().expect("The model has to exist.");
+                let artist = model
+                    .item(position)
+                    .and_downcast::<Artist>()
+                    .expect("The item has to be a `common::Artist`.");
+                window.goto_artist(&artist);
+            }
+        ));
+    }
+
+    fn setup_playlist_list(&self, window: &EuphonicaWindow) {
+        let library = self.imp().library.get().unwrap().clone();
+        let library_settings = settings_manager().child("library");
+        self.imp().playlist_filter.set_filter_func(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            library_settings,
+            #[upgrade_or]
+            false,
+            move |obj| {
+                let inode = obj
+                    .downcast_ref::<INode>()
+                    .expect("Search obj has to be a common::INode.");
+                let search_term = this.search_term();
+                if search_term.is_empty() {
+                    return false;
+                }
+                g_search_substr(
+                    Some(inode.get_uri()),
+                    &search_term,
+                    library_settings.boolean("search-case-sensitive"),
+                )
+            }
+        ));
+
+        let playlist_results = gtk::FilterListModel::new(
+            Some(library.playlists()),
+            Some(self.imp().playlist_filter.clone()),
+        );
+        self.bind_section_visibility(&playlist_results, &self.imp().playlist_section.get());
+
+        self.imp().playlist_list.bind_model(
+            Some(&playlist_results),
+            clone!(
+                #[weak]
+                window,
+                #[upgrade_or]
+                adw::ActionRow::new().upcast::<gtk::Widget>(),
+                move |obj| {
+                    let playlist = obj.downcast_ref::<INode>().unwrap().clone();
+                    let row = adw::ActionRow::builder()
+                        .title(playlist.get_uri())
+                        .activatable(true)
+                        .build();
+                    row.add_prefix(&gtk::Image::from_icon_name("playlist-symbolic"));
+                    let append_btn = gtk::Button::builder()
+                        .icon_name("list-add-symbolic")
+                        .tooltip_text("Add this playlist to the end of the queue")
+                        .valign(gtk::Align::Center)
+                        .build();
+                    append_btn.add_css_class("flat");
+                    append_btn.connect_clicked(clone!(
+                        #[weak]
+                        library,
+                        #[weak]
+                        playlist,
+                        move |_| {
+                            library.queue_playlist(playlist.get_uri(), false, false);
+                        }
+                    ));
+                    row.add_suffix(&append_btn);
+                    row.connect_activated(clone!(
+                        #[weak]
+                        window,
+                        move |_| {
+                            window.goto_playlist(&playlist);
+                        }
+                    ));
+                    row.upcast::<gtk::Widget>()
+                }
+            ),
+        );
+        let _ = self.imp().playlist_results.set(playlist_results);
+    }
+
+    fn setup_song_list(&self, cache: Rc<Cache>) {
+        let library = self.imp().library.get().unwrap().clone();
+        let song_list = library.search_songs();
+        self.bind_section_visibility(&song_list, &self.imp().song_section.get());
+
+        self.imp().song_list.bind_model(Some(&song_list), move |obj| {
+            let row = RecentSongRow::new(
+                library.clone(),
+                obj.downcast_ref::<Song>().unwrap(),
+                cache.clone(),
+            );
+            row.into()
+        });
+    }
+}
+
+impl LazyInit for SearchView {
+    fn clear(&self) {
+        self.imp().initialized.set(false);
+        self.imp().library.get().unwrap().clear_search();
+    }
+
+    fn populate(&self) {
+        let was_populated = self.imp().initialized.replace(true);
+        if !was_populated {
+            // Rerun any search typed in before (re)connecting
+            self.search();
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use adw::subclass::prelude::*;
+use glib::{clone, closure_local};
+use gtk::{gio, glib, prelude::*, CompositeTemplate};
+use std::{
+    cell::{OnceCell, RefCell},
+    rc::Rc,
+};
+
+use super::{recent_song_row::RecentSongRow, smart_playlist_editor::SmartPlaylistEditor, Library};
+use crate::{
+    cache::Cache,
+    client::ClientState,
+    common::{SmartPlaylist, Song},
+};
+
+mod imp {
+    use std::sync::OnceLock;
+
+    use glib::subclass::Signal;
+
+    use super::*;
+
+    #[derive(Debug, CompositeTemplate)]
+    #[template(resource = "/io/github/htkhiem/Euphonica/gtk/library/smart-playlist-content-view.ui")]
+    pub struct SmartPlaylistContentView {
+        #[template_child]
+        pub delete: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub delete_popover: TemplateChild<gtk::Popover>,
+        #[template_child]
+        pub freeze_name: TemplateChild<gtk::Entry>,
+        #[template_child]
+        pub freeze: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub freeze_popover: TemplateChild<gtk::Popover>,
+        #[template_child]
+        pub freeze_menu_btn: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub edit_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub delete_menu_btn: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub title: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub description: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub track_count: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub replace_queue: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub append_queue: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub refresh: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub content_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub content: TemplateChild<gtk::ListBox>,
+
+        pub song_list: gio::ListStore,
+        pub playlist: RefCell<Option<SmartPlaylist>>,
+        pub library: OnceCell<Library>,
+    }
+
+    impl Default for SmartPlaylistContentView {
+        fn default() -> Self {
+            Self {
+                delete: TemplateChild::default(),
+                delete_popover: TemplateChild::default(),
+                freeze_name: TemplateChild::default(),
+                freeze: TemplateChild::default(),
+                freeze_popover: TemplateChild::default(),
+                freeze_menu_btn: TemplateChild::default(),
+                edit_btn: TemplateChild::default(),
+                delete_menu_btn: TemplateChild::default(),
+                title: TemplateChild::default(),
+                description: TemplateChild::default(),
+                track_count: TemplateChild::default(),
+                replace_queue: TemplateChild::default(),
+                append_queue: TemplateChild::default(),
+                refresh: TemplateChild::default(),
+                content_stack: TemplateChild::default(),
+                content: TemplateChild::default(),
+                song_list: gio::ListStore::new::<Song>(),
+                playlist: RefCell::new(None),
+                library: OnceCell::new(),
+            }
+        }
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SmartPlaylistContentView {
+        const NAME: &'static str = "EuphonicaSmartPlaylistContentView";
+        type Type = super::SmartPlaylistContentView;
+        type ParentType = gtk::Widget;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.set_layout_manager_type::<gtk::BinLayout>();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for SmartPlaylistContentView {
+        fn dispose(&self) {
+            while let Some(child) = self.obj().first_child() {
+                child.unparent();
+            }
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| vec![Signal::builder("deleted").build()])
+        }
+    }
+
+    impl WidgetImpl for SmartPlaylistContentView {}
+}
+
+glib::wrapper! {
+    pub struct SmartPlaylistContentView(ObjectSubclass<imp::SmartPlaylistContentView>)
+    @extends gtk::Widget,
+    @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl Default for SmartPlaylistContentView {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl SmartPlaylistContentView {
+    pub fn setup(&self, library: Library, client_state: ClientState, cache: Rc<Cache>) {
+        let imp = self.imp();
+        imp.library
+            .set(library.clone())
+            .expect("Cannot init SmartPlaylistContentView with Library");
+
+        imp.content.bind_model(
+            Some(&imp.song_list),
+            clone!(
+                #[weak]
+                library,
+                #[upgrade_or_panic]
+                move |obj| {
+                    RecentSongRow::new(library, obj.downcast_ref::<Song>().unwrap(), cache.clone())
+                        .into()
+                }
+            ),
+        );
+
+        client_state.connect_closure(
+            "smart-playlist-songs-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, name: String, songs: glib::BoxedAnyObject| {
+                    if this
+                        .imp()
+                        .playlist
+                        .borrow()
+                        .as_ref()
+                        .is_some_and(|playlist| playlist.name == name)
+                    {
+                        this.on_songs_downloaded(songs.borrow::<Vec<Song>>().as_ref());
+                    }
+                }
+            ),
+        );
+
+        imp.replace_queue.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                let songs: Vec<Song> = this.imp().song_list.iter::<Song>().flatten().collect();
+                this.library().queue_songs(&songs, true, true);
+            }
+        ));
+
+        imp.append_queue.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                let songs: Vec<Song> = this.imp().song_list.iter::<Song>().flatten().collect();
+                this.library().queue_songs(&songs, false, false);
+            }
+        ));
+
+        imp.refresh.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.evaluate();
+            }
+        ));
+
+        imp.freeze_name.connect_changed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |entry| {
+                this.imp()
+                    .freeze
+                    .set_sensitive(!entry.text().trim().is_empty());
+            }
+        ));
+
+        imp.freeze.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                let imp = this.imp();
+                let name = imp.freeze_name.text().trim().to_owned();
+                let songs: Vec<Song> = imp.song_list.iter::<Song>().flatten().collect();
+                if this.library().freeze_songs_as_playlist(&name, &songs).is_ok() {
+                    imp.freeze_popover.popdown();
+                }
+            }
+        ));
+
+        imp.edit_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.open_editor();
+            }
+        ));
+
+        imp.delete.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                let maybe_name = this
+                    .imp()
+                    .playlist
+                    .borrow()
+                    .as_ref()
+                    .map(|playlist| playlist.name.clone());
+                if let Some(name) = maybe_name {
+                    this.imp().delete_popover.popdown();
+                    this.library().delete_smart_playlist(&name);
+                    this.unbind();
+                    this.emit_by_name::<()>("deleted", &[]);
+                }
+            }
+        ));
+    }
+
+    fn library(&self) -> &Library {
+        self.imp().library.get().unwrap()
+    }
+
+    fn on_songs_downloaded(&self, songs: &[Song]) {
+        let imp = self.imp();
+        imp.song_list.remove_all();
+        imp.song_list.extend_from_slice(songs);
+        imp.track_count.set_label(&songs.len().to_string());
+        let has_songs = !songs.is_empty();
+        imp.replace_queue.set_sensitive(has_songs);
+        imp.append_queue.set_sensitive(has_songs);
+        imp.freeze_menu_btn.set_sensitive(has_songs);
+        imp.content_stack
+            .set_visible_child_name(if has_songs { "content" } else { "empty" });
+    }
+
+    fn open_editor(&self) {
+        let editor = SmartPlaylistEditor::new(
+            self.library().clone(),
+            self.imp().playlist.borrow().as_ref(),
+        );
+        editor.connect_closure(
+            "saved",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: SmartPlaylistEditor, name: String| {
+                    // Rebind to the edited version, which might have been renamed
+                    let maybe_playlist = this
+                        .library()
+                        .smart_playlists()
+                        .iter::<glib::BoxedAnyObject>()
+                        .flatten()
+                        .map(|obj| obj.borrow::<SmartPlaylist>().clone())
+                        .find(|playlist| playlist.name == name);
+                    if let Some(playlist) = maybe_playlist {
+                        this.bind(playlist);
+                    }
+                }
+            ),
+        );
+        editor.present(Some(self));
+    }
+
+    fn evaluate(&self) {
+        let imp = self.imp();
+        if let Some(playlist) = imp.playlist.borrow().as_ref() {
+            imp.content_stack.set_visible_child_name("loading");
+            self.library().evaluate_smart_playlist(playlist);
+        }
+    }
+
+    pub fn bind(&self, playlist: SmartPlaylist) {
+        let imp = self.imp();
+        imp.title.set_label(&playlist.name);
+        imp.description.set_label(&playlist.describe());
+        imp.track_count.set_label("-");
+        imp.freeze_name.set_text(&playlist.name);
+        imp.edit_btn.set_visible(!playlist.builtin);
+        imp.delete_menu_btn.set_visible(!playlist.builtin);
+        imp.song_list.remove_all();
+        imp.playlist.replace(Some(playlist));
+        self.evaluate();
+    }
+
+    pub fn unbind(&self) {
+        let imp = self.imp();
+        imp.playlist.take();
+        imp.song_list.remove_all();
+    }
+
+    pub fn current_playlist(&self) -> Option<SmartPlaylist> {
+        self.imp().playlist.borrow().clone()
+    }
+}
@@ -0,0 +1,244 @@
+use std::cell::{OnceCell, RefCell};
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gtk::{glib, CompositeTemplate};
+
+use glib::clone;
+
+use super::Library;
+use crate::common::{filter::get_saved_queries, FilterExpr, SmartPlaylist, SmartSort};
+
+fn zero_to_none(val: f64) -> Option<u32> {
+    if val >= 1.0 {
+        Some(val as u32)
+    } else {
+        None
+    }
+}
+
+mod imp {
+    use std::sync::OnceLock;
+
+    use glib::subclass::Signal;
+
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/io/github/htkhiem/Euphonica/gtk/library/smart-playlist-editor.ui")]
+    pub struct SmartPlaylistEditor {
+        #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        pub save_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub name_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub source_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub min_rating_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub liked_only_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub exclude_disliked_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub min_play_count_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub played_within_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub not_played_for_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub sort_row: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub descending_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub limit_row: TemplateChild<adw::SpinRow>,
+
+        // Filters offered by source_row, in the same order
+        pub sources: RefCell<Vec<Option<FilterExpr>>>,
+        // Name of the smart playlist being edited, if not creating a new one
+        pub old_name: RefCell<Option<String>>,
+        pub library: OnceCell<Library>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SmartPlaylistEditor {
+        const NAME: &'static str = "EuphonicaSmartPlaylistEditor";
+        type Type = super::SmartPlaylistEditor;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for SmartPlaylistEditor {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| {
+                vec![
+                    // Emitted with the (possibly new) name after saving
+                    Signal::builder("saved")
+                        .param_types([String::static_type()])
+                        .build(),
+                ]
+            })
+        }
+    }
+    impl WidgetImpl for SmartPlaylistEditor {}
+    impl AdwDialogImpl for SmartPlaylistEditor {}
+}
+
+glib::wrapper! {
+    pub struct SmartPlaylistEditor(ObjectSubclass<imp::SmartPlaylistEditor>)
+        @extends adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::ShortcutManager;
+}
+
+impl Default for SmartPlaylistEditor {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl SmartPlaylistEditor {
+    /// Edit an existing smart playlist, or create a new one if None is given.
+    pub fn new(library: Library, playlist: Option<&SmartPlaylist>) -> Self {
+        let res = Self::default();
+        let _ = res.imp().library.set(library);
+        res.setup();
+        res.load(playlist);
+        res
+    }
+
+    fn setup(&self) {
+        let imp = self.imp();
+        let sort_labels: Vec<&str> = SmartSort::ALL.iter().map(SmartSort::label).collect();
+        imp.sort_row.set_model(Some(&gtk::StringList::new(&sort_labels)));
+        // Random order has no direction
+        imp.sort_row
+            .bind_property("selected", &imp.descending_row.get(), "sensitive")
+            .transform_to(|_, idx: u32| Some(SmartSort::ALL[idx as usize] != SmartSort::Random))
+            .sync_create()
+            .build();
+
+        imp.name_row.connect_changed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |entry| {
+                entry.remove_css_class("error");
+                this.imp()
+                    .save_btn
+                    .set_sensitive(!entry.text().trim().is_empty());
+            }
+        ));
+
+        imp.save_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.save();
+            }
+        ));
+    }
+
+    fn load(&self, playlist: Option<&SmartPlaylist>) {
+        let imp = self.imp();
+        let default = SmartPlaylist::default();
+        let playlist = playlist.unwrap_or(&default);
+        imp.old_name
+            .replace(Some(playlist.name.clone()).filter(|name| !name.is_empty()));
+        imp.name_row.set_text(&playlist.name);
+
+        let mut labels: Vec<String> = vec!["Whole library".to_owned()];
+        let mut sources: Vec<Option<FilterExpr>> = vec![None];
+        let mut selected: u32 = 0;
+        for (name, filter) in get_saved_queries().into_iter() {
+            if playlist.filter.as_ref() == Some(&filter) {
+                selected = sources.len() as u32;
+            }
+            labels.push(name);
+            sources.push(Some(filter));
+        }
+        if let (0, Some(filter)) = (selected, playlist.filter.as_ref()) {
+            // Saved query has since been edited or deleted. Keep our own copy.
+            selected = sources.len() as u32;
+            labels.push("Current filter".to_owned());
+            sources.push(Some(filter.clone()));
+        }
+        let label_strs: Vec<&str> = labels.iter().map(String::as_str).collect();
+        imp.source_row
+            .set_model(Some(&gtk::StringList::new(&label_strs)));
+        imp.source_row.set_selected(selected);
+        imp.sources.replace(sources);
+
+        imp.min_rating_row
+            .set_value(playlist.min_rating.unwrap_or(0) as f64);
+        imp.liked_only_row.set_active(playlist.liked_only);
+        imp.exclude_disliked_row.set_active(playlist.exclude_disliked);
+        imp.min_play_count_row
+            .set_value(playlist.min_play_count.unwrap_or(0) as f64);
+        imp.played_within_row
+            .set_value(playlist.played_within_days.unwrap_or(0) as f64);
+        imp.not_played_for_row
+            .set_value(playlist.not_played_for_days.unwrap_or(0) as f64);
+        imp.sort_row.set_selected(
+            SmartSort::ALL
+                .iter()
+                .position(|s| *s == playlist.sort)
+                .unwrap_or(0) as u32,
+        );
+        imp.descending_row.set_active(playlist.descending);
+        imp.limit_row.set_value(playlist.limit.unwrap_or(0) as f64);
+    }
+
+    fn save(&self) {
+        let imp = self.imp();
+        let library = imp.library.get().unwrap();
+        let name = imp.name_row.text().trim().to_owned();
+        let old_name = imp.old_name.borrow().clone();
+        // Don't silently overwrite another smart playlist
+        if old_name.as_deref() != Some(name.as_str())
+            && library
+                .smart_playlists()
+                .iter::<glib::BoxedAnyObject>()
+                .flatten()
+                .any(|obj| obj.borrow::<SmartPlaylist>().name == name)
+        {
+            imp.name_row.add_css_class("error");
+            imp.toast_overlay.add_toast(
+                adw::Toast::builder()
+                    .title("A smart playlist with this name already exists")
+                    .timeout(3)
+                    .build(),
+            );
+            return;
+        }
+        let playlist = SmartPlaylist {
+            name: name.clone(),
+            filter: imp
+                .sources
+                .borrow()
+                .get(imp.source_row.selected() as usize)
+                .cloned()
+                .flatten(),
+            min_rating: zero_to_none(imp.min_rating_row.value()).map(|r| r as i8),
+            liked_only: imp.liked_only_row.is_active(),
+            exclude_disliked: imp.exclude_disliked_row.is_active(),
+            min_play_count: zero_to_none(imp.min_play_count_row.value()),
+            played_within_days: zero_to_none(imp.played_within_row.value()),
+            not_played_for_days: zero_to_none(imp.not_played_for_row.value()),
+            limit: zero_to_none(imp.limit_row.value()),
+            sort: SmartSort::ALL[imp.sort_row.selected() as usize],
+            descending: imp.descending_row.is_active(),
+            builtin: false,
+        };
+        library.save_smart_playlist(&playlist, old_name.as_deref());
+        self.emit_by_name::<()>("saved", &[&name]);
+        self.close();
+    }
+}
@@ -0,0 +1,468 @@
+use std::collections::HashMap;
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gtk::{glib, CompositeTemplate};
+use time::{Date, Duration};
+
+use glib::{clone, closure_local, BoxedAnyObject, Properties};
+
+use super::Library;
+use crate::{
+    client::ClientState,
+    common::{stats::local_today, Album, Artist, ListeningStats, StatsRange},
+    player::Player,
+    utils::LazyInit,
+    window::EuphonicaWindow,
+};
+
+// TODO: translations
+const WEEKDAYS: [&str; 7] = ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+mod imp {
+    use std::{cell::{Cell, OnceCell}, sync::OnceLock};
+
+    use glib::subclass::Signal;
+
+    use super::*;
+
+    #[derive(Debug, CompositeTemplate, Properties, Default)]
+    #[properties(wrapper_type = super::StatsView)]
+    #[template(resource = "/io/github/htkhiem/Euphonica/gtk/library/stats-view.ui")]
+    pub struct StatsView {
+        #[template_child]
+        pub show_sidebar: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub range: TemplateChild<gtk::DropDown>,
+        #[template_child]
+        pub stack: TemplateChild<gtk::Stack>,
+
+        // Summary cards
+        #[template_child]
+        pub total_plays: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub total_time: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub current_streak: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub longest_streak: TemplateChild<gtk::Label>,
+
+        // Heatmaps
+        #[template_child]
+        pub day_heatmap: TemplateChild<gtk::Grid>,
+        #[template_child]
+        pub hour_heatmap: TemplateChild<gtk::Grid>,
+
+        // Top lists
+        #[template_child]
+        pub top_songs: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub top_albums: TemplateChild<gtk::ListBox>,
+        #[template_child]
+        pub top_artists: TemplateChild<gtk::ListBox>,
+
+        pub library: OnceCell<Library>,
+        pub window: OnceCell<EuphonicaWindow>,
+
+        #[property(get, set)]
+        pub collapsed: Cell<bool>,
+
+        pub initialized: Cell<bool>,  // Only start fetching content when navigated to for the first time
+        pub stale: Cell<bool>  // History changed while this view was not shown
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for StatsView {
+        const NAME: &'static str = "EuphonicaStatsView";
+        type Type = super::StatsView;
+        type ParentType = gtk::Widget;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+            klass.set_layout_manager_type::<gtk::BinLayout>();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for StatsView {
+        fn dispose(&self) {
+            while let Some(child) = self.obj().first_child() {
+                child.unparent();
+            }
+        }
+
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            self.obj()
+                .bind_property("collapsed", &self.show_sidebar.get(), "visible")
+                .sync_create()
+                .build();
+
+            self.show_sidebar.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    this.obj().emit_by_name::<()>("show-sidebar-clicked", &[]);
+                }
+            ));
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
+            SIGNALS.get_or_init(|| vec![Signal::builder("show-sidebar-clicked").build()])
+        }
+    }
+
+    impl WidgetImpl for StatsView {}
+}
+
+glib::wrapper! {
+    pub struct StatsView(ObjectSubclass<imp::StatsView>)
+        @extends gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl Default for StatsView {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+fn format_listening_time(secs: u64) -> String {
+    let hours = secs / 3600;
+    let minutes = (secs % 3600) / 60;
+    if hours > 0 {
+        format!("{hours}h {minutes}m")
+    } else {
+        format!("{minutes}m")
+    }
+}
+
+fn format_days(n: u32) -> String {
+    if n == 1 { "1 day".to_owned() } else { format!("{n} days") }
+}
+
+fn format_plays(n: u32) -> String {
+    if n == 1 { "1 play".to_owned() } else { format!("{n} plays") }
+}
+
+fn format_date(date: Date) -> String {
+    format!("{} {} {}", date.day(), date.month(), date.year())
+}
+
+fn axis_label(text: &str) -> gtk::Label {
+    gtk::Label::builder()
+        .label(text)
+        .xalign(0.0)
+        .css_classes(["caption", "dim-label"])
+        .build()
+}
+
+/// A heatmap square, shaded relative to the busiest one.
+fn heatmap_cell(secs: u64, max: u64, tooltip: &str) -> gtk::Box {
+    let cell = gtk::Box::builder()
+        .css_classes(["heatmap-cell"])
+        .tooltip_text(tooltip)
+        .build();
+    if secs > 0 && max > 0 {
+        let level = ((secs as f64 / max as f64) * 4.0).ceil().clamp(1.0, 4.0) as u32;
+        cell.add_css_class(&format!("level-{level}"));
+    }
+    cell
+}
+
+fn clear_grid(grid: &gtk::Grid) {
+    while let Some(child) = grid.first_child() {
+        grid.remove(&child);
+    }
+}
+
+fn top_row(rank: usize, title: &str, subtitle: &str, plays: u32) -> adw::ActionRow {
+    let row = adw::ActionRow::builder()
+        .title(title)
+        .subtitle(subtitle)
+        .use_markup(false)
+        .build();
+    row.add_prefix(
+        &gtk::Label::builder()
+            .label(rank.to_string())
+            .width_chars(2)
+            .css_classes(["dim-label", "numeric"])
+            .build(),
+    );
+    row.add_suffix(&gtk::Label::new(Some(&format_plays(plays))));
+    row
+}
+
+impl StatsView {
+    pub fn setup(
+        &self,
+        library: Library,
+        player: Player,
+        client_state: ClientState,
+        window: &EuphonicaWindow,
+    ) {
+        let imp = self.imp();
+        imp.library
+            .set(library)
+            .expect("Cannot init StatsView with Library");
+        imp.window
+            .set(window.clone())
+            .expect("Cannot init StatsView with window");
+
+        player.connect_closure(
+            "history-changed",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: Player| {
+                    this.on_history_changed();
+                }
+            ),
+        );
+
+        client_state.connect_closure(
+            "listening-stats-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, stats: BoxedAnyObject| {
+                    this.on_stats_downloaded(&stats.borrow::<ListeningStats>());
+                }
+            ),
+        );
+
+        imp.range.connect_selected_notify(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                if this.imp().initialized.get() {
+                    this.imp().stack.set_visible_child_name("loading");
+                    this.refresh();
+                }
+            }
+        ));
+
+        self.connect_map(|this| {
+            if this.imp().stale.get() {
+                this.refresh();
+            }
+        });
+    }
+
+    fn selected_range(&self) -> StatsRange {
+        StatsRange::from_index(self.imp().range.selected())
+    }
+
+    fn refresh(&self) {
+        let imp = self.imp();
+        if imp.initialized.get() {
+            imp.stale.set(false);
+            imp.library
+                .get()
+                .unwrap()
+                .fetch_listening_stats(self.selected_range());
+        }
+    }
+
+    /// Aggregating the whole history is not free, so only do so while visible.
+    fn on_history_changed(&self) {
+        if self.is_mapped() {
+            self.refresh();
+        } else {
+            self.imp().stale.set(true);
+        }
+    }
+
+    fn on_stats_downloaded(&self, stats: &ListeningStats) {
+        // Results for a previously-selected range
+        if stats.range != self.selected_range() {
+            return;
+        }
+        let imp = self.imp();
+        if stats.total_plays == 0 {
+            imp.stack.set_visible_child_name("empty");
+            return;
+        }
+        imp.total_plays.set_label(&stats.total_plays.to_string());
+        imp.total_time.set_label(&format_listening_time(stats.total_time));
+        imp.current_streak.set_label(&format_days(stats.current_streak));
+        imp.longest_streak.set_label(&format_days(stats.longest_streak));
+        self.fill_day_heatmap(stats);
+        self.fill_hour_heatmap(stats);
+        self.fill_top_lists(stats);
+        imp.stack.set_visible_child_name("content");
+    }
+
+    /// Calendar-style heatmap with one column per week, Mondays first.
+    fn fill_day_heatmap(&self, stats: &ListeningStats) {
+        let grid = self.imp().day_heatmap.get();
+        clear_grid(&grid);
+
+        let today = local_today();
+        let first = today - Duration::days(stats.range.calendar_days() - 1);
+        let start = first - Duration::days(first.weekday().number_days_from_monday() as i64);
+        let n_cols = ((today - start).whole_days() / 7 + 1) as i32;
+        let by_day: HashMap<Date, u64> = stats.by_day.iter().copied().collect();
+        let max = by_day.values().copied().max().unwrap_or(0);
+
+        for (row, name) in WEEKDAYS.iter().enumerate().step_by(2) {
+            grid.attach(&axis_label(name), 0, row as i32 + 1, 1, 1);
+        }
+
+        let mut last_month_col: Option<i32> = None;
+        let mut day = start;
+        while day <= today {
+            let col = ((day - start).whole_days() / 7) as i32 + 1;
+            let row = day.weekday().number_days_from_monday() as i32 + 1;
+            // Label the first week of each month, without overlapping the previous label
+            if row == 1
+                && (day.day() <= 7 || col == 1)
+                && last_month_col.is_none_or(|last| col >= last + 3)
+            {
+                let month = day.month().to_string();
+                grid.attach(&axis_label(&month[..3]), col, 0, 3.min(n_cols - col + 1), 1);
+                last_month_col = Some(col);
+            }
+            if day >= first {
+                let secs = by_day.get(&day).copied().unwrap_or(0);
+                grid.attach(
+                    &heatmap_cell(
+                        secs,
+                        max,
+                        &format!("{}: {}", format_date(day), format_listening_time(secs)),
+                    ),
+                    col,
+                    row,
+                    1,
+                    1,
+                );
+            }
+            day = day.next_day().unwrap();
+        }
+    }
+
+    /// Weekday by hour-of-day heatmap.
+    fn fill_hour_heatmap(&self, stats: &ListeningStats) {
+        let grid = self.imp().hour_heatmap.get();
+        clear_grid(&grid);
+
+        let max = stats.by_hour.iter().flatten().copied().max().unwrap_or(0);
+        for hour in (0..24).step_by(6) {
+            grid.attach(&axis_label(&format!("{hour:02}:00")), hour + 1, 0, 6, 1);
+        }
+        for (weekday, hours) in stats.by_hour.iter().enumerate() {
+            grid.attach(&axis_label(WEEKDAYS[weekday]), 0, weekday as i32 + 1, 1, 1);
+            for (hour, secs) in hours.iter().enumerate() {
+                grid.attach(
+                    &heatmap_cell(
+                        *secs,
+                        max,
+                        &format!(
+                            "{} {:02}:00–{:02}:00: {}",
+                            WEEKDAYS[weekday],
+                            hour,
+                            (hour + 1) % 24,
+                            format_listening_time(*secs)
+                        ),
+                    ),
+                    hour as i32 + 1,
+                    weekday as i32 + 1,
+                    1,
+                    1,
+                );
+            }
+        }
+    }
+
+    /// Activating a song goes to its album. Albums & artists go to their own pages.
+    fn fill_top_lists(&self, stats: &ListeningStats) {
+        let imp = self.imp();
+        let window = imp.window.get().unwrap();
+
+        imp.top_songs.remove_all();
+        for (idx, (info, plays, first_listened)) in stats.top_songs.iter().enumerate() {
+            let subtitle = match info.artist_tag.as_ref() {
+                Some(artist) => format!("{artist} · first listened {}", format_date(*first_listened)),
+                None => format!("First listened {}", format_date(*first_listened)),
+            };
+            let row = top_row(idx + 1, &info.title, &subtitle, *plays);
+            if let Some(album) = info.album.clone().map(Album::from) {
+                row.set_activatable(true);
+                row.connect_activated(clone!(
+                    #[weak]
+                    window,
+                    move |_| {
+                        window.goto_album(&album);
+                    }
+                ));
+            }
+            imp.top_songs.append(&row);
+        }
+
+        imp.top_albums.remove_all();
+        for (idx, (info, plays, first_listened)) in stats.top_albums.iter().enumerate() {
+            let subtitle = match info.albumartist.as_ref() {
+                Some(artist) => format!("{artist} · first listened {}", format_date(*first_listened)),
+                None => format!("First listened {}", format_date(*first_listened)),
+            };
+            let row = top_row(idx + 1, &info.title, &subtitle, *plays);
+            let album = Album::from(info.clone());
+            row.set_activatable(true);
+            row.connect_activated(clone!(
+                #[weak]
+                window,
+                move |_| {
+                    window.goto_album(&album);
+                }
+            ));
+            imp.top_albums.append(&row);
+        }
+
+        imp.top_artists.remove_all();
+        for (idx, (info, plays, first_listened)) in stats.top_artists.iter().enumerate() {
+            let row = top_row(
+                idx + 1,
+                &info.name,
+                &format!("First listened {}", format_date(*first_listened)),
+                *plays,
+            );
+            let artist = Artist::from(info.clone());
+            row.set_activatable(true);
+            row.connect_activated(clone!(
+                #[weak]
+                window,
+                move |_| {
+                    window.goto_artist(&artist);
+                }
+            ));
+            imp.top_artists.append(&row);
+        }
+
+        imp.top_songs.set_visible(!stats.top_songs.is_empty());
+        imp.top_albums.set_visible(!stats.top_albums.is_empty());
+        imp.top_artists.set_visible(!stats.top_artists.is_empty());
+    }
+}
+
+impl LazyInit for StatsView {
+    fn clear(&self) {
+        self.imp().initialized.set(false);
+    }
+
+    fn populate(&self) {
+        let was_populated = self.imp().initialized.replace(true);
+        if !was_populated {
+            println!("Initialising statistics");
+            self.imp().stack.set_visible_child_name("loading");
+            self.refresh();
+        }
+    }
+}
@@ -23,10 +23,13 @@ mod cache;
 mod client;
 mod common;
 mod config;
+mod history;
 mod library;
 mod meta_providers;
+mod playlist_file;
 mod player;
 mod preferences;
+mod scrobbler;
 mod sidebar;
 mod utils;
 mod window;
@@ -8,7 +8,10 @@ use reqwest::{
 
 use crate::{common::{AlbumInfo, ArtistInfo}, config::APPLICATION_USER_AGENT, utils::meta_provider_settings};
 
-use super::models::{LastfmAlbumResponse, LastfmArtistResponse};
+use super::models::{
+    LastfmAlbumResponse, LastfmArtistResponse, LastfmSimilarArtistsResponse,
+    LastfmSimilarTracksResponse,
+};
 use super::{
     super::{models, prelude::*, MetadataProvider},
     PROVIDER_KEY,
@@ -50,6 +53,68 @@ impl LastfmWrapper {
         }
         None
     }
+
+    /// Get up to `limit` tracks similar to the given one, as (artist, title) pairs,
+    /// most similar first. Returns an empty list if Last.fm is disabled or unreachable.
+    pub fn get_similar_tracks(&self, artist: &str, title: &str, limit: u32) -> Vec<(String, String)> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return Vec::with_capacity(0);
+        }
+        let limit = limit.to_string();
+        let params = [("artist", artist), ("track", title), ("limit", limit.as_str()), ("autocorrect", "1")];
+        if let Some(resp) = self.get_lastfm("track.getsimilar", &params) {
+            match resp.status() {
+                reqwest::StatusCode::OK => match resp.json::<LastfmSimilarTracksResponse>() {
+                    Ok(parsed) => {
+                        return parsed
+                            .similartracks
+                            .track
+                            .into_iter()
+                            .map(|track| (track.artist.name, track.name))
+                            .collect();
+                    }
+                    Err(err) => {
+                        println!("[Last.fm] get_similar_tracks: {}", err);
+                    }
+                },
+                other => {
+                    println!("[Last.fm] get_similar_tracks: failed with status {:?}", other);
+                }
+            }
+        }
+        Vec::with_capacity(0)
+    }
+
+    /// Get the names of up to `limit` artists similar to the given one, most similar first.
+    /// Returns an empty list if Last.fm is disabled or unreachable.
+    pub fn get_similar_artists(&self, artist: &str, limit: u32) -> Vec<String> {
+        if !meta_provider_settings(PROVIDER_KEY).boolean("enabled") {
+            return Vec::with_capacity(0);
+        }
+        let limit = limit.to_string();
+        let params = [("artist", artist), ("limit", limit.as_str()), ("autocorrect", "1")];
+        if let Some(resp) = self.get_lastfm("artist.getsimilar", &params) {
+            match resp.status() {
+                reqwest::StatusCode::OK => match resp.json::<LastfmSimilarArtistsResponse>() {
+                    Ok(parsed) => {
+                        return parsed
+                            .similarartists
+                            .artist
+                            .into_iter()
+                            .map(|artist| artist.name)
+                            .collect();
+                    }
+                    Err(err) => {
+                        println!("[Last.fm] get_similar_artists: {}", err);
+                    }
+                },
+                other => {
+                    println!("[Last.fm] get_similar_artists: failed with status {:?}", other);
+                }
+            }
+        }
+        Vec::with_capacity(0)
+    }
 }
 
 impl MetadataProvider for LastfmWrapper {
@@ -252,3 +252,58 @@ impl Into<ArtistMeta> for LastfmArtist {
 pub struct LastfmArtistResponse {
     pub artist: LastfmArtist,
 }
+
+// artist.getsimilar. Same item structure as the similar artists embedded in
+// artist.getinfo responses.
+#[derive(Deserialize)]
+pub struct LastfmSimilarArtistsResponse {
+    pub similarartists: LastfmSimilar,
+}
+
+// track.getsimilar
+#[derive(Deserialize, Debug)]
+#[non_exhaustive]
+pub struct LastfmTrackArtist {
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug)]
+#[non_exhaustive]
+pub struct LastfmSimilarTrack {
+    pub name: String,
+    pub artist: LastfmTrackArtist,
+}
+
+#[derive(Deserialize, Debug)]
+#[non_exhaustive]
+pub struct LastfmSimilarTracks {
+    pub track: Vec<LastfmSimilarTrack>,
+}
+
+#[derive(Deserialize)]
+pub struct LastfmSimilarTracksResponse {
+    pub similartracks: LastfmSimilarTracks,
+}
+
+// Authenticated API calls (auth.*, track.scrobble, etc.)
+#[derive(Deserialize, Debug)]
+pub struct LastfmErrorResponse {
+    pub error: u32,
+    pub message: String,
+}
+
+#[derive(Deserialize)]
+pub struct LastfmTokenResponse {
+    pub token: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LastfmSession {
+    pub name: String,
+    pub key: String,
+}
+
+#[derive(Deserialize)]
+pub struct LastfmSessionResponse {
+    pub session: LastfmSession,
+}
@@ -0,0 +1,271 @@
+use std::{
+    cell::{OnceCell, RefCell},
+    rc::Rc,
+};
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gtk::{glib, CompositeTemplate};
+
+use glib::clone;
+use time::OffsetDateTime;
+
+use crate::{
+    cache::sqlite,
+    client::MpdWrapper,
+    common::{alarm::WEEKDAY_NAMES, Alarm, AlarmSource, SavedAlarm},
+};
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/io/github/htkhiem/Euphonica/gtk/player/alarms.ui")]
+    pub struct AlarmsDialog {
+        #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        pub alarms: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub add_alarm: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub hour: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub minute: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub days: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub source_kind: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub playlist: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub stream_url: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub start_volume: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub end_volume: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub ramp_secs: TemplateChild<adw::SpinRow>,
+        // One per day of the week, Monday first
+        pub day_toggles: RefCell<Vec<gtk::ToggleButton>>,
+        pub playlist_names: RefCell<Vec<String>>,
+        // Rows of the alarm list. Rebuilt on every refresh.
+        pub rows: RefCell<Vec<adw::ActionRow>>,
+        pub client: OnceCell<Rc<MpdWrapper>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for AlarmsDialog {
+        const NAME: &'static str = "EuphonicaAlarmsDialog";
+        type Type = super::AlarmsDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for AlarmsDialog {}
+    impl WidgetImpl for AlarmsDialog {}
+    impl AdwDialogImpl for AlarmsDialog {}
+}
+
+glib::wrapper! {
+    pub struct AlarmsDialog(ObjectSubclass<imp::AlarmsDialog>)
+        @extends adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::ShortcutManager;
+}
+
+impl Default for AlarmsDialog {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl AlarmsDialog {
+    pub fn new(client: Rc<MpdWrapper>) -> Self {
+        let res = Self::default();
+        let _ = res.imp().client.set(client);
+        res.setup();
+        res.refresh();
+        res
+    }
+
+    fn client(&self) -> &Rc<MpdWrapper> {
+        self.imp().client.get().unwrap()
+    }
+
+    fn setup(&self) {
+        let imp = self.imp();
+        let toggles: Vec<gtk::ToggleButton> = WEEKDAY_NAMES
+            .iter()
+            .map(|name| {
+                let toggle = gtk::ToggleButton::with_label(name);
+                imp.days.append(&toggle);
+                toggle
+            })
+            .collect();
+        imp.day_toggles.replace(toggles);
+
+        let names: Vec<String> = self
+            .client()
+            .get_playlists()
+            .iter()
+            .filter_map(|inode| inode.get_name().map(str::to_owned))
+            .collect();
+        imp.playlist.set_model(Some(&gtk::StringList::new(
+            &names.iter().map(String::as_str).collect::<Vec<&str>>(),
+        )));
+        imp.playlist_names.replace(names);
+
+        let playlist = imp.playlist.get();
+        let stream_url = imp.stream_url.get();
+        imp.source_kind.connect_selected_notify(move |row| {
+            let is_stream = row.selected() == 1;
+            playlist.set_visible(!is_stream);
+            stream_url.set_visible(is_stream);
+        });
+
+        imp.add_alarm.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.add_alarm();
+            }
+        ));
+    }
+
+    fn show_toast(&self, msg: &str) {
+        self.imp()
+            .toast_overlay
+            .add_toast(adw::Toast::builder().title(msg).timeout(3).build());
+    }
+
+    fn add_alarm(&self) {
+        let imp = self.imp();
+        // TODO: translatable
+        let source = if imp.source_kind.selected() == 1 {
+            let url = imp.stream_url.text().trim().to_owned();
+            if url.is_empty() {
+                self.show_toast("Enter the URL of a stream to play");
+                return;
+            }
+            AlarmSource::Stream(url)
+        } else {
+            let maybe_name = imp
+                .playlist_names
+                .borrow()
+                .get(imp.playlist.selected() as usize)
+                .cloned();
+            let Some(name) = maybe_name else {
+                self.show_toast("Pick a playlist to play");
+                return;
+            };
+            AlarmSource::Playlist(name)
+        };
+        let days = imp
+            .day_toggles
+            .borrow()
+            .iter()
+            .enumerate()
+            .filter(|(_, toggle)| toggle.is_active())
+            .fold(0u8, |days, (idx, _)| days | (1 << idx));
+        let alarm = Alarm {
+            hour: imp.hour.value() as u8,
+            minute: imp.minute.value() as u8,
+            days,
+            source,
+            start_volume: imp.start_volume.value() as u8,
+            end_volume: imp.end_volume.value() as u8,
+            ramp_secs: imp.ramp_secs.value() as u32,
+            enabled: true,
+        };
+        match sqlite::add_alarm(&alarm) {
+            Ok(()) => {
+                imp.stream_url.set_text("");
+                self.refresh();
+            }
+            Err(e) => {
+                self.show_toast(&format!("Unable to save alarm: {e:?}"));
+            }
+        }
+    }
+
+    fn add_row(&self, saved: SavedAlarm) {
+        let row = adw::ActionRow::builder()
+            .title(format!("{} · {}", saved.alarm.time_desc(), saved.alarm.days_desc()))
+            .subtitle(glib::markup_escape_text(saved.alarm.source_desc()))
+            .build();
+
+        let enabled = gtk::Switch::builder()
+            .active(saved.alarm.enabled)
+            .valign(gtk::Align::Center)
+            .build();
+        enabled.connect_active_notify(clone!(
+            #[strong]
+            saved,
+            move |sw| {
+                let mut saved = saved.clone();
+                saved.alarm.enabled = sw.is_active();
+                // Don't report times it was due at while it was turned off.
+                saved.checked = OffsetDateTime::now_utc();
+                if let Err(e) = sqlite::update_alarm(&saved) {
+                    println!("Unable to update alarm {}: {e:?}", saved.id);
+                }
+            }
+        ));
+        row.add_suffix(&enabled);
+
+        let delete_btn = gtk::Button::builder()
+            .icon_name("user-trash-symbolic")
+            .tooltip_text("Delete alarm")
+            .valign(gtk::Align::Center)
+            .build();
+        delete_btn.add_css_class("flat");
+        let id = saved.id;
+        delete_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                if let Err(e) = sqlite::delete_alarm(id) {
+                    this.show_toast(&format!("Unable to delete alarm: {e:?}"));
+                }
+                this.refresh();
+            }
+        ));
+        row.add_suffix(&delete_btn);
+
+        self.imp().alarms.add(&row);
+        self.imp().rows.borrow_mut().push(row);
+    }
+
+    /// Repopulate the list of alarms.
+    pub fn refresh(&self) {
+        let imp = self.imp();
+        for row in imp.rows.take().into_iter() {
+            imp.alarms.remove(&row);
+        }
+        let alarms = match sqlite::get_alarms() {
+            Ok(alarms) => alarms,
+            Err(e) => {
+                println!("Unable to load alarms: {e:?}");
+                Vec::new()
+            }
+        };
+        if alarms.is_empty() {
+            let row = adw::ActionRow::builder()
+                .title("No alarms yet")
+                .sensitive(false)
+                .build();
+            imp.alarms.add(&row);
+            imp.rows.borrow_mut().push(row);
+        }
+        for saved in alarms.into_iter() {
+            self.add_row(saved);
+        }
+    }
+}
@@ -2,11 +2,13 @@ extern crate mpd;
 use crate::{
     application::EuphonicaApplication,
     cache::{get_image_cache_path, sqlite, Cache, CacheState},
-    client::{ClientState, ConnectionState, MpdWrapper},
-    common::{CoverSource, QualityGrade, Song, SongInfo},
+    client::{BackgroundTask, ClientState, ConnectionState, MpdWrapper},
+    common::{queue_moves, Alarm, AlarmSource, CoverSource, QualityGrade, QueueMove, QueueSnapshot, SavedQueue, Song, SongInfo, Stickers, Thumbs},
     config::APPLICATION_ID,
     meta_providers::models::Lyrics,
-    utils::{prettify_audio_format, settings_manager, strip_filename_linux}
+    playlist_file::ExportOptions,
+    scrobbler::{self, Scrobbler},
+    utils::{format_secs_as_duration, prettify_audio_format, settings_manager, strip_filename_linux}
 };
 use async_lock::OnceCell as AsyncOnceCell;
 use mpris_server::{
@@ -28,6 +30,30 @@ use std::{
     ops::Deref, path::PathBuf,
     rc::Rc, sync::{Arc, Mutex, OnceLock}, vec::Vec,
 };
+use rustc_hash::{FxHashMap, FxHashSet};
+use gettextrs::gettext;
+use time::OffsetDateTime;
+
+/// How often to save the position of long songs while they are playing, in seconds.
+const RESUME_SAVE_INTERVAL: f64 = 30.0;
+/// Positions closer to the start of a song than this are not worth saving.
+const RESUME_MIN_POSITION: f64 = 10.0;
+/// Songs stopped within this many seconds of their end count as finished, in which
+/// case their saved position is cleared.
+const RESUME_FINISHED_MARGIN: f64 = 15.0;
+/// Priority of the first up-next song. Each following one gets one less, down to
+/// UP_NEXT_MIN_PRIO.
+const UP_NEXT_MAX_PRIO: u8 = 255;
+/// Priorities from here up are reserved for up-next songs, so that the list can be told
+/// apart from songs given a fixed priority when restoring it. Those are capped below.
+const UP_NEXT_MIN_PRIO: u8 = 128;
+/// Highest priority that can be given to queued songs by hand.
+pub const MAX_FIXED_PRIO: u8 = UP_NEXT_MIN_PRIO - 1;
+/// How often to check for alarms that are due, in seconds.
+const ALARM_CHECK_INTERVAL: u32 = 20;
+/// Alarms found to be due more than this many seconds late count as missed instead
+/// of going off, such as when Euphonica was not running at the time.
+const ALARM_GRACE_SECS: i64 = 120;
 
 use super::fft_backends::{
     backend::{FftBackendExt, FftStatus}, FifoFftBackend, PipeWireFftBackend
@@ -74,11 +100,23 @@ impl FftStatus {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
 pub enum SwapDirection {
     Up,
     Down,
 }
 
+/// What a sleep timer waits for before pausing playback.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SleepTimer {
+    /// Monotonic time to pause at, in microseconds (see glib::monotonic_time).
+    Until(i64),
+    /// Number of songs left to finish, counting the current one.
+    Songs(u32),
+    /// Last song of the queue. Once it starts, the timer becomes Songs(1).
+    EndOfQueue,
+}
+
 impl PlaybackFlow {
     pub fn from_status(st: &mpd::status::Status) -> Self {
         if st.repeat {
@@ -179,6 +217,11 @@ mod imp {
         pub state: Cell<PlaybackState>,
         pub position: Cell<f64>,
         pub queue: gio::ListStore,
+        // Up-next list, as queue IDs in the order they should be played. Backed by
+        // MPD priorities so that it also works in random mode.
+        pub up_next_ids: RefCell<Vec<u32>>,
+        // Queued Song objects of the above, for display.
+        pub up_next: gio::ListStore,
         pub lyric_lines: gtk::StringList,  // Line by line for display. May be empty.
         pub lyrics: RefCell<Option<Lyrics>>,
         pub queue_len: Cell<u32>,
@@ -222,7 +265,30 @@ mod imp {
         // to the bar & pane.
         pub cover_source: Cell<CoverSource>,
         pub saved_to_history: Cell<bool>,
-        pub is_foreground: Cell<bool>
+        pub scrobbler: Scrobbler,
+        pub scrobbled: Cell<bool>,
+        // Whether the current song's play count sticker has been incremented
+        pub counted_as_played: Cell<bool>,
+        // Position at which the current song's elapsed sticker was last saved
+        pub resume_saved_at: Cell<f64>,
+        pub is_foreground: Cell<bool>,
+        // Radio mode: (seed queue ID, queue length) of the last top-up request, so that
+        // we don't ask again until either changes.
+        pub radio_requested: Cell<Option<(u32, u32)>>,
+        pub sleep_timer: Cell<Option<SleepTimer>>,
+        // Handle to the sleep timer's ticking task
+        pub sleep_handle: RefCell<Option<glib::JoinHandle<()>>>,
+        // Length of the fade-out in seconds, fixed when the timer is started.
+        pub sleep_fade: Cell<f64>,
+        // Volume from before the fade-out began, to be restored afterwards.
+        pub sleep_volume: Cell<Option<i8>>,
+        // Playback flow from before single oneshot was turned on for the timer's
+        // last song, if it was.
+        pub sleep_oneshot: Cell<Option<PlaybackFlow>>,
+        // Handle to the alarm checking loop
+        pub alarm_handle: RefCell<Option<glib::JoinHandle<()>>>,
+        // Handle to the volume ramp of the last alarm to go off
+        pub alarm_ramp: RefCell<Option<glib::JoinHandle<()>>>
     }
 
     #[glib::object_subclass]
@@ -247,6 +313,8 @@ mod imp {
                 mixramp_db: Cell::new(0.0),
                 mixramp_delay: Cell::new(0.0),
                 queue: gio::ListStore::new::<Song>(),
+                up_next_ids: RefCell::new(Vec::new()),
+                up_next: gio::ListStore::new::<Song>(),
                 queue_len: Cell::new(0),
                 current_song: RefCell::new(None),
                 current_lyric_line: Cell::default(),
@@ -282,7 +350,19 @@ mod imp {
                 outputs: gio::ListStore::new::<BoxedAnyObject>(),
                 cover_source: Cell::default(),
                 saved_to_history: Cell::new(false),
-                is_foreground: Cell::new(false)
+                scrobbler: Scrobbler::new(),
+                scrobbled: Cell::new(false),
+                counted_as_played: Cell::new(false),
+                resume_saved_at: Cell::new(0.0),
+                is_foreground: Cell::new(false),
+                radio_requested: Cell::new(None),
+                sleep_timer: Cell::new(None),
+                sleep_handle: RefCell::new(None),
+                sleep_fade: Cell::new(0.0),
+                sleep_volume: Cell::new(None),
+                sleep_oneshot: Cell::new(None),
+                alarm_handle: RefCell::new(None),
+                alarm_ramp: RefCell::new(None)
             };
             res
         }
@@ -372,7 +452,9 @@ mod imp {
                         .build(),
                     ParamSpecString::builder("format-desc").read_only().build(),
                     ParamSpecInt::builder("fft-backend-idx").build(),
-                    ParamSpecBoolean::builder("pipewire-restart-between-songs").build()
+                    ParamSpecBoolean::builder("pipewire-restart-between-songs").build(),
+                    ParamSpecBoolean::builder("sleep-timer-active").read_only().build(),
+                    ParamSpecString::builder("sleep-timer-desc").read_only().build()
                 ]
             });
             PROPERTIES.as_ref()
@@ -406,6 +488,8 @@ mod imp {
                 "format-desc" => obj.format_desc().to_value(),
                 "fft-backend-idx" => self.fft_backend_idx.get().to_value(),
                 "pipewire-restart-between-songs" => self.pipewire_restart_between_songs.get().to_value(),
+                "sleep-timer-active" => self.sleep_timer.get().is_some().to_value(),
+                "sleep-timer-desc" => obj.sleep_timer_desc().to_value(),
                 _ => unimplemented!(),
             }
         }
@@ -506,6 +590,11 @@ mod imp {
                         .build(),
                     Signal::builder("history-changed")
                         .build(),
+                    // A long song with a saved position has started playing. Carries its
+                    // queue ID and the saved position in seconds.
+                    Signal::builder("resume-available")
+                        .param_types([u32::static_type(), f64::static_type()])
+                        .build(),
                     // For simplicity we'll always use the hires version
                     Signal::builder("cover-changed")
                         .param_types([Option::<gdk::Texture>::static_type()])
@@ -642,7 +731,10 @@ impl Player {
 
     pub fn clear(&self) {
         self.imp().queue.remove_all();
+        self.imp().up_next_ids.borrow_mut().clear();
+        self.imp().up_next.remove_all();
         self.imp().outputs.remove_all();
+        self.cancel_sleep_timer();
         self.update_status(&mpd::Status::default());
     }
 
@@ -811,6 +903,30 @@ impl Player {
             )
         );
 
+        client_state.connect_closure(
+            "queue-downloaded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState| {
+                    this.restore_up_next();
+                }
+            )
+        );
+
+        client_state.connect_closure(
+            "up-next-queued",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, ids: BoxedAnyObject, first: bool| {
+                    this.on_up_next_queued(ids.borrow::<Vec<u32>>().as_ref(), first);
+                }
+            )
+        );
+
         client_state.connect_closure(
             "queue-changed",
             false,
@@ -845,6 +961,8 @@ impl Player {
                 }
             ),
         );
+
+        self.start_alarm_checks();
     }
 
     fn update_mpris_properties(&self, properties: Vec<Property>) {
@@ -899,6 +1017,8 @@ impl Player {
     /// relevant and update the GObject properties accordingly.
     pub fn update_status(&self, status: &Status) {
         let mut mpris_changes: Vec<Property> = Vec::new();
+        // Whether playback was paused since the last update
+        let mut just_paused = false;
         match status.state {
             State::Play => {
                 let new_state = PlaybackState::Playing;
@@ -915,6 +1035,7 @@ impl Player {
                 let new_state = PlaybackState::Paused;
                 let old_state = self.imp().state.replace(new_state);
                 self.stop_polling();
+                just_paused = old_state == PlaybackState::Playing;
                 if old_state != new_state {
                     self.notify("playback-state");
                     if self.imp().mpris_enabled.get() {
@@ -927,6 +1048,15 @@ impl Player {
                 let old_state = self.imp().state.replace(new_state);
                 self.stop_polling();
                 if old_state != new_state {
+                    // Song-based sleep timers are done once MPD stops by itself.
+                    if self
+                        .imp()
+                        .sleep_timer
+                        .get()
+                        .is_some_and(|timer| !matches!(timer, SleepTimer::Until(_)))
+                    {
+                        self.end_sleep_timer(false);
+                    }
                     self.notify("playback-state");
                     if self.imp().mpris_enabled.get() {
                         mpris_changes.push(Property::PlaybackStatus(MprisPlaybackStatus::Stopped));
@@ -1017,6 +1147,14 @@ impl Player {
         // Update playing status of songs in the queue
         if let Some(new_queue_place) = status.song {
             let mut needs_refresh: bool = false;
+            // Whether we moved on from another song, rather than starting from nothing.
+            let mut moved_on: bool = false;
+            // Play & skip statistics to write to the stickers DB once the current song
+            // is no longer borrowed.
+            let mut played_song: Option<Song> = None;
+            let mut skipped_song: Option<Song> = None;
+            // Same goes for resume points: (song, position, whether to save right away)
+            let mut resume_point: Option<(Song, f64, bool)> = None;
             {
                 // There is now a playing song. Fetch if we haven't already.
                 let mut local_curr_song = self
@@ -1028,7 +1166,18 @@ impl Player {
                     needs_refresh = true;
                     if let Some(new_song) = self.client().get_song_at_queue_id(new_queue_place.id.0) {
                         // Always fetch as the queue might not have been populated yet
-                        local_curr_song.replace(new_song.clone());
+                        if let Some(old_song) = local_curr_song.replace(new_song.clone()) {
+                            moved_on = true;
+                            // Moving on early counts as a skip. Our position has not been
+                            // updated yet and still refers to the old song.
+                            let old_position = self.imp().position.get();
+                            if !self.imp().counted_as_played.get()
+                                && Stickers::is_skipped(old_song.get_duration(), old_position)
+                            {
+                                skipped_song = Some(old_song.clone());
+                            }
+                            resume_point = Some((old_song, old_position, true));
+                        }
                         // If using PipeWire visualiser, might need to restart it
                         if self.imp().pipewire_restart_between_songs.get()
                             && self.imp().fft_backend.borrow().as_ref().is_some_and(
@@ -1040,7 +1189,29 @@ impl Player {
                         }
                     }
                 } else if let Some(curr_song) = local_curr_song.as_ref() {
-                    // Same old song. Might want to record into playback history.
+                    // Same old song. Might want to save its position.
+                    if let Some(elapsed) = status.elapsed {
+                        resume_point = Some((curr_song.clone(), elapsed.as_secs_f64(), just_paused));
+                    }
+                    // Might also want to count it as played.
+                    if !self.imp().counted_as_played.get() {
+                        if let Some(elapsed) = status.elapsed {
+                            if Stickers::is_played(curr_song.get_duration(), elapsed.as_secs_f64()) {
+                                played_song = Some(curr_song.clone());
+                                self.imp().counted_as_played.set(true);
+                            }
+                        }
+                    }
+                    // Might also want to scrobble it.
+                    if !self.imp().scrobbled.get() {
+                        if let Some(elapsed) = status.elapsed {
+                            if scrobbler::is_scrobblable(curr_song.get_duration(), elapsed.as_secs_f64()) {
+                                self.imp().scrobbler.scrobble(curr_song, OffsetDateTime::now_utc() - elapsed);
+                                self.imp().scrobbled.set(true);
+                            }
+                        }
+                    }
+                    // Might also want to record into playback history.
                     if !settings_manager().child("library").boolean("pause-recent") {
                         let dur = curr_song.get_duration() as f32;
                         if dur >= 10.0 {
@@ -1056,9 +1227,31 @@ impl Player {
                     }
                 }
             }
+            if let Some(song) = played_song {
+                self.increment_play_stat(&song, true);
+            }
+            if let Some(song) = skipped_song {
+                self.increment_play_stat(&song, false);
+            }
+            if let Some((song, position, force)) = resume_point {
+                self.update_resume_point(&song, position, force);
+            }
             if needs_refresh {
+                let maybe_new_song = self.imp().current_song.borrow().clone();
+                if let Some(new_song) = maybe_new_song {
+                    self.maybe_resume(&new_song, status.elapsed.map_or(0.0, |dur| dur.as_secs_f64()));
+                    self.finish_up_next(new_song.get_queue_id());
+                }
+                if moved_on {
+                    self.on_sleep_song_changed();
+                }
                 if let Some(new_song) = self.imp().current_song.borrow().as_ref() {
                     self.imp().saved_to_history.set(false);
+                    self.imp().scrobbled.set(false);
+                    self.imp().counted_as_played.set(false);
+                    if status.state == State::Play {
+                        self.imp().scrobbler.now_playing(new_song);
+                    }
                     self.notify("title");
                     self.notify("artist");
                     self.notify("duration");
@@ -1109,8 +1302,10 @@ impl Player {
         if status.song.is_none() || status.state == State::Stop {
             println!("No song playing right now");
             // No song is playing. Update state accordingly.
-            if let Some(_) = self.imp().current_song.take() {
+            if let Some(old_song) = self.imp().current_song.take() {
                 self.imp().saved_to_history.set(false);
+                self.imp().counted_as_played.set(false);
+                self.update_resume_point(&old_song, self.imp().position.get(), true);
                 self.notify("title");
                 self.notify("artist");
                 self.notify("album");
@@ -1172,12 +1367,154 @@ impl Player {
             self.imp()
                 .queue
                 .splice(new_len, old_len - new_len, &[] as &[Song; 0]);
+            self.refresh_up_next();
         }
+        self.maybe_fill_radio();
         if self.imp().mpris_enabled.get() {
             self.update_mpris_properties(mpris_changes);
         }
     }
 
+    /// Radio mode: when playing and fewer than the configured number of songs remain
+    /// after the current one, ask the background client to append similar songs.
+    fn maybe_fill_radio(&self) {
+        let settings = settings_manager().child("player");
+        let imp = self.imp();
+        if !settings.boolean("radio-mode") || imp.state.get() != PlaybackState::Playing {
+            return;
+        }
+        let queue_len = imp.queue_len.get();
+        // Wait for the queue to be fully fetched.
+        if imp.queue.n_items() != queue_len {
+            return;
+        }
+        let Some(seed) = imp.current_song.borrow().clone() else {
+            return;
+        };
+        let remaining = queue_len.saturating_sub(seed.get_queue_pos() + 1);
+        if remaining >= settings.uint("radio-threshold").max(1) {
+            return;
+        }
+        // Only retry once the queue or current song has changed. This also stops us from
+        // hammering Last.fm and MPD when nothing new could be found.
+        let key = Some((seed.get_queue_id(), queue_len));
+        if imp.radio_requested.replace(key) == key {
+            return;
+        }
+        let exclude: Vec<String> = imp
+            .queue
+            .iter::<Song>()
+            .flatten()
+            .map(|song| song.get_uri().to_owned())
+            .collect();
+        self.client().queue_background(
+            BackgroundTask::FillRadio(
+                seed.get_info().clone(),
+                exclude,
+                settings.uint("radio-batch-size").max(1),
+                settings.uint("radio-no-repeat-window"),
+            ),
+            false,
+        );
+    }
+
+    /// Increment the play or skip count sticker of a song and update its last played/skipped
+    /// timestamp, using the same keys as myMPD so that other clients can use them too.
+    fn increment_play_stat(&self, song: &Song, played: bool) {
+        let (count_key, time_key) = if played {
+            (Stickers::PLAY_COUNT_KEY, Stickers::LAST_PLAYED_KEY)
+        } else {
+            (Stickers::SKIP_COUNT_KEY, Stickers::LAST_SKIPPED_KEY)
+        };
+        let client = self.client();
+        let count = client
+            .get_sticker("song", song.get_uri(), count_key)
+            .and_then(|val| val.trim().parse::<i64>().ok())
+            .unwrap_or(0);
+        client.set_sticker("song", song.get_uri(), count_key, &(count + 1).to_string());
+        client.set_sticker(
+            "song",
+            song.get_uri(),
+            time_key,
+            &OffsetDateTime::now_utc().unix_timestamp().to_string(),
+        );
+    }
+
+    /// Whether the position within the given song should be remembered.
+    fn remembers_position(&self, song: &Song) -> bool {
+        let settings = settings_manager().child("player");
+        settings.boolean("remember-position")
+            && song.get_duration() >= settings.uint("remember-position-min-length") as u64 * 60
+    }
+
+    /// Save the position within a long song to its elapsed sticker (myMPD-compatible),
+    /// or clear it if the song has been finished. Unless forced, only saves once every
+    /// RESUME_SAVE_INTERVAL seconds of playback.
+    fn update_resume_point(&self, song: &Song, position: f64, force: bool) {
+        if !self.remembers_position(song) {
+            return;
+        }
+        if !force && (position - self.imp().resume_saved_at.get()).abs() < RESUME_SAVE_INTERVAL {
+            return;
+        }
+        self.imp().resume_saved_at.set(position);
+        if position >= song.get_duration() as f64 - RESUME_FINISHED_MARGIN {
+            self.client().delete_sticker("song", song.get_uri(), Stickers::ELAPSED_KEY);
+        } else if position >= RESUME_MIN_POSITION {
+            self.client().set_sticker(
+                "song",
+                song.get_uri(),
+                Stickers::ELAPSED_KEY,
+                &(position as i64).to_string(),
+            );
+        } else {
+            return;
+        }
+        // Only refresh the continue listening shelf once we're done with a song, not
+        // every time its position is saved.
+        if force {
+            self.emit_by_name::<()>("history-changed", &[]);
+        }
+    }
+
+    /// Called when a new song starts. If it has a saved position further in, either seek
+    /// there right away or let the UI offer to do so.
+    fn maybe_resume(&self, song: &Song, position: f64) {
+        self.imp().resume_saved_at.set(position);
+        if !self.remembers_position(song) || position >= RESUME_MIN_POSITION {
+            // Already playing from somewhere in the middle, such as when reconnecting
+            return;
+        }
+        let Some(saved) = self
+            .client()
+            .get_sticker("song", song.get_uri(), Stickers::ELAPSED_KEY)
+            .and_then(|val| val.trim().parse::<i64>().ok())
+            .map(|secs| secs as f64)
+            .filter(|secs| *secs >= RESUME_MIN_POSITION)
+        else {
+            return;
+        };
+        if settings_manager().child("player").boolean("auto-resume") {
+            self.imp().resume_saved_at.set(saved);
+            self.send_seek(saved);
+        } else {
+            self.emit_by_name::<()>("resume-available", &[&song.get_queue_id(), &saved]);
+        }
+    }
+
+    /// Seek to a saved position, as long as the song it was offered for is still playing.
+    pub fn resume_at(&self, queue_id: u32, position: f64) {
+        if self
+            .imp()
+            .current_song
+            .borrow()
+            .as_ref()
+            .is_some_and(|song| song.get_queue_id() == queue_id)
+        {
+            self.send_seek(position);
+        }
+    }
+
     pub fn update_lyrics(&self, lyrics: Lyrics) {
         self.imp().current_lyric_line.set(0);
         self.imp().lyric_lines.splice(0, 0, &lyrics.to_plain_lines());
@@ -1262,6 +1599,7 @@ impl Player {
             } else {
                 queue.extend_from_slice(&new_segment);
             }
+            self.refresh_up_next();
         }
     }
 
@@ -1510,6 +1848,65 @@ impl Player {
         self.client().clear_queue();
     }
 
+    /// Named queue workspaces & automatic snapshots, workspaces first.
+    pub fn saved_queues(&self) -> Vec<SavedQueue> {
+        sqlite::get_saved_queues().unwrap_or_else(|e| {
+            println!("Unable to load saved queues: {e:?}");
+            Vec::new()
+        })
+    }
+
+    /// Name of the workspace the queue was last saved to or loaded from, if any.
+    pub fn current_workspace(&self) -> Option<String> {
+        Some(
+            settings_manager()
+                .child("state")
+                .child("queueview")
+                .string("workspace")
+                .to_string(),
+        )
+        .filter(|name| !name.is_empty())
+    }
+
+    fn set_current_workspace(&self, name: &str) {
+        let _ = settings_manager()
+            .child("state")
+            .child("queueview")
+            .set_string("workspace", name);
+    }
+
+    /// Save the current queue as a workspace, replacing any other with the same name.
+    pub fn save_queue_workspace(&self, name: &str) {
+        if let Some(snapshot) = self.client().get_queue_snapshot() {
+            sqlite::write_queue_workspace(name, &snapshot).expect("Unable to save queue workspace");
+            self.set_current_workspace(name);
+        }
+    }
+
+    pub fn delete_queue_workspace(&self, name: &str) {
+        sqlite::delete_queue_workspace(name).expect("Unable to delete queue workspace");
+        if self.current_workspace().as_deref() == Some(name) {
+            self.set_current_workspace("");
+        }
+    }
+
+    /// Switch to another workspace. The current one, if any, is updated with the
+    /// queue as it is now, so switching back continues where we left off.
+    pub fn switch_queue_workspace(&self, saved: &SavedQueue) {
+        if let Some(current) = self.current_workspace() {
+            self.save_queue_workspace(&current);
+        }
+        self.restore_queue(saved.snapshot.clone());
+        self.set_current_workspace(saved.name.as_deref().unwrap_or(""));
+    }
+
+    /// Replace the queue with a saved one, then seek to where it was.
+    pub fn restore_queue(&self, snapshot: QueueSnapshot) {
+        self.client().clear_queue_for_replace();
+        self.client()
+            .queue_background(BackgroundTask::RestoreQueue(snapshot), true);
+    }
+
     pub fn send_set_volume(&self, val: i8) {
         let old_vol = self.imp().volume.replace(val);
         if old_vol != val {
@@ -1529,13 +1926,128 @@ impl Player {
         self.client().play_at(song.get_queue_id(), true);
     }
 
+    fn queued_with_uri<'a>(&'a self, uri: &'a str) -> impl Iterator<Item = Song> + 'a {
+        self.queue()
+            .iter::<Song>()
+            .flatten()
+            .filter(move |song| song.get_uri() == uri)
+    }
+
+    /// Rate a queued song. Other occurrences of it in the queue are updated too.
+    pub fn rate_song(&self, song: &Song, score: Option<i8>) {
+        for queued in self.queued_with_uri(song.get_uri()) {
+            queued.set_rating(score);
+        }
+        self.client().set_song_rating(song.get_uri(), score);
+    }
+
+    /// Like or dislike a queued song. Other occurrences of it in the queue are updated too.
+    pub fn like_song(&self, song: &Song, status: Thumbs) {
+        for queued in self.queued_with_uri(song.get_uri()) {
+            queued.set_like(status);
+        }
+        self.client().set_song_like(song.get_uri(), status);
+    }
+
     /// Remove given song from queue.
     pub fn remove_pos(&self, pos: u32) {
         self.client().register_local_queue_changes(1);
         self.queue().remove(pos);
+        self.refresh_up_next();
         self.client().delete_at(pos, false);
     }
 
+    /// Remove the songs at the given sorted queue positions.
+    pub fn remove_positions(&self, positions: &[u32]) {
+        let ranges = queue_moves::to_ranges(positions);
+        if ranges.is_empty() {
+            return;
+        }
+        self.client().register_local_queue_changes(ranges.len() as u32);
+        for range in ranges.iter().rev() {
+            self.imp()
+                .queue
+                .splice(range.start, range.len() as u32, &[] as &[Song; 0]);
+        }
+        self.refresh_up_next();
+        self.client().delete_positions(positions.to_vec());
+    }
+
+    /// Move the songs at the given sorted queue positions, keeping their order, to just
+    /// before the song at position `to` (or to the end of the queue if `to` is its length).
+    pub fn move_positions(&self, positions: &[u32], to: u32) {
+        let moves = queue_moves::gather_moves(positions, to, self.queue().n_items());
+        self.apply_moves(moves, &gettext("Queue reordered"), false);
+    }
+
+    /// Local copy of the whole queue, or None if it hasn't been fully fetched yet.
+    fn full_queue(&self) -> Option<Vec<Song>> {
+        let queue = self.queue();
+        (queue.n_items() == self.imp().queue_len.get())
+            .then(|| queue.iter::<Song>().flatten().collect())
+    }
+
+    /// Sort the queue by album, then by disc & track.
+    pub fn sort_queue_by_album(&self) {
+        if let Some(songs) = self.full_queue() {
+            let moves = queue_moves::permutation_moves(&queue_moves::album_order(&songs));
+            self.apply_moves(moves, &gettext("Queue sorted by album"), true);
+        }
+    }
+
+    /// Shuffle the albums in the queue, keeping the songs of each one together & in order.
+    pub fn shuffle_queue_by_album(&self) {
+        if let Some(songs) = self.full_queue() {
+            let moves = queue_moves::permutation_moves(&queue_moves::album_shuffle_order(&songs));
+            self.apply_moves(moves, &gettext("Queue shuffled by album"), true);
+        }
+    }
+
+    /// Remove repeated songs from the queue, keeping the first copy of each or the
+    /// one now playing.
+    pub fn deduplicate_queue(&self) {
+        if let Some(songs) = self.full_queue() {
+            let current = self
+                .queue_id()
+                .and_then(|id| songs.iter().position(|song| song.get_queue_id() == id))
+                .map(|pos| pos as u32);
+            self.remove_positions(&queue_moves::duplicate_positions(&songs, current));
+        }
+    }
+
+    /// Apply moves to our local copy of the queue, then send them to MPD. The current
+    /// song keeps playing as moving songs doesn't interrupt playback.
+    fn apply_moves(&self, moves: Vec<QueueMove>, label: &str, announce: bool) {
+        let queue = &self.imp().queue;
+        if moves.is_empty() {
+            return;
+        }
+        self.client().register_local_queue_changes(moves.len() as u32);
+        let mut items: Vec<glib::Object> = queue.iter::<glib::Object>().flatten().collect();
+        for m in moves.iter() {
+            m.apply(&mut items);
+        }
+        // Only touch the part of the queue that actually changed.
+        let first = moves.iter().map(|m| m.start.min(m.to)).min().unwrap_or(0);
+        let last = moves.iter().map(|m| m.end.max(m.to + m.n_songs())).max().unwrap_or(0);
+        queue.splice(first, last - first, &items[first as usize..last as usize]);
+        self.client().move_ranges(moves, label, announce);
+    }
+
+    /// Give queued songs a fixed priority, below those reserved for up-next songs.
+    /// They're taken out of the up-next list if they were in it, as its own priorities
+    /// would otherwise override this.
+    pub fn set_priority(&self, ids: &[u32], prio: u8) {
+        let prio = prio.min(MAX_FIXED_PRIO);
+        self.imp()
+            .up_next_ids
+            .borrow_mut()
+            .retain(|id| !ids.contains(id));
+        self.client()
+            .set_priorities(&ids.iter().map(|id| (*id, prio)).collect::<Vec<(u32, u8)>>());
+        self.refresh_up_next();
+    }
+
     pub fn swap_dir(&self, pos: u32, direction: SwapDirection) {
         self.client().register_local_queue_changes(1);
         let target = self.imp().queue.item(pos).and_downcast::<Song>().unwrap();
@@ -1563,10 +2075,164 @@ impl Player {
         }
     }
 
+    pub fn up_next(&self) -> &gio::ListStore {
+        &self.imp().up_next
+    }
+
+    /// Queue songs to be played next, before (first = true) or after the existing
+    /// up-next songs. They're inserted right after those in the queue so sequential
+    /// playback gets to them next, and given raised priorities for random mode.
+    pub fn queue_up_next(&self, uris: Vec<String>, first: bool) {
+        if uris.is_empty() {
+            return;
+        }
+        let last_up_next = if first {
+            None
+        } else {
+            self.imp().up_next_ids.borrow().last().copied()
+        };
+        let pos = last_up_next
+            .and_then(|id| self.client().get_song_at_queue_id(id))
+            .map(|song| song.get_queue_pos() + 1)
+            .or_else(|| self.queue_pos().map(|pos| pos + 1))
+            .unwrap_or(0);
+        self.client()
+            .queue_background(BackgroundTask::QueueUpNext(uris, pos, first), true);
+    }
+
+    fn on_up_next_queued(&self, ids: &[u32], first: bool) {
+        {
+            let mut up_next = self.imp().up_next_ids.borrow_mut();
+            up_next.retain(|id| !ids.contains(id));
+            if first {
+                up_next.splice(0..0, ids.iter().copied());
+            } else {
+                up_next.extend_from_slice(ids);
+            }
+        }
+        self.apply_up_next_priorities();
+        self.refresh_up_next();
+    }
+
+    /// Send the priorities implied by the up-next order to MPD.
+    fn apply_up_next_priorities(&self) {
+        let prios: Vec<(u32, u8)> = self
+            .imp()
+            .up_next_ids
+            .borrow()
+            .iter()
+            .enumerate()
+            .map(|(idx, id)| {
+                let prio = (UP_NEXT_MAX_PRIO as usize)
+                    .saturating_sub(idx)
+                    .max(UP_NEXT_MIN_PRIO as usize);
+                (*id, prio as u8)
+            })
+            .collect();
+        self.client().set_priorities(&prios);
+    }
+
+    /// Rebuild the displayed up-next list from the queue. Songs that have since left
+    /// the queue are dropped. Newly added ones might not have been fetched yet, so
+    /// they're kept until they show up.
+    fn refresh_up_next(&self) {
+        let queue = self.queue();
+        let by_id: FxHashMap<u32, Song> = queue
+            .iter::<Song>()
+            .flatten()
+            .map(|song| (song.get_queue_id(), song))
+            .collect();
+        let up_next = &self.imp().up_next;
+        let shown: FxHashSet<u32> = up_next
+            .iter::<Song>()
+            .flatten()
+            .map(|song| song.get_queue_id())
+            .collect();
+        let mut songs: Vec<Song> = Vec::new();
+        self.imp().up_next_ids.borrow_mut().retain(|id| {
+            if let Some(song) = by_id.get(id) {
+                songs.push(song.clone());
+                true
+            } else {
+                !shown.contains(id)
+            }
+        });
+        up_next.splice(0, up_next.n_items(), &songs);
+    }
+
+    /// Rebuild the up-next list from the priorities of a freshly fetched queue, such as
+    /// after reconnecting. Only priorities in the up-next band count.
+    fn restore_up_next(&self) {
+        let mut prioritised: Vec<(u8, u32)> = self
+            .queue()
+            .iter::<Song>()
+            .flatten()
+            .filter_map(|song| {
+                let info = song.get_info();
+                (info.queue_prio >= UP_NEXT_MIN_PRIO).then(|| (info.queue_prio, song.get_queue_id()))
+            })
+            .collect();
+        // Stable, so songs of equal priority stay in queue order.
+        prioritised.sort_by(|a, b| b.0.cmp(&a.0));
+        self.imp()
+            .up_next_ids
+            .replace(prioritised.into_iter().map(|(_, id)| id).collect());
+        self.refresh_up_next();
+    }
+
+    /// Called when a song starts playing. If it was in the up-next list, take it
+    /// out and reset its priority.
+    fn finish_up_next(&self, id: u32) {
+        let found = {
+            let mut up_next = self.imp().up_next_ids.borrow_mut();
+            let len = up_next.len();
+            up_next.retain(|up_next_id| *up_next_id != id);
+            up_next.len() != len
+        };
+        if found {
+            self.client().set_priorities(&[(id, 0)]);
+            self.refresh_up_next();
+        }
+    }
+
+    /// Move an up-next song up or down the list. The songs also swap places in the queue
+    /// so that the order holds in sequential mode too.
+    pub fn swap_up_next(&self, idx: u32, direction: SwapDirection) {
+        let pair = {
+            let mut up_next = self.imp().up_next_ids.borrow_mut();
+            let idx = idx as usize;
+            let other = match direction {
+                SwapDirection::Up => idx.checked_sub(1),
+                SwapDirection::Down => Some(idx + 1).filter(|other| *other < up_next.len()),
+            };
+            other.filter(|_| idx < up_next.len()).map(|other| {
+                up_next.swap(idx, other);
+                (up_next[idx], up_next[other])
+            })
+        };
+        if let Some((id1, id2)) = pair {
+            self.client().swap(id1, id2, true);
+            self.apply_up_next_priorities();
+            self.refresh_up_next();
+        }
+    }
+
+    /// Take a song out of the up-next list, leaving it in the queue.
+    pub fn remove_from_up_next(&self, id: u32) {
+        self.finish_up_next(id);
+    }
+
     pub fn save_queue(&self, name: &str, save_mode: SaveMode) -> Result<(), Option<MpdError>> {
         return self.client().save_queue_as_playlist(name, save_mode);
     }
 
+    pub fn export_queue_file(&self, path: PathBuf, options: ExportOptions) {
+        self.client().queue_background(
+            BackgroundTask::ExportPlaylistFile(None, path, options),
+            true,
+        );
+    }
+
     /// Periodically poll for player progress to update seekbar.
     /// Won't start a new loop if there is already one or when polling is blocked by a seekbar.
     pub fn maybe_start_polling(&self) {
@@ -1588,6 +2254,279 @@ impl Player {
         }
     }
 
+    /// Start a sleep timer, replacing any running one. Playback is paused once it runs
+    /// out, after fading the volume out over the configured length.
+    pub fn start_sleep_timer(&self, timer: SleepTimer) {
+        let fade = settings_manager().child("player").uint("sleep-fade-s") as f64;
+        self.set_sleep_timer(timer, fade);
+    }
+
+    /// Stop once the current song has finished, without fading out.
+    pub fn stop_after_current(&self) {
+        self.set_sleep_timer(SleepTimer::Songs(1), 0.0);
+    }
+
+    fn set_sleep_timer(&self, timer: SleepTimer, fade: f64) {
+        self.cancel_sleep_timer();
+        let imp = self.imp();
+        imp.sleep_timer.set(Some(timer));
+        imp.sleep_fade.set(fade);
+        self.check_last_sleep_song();
+        let this = self.clone();
+        let handle = glib::MainContext::default().spawn_local(async move {
+            loop {
+                this.sleep_timer_tick();
+                glib::timeout_future_seconds(1).await;
+            }
+        });
+        imp.sleep_handle.replace(Some(handle));
+        self.notify("sleep-timer-active");
+        self.notify("sleep-timer-desc");
+    }
+
+    /// Cancel the sleep timer if there is one, putting the volume & playback flow back.
+    pub fn cancel_sleep_timer(&self) {
+        self.end_sleep_timer(false);
+    }
+
+    fn end_sleep_timer(&self, pause: bool) {
+        let imp = self.imp();
+        if imp.sleep_timer.take().is_none() {
+            return;
+        }
+        if let Some(handle) = imp.sleep_handle.take() {
+            handle.abort();
+        }
+        if pause && imp.state.get() == PlaybackState::Playing {
+            self.client().pause(true);
+        }
+        if let Some(flow) = imp.sleep_oneshot.take() {
+            self.client().set_playback_flow(flow);
+        }
+        if let Some(volume) = imp.sleep_volume.take() {
+            self.client().volume(volume);
+        }
+        self.notify("sleep-timer-active");
+        self.notify("sleep-timer-desc");
+    }
+
+    /// Human-readable state of the sleep timer. Empty if there is none.
+    pub fn sleep_timer_desc(&self) -> String {
+        // TODO: translatable
+        match self.imp().sleep_timer.get() {
+            None => String::new(),
+            Some(SleepTimer::Until(deadline)) => format!(
+                "Pausing in {}",
+                format_secs_as_duration((deadline - glib::monotonic_time()).max(0) as f64 / 1e6)
+            ),
+            Some(SleepTimer::Songs(1)) => "Stopping after this song".to_owned(),
+            Some(SleepTimer::Songs(n)) => format!("Stopping after {n} songs"),
+            Some(SleepTimer::EndOfQueue) => "Stopping at the end of the queue".to_owned(),
+        }
+    }
+
+    /// Once on the last song of a song-based sleep timer, have MPD stop by itself at
+    /// its end where supported. Otherwise we pause as soon as the next one starts.
+    fn check_last_sleep_song(&self) {
+        let imp = self.imp();
+        let is_last = match imp.sleep_timer.get() {
+            Some(SleepTimer::Songs(n)) => n <= 1,
+            // We can't tell which song is last in random mode. MPD stopping at the end
+            // of the queue will still end the timer.
+            Some(SleepTimer::EndOfQueue) => {
+                !imp.random.get()
+                    && self
+                        .queue_pos()
+                        .is_some_and(|pos| pos + 1 >= imp.queue_len.get())
+            }
+            _ => false,
+        };
+        if !is_last {
+            return;
+        }
+        imp.sleep_timer.set(Some(SleepTimer::Songs(1)));
+        if imp.sleep_oneshot.get().is_none() {
+            let flow = imp.flow.get();
+            if self.client().single_oneshot() {
+                imp.sleep_oneshot.set(Some(flow));
+            }
+        }
+    }
+
+    fn on_sleep_song_changed(&self) {
+        let imp = self.imp();
+        match imp.sleep_timer.get() {
+            // The last song is over but MPD moved on anyway, either because single
+            // oneshot is not supported or because it was skipped.
+            Some(SleepTimer::Songs(n)) if n <= 1 => {
+                self.end_sleep_timer(true);
+                return;
+            }
+            Some(SleepTimer::Songs(n)) => imp.sleep_timer.set(Some(SleepTimer::Songs(n - 1))),
+            Some(SleepTimer::EndOfQueue) => {}
+            _ => return,
+        }
+        self.check_last_sleep_song();
+        self.notify("sleep-timer-desc");
+    }
+
+    /// Seconds until the sleep timer runs out. Only known for time-based ones, or once
+    /// the last song of the others has started (and has a known duration).
+    fn sleep_seconds_left(&self, timer: SleepTimer) -> Option<f64> {
+        match timer {
+            SleepTimer::Until(deadline) => Some((deadline - glib::monotonic_time()) as f64 / 1e6),
+            SleepTimer::Songs(1) if self.duration() > 0 => {
+                Some((self.duration() as f64 - self.position()).max(0.0))
+            }
+            _ => None,
+        }
+    }
+
+    fn sleep_timer_tick(&self) {
+        let imp = self.imp();
+        let Some(timer) = imp.sleep_timer.get() else {
+            return;
+        };
+        if let Some(secs) = self.sleep_seconds_left(timer) {
+            // Song-based timers instead end along with their last song.
+            if secs <= 0.0 && matches!(timer, SleepTimer::Until(_)) {
+                self.end_sleep_timer(true);
+                return;
+            }
+            let fade = imp.sleep_fade.get();
+            if imp.state.get() == PlaybackState::Playing && secs < fade {
+                let volume = imp.sleep_volume.get().unwrap_or_else(|| imp.volume.get());
+                // Negative volumes mean MPD has no mixer for us to fade with.
+                if volume > 0 {
+                    imp.sleep_volume.set(Some(volume));
+                    self.client()
+                        .volume((volume as f64 * secs / fade).round() as i8);
+                }
+            }
+        }
+        self.notify("sleep-timer-desc");
+    }
+
+    /// Periodically check for alarms that are due. Runs for as long as Euphonica does,
+    /// including in the background.
+    fn start_alarm_checks(&self) {
+        let this = self.clone();
+        let handle = glib::MainContext::default().spawn_local(async move {
+            loop {
+                this.check_alarms();
+                glib::timeout_future_seconds(ALARM_CHECK_INTERVAL).await;
+            }
+        });
+        self.imp().alarm_handle.replace(Some(handle));
+    }
+
+    /// Set off alarms that have just become due, and report those that went by
+    /// unnoticed since they were last checked.
+    fn check_alarms(&self) {
+        let alarms = match sqlite::get_alarms() {
+            Ok(alarms) => alarms,
+            Err(e) => {
+                println!("Unable to load alarms: {e:?}");
+                return;
+            }
+        };
+        let now = OffsetDateTime::now_utc();
+        let connected = self.client().get_client_state().get_connection_state()
+            == ConnectionState::Connected;
+        let mut missed: Vec<Alarm> = Vec::new();
+        for mut saved in alarms.into_iter().filter(|saved| saved.alarm.enabled) {
+            let Some(due) = saved
+                .alarm
+                .last_due(saved.checked.unix_timestamp(), now.unix_timestamp())
+            else {
+                continue;
+            };
+            if now.unix_timestamp() - due <= ALARM_GRACE_SECS {
+                if !connected {
+                    // Might just be starting up. Try again on the next check.
+                    continue;
+                }
+                self.set_off_alarm(&saved.alarm);
+            } else {
+                missed.push(saved.alarm.clone());
+            }
+            saved.checked = now;
+            if saved.alarm.is_one_off() {
+                saved.alarm.enabled = false;
+            }
+            if let Err(e) = sqlite::update_alarm(&saved) {
+                println!("Unable to update alarm {}: {e:?}", saved.id);
+            }
+        }
+        if !missed.is_empty() {
+            self.report_missed_alarms(&missed);
+        }
+    }
+
+    /// Replace the queue with the alarm's playlist or stream & start playing it,
+    /// ramping the volume up as it goes.
+    fn set_off_alarm(&self, alarm: &Alarm) {
+        println!("Alarm going off: {} {}", alarm.time_desc(), alarm.source_desc());
+        self.cancel_sleep_timer();
+        let client = self.client();
+        client.clear_queue_for_replace();
+        match &alarm.source {
+            AlarmSource::Playlist(name) => client.queue_background(
+                BackgroundTask::QueuePlaylist(name.clone(), Some(0)),
+                true,
+            ),
+            AlarmSource::Stream(url) => client.queue_background(
+                BackgroundTask::QueueUris(vec![url.clone()], false, Some(0), None),
+                true,
+            ),
+        }
+
+        if let Some(handle) = self.imp().alarm_ramp.take() {
+            handle.abort();
+        }
+        let (start, end, secs) = (
+            alarm.start_volume as f64,
+            alarm.end_volume as f64,
+            alarm.ramp_secs,
+        );
+        let this = self.clone();
+        let handle = glib::MainContext::default().spawn_local(async move {
+            // Some mixers can only be set while there is something playing, which might
+            // take a moment, so keep setting it from the very start.
+            for elapsed in 0..=secs {
+                let progress = if secs > 0 {
+                    elapsed as f64 / secs as f64
+                } else {
+                    1.0
+                };
+                this.client()
+                    .volume((start + (end - start) * progress).round() as i8);
+                glib::timeout_future_seconds(1).await;
+            }
+        });
+        self.imp().alarm_ramp.replace(Some(handle));
+    }
+
+    fn report_missed_alarms(&self, missed: &[Alarm]) {
+        let Some(app) = self.imp().app.get() else {
+            return;
+        };
+        // TODO: translatable
+        let notification = gio::Notification::new(if missed.len() > 1 {
+            "Missed alarms"
+        } else {
+            "Missed an alarm"
+        });
+        notification.set_body(Some(
+            &missed
+                .iter()
+                .map(|alarm| format!("{} · {}", alarm.time_desc(), alarm.source_desc()))
+                .collect::<Vec<String>>()
+                .join("\n"),
+        ));
+        app.send_notification(Some("missed-alarms"), &notification);
+    }
+
     /// Stop poller loop. Seekbar should call this when being interacted with.
     pub fn stop_polling(&self) {
         if let Some(handle) = self.imp().poller_handle.take() {
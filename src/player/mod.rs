@@ -1,23 +1,29 @@
+mod alarms;
 mod bar;
 mod controller;
 mod fft_backends;
 mod knob;
 mod output;
 mod pane;
+mod partitions;
 mod playback_controls;
 mod queue_row;
 mod queue_view;
 mod ratio_center_box;
 mod seekbar;
+mod workspaces;
 
 use knob::VolumeKnob;
 use output::MpdOutput;
 use queue_row::QueueRow;
 
+pub use alarms::AlarmsDialog;
 pub use fft_backends::backend::FftStatus;
 pub use bar::PlayerBar;
 pub use controller::PlaybackState;
-pub use controller::{PlaybackFlow, Player};
+pub use controller::{PlaybackFlow, Player, SleepTimer};
 pub use pane::PlayerPane;
+pub use partitions::PartitionsDialog;
 pub use playback_controls::PlaybackControls;
 pub use queue_view::QueueView;
+pub use workspaces::WorkspacesDialog;
@@ -15,7 +15,7 @@ use crate::{
     utils::{self, settings_manager},
 };
 
-use super::{MpdOutput, PlaybackControls, PlaybackState, Player, VolumeKnob};
+use super::{MpdOutput, PlaybackControls, PlaybackState, Player, SleepTimer, VolumeKnob};
 
 mod imp {
     use crate::player::seekbar::Seekbar;
@@ -63,6 +63,26 @@ mod imp {
         #[template_child]
         pub mixramp_delay: TemplateChild<gtk::SpinButton>,
         #[template_child]
+        pub sleep_btn: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub sleep_status: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub sleep_cancel: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub sleep_minutes: TemplateChild<gtk::SpinButton>,
+        #[template_child]
+        pub sleep_start_minutes: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub sleep_songs: TemplateChild<gtk::SpinButton>,
+        #[template_child]
+        pub sleep_start_songs: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub sleep_end_of_queue: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub stop_after_current: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub sleep_fade: TemplateChild<gtk::SpinButton>,
+        #[template_child]
         pub lyrics_btn: TemplateChild<gtk::MenuButton>,
         #[template_child]
         pub show_lyrics: TemplateChild<gtk::Switch>,
@@ -142,6 +162,11 @@ mod imp {
                 .bind("use-synced-lyrics", &self.use_synced_lyrics.get(), "active")
                 .build();
 
+            settings_manager()
+                .child("player")
+                .bind("sleep-fade-s", &self.sleep_fade.get(), "value")
+                .build();
+
             self.show_lyrics
                 .bind_property(
                     "active",
@@ -247,6 +272,7 @@ impl PlayerPane {
     pub fn setup(&self, player: &Player) {
         self.setup_volume_knob(player);
         self.bind_state(player);
+        self.setup_sleep_timer(player);
         self.imp().playback_controls.setup(player);
         self.imp().seekbar.setup(player);
     }
@@ -362,6 +388,90 @@ impl PlayerPane {
             .build();
     }
 
+    fn setup_sleep_timer(&self, player: &Player) {
+        let imp = self.imp();
+        let sleep_btn = imp.sleep_btn.get();
+        player.connect_notify_local(
+            Some("sleep-timer-active"),
+            clone!(
+                #[weak]
+                sleep_btn,
+                move |player, _| {
+                    if player.property::<bool>("sleep-timer-active") {
+                        sleep_btn.add_css_class("accent");
+                    } else {
+                        sleep_btn.remove_css_class("accent");
+                    }
+                }
+            ),
+        );
+        player
+            .bind_property("sleep-timer-desc", &sleep_btn, "tooltip-text")
+            .transform_to(|_, desc: String| {
+                // TODO: translatable
+                Some(if desc.is_empty() { "Sleep Timer".to_owned() } else { desc })
+            })
+            .sync_create()
+            .build();
+
+        let sleep_status = imp.sleep_status.get();
+        player
+            .bind_property("sleep-timer-desc", &sleep_status, "label")
+            .sync_create()
+            .build();
+        player
+            .bind_property("sleep-timer-active", &sleep_status, "visible")
+            .sync_create()
+            .build();
+        player
+            .bind_property("sleep-timer-active", &imp.sleep_cancel.get(), "visible")
+            .sync_create()
+            .build();
+
+        imp.sleep_cancel.connect_clicked(clone!(
+            #[weak]
+            player,
+            move |_| {
+                player.cancel_sleep_timer();
+            }
+        ));
+        let minutes = imp.sleep_minutes.get();
+        imp.sleep_start_minutes.connect_clicked(clone!(
+            #[weak]
+            player,
+            #[weak]
+            minutes,
+            move |_| {
+                let deadline = glib::monotonic_time() + minutes.value() as i64 * 60_000_000;
+                player.start_sleep_timer(SleepTimer::Until(deadline));
+            }
+        ));
+        let songs = imp.sleep_songs.get();
+        imp.sleep_start_songs.connect_clicked(clone!(
+            #[weak]
+            player,
+            #[weak]
+            songs,
+            move |_| {
+                player.start_sleep_timer(SleepTimer::Songs(songs.value_as_int().max(1) as u32));
+            }
+        ));
+        imp.sleep_end_of_queue.connect_clicked(clone!(
+            #[weak]
+            player,
+            move |_| {
+                player.start_sleep_timer(SleepTimer::EndOfQueue);
+            }
+        ));
+        imp.stop_after_current.connect_clicked(clone!(
+            #[weak]
+            player,
+            move |_| {
+                player.stop_after_current();
+            }
+        ));
+    }
+
     fn bind_state(&self, player: &Player) {
         let imp = self.imp();
         let info_box = imp.info_box.get();
@@ -0,0 +1,244 @@
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    rc::Rc,
+};
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gtk::{glib, CompositeTemplate};
+
+use glib::clone;
+use mpd::error::Error as MpdError;
+
+use crate::client::{wrapper::DEFAULT_PARTITION, MpdWrapper};
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/io/github/htkhiem/Euphonica/gtk/player/partitions.ui")]
+    pub struct PartitionsDialog {
+        #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
+        pub page: TemplateChild<adw::PreferencesPage>,
+        #[template_child]
+        pub current_partition: TemplateChild<adw::ComboRow>,
+        #[template_child]
+        pub new_partition: TemplateChild<adw::EntryRow>,
+        // One group per partition, listing its outputs. Rebuilt on every refresh.
+        pub partition_groups: RefCell<Vec<adw::PreferencesGroup>>,
+        pub partition_names: RefCell<Vec<String>>,
+        // Set while repopulating the picker to avoid switching partitions
+        pub updating: Cell<bool>,
+        pub client: OnceCell<Rc<MpdWrapper>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for PartitionsDialog {
+        const NAME: &'static str = "EuphonicaPartitionsDialog";
+        type Type = super::PartitionsDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for PartitionsDialog {}
+    impl WidgetImpl for PartitionsDialog {}
+    impl AdwDialogImpl for PartitionsDialog {}
+}
+
+glib::wrapper! {
+    pub struct PartitionsDialog(ObjectSubclass<imp::PartitionsDialog>)
+        @extends adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::ShortcutManager;
+}
+
+impl Default for PartitionsDialog {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+impl PartitionsDialog {
+    pub fn new(client: Rc<MpdWrapper>) -> Self {
+        let res = Self::default();
+        let _ = res.imp().client.set(client);
+        res.setup();
+        res.refresh();
+        res
+    }
+
+    fn client(&self) -> &Rc<MpdWrapper> {
+        self.imp().client.get().unwrap()
+    }
+
+    fn setup(&self) {
+        let imp = self.imp();
+        imp.current_partition.connect_selected_notify(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |row| {
+                if this.imp().updating.get() {
+                    return;
+                }
+                let maybe_name = this
+                    .imp()
+                    .partition_names
+                    .borrow()
+                    .get(row.selected() as usize)
+                    .cloned();
+                if let Some(name) = maybe_name {
+                    if name != this.client().get_partition() {
+                        let res = this.client().switch_partition(&name);
+                        this.handle_result(res);
+                        this.refresh();
+                    }
+                }
+            }
+        ));
+
+        imp.new_partition.connect_apply(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |entry| {
+                let name = entry.text().trim().to_owned();
+                if !name.is_empty() {
+                    entry.set_text("");
+                    let res = this.client().new_partition(&name);
+                    this.handle_result(res);
+                    this.refresh();
+                }
+            }
+        ));
+    }
+
+    fn handle_result(&self, res: Result<(), Option<MpdError>>) {
+        // TODO: translatable
+        let msg = match res {
+            Ok(()) => {
+                return;
+            }
+            Err(Some(MpdError::Server(se))) => se.detail,
+            Err(Some(e)) => format!("{:?}", e),
+            Err(None) => String::from("Not connected"),
+        };
+        self.imp()
+            .toast_overlay
+            .add_toast(adw::Toast::builder().title(&msg).timeout(3).build());
+    }
+
+    /// Repopulate the partition picker & per-partition output lists.
+    pub fn refresh(&self) {
+        let imp = self.imp();
+        for group in imp.partition_groups.take().into_iter() {
+            imp.page.remove(&group);
+        }
+        let picker = imp.current_partition.get();
+
+        let Some(partitions) = self.client().get_partition_outputs() else {
+            // Daemon too old (<0.22) or not connected
+            picker.set_sensitive(false);
+            picker.set_subtitle("Partitions are not available");
+            imp.new_partition.set_sensitive(false);
+            return;
+        };
+        picker.set_sensitive(true);
+        picker.set_subtitle("");
+        imp.new_partition.set_sensitive(true);
+
+        let current = self.client().get_partition();
+        let names: Vec<String> = partitions.iter().map(|(name, _)| name.clone()).collect();
+        imp.updating.set(true);
+        picker.set_model(Some(&gtk::StringList::new(
+            &names.iter().map(String::as_str).collect::<Vec<&str>>(),
+        )));
+        if let Some(idx) = names.iter().position(|name| name == &current) {
+            picker.set_selected(idx as u32);
+        }
+        imp.updating.set(false);
+        imp.partition_names.replace(names);
+
+        let mut groups: Vec<adw::PreferencesGroup> = Vec::with_capacity(partitions.len());
+        for (name, outputs) in partitions.into_iter() {
+            let is_current = name == current;
+            let group = adw::PreferencesGroup::builder()
+                .title(&name)
+                .description(if is_current {
+                    "Current partition"
+                } else {
+                    "Move outputs here to play from the current partition through them"
+                })
+                .build();
+            // The default partition cannot be deleted, and neither can the one
+            // we're in (it still has our clients).
+            if !is_current && name != DEFAULT_PARTITION {
+                let delete_btn = gtk::Button::builder()
+                    .icon_name("user-trash-symbolic")
+                    .tooltip_text("Delete partition")
+                    .valign(gtk::Align::Center)
+                    .build();
+                delete_btn.add_css_class("flat");
+                delete_btn.connect_clicked(clone!(
+                    #[weak(rename_to = this)]
+                    self,
+                    #[strong]
+                    name,
+                    move |_| {
+                        let res = this.client().delete_partition(&name);
+                        this.handle_result(res);
+                        this.refresh();
+                    }
+                ));
+                group.set_header_suffix(Some(&delete_btn));
+            }
+            if outputs.is_empty() {
+                group.add(
+                    &adw::ActionRow::builder()
+                        .title("No outputs")
+                        .sensitive(false)
+                        .build(),
+                );
+            }
+            for output in outputs.iter() {
+                let row = adw::ActionRow::builder()
+                    .title(&output.name)
+                    .subtitle(if output.enabled {
+                        format!("{} (enabled)", &output.plugin)
+                    } else {
+                        output.plugin.clone()
+                    })
+                    .build();
+                if !is_current {
+                    let move_btn = gtk::Button::builder()
+                        .label("Move here")
+                        .tooltip_text("Move this output to the current partition")
+                        .valign(gtk::Align::Center)
+                        .build();
+                    move_btn.add_css_class("flat");
+                    let output_name = output.name.clone();
+                    move_btn.connect_clicked(clone!(
+                        #[weak(rename_to = this)]
+                        self,
+                        move |_| {
+                            let res = this.client().move_output(&output_name);
+                            this.handle_result(res);
+                            this.refresh();
+                        }
+                    ));
+                    row.add_suffix(&move_btn);
+                }
+                group.add(&row);
+            }
+            imp.page.add(&group);
+            groups.push(group);
+        }
+        imp.partition_groups.replace(groups);
+    }
+}
@@ -6,7 +6,7 @@ use std::{cell::RefCell, rc::Rc};
 
 use crate::{
     cache::{placeholders::ALBUMART_THUMBNAIL_PLACEHOLDER, Cache, CacheState},
-    common::{CoverSource, Marquee, Song, SongInfo},
+    common::{CoverSource, LikeButton, Marquee, Rating, Song, SongInfo},
     utils::strip_filename_linux,
 };
 
@@ -43,6 +43,10 @@ mod imp {
         pub quality_grade: TemplateChild<gtk::Image>,
         #[template_child]
         pub remove: TemplateChild<Button>,
+        #[template_child]
+        pub rating: TemplateChild<Rating>,
+        #[template_child]
+        pub like: TemplateChild<LikeButton>,
         pub signal_ids: RefCell<Option<(SignalHandlerId, SignalHandlerId, SignalHandlerId)>>,
         pub item: WeakRef<gtk::ListItem>,
         pub player: OnceCell<Player>,
@@ -194,8 +198,8 @@ impl QueueRow {
             #[weak]
             player,
             move |_| {
-                if let Some(item) = this.imp().item.upgrade() {
-                    player.remove_pos(item.position());
+                if let Some(pos) = this.queue_pos() {
+                    player.remove_pos(pos);
                 }
             }
         ));
@@ -206,8 +210,8 @@ impl QueueRow {
             #[weak]
             player,
             move |_| {
-                if let Some(item) = this.imp().item.upgrade() {
-                    player.swap_dir(item.position(), SwapDirection::Up);
+                if let Some(pos) = this.queue_pos() {
+                    player.swap_dir(pos, SwapDirection::Up);
                 }
             }
         ));
@@ -218,16 +222,57 @@ impl QueueRow {
             #[weak]
             player,
             move |_| {
-                if let Some(item) = this.imp().item.upgrade() {
-                    player.swap_dir(item.position(), SwapDirection::Down);
+                if let Some(pos) = this.queue_pos() {
+                    player.swap_dir(pos, SwapDirection::Down);
                 }
             }
         ));
 
+        self.imp().rating.connect_closure(
+            "changed",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                player,
+                move |rating: Rating| {
+                    if let Some(song) = this.song() {
+                        let val = rating.value();
+                        player.rate_song(&song, if val > 0 { Some(val) } else { None });
+                    }
+                }
+            ),
+        );
+
+        self.imp().like.connect_closure(
+            "changed",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                player,
+                move |like: LikeButton| {
+                    if let Some(song) = this.song() {
+                        player.like_song(&song, like.thumbs());
+                    }
+                }
+            ),
+        );
+
         item.property_expression("item")
             .chain_property::<Song>("name")
             .bind(self, "name", gtk::Widget::NONE);
 
+        item.property_expression("item")
+            .chain_property::<Song>("rating")
+            .bind(&self.imp().rating.get(), "value", gtk::Widget::NONE);
+
+        item.property_expression("item")
+            .chain_property::<Song>("like")
+            .bind(&self.imp().like.get(), "value", gtk::Widget::NONE);
+
         item.property_expression("item")
             .chain_property::<Song>("album")
             .bind(self, "album", gtk::Widget::NONE);
@@ -364,7 +409,14 @@ impl QueueRow {
         self.imp().thumbnail_source.set(src);
     }
 
-    fn song(&self) -> Option<Song> {
+    /// Position of this row's song in the player's queue. Not necessarily the
+    /// position of the row itself, as the queue view may be filtered.
+    fn queue_pos(&self) -> Option<u32> {
+        let song = self.song()?;
+        self.imp().player.get()?.queue().find(&song)
+    }
+
+    pub fn song(&self) -> Option<Song> {
         self.imp().item.upgrade().map(|item| item
             .item()
             .and_downcast::<Song>()
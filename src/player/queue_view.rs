@@ -1,12 +1,13 @@
-use std::rc::Rc;
+use std::{cmp::Ordering, rc::Rc};
 
 use adw::prelude::*;
 use adw::subclass::prelude::*;
 use gio::glib::closure_local;
 use glib::clone;
 use gtk::{
-    gio, glib, CompositeTemplate, ListItem, SignalListItemFactory, SingleSelection,
+    gdk, gio, glib, CompositeTemplate, ListItem, MultiSelection, SignalListItemFactory,
 };
+use rustc_hash::FxHashSet;
 use mpd::{
     error::{Error as MpdError, ErrorCode as MpdErrorCode, ServerError},
     SaveMode,
@@ -14,9 +15,16 @@ use mpd::{
 
 use super::PlayerPane;
 
-use crate::{cache::Cache, common::Song, window::EuphonicaWindow, utils::LazyInit};
+use crate::{
+    cache::Cache,
+    common::Song,
+    library::{AddToPlaylistButton, Library},
+    playlist_file,
+    window::EuphonicaWindow,
+    utils::{g_search_substr, settings_manager, LazyInit},
+};
 
-use super::{Player, QueueRow};
+use super::{controller::{SwapDirection, MAX_FIXED_PRIO}, Player, QueueRow};
 
 mod imp {
     use std::{cell::{Cell, OnceCell}, sync::OnceLock};
@@ -47,8 +55,14 @@ mod imp {
         #[template_child]
         pub consume: TemplateChild<gtk::ToggleButton>,
         #[template_child]
+        pub radio: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
         pub clear_queue: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub transform: TemplateChild<gtk::MenuButton>,
 
+        #[template_child]
+        pub export_file: TemplateChild<gtk::Button>,
         #[template_child]
         pub save: TemplateChild<gtk::MenuButton>,
         #[template_child]
@@ -56,6 +70,55 @@ mod imp {
         #[template_child]
         pub save_confirm: TemplateChild<gtk::Button>,
 
+        // Search & filter widgets
+        #[template_child]
+        pub search_btn: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub search_bar: TemplateChild<gtk::SearchBar>,
+        #[template_child]
+        pub search_entry: TemplateChild<gtk::SearchEntry>,
+        #[template_child]
+        pub prev_match: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub next_match: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub jump_to_current: TemplateChild<gtk::Button>,
+
+        // Multi-selection widgets
+        #[template_child]
+        pub select_btn: TemplateChild<gtk::ToggleButton>,
+        #[template_child]
+        pub remove_selected: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub selection_label: TemplateChild<gtk::Label>,
+        #[template_child]
+        pub move_top: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub move_next: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub move_bottom: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub priority_btn: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub priority_value: TemplateChild<gtk::SpinButton>,
+        #[template_child]
+        pub priority_confirm: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub add_to_playlist: TemplateChild<AddToPlaylistButton>,
+
+        // Up-next section
+        #[template_child]
+        pub up_next_revealer: TemplateChild<gtk::Revealer>,
+        #[template_child]
+        pub up_next: TemplateChild<gtk::ListBox>,
+
+        // Search & filter models. Rows are looked up in the player's queue by
+        // their song, so acting on a filtered row still hits the right queue position.
+        pub search_filter: gtk::CustomFilter,
+        pub filtered: OnceCell<gtk::FilterListModel>,
+        // See FolderView.
+        pub last_search_len: Cell<usize>,
+
         pub window: OnceCell<EuphonicaWindow>,
 
         #[property(get, set)]
@@ -177,6 +240,53 @@ fn format_song_count(count: u32) -> Option<String> {
     }
 }
 
+/// Row of the up-next section, with buttons to reorder it or take the song out of it.
+fn up_next_row(player: &Player, song: &Song) -> adw::ActionRow {
+    let row = adw::ActionRow::builder()
+        .title(glib::markup_escape_text(song.get_name()))
+        .subtitle(glib::markup_escape_text(song.get_artist_tag().unwrap_or_default()))
+        .activatable(true)
+        .build();
+    // TODO: translatable
+    for (icon, tooltip, direction) in [
+        ("up-symbolic", "Play earlier", SwapDirection::Up),
+        ("down-symbolic", "Play later", SwapDirection::Down),
+    ] {
+        let btn = gtk::Button::builder()
+            .icon_name(icon)
+            .tooltip_text(tooltip)
+            .valign(gtk::Align::Center)
+            .build();
+        btn.add_css_class("flat");
+        btn.connect_clicked(clone!(
+            #[weak]
+            player,
+            #[weak]
+            row,
+            move |_| {
+                player.swap_up_next(row.index() as u32, direction);
+            }
+        ));
+        row.add_suffix(&btn);
+    }
+    let remove_btn = gtk::Button::builder()
+        .icon_name("list-remove-symbolic")
+        .tooltip_text("Remove from up next. The song stays in the queue.")
+        .valign(gtk::Align::Center)
+        .build();
+    remove_btn.add_css_class("flat");
+    let id = song.get_queue_id();
+    remove_btn.connect_clicked(clone!(
+        #[weak]
+        player,
+        move |_| {
+            player.remove_from_up_next(id);
+        }
+    ));
+    row.add_suffix(&remove_btn);
+    row
+}
+
 impl QueueView {
     pub fn new() -> Self {
         Self::default()
@@ -187,20 +297,30 @@ impl QueueView {
         // Set selection mode
         // TODO: Allow click to jump to song
         let queue_model = player.queue().clone();
-        let stack = self.imp().content_stack.get();
-        queue_model
-            .bind_property("n-items", &stack, "visible-child-name")
-            .transform_to(|_, val: u32| {
-                if val == 0 {
-                    Some("empty")
-                } else {
-                    Some("queue")
-                }
-            })
-            .sync_create()
-            .build();
-        let sel_model = SingleSelection::new(Some(queue_model));
+        let filtered = gtk::FilterListModel::new(
+            Some(queue_model.clone()),
+            Some(self.imp().search_filter.clone()),
+        );
+        // Keep filtering synchronous so that match positions are always valid.
+        filtered.set_incremental(false);
+        queue_model.connect_items_changed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_, _, _, _| {
+                this.update_content_page();
+            }
+        ));
+        filtered.connect_items_changed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_, _, _, _| {
+                this.update_content_page();
+            }
+        ));
+        let sel_model = MultiSelection::new(Some(filtered.clone()));
         self.imp().queue.set_model(Some(&sel_model));
+        let _ = self.imp().filtered.set(filtered);
+        self.update_content_page();
 
         // Set up factory
         let factory = SignalListItemFactory::new();
@@ -219,6 +339,19 @@ impl QueueView {
                 item.set_child(Some(&queue_row));
             }
         ));
+        factory.connect_setup(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_, list_item| {
+                let queue_row = list_item
+                    .downcast_ref::<ListItem>()
+                    .expect("Needs to be ListItem")
+                    .child()
+                    .and_downcast::<QueueRow>()
+                    .expect("The child has to be a `QueueRow`.");
+                this.setup_row_dnd(&queue_row);
+            }
+        ));
         // Tell factory how to bind `QueueRow` to one of our Song GObjects
         factory.connect_bind(clone!(
             #[weak(rename_to = this)]
@@ -281,6 +414,483 @@ impl QueueView {
         });
     }
 
+    fn update_content_page(&self) {
+        let Some(filtered) = self.imp().filtered.get() else {
+            return;
+        };
+        let page = if filtered.model().map_or(0, |model| model.n_items()) == 0 {
+            "empty"
+        } else if filtered.n_items() == 0 {
+            "no-matches"
+        } else {
+            "queue"
+        };
+        self.imp().content_stack.set_visible_child_name(page);
+    }
+
+    fn setup_search(&self, player: &Player) {
+        let library_settings = settings_manager().child("library");
+        self.imp().search_filter.set_filter_func(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            library_settings,
+            #[upgrade_or]
+            true,
+            move |obj| {
+                let song = obj
+                    .downcast_ref::<Song>()
+                    .expect("Search obj has to be a common::Song.");
+
+                let search_term = this.imp().search_entry.text();
+                if search_term.is_empty() {
+                    return true;
+                }
+
+                let case_sensitive = library_settings.boolean("search-case-sensitive");
+                [Some(song.get_name()), song.get_artist_tag(), song.get_album_title()]
+                    .into_iter()
+                    .any(|field| g_search_substr(field, &search_term, case_sensitive))
+            }
+        ));
+
+        let search_entry = self.imp().search_entry.get();
+        search_entry.connect_search_changed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |entry| {
+                let new_len = entry.text().len();
+                let old_len = this.imp().last_search_len.replace(new_len);
+                this.imp().search_filter.changed(match new_len.cmp(&old_len) {
+                    Ordering::Greater => gtk::FilterChange::MoreStrict,
+                    Ordering::Less => gtk::FilterChange::LessStrict,
+                    Ordering::Equal => gtk::FilterChange::Different,
+                });
+            }
+        ));
+
+        // Enter plays the selected match (or the first one), while the arrow keys
+        // & the usual Ctrl+G shortcuts move between matches.
+        search_entry.connect_activate(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                if let Some(song) = this.selected_match().or_else(|| this.match_at(0)) {
+                    player.on_song_clicked(song);
+                }
+            }
+        ));
+        search_entry.connect_next_match(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.select_match(true);
+            }
+        ));
+        search_entry.connect_previous_match(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.select_match(false);
+            }
+        ));
+        let keys = gtk::EventControllerKey::new();
+        keys.connect_key_pressed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[upgrade_or]
+            glib::Propagation::Proceed,
+            move |_, key, _, _| match key {
+                gdk::Key::Down => {
+                    this.select_match(true);
+                    glib::Propagation::Stop
+                }
+                gdk::Key::Up => {
+                    this.select_match(false);
+                    glib::Propagation::Stop
+                }
+                _ => glib::Propagation::Proceed,
+            }
+        ));
+        search_entry.add_controller(keys);
+        self.imp().next_match.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.select_match(true);
+            }
+        ));
+        self.imp().prev_match.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.select_match(false);
+            }
+        ));
+
+        let search_bar = self.imp().search_bar.get();
+        search_bar.connect_entry(&search_entry);
+        search_bar.set_key_capture_widget(Some(&self.imp().queue.get()));
+        self.imp()
+            .search_btn
+            .bind_property("active", &search_bar, "search-mode-enabled")
+            .bidirectional()
+            .sync_create()
+            .build();
+        // Closing the search bar shows the whole queue again.
+        search_bar.connect_search_mode_enabled_notify(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |bar| {
+                if !bar.is_search_mode() {
+                    this.clear_search();
+                }
+            }
+        ));
+
+        self.imp().jump_to_current.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.jump_to_current();
+            }
+        ));
+    }
+
+    fn selection(&self) -> Option<MultiSelection> {
+        self.imp().queue.model().and_downcast::<MultiSelection>()
+    }
+
+    fn match_at(&self, idx: u32) -> Option<Song> {
+        self.imp().filtered.get()?.item(idx).and_downcast::<Song>()
+    }
+
+    /// Index of the first selected row, if any.
+    fn first_selected(&self) -> Option<u32> {
+        let bitset = self.selection()?.selection();
+        (!bitset.is_empty()).then(|| bitset.minimum())
+    }
+
+    fn selected_match(&self) -> Option<Song> {
+        self.match_at(self.first_selected()?)
+    }
+
+    /// Move the selection to the next or previous visible row, wrapping around.
+    fn select_match(&self, forward: bool) {
+        let Some(sel) = self.selection() else {
+            return;
+        };
+        let n_items = sel.n_items();
+        if n_items == 0 {
+            return;
+        }
+        let idx = if let Some(curr) = self.first_selected() {
+            if forward {
+                (curr + 1) % n_items
+            } else {
+                (curr + n_items - 1) % n_items
+            }
+        } else if forward {
+            0
+        } else {
+            n_items - 1
+        };
+        self.imp().queue.scroll_to(idx, gtk::ListScrollFlags::SELECT, None);
+    }
+
+    fn clear_search(&self) {
+        let imp = self.imp();
+        if !imp.search_entry.text().is_empty() {
+            imp.search_entry.set_text("");
+            imp.last_search_len.set(0);
+            // Don't wait for the search delay, as we might be about to look for a row.
+            imp.search_filter.changed(gtk::FilterChange::LessStrict);
+        }
+    }
+
+    /// Scroll to & select the current song, clearing the search if it's filtered out.
+    pub fn jump_to_current(&self) {
+        let Some(id) = self.imp().player.upgrade().and_then(|player| player.queue_id()) else {
+            return;
+        };
+        let find = |this: &Self| {
+            let filtered = this.imp().filtered.get()?;
+            (0..filtered.n_items()).find(|idx| {
+                filtered
+                    .item(*idx)
+                    .and_downcast::<Song>()
+                    .is_some_and(|song| song.get_queue_id() == id)
+            })
+        };
+        let idx = find(self).or_else(|| {
+            self.clear_search();
+            find(self)
+        });
+        if let Some(idx) = idx {
+            self.imp().queue.scroll_to(
+                idx,
+                gtk::ListScrollFlags::SELECT | gtk::ListScrollFlags::FOCUS,
+                None,
+            );
+        }
+    }
+
+    fn setup_up_next(&self, player: &Player) {
+        let up_next = player.up_next();
+        up_next
+            .bind_property("n-items", &self.imp().up_next_revealer.get(), "reveal-child")
+            .transform_to(|_, size: u32| Some(size > 0))
+            .sync_create()
+            .build();
+
+        let list = self.imp().up_next.get();
+        list.bind_model(
+            Some(up_next),
+            clone!(
+                #[weak]
+                player,
+                #[upgrade_or_panic]
+                move |obj| {
+                    let song = obj
+                        .downcast_ref::<Song>()
+                        .expect("Up-next item has to be a common::Song.");
+                    up_next_row(&player, song).upcast()
+                }
+            ),
+        );
+        list.connect_row_activated(clone!(
+            #[weak]
+            player,
+            move |_, row| {
+                if let Some(song) = player.up_next().item(row.index() as u32).and_downcast::<Song>() {
+                    player.on_song_clicked(song);
+                }
+            }
+        ));
+    }
+
+    /// Queue positions of the selected songs, in order.
+    fn selected_positions(&self) -> Vec<u32> {
+        let (Some(sel), Some(player)) = (self.selection(), self.imp().player.upgrade()) else {
+            return Vec::new();
+        };
+        let bitset = sel.selection();
+        let Some((iter, first_idx)) = gtk::BitsetIter::init_first(&bitset) else {
+            return Vec::new();
+        };
+        // Rows might be filtered, so look their songs up in the queue itself.
+        let selected: FxHashSet<Song> = std::iter::once(first_idx)
+            .chain(iter)
+            .filter_map(|idx| sel.item(idx).and_downcast::<Song>())
+            .collect();
+        player
+            .queue()
+            .iter::<Song>()
+            .flatten()
+            .enumerate()
+            .filter_map(|(pos, song)| selected.contains(&song).then_some(pos as u32))
+            .collect()
+    }
+
+    fn setup_selection(&self, player: &Player, library: Library) {
+        let imp = self.imp();
+        let Some(sel) = self.selection() else {
+            return;
+        };
+        imp.add_to_playlist.setup(library, sel.clone());
+        player
+            .bind_property("supports-playlists", &imp.add_to_playlist.get(), "visible")
+            .sync_create()
+            .build();
+
+        // Clicking a row selects it instead of playing it while in selection mode.
+        imp.select_btn.connect_toggled(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |btn| {
+                let selecting = btn.is_active();
+                let queue = this.imp().queue.get();
+                queue.set_single_click_activate(!selecting);
+                queue.set_enable_rubberband(selecting);
+                if !selecting {
+                    if let Some(sel) = this.selection() {
+                        sel.unselect_all();
+                    }
+                }
+            }
+        ));
+
+        let update_selection_bar = clone!(
+            #[weak(rename_to = this)]
+            self,
+            move || {
+                let imp = this.imp();
+                let n_sel = this
+                    .selection()
+                    .map_or(0, |sel| sel.selection().size());
+                // TODO: l10n
+                imp.selection_label.set_label(&if n_sel == 1 {
+                    String::from("1 song selected")
+                } else {
+                    format!("{n_sel} songs selected")
+                });
+                for widget in [
+                    imp.remove_selected.upcast_ref::<gtk::Widget>(),
+                    imp.move_top.upcast_ref(),
+                    imp.move_next.upcast_ref(),
+                    imp.move_bottom.upcast_ref(),
+                    imp.priority_btn.upcast_ref(),
+                    imp.add_to_playlist.upcast_ref(),
+                ] {
+                    widget.set_sensitive(n_sel > 0);
+                }
+            }
+        );
+        update_selection_bar();
+        sel.connect_selection_changed(clone!(
+            #[strong]
+            update_selection_bar,
+            move |_, _, _| {
+                update_selection_bar();
+            }
+        ));
+        sel.connect_items_changed(move |_, _, _, _| {
+            update_selection_bar();
+        });
+
+        imp.remove_selected.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                player.remove_positions(&this.selected_positions());
+            }
+        ));
+        imp.move_top.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                player.move_positions(&this.selected_positions(), 0);
+            }
+        ));
+        imp.move_next.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                // The current song's own position might predate local edits, so look it up.
+                let to = player
+                    .queue_id()
+                    .and_then(|id| {
+                        player
+                            .queue()
+                            .iter::<Song>()
+                            .flatten()
+                            .position(|song| song.get_queue_id() == id)
+                    })
+                    .map_or(0, |pos| pos as u32 + 1);
+                player.move_positions(&this.selected_positions(), to);
+            }
+        ));
+        imp.move_bottom.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                player.move_positions(&this.selected_positions(), player.queue().n_items());
+            }
+        ));
+        imp.priority_value.set_range(0.0, MAX_FIXED_PRIO as f64);
+        imp.priority_confirm.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                let imp = this.imp();
+                imp.priority_btn.set_active(false);
+                let prio = imp.priority_value.value_as_int().clamp(0, MAX_FIXED_PRIO as i32) as u8;
+                let ids: Vec<u32> = this
+                    .selected_positions()
+                    .into_iter()
+                    .filter_map(|pos| player.queue().item(pos).and_downcast::<Song>())
+                    .map(|song| song.get_queue_id())
+                    .collect();
+                player.set_priority(&ids, prio);
+            }
+        ));
+    }
+
+    /// Let a row be dragged onto another to move it there. If the dragged row is
+    /// selected, all selected rows are moved along with it.
+    fn setup_row_dnd(&self, row: &QueueRow) {
+        let drag_source = gtk::DragSource::builder()
+            .actions(gdk::DragAction::MOVE)
+            .build();
+        drag_source.connect_prepare(clone!(
+            #[weak]
+            row,
+            #[upgrade_or]
+            None,
+            move |_, _, _| {
+                let song = row.song()?;
+                Some(gdk::ContentProvider::for_value(&song.to_value()))
+            }
+        ));
+        drag_source.connect_drag_begin(clone!(
+            #[weak]
+            row,
+            move |source, _| {
+                let icon = gtk::WidgetPaintable::new(Some(&row));
+                source.set_icon(Some(&icon), 0, 0);
+            }
+        ));
+        row.add_controller(drag_source);
+
+        let drop_target = gtk::DropTarget::new(Song::static_type(), gdk::DragAction::MOVE);
+        drop_target.connect_drop(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            row,
+            #[upgrade_or]
+            false,
+            move |_, value, _, y| {
+                let (Ok(dragged), Some(target)) = (value.get::<Song>(), row.song()) else {
+                    return false;
+                };
+                // Dropping on the lower half of a row puts the songs after it.
+                let below = y > row.height() as f64 / 2.0;
+                this.on_row_dropped(&dragged, &target, below)
+            }
+        ));
+        row.add_controller(drop_target);
+    }
+
+    fn on_row_dropped(&self, dragged: &Song, target: &Song, below: bool) -> bool {
+        let Some(player) = self.imp().player.upgrade() else {
+            return false;
+        };
+        let (Some(dragged_pos), Some(target_pos)) =
+            (player.queue().find(dragged), player.queue().find(target))
+        else {
+            return false;
+        };
+        let mut positions = self.selected_positions();
+        if !positions.contains(&dragged_pos) {
+            positions = vec![dragged_pos];
+        }
+        player.move_positions(&positions, target_pos + below as u32);
+        true
+    }
+
     fn show_save_error_dialog(&self, name: String, player: Player) {
         // TODO: translatable
         let diag = adw::AlertDialog::builder()
@@ -311,6 +921,8 @@ impl QueueView {
         let player_queue = player.queue();
         let queue_title = self.imp().queue_title.get();
         let clear_queue_btn = self.imp().clear_queue.get();
+        let transform_btn = self.imp().transform.get();
+        let export_file_btn = self.imp().export_file.get();
         let consume = self.imp().consume.get();
         let save = self.imp().save.get();
         let save_name = self.imp().save_name.get();
@@ -321,6 +933,49 @@ impl QueueView {
             .sync_create()
             .build();
 
+        player_queue
+            .bind_property("n-items", &export_file_btn, "sensitive")
+            .transform_to(|_, size: u32| Some(size > 0))
+            .sync_create()
+            .build();
+
+        player_queue
+            .bind_property("n-items", &transform_btn, "sensitive")
+            .transform_to(|_, size: u32| Some(size > 1))
+            .sync_create()
+            .build();
+
+        let action_sort_album = gio::ActionEntry::builder("sort-album")
+            .activate(clone!(
+                #[weak]
+                player,
+                move |_, _, _| {
+                    player.sort_queue_by_album();
+                }
+            ))
+            .build();
+        let action_shuffle_album = gio::ActionEntry::builder("shuffle-album")
+            .activate(clone!(
+                #[weak]
+                player,
+                move |_, _, _| {
+                    player.shuffle_queue_by_album();
+                }
+            ))
+            .build();
+        let action_deduplicate = gio::ActionEntry::builder("deduplicate")
+            .activate(clone!(
+                #[weak]
+                player,
+                move |_, _, _| {
+                    player.deduplicate_queue();
+                }
+            ))
+            .build();
+        let actions = gio::SimpleActionGroup::new();
+        actions.add_action_entries([action_sort_album, action_shuffle_album, action_deduplicate]);
+        self.insert_action_group("queue-view", Some(&actions));
+
         player_queue
             .bind_property("n-items", &queue_title, "subtitle")
             // TODO: l10n
@@ -432,6 +1087,12 @@ impl QueueView {
             }
         ));
 
+        // Radio mode is a purely client-side setting
+        settings_manager()
+            .child("player")
+            .bind("radio-mode", &self.imp().radio.get(), "active")
+            .build();
+
         clear_queue_btn.connect_clicked(clone!(
             #[weak]
             player,
@@ -439,11 +1100,34 @@ impl QueueView {
                 player.clear_queue();
             }
         ));
+
+        export_file_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            player,
+            move |_| {
+                glib::spawn_future_local(clone!(
+                    #[weak]
+                    this,
+                    #[weak]
+                    player,
+                    async move {
+                        if let Some((path, options)) = playlist_file::choose_export_target(&this, "Queue").await {
+                            player.export_queue_file(path, options);
+                        }
+                    }
+                ));
+            }
+        ));
     }
 
-    pub fn setup(&self, player: Player, cache: Rc<Cache>, window: EuphonicaWindow) {
+    pub fn setup(&self, player: Player, library: Library, cache: Rc<Cache>, window: EuphonicaWindow) {
         let _ = self.imp().window.set(window);
         self.setup_listview(player.clone(), cache);
+        self.setup_search(&player);
+        self.setup_selection(&player, library);
+        self.setup_up_next(&player);
         self.imp().player_pane.setup(&player);
         self.bind_state(&player);
         self.imp().player.set(Some(&player));
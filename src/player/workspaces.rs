@@ -0,0 +1,180 @@
+use std::cell::{OnceCell, RefCell};
+
+use adw::prelude::*;
+use adw::subclass::prelude::*;
+use gtk::{glib, CompositeTemplate};
+
+use glib::clone;
+
+use crate::common::SavedQueue;
+
+use super::Player;
+
+mod imp {
+    use super::*;
+
+    #[derive(Debug, Default, CompositeTemplate)]
+    #[template(resource = "/io/github/htkhiem/Euphonica/gtk/player/workspaces.ui")]
+    pub struct WorkspacesDialog {
+        #[template_child]
+        pub workspaces: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub new_workspace: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub snapshots: TemplateChild<adw::PreferencesGroup>,
+        // Rows of both groups, except for the entry row. Rebuilt on every refresh.
+        pub rows: RefCell<Vec<(adw::PreferencesGroup, adw::ActionRow)>>,
+        pub player: OnceCell<Player>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for WorkspacesDialog {
+        const NAME: &'static str = "EuphonicaWorkspacesDialog";
+        type Type = super::WorkspacesDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            Self::bind_template(klass);
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for WorkspacesDialog {}
+    impl WidgetImpl for WorkspacesDialog {}
+    impl AdwDialogImpl for WorkspacesDialog {}
+}
+
+glib::wrapper! {
+    pub struct WorkspacesDialog(ObjectSubclass<imp::WorkspacesDialog>)
+        @extends adw::Dialog, gtk::Widget,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget, gtk::ShortcutManager;
+}
+
+impl Default for WorkspacesDialog {
+    fn default() -> Self {
+        glib::Object::new()
+    }
+}
+
+fn format_saved_time(saved: &SavedQueue) -> String {
+    glib::DateTime::from_unix_local(saved.saved.unix_timestamp())
+        .and_then(|dt| dt.format("%x %R"))
+        .map(|s| s.to_string())
+        .unwrap_or_default()
+}
+
+impl WorkspacesDialog {
+    pub fn new(player: &Player) -> Self {
+        let res = Self::default();
+        let _ = res.imp().player.set(player.clone());
+        res.setup();
+        res.refresh();
+        res
+    }
+
+    fn player(&self) -> &Player {
+        self.imp().player.get().unwrap()
+    }
+
+    fn setup(&self) {
+        self.imp().new_workspace.connect_apply(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |entry| {
+                let name = entry.text().trim().to_owned();
+                if !name.is_empty() {
+                    entry.set_text("");
+                    this.player().save_queue_workspace(&name);
+                    this.refresh();
+                }
+            }
+        ));
+    }
+
+    fn add_row(&self, group: &adw::PreferencesGroup, saved: SavedQueue, is_current: bool) {
+        let n_songs = saved.snapshot.uris.len();
+        let subtitle = if is_current {
+            format!("{n_songs} song(s) · in use")
+        } else {
+            format!("{n_songs} song(s) · {}", format_saved_time(&saved))
+        };
+        let row = adw::ActionRow::builder()
+            .title(glib::markup_escape_text(
+                saved
+                    .name
+                    .as_deref()
+                    .map_or_else(|| format_saved_time(&saved), str::to_owned)
+                    .as_str(),
+            ))
+            .subtitle(subtitle)
+            .build();
+
+        if let Some(name) = saved.name.clone() {
+            let delete_btn = gtk::Button::builder()
+                .icon_name("user-trash-symbolic")
+                .tooltip_text("Delete workspace")
+                .valign(gtk::Align::Center)
+                .build();
+            delete_btn.add_css_class("flat");
+            delete_btn.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    this.player().delete_queue_workspace(&name);
+                    this.refresh();
+                }
+            ));
+            row.add_suffix(&delete_btn);
+        }
+        if !is_current {
+            let restore_btn = gtk::Button::builder()
+                .label(if saved.name.is_some() { "Switch" } else { "Restore" })
+                .valign(gtk::Align::Center)
+                .build();
+            restore_btn.add_css_class("flat");
+            restore_btn.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_| {
+                    this.player().switch_queue_workspace(&saved);
+                    this.close();
+                }
+            ));
+            row.add_suffix(&restore_btn);
+        }
+        group.add(&row);
+        self.imp().rows.borrow_mut().push((group.clone(), row));
+    }
+
+    /// Repopulate both the workspace & snapshot lists.
+    pub fn refresh(&self) {
+        let imp = self.imp();
+        for (group, row) in imp.rows.take().into_iter() {
+            group.remove(&row);
+        }
+        let current = self.player().current_workspace();
+        let workspaces = imp.workspaces.get();
+        let snapshots = imp.snapshots.get();
+        let mut has_snapshots = false;
+        for saved in self.player().saved_queues().into_iter() {
+            if saved.name.is_some() {
+                let is_current = saved.name == current;
+                self.add_row(&workspaces, saved, is_current);
+            } else {
+                has_snapshots = true;
+                self.add_row(&snapshots, saved, false);
+            }
+        }
+        if !has_snapshots {
+            let row = adw::ActionRow::builder()
+                .title("No snapshots yet")
+                .sensitive(false)
+                .build();
+            snapshots.add(&row);
+            imp.rows.borrow_mut().push((snapshots, row));
+        }
+    }
+}
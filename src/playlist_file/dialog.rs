@@ -0,0 +1,99 @@
+use std::path::PathBuf;
+
+use adw::prelude::*;
+use gtk::gio;
+
+use crate::utils;
+
+use super::{ExportOptions, PlaylistFormat};
+
+/// Ask for a format & export options, then where to save the playlist file.
+/// Returns None if cancelled at either step.
+pub async fn choose_export_target(
+    parent: &impl IsA<gtk::Widget>,
+    name: &str,
+) -> Option<(PathBuf, ExportOptions)> {
+    let format_row = adw::ComboRow::builder()
+        .title("Format")
+        .model(&gtk::StringList::new(&PlaylistFormat::ALL.map(|format| format.label())))
+        .build();
+    let absolute_row = adw::SwitchRow::builder()
+        .title("Absolute paths")
+        .subtitle("Instead of paths relative to the playlist file")
+        .build();
+    let extended_row = adw::SwitchRow::builder()
+        .title("Extended info")
+        .subtitle("Titles, artists, albums and durations")
+        .active(true)
+        .build();
+    let list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(["boxed-list"])
+        .build();
+    list.append(&format_row);
+    list.append(&absolute_row);
+    list.append(&extended_row);
+
+    let has_music_dir = !utils::settings_manager()
+        .child("client")
+        .string("mpd-music-directory")
+        .is_empty();
+    // TODO: translatable
+    let diag = adw::AlertDialog::builder()
+        .heading("Export Playlist")
+        .body(if has_music_dir {
+            "Songs will be written as paths to files in your music directory."
+        } else {
+            "No music directory has been set in the preferences, so songs will be written as paths relative to MPD's music directory."
+        })
+        .extra_child(&list)
+        .build();
+    diag.add_response("cancel", "_Cancel");
+    diag.add_response("export", "_Export");
+    diag.set_response_appearance("export", adw::ResponseAppearance::Suggested);
+    diag.set_default_response(Some("export"));
+    diag.set_close_response("cancel");
+    if diag.choose_future(Some(parent)).await.as_str() != "export" {
+        return None;
+    }
+
+    let format = PlaylistFormat::ALL
+        .get(format_row.selected() as usize)
+        .copied()
+        .unwrap_or(PlaylistFormat::M3u8);
+    let options = ExportOptions {
+        absolute: absolute_row.is_active(),
+        extended: extended_row.is_active(),
+    };
+    let dialog = gtk::FileDialog::builder()
+        .title("Export playlist")
+        .modal(true)
+        .initial_name(format!("{name}.{}", format.extension()))
+        .build();
+    let file = dialog
+        .save_future(parent.as_ref().root().and_downcast_ref::<gtk::Window>())
+        .await
+        .ok()?;
+    Some((utils::with_extension(file.path()?, format.extension()), options))
+}
+
+/// Ask for a playlist file to import.
+pub async fn choose_import_source(parent: &impl IsA<gtk::Widget>) -> Option<PathBuf> {
+    let filter = gtk::FileFilter::new();
+    filter.set_name(Some("Playlist files"));
+    for pattern in ["*.m3u", "*.m3u8", "*.xspf", "*.pls"] {
+        filter.add_pattern(pattern);
+    }
+    let filters = gio::ListStore::new::<gtk::FileFilter>();
+    filters.append(&filter);
+    let dialog = gtk::FileDialog::builder()
+        .title("Import playlist")
+        .modal(true)
+        .filters(&filters)
+        .build();
+    dialog
+        .open_future(parent.as_ref().root().and_downcast_ref::<gtk::Window>())
+        .await
+        .ok()?
+        .path()
+}
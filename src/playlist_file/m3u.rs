@@ -0,0 +1,48 @@
+use std::io::{self, Write};
+
+use super::PlaylistEntry;
+
+pub fn write<W: Write>(writer: &mut W, entries: &[PlaylistEntry], extended: bool) -> io::Result<()> {
+    if extended {
+        writeln!(writer, "#EXTM3U")?;
+    }
+    for entry in entries.iter() {
+        if let Some(title) = entry.display_title() {
+            let duration = entry.duration.map_or(-1, |secs| secs as i64);
+            writeln!(writer, "#EXTINF:{duration},{title}")?;
+        }
+        if let Some(album) = entry.album.as_ref() {
+            writeln!(writer, "#EXTALB:{album}")?;
+        }
+        writeln!(writer, "{}", entry.location)?;
+    }
+    Ok(())
+}
+
+/// Plain & extended M3U. Extended info applies to the next location line.
+pub fn parse(text: &str) -> Vec<PlaylistEntry> {
+    let mut res: Vec<PlaylistEntry> = Vec::new();
+    let mut pending = PlaylistEntry::default();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some(info) = line.strip_prefix("#EXTINF:") {
+            // Duration, optionally followed by attributes, then the display title
+            if let Some((head, title)) = info.split_once(',') {
+                pending.duration = head
+                    .split_whitespace()
+                    .next()
+                    .and_then(|secs| secs.parse::<f64>().ok())
+                    .filter(|secs| *secs > 0.0)
+                    .map(|secs| secs.round() as u64);
+                pending.set_display_title(title);
+            }
+        } else if let Some(album) = line.strip_prefix("#EXTALB:") {
+            pending.album = Some(album.trim().to_owned());
+        } else if let Some(artist) = line.strip_prefix("#EXTART:") {
+            pending.artist = Some(artist.trim().to_owned());
+        } else if !line.starts_with('#') {
+            pending.location = line.to_owned();
+            res.push(std::mem::take(&mut pending));
+        }
+    }
+    res
+}
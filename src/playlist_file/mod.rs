@@ -0,0 +1,300 @@
+mod dialog;
+mod m3u;
+mod pls;
+mod xspf;
+
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::{Component, Path, PathBuf},
+};
+
+use crate::common::SongInfo;
+
+pub use dialog::{choose_export_target, choose_import_source};
+
+// Playlist files for use outside of MPD. MPD URIs are relative to its music directory,
+// so exports need the local path of that directory (set in the preferences) to write
+// real paths, either absolute or relative to the playlist file itself. Without it,
+// URIs are written as-is. Imports map each entry back to an MPD URI by matching its
+// path against the music directory first, then by tags (see the background client).
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlaylistFormat {
+    M3u8,
+    Xspf,
+    Pls,
+}
+
+impl PlaylistFormat {
+    pub const ALL: [PlaylistFormat; 3] = [Self::M3u8, Self::Xspf, Self::Pls];
+
+    /// Guess from the file extension. Plain M3U files are read as UTF-8 too.
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        match ext.as_str() {
+            "m3u8" | "m3u" => Some(Self::M3u8),
+            "xspf" => Some(Self::Xspf),
+            "pls" => Some(Self::Pls),
+            _ => None,
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::M3u8 => "m3u8",
+            Self::Xspf => "xspf",
+            Self::Pls => "pls",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::M3u8 => "M3U8",
+            Self::Xspf => "XSPF",
+            Self::Pls => "PLS",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExportOptions {
+    /// Absolute paths instead of paths relative to the playlist file.
+    pub absolute: bool,
+    /// Also write titles, artists, albums & durations where the format allows.
+    pub extended: bool,
+}
+
+/// A single entry of a playlist file.
+#[derive(Debug, Clone, Default)]
+pub struct PlaylistEntry {
+    /// Path (absolute or relative to the playlist file) or URL, as found in the file.
+    pub location: String,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration: Option<u64>, // In seconds
+}
+
+impl PlaylistEntry {
+    fn new(song: &SongInfo, location: String, extended: bool) -> Self {
+        if !extended {
+            return Self {
+                location,
+                ..Default::default()
+            };
+        }
+        Self {
+            location,
+            title: Some(song.title.clone()),
+            artist: song.artist_tag.clone(),
+            album: song.album.as_ref().map(|album| album.title.clone()),
+            duration: song.duration.map(|dur| dur.as_secs()),
+        }
+    }
+
+    /// "Artist - Title" as used by M3U & PLS, or just the title if there's no artist.
+    fn display_title(&self) -> Option<String> {
+        let title = self.title.as_deref()?;
+        Some(match self.artist.as_deref() {
+            Some(artist) => format!("{artist} - {title}"),
+            None => title.to_owned(),
+        })
+    }
+
+    /// Reverse of display_title(). Titles without a separator are kept whole.
+    fn set_display_title(&mut self, text: &str) {
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        match text.split_once(" - ") {
+            Some((artist, title)) => {
+                self.artist = Some(artist.trim().to_owned());
+                self.title = Some(title.trim().to_owned());
+            }
+            None => self.title = Some(text.to_owned()),
+        }
+    }
+}
+
+/// Outcome of a playlist import.
+#[derive(Debug, Default)]
+pub struct PlaylistImportReport {
+    /// Name of the new MPD playlist.
+    pub name: String,
+    pub by_path: usize,
+    pub by_tags: usize,
+    /// Locations of entries not found in the library, in playlist order.
+    pub unmatched: Vec<String>,
+}
+
+impl PlaylistImportReport {
+    pub fn n_imported(&self) -> usize {
+        self.by_path + self.by_tags
+    }
+}
+
+pub fn is_url(location: &str) -> bool {
+    location.contains("://")
+}
+
+/// Path to `to` from the directory `from`, using ".." where needed. Both must be absolute.
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from: Vec<Component> = from.components().collect();
+    let to: Vec<Component> = to.components().collect();
+    let common = from
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut res = PathBuf::new();
+    for _ in common..from.len() {
+        res.push("..");
+    }
+    for comp in to[common..].iter() {
+        res.push(comp.as_os_str());
+    }
+    res
+}
+
+/// Where to point to an MPD URI from a playlist file in `playlist_dir`.
+fn locate(uri: &str, music_dir: Option<&Path>, playlist_dir: &Path, absolute: bool) -> String {
+    let Some(music_dir) = music_dir.filter(|_| !is_url(uri)) else {
+        return uri.to_owned();
+    };
+    let path = music_dir.join(uri);
+    if absolute {
+        path.to_string_lossy().into_owned()
+    } else {
+        relative_path(playlist_dir, &path)
+            .to_string_lossy()
+            .into_owned()
+    }
+}
+
+/// Write songs to a playlist file in the format implied by its extension (M3U8 if unknown).
+pub fn write_playlist(
+    path: &Path,
+    name: &str,
+    songs: &[SongInfo],
+    options: ExportOptions,
+    music_dir: Option<&Path>,
+) -> io::Result<()> {
+    let playlist_dir = path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let format = PlaylistFormat::from_path(path).unwrap_or(PlaylistFormat::M3u8);
+    let entries: Vec<PlaylistEntry> = songs
+        .iter()
+        .map(|song| {
+            let location = locate(&song.uri, music_dir, &playlist_dir, options.absolute);
+            PlaylistEntry::new(song, location, options.extended)
+        })
+        .collect();
+    let mut writer = BufWriter::new(File::create(path)?);
+    match format {
+        PlaylistFormat::M3u8 => m3u::write(&mut writer, &entries, options.extended)?,
+        PlaylistFormat::Xspf => xspf::write(&mut writer, name, &entries)?,
+        PlaylistFormat::Pls => pls::write(&mut writer, &entries)?,
+    }
+    writer.flush()
+}
+
+/// Read all entries of a playlist file, in order.
+pub fn read_playlist(path: &Path) -> Result<Vec<PlaylistEntry>, String> {
+    let format = PlaylistFormat::from_path(path)
+        .ok_or_else(|| "Unsupported playlist format".to_owned())?;
+    let text = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let text = text.trim_start_matches('\u{feff}');
+    let res = match format {
+        PlaylistFormat::M3u8 => m3u::parse(text),
+        PlaylistFormat::Xspf => xspf::parse(text),
+        PlaylistFormat::Pls => pls::parse(text),
+    };
+    if res.is_empty() {
+        Err("No entries found in file".to_owned())
+    } else {
+        Ok(res)
+    }
+}
+
+/// Absolute local path of an entry, resolving paths relative to the playlist file.
+/// None for remote URLs, and for relative paths if the playlist's location is unknown.
+pub fn entry_path(entry: &PlaylistEntry, playlist_path: &Path) -> Option<PathBuf> {
+    if is_url(&entry.location) {
+        return None;
+    }
+    let path = Path::new(&entry.location);
+    if path.is_absolute() {
+        Some(path.to_path_buf())
+    } else {
+        playlist_path.parent().map(|dir| normalize(&dir.join(path)))
+    }
+}
+
+/// Resolve "." and ".." without touching the filesystem, as the files might not be here.
+fn normalize(path: &Path) -> PathBuf {
+    let mut res = PathBuf::new();
+    for comp in path.components() {
+        match comp {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                res.pop();
+            }
+            other => res.push(other.as_os_str()),
+        }
+    }
+    res
+}
+
+/// MPD URIs an entry might point to, most likely first. Paths within the music directory
+/// map to exactly one URI. Otherwise, as the playlist might come from another machine
+/// with its music stored elsewhere, try the raw location (which MPD's own playlists use)
+/// then ever shorter trailing parts of the path.
+pub fn uri_candidates(entry: &PlaylistEntry, playlist_path: &Path, music_dir: Option<&Path>) -> Vec<String> {
+    if is_url(&entry.location) {
+        return vec![entry.location.clone()];
+    }
+    let mut res: Vec<String> = Vec::new();
+    let path = entry_path(entry, playlist_path);
+    if let (Some(path), Some(music_dir)) = (path.as_ref(), music_dir) {
+        if let Ok(rel) = path.strip_prefix(music_dir) {
+            return vec![rel.to_string_lossy().into_owned()];
+        }
+    }
+    if !Path::new(&entry.location).is_absolute() {
+        res.push(entry.location.trim_start_matches("./").to_owned());
+    }
+    if let Some(path) = path {
+        let comps: Vec<String> = path
+            .components()
+            .filter_map(|comp| match comp {
+                Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+        for start in 0..comps.len() {
+            let uri = comps[start..].join("/");
+            if !res.contains(&uri) {
+                res.push(uri);
+            }
+        }
+    }
+    res
+}
+
+/// Title & artist to search the library with when an entry can't be found by path.
+/// Falls back to the file name, which is often "Artist - Title" or "01 Title".
+pub fn search_tags(entry: &PlaylistEntry) -> Option<(String, String)> {
+    if let Some(title) = entry.title.as_ref() {
+        return Some((entry.artist.clone().unwrap_or_default(), title.clone()));
+    }
+    let stem = Path::new(&entry.location).file_stem()?.to_str()?;
+    let mut fallback = PlaylistEntry::default();
+    fallback.set_display_title(
+        stem.trim_start_matches(|c: char| c.is_ascii_digit() || c == '.' || c == '-' || c == '_' || c == ' '),
+    );
+    Some((fallback.artist.unwrap_or_default(), fallback.title?))
+}
@@ -0,0 +1,52 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+};
+
+use super::PlaylistEntry;
+
+pub fn write<W: Write>(writer: &mut W, entries: &[PlaylistEntry]) -> io::Result<()> {
+    writeln!(writer, "[playlist]")?;
+    for (idx, entry) in entries.iter().enumerate() {
+        let n = idx + 1;
+        writeln!(writer, "File{n}={}", entry.location)?;
+        if let Some(title) = entry.display_title() {
+            writeln!(writer, "Title{n}={title}")?;
+        }
+        if let Some(secs) = entry.duration {
+            writeln!(writer, "Length{n}={secs}")?;
+        }
+    }
+    writeln!(writer, "NumberOfEntries={}", entries.len())?;
+    writeln!(writer, "Version=2")
+}
+
+/// Entries are numbered, and may be listed in any order.
+pub fn parse(text: &str) -> Vec<PlaylistEntry> {
+    let mut entries: BTreeMap<u32, PlaylistEntry> = BTreeMap::new();
+    for line in text.lines() {
+        let Some((key, val)) = line.trim().split_once('=') else {
+            continue;
+        };
+        let key = key.trim().to_lowercase();
+        let Some((field, n)) = ["file", "title", "length"]
+            .into_iter()
+            .find_map(|field| Some((field, key.strip_prefix(field)?.parse::<u32>().ok()?)))
+        else {
+            continue;
+        };
+        let entry = entries.entry(n).or_default();
+        let val = val.trim();
+        match field {
+            "file" => entry.location = val.to_owned(),
+            "title" => entry.set_display_title(val),
+            _ => {
+                entry.duration = val.parse::<i64>().ok().and_then(|secs| u64::try_from(secs).ok());
+            }
+        }
+    }
+    entries
+        .into_values()
+        .filter(|entry| !entry.location.is_empty())
+        .collect()
+}
@@ -0,0 +1,169 @@
+use std::{
+    borrow::Cow,
+    io::{self, Write},
+    path::Path,
+};
+
+use gtk::glib;
+
+use super::{is_url, PlaylistEntry};
+
+// Just enough XML for XSPF's flat track list, so as not to pull in another dependency.
+
+fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"', '\'']) {
+        return Cow::Borrowed(text);
+    }
+    let mut res = String::with_capacity(text.len() + 16);
+    for c in text.chars() {
+        match c {
+            '&' => res.push_str("&amp;"),
+            '<' => res.push_str("&lt;"),
+            '>' => res.push_str("&gt;"),
+            '"' => res.push_str("&quot;"),
+            '\'' => res.push_str("&apos;"),
+            c => res.push(c),
+        }
+    }
+    Cow::Owned(res)
+}
+
+fn unescape(text: &str) -> String {
+    let mut res = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        res.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let Some(end) = rest.find(';') else {
+            break;
+        };
+        let entity = &rest[1..end];
+        let decoded = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => entity
+                .strip_prefix("#x")
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+                .or_else(|| entity.strip_prefix('#').and_then(|dec| dec.parse().ok()))
+                .and_then(char::from_u32),
+        };
+        match decoded {
+            Some(c) => {
+                res.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                res.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    res.push_str(rest);
+    res
+}
+
+/// XSPF locations are URIs. Absolute paths become file:// URIs while relative ones
+/// are percent-encoded segment by segment.
+fn to_uri(location: &str) -> String {
+    if is_url(location) {
+        return location.to_owned();
+    }
+    let path = Path::new(location);
+    if path.is_absolute() {
+        if let Ok(uri) = glib::filename_to_uri(path, None) {
+            return uri.to_string();
+        }
+    }
+    location
+        .split('/')
+        .map(|segment| urlencoding::encode(segment).into_owned())
+        .collect::<Vec<String>>()
+        .join("/")
+}
+
+fn from_uri(uri: &str) -> String {
+    if uri.starts_with("file:") {
+        if let Ok((path, _)) = glib::filename_from_uri(uri) {
+            return path.to_string_lossy().into_owned();
+        }
+    }
+    if is_url(uri) {
+        return uri.to_owned();
+    }
+    urlencoding::decode(uri)
+        .map(Cow::into_owned)
+        .unwrap_or_else(|_| uri.to_owned())
+}
+
+fn write_element<W: Write>(writer: &mut W, name: &str, text: &str) -> io::Result<()> {
+    writeln!(writer, "      <{name}>{}</{name}>", escape(text))
+}
+
+pub fn write<W: Write>(writer: &mut W, name: &str, entries: &[PlaylistEntry]) -> io::Result<()> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(writer, r#"<playlist version="1" xmlns="http://xspf.org/ns/0/">"#)?;
+    writeln!(writer, "  <title>{}</title>", escape(name))?;
+    writeln!(writer, "  <trackList>")?;
+    for entry in entries.iter() {
+        writeln!(writer, "    <track>")?;
+        write_element(writer, "location", &to_uri(&entry.location))?;
+        if let Some(title) = entry.title.as_ref() {
+            write_element(writer, "title", title)?;
+        }
+        if let Some(artist) = entry.artist.as_ref() {
+            write_element(writer, "creator", artist)?;
+        }
+        if let Some(album) = entry.album.as_ref() {
+            write_element(writer, "album", album)?;
+        }
+        if let Some(secs) = entry.duration {
+            // In milliseconds
+            write_element(writer, "duration", &(secs * 1000).to_string())?;
+        }
+        writeln!(writer, "    </track>")?;
+    }
+    writeln!(writer, "  </trackList>")?;
+    writeln!(writer, "</playlist>")
+}
+
+/// Text of the first child element with the given name, if any.
+fn element_text(block: &str, name: &str) -> Option<String> {
+    let open = format!("<{name}>");
+    let start = block.find(&open)? + open.len();
+    let end = start + block[start..].find(&format!("</{name}>"))?;
+    let text = block[start..end].trim();
+    let text = text
+        .strip_prefix("<![CDATA[")
+        .and_then(|inner| inner.strip_suffix("]]>"))
+        .map(str::to_owned)
+        .unwrap_or_else(|| unescape(text));
+    Some(text).filter(|text| !text.is_empty())
+}
+
+pub fn parse(text: &str) -> Vec<PlaylistEntry> {
+    let mut res: Vec<PlaylistEntry> = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("<track>") {
+        rest = &rest[start + "<track>".len()..];
+        let end = rest.find("</track>").unwrap_or(rest.len());
+        let block = &rest[..end];
+        rest = &rest[end..];
+        // Tracks may have several locations, of which we only need the first.
+        let Some(location) = element_text(block, "location") else {
+            continue;
+        };
+        res.push(PlaylistEntry {
+            location: from_uri(&location),
+            title: element_text(block, "title"),
+            artist: element_text(block, "creator"),
+            album: element_text(block, "album"),
+            duration: element_text(block, "duration")
+                .and_then(|ms| ms.parse::<u64>().ok())
+                .map(|ms| ms / 1000),
+        });
+    }
+    res
+}
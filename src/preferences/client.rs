@@ -11,7 +11,11 @@ use glib::clone;
 use mpd::status::AudioFormat;
 
 use crate::{
-    client::{password::{get_mpd_password, set_mpd_password}, state::StickersSupportLevel, ClientState, ConnectionState, MpdWrapper},
+    client::{
+        password::{get_mpd_password, set_mpd_password},
+        profile::{self, ConnectionProfile},
+        state::StickersSupportLevel, ClientState, ConnectionState, MpdWrapper
+    },
     player::{FftStatus, Player},
     utils,
 };
@@ -107,6 +111,14 @@ mod imp {
         pub reconnect: TemplateChild<adw::ButtonRow>,
         #[template_child]
         pub mpd_download_album_art: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub mpd_auto_reconnect: TemplateChild<adw::SwitchRow>,
+
+        // Connection profiles
+        #[template_child]
+        pub profile_name: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub profiles_list: TemplateChild<gtk::ListBox>,
 
         // Visualiser data source
         #[template_child]
@@ -116,6 +128,12 @@ mod imp {
         pub pipewire_devices: TemplateChild<adw::ComboRow>,
         #[template_child]
         pub pipewire_restart_between_songs: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub music_dir_path: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub music_dir_browse: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub music_dir_clear: TemplateChild<gtk::Button>,
         // FIFO
         #[template_child]
         pub fifo_path: TemplateChild<adw::ActionRow>,
@@ -184,6 +202,43 @@ mod imp {
                 .sync_create()
                 .build();
 
+            let client_settings = utils::settings_manager().child("client");
+            client_settings
+                .bind("mpd-music-directory", &self.music_dir_path.get(), "subtitle")
+                .get_only()
+                .build();
+            self.music_dir_browse.connect_clicked(|_| {
+                utils::tokio_runtime().spawn(async move {
+                    let maybe_files = SelectedFiles::open_file()
+                        .title("Select MPD's music directory")
+                        .modal(true)
+                        .multiple(false)
+                        .directory(true)
+                        .send()
+                        .await
+                        .expect("ashpd file open await failure")
+                        .response();
+
+                    if let Ok(files) = maybe_files {
+                        // Stored as a plain path rather than a URI, as it is joined with MPD URIs
+                        if let Some(path) = files.uris().first().and_then(|uri| uri.to_file_path().ok()) {
+                            utils::settings_manager()
+                                .child("client")
+                                .set_string("mpd-music-directory", &path.to_string_lossy())
+                                .expect("Unable to save music directory");
+                        }
+                    }
+                    else {
+                        println!("{:?}", maybe_files);
+                    }
+                });
+            });
+            self.music_dir_clear.connect_clicked(|_| {
+                let _ = utils::settings_manager()
+                    .child("client")
+                    .set_string("mpd-music-directory", "");
+            });
+
             let viz_settings = utils::settings_manager().child("client");
             let fifo_path_row = self.fifo_path.get();
             viz_settings
@@ -343,6 +398,17 @@ impl ClientPreferences {
                     self.imp().reconnect.set_sensitive(true);
                 }
             }
+            ConnectionState::Reconnecting => {
+                self.imp().mpd_status.set_subtitle(
+                    &format!("Reconnecting (attempt {})", cs.get_reconnect_attempt())
+                );
+                self.imp().mpd_status.set_enable_expansion(false);
+                set_status_icon(&self.imp().mpd_status_icon.get(), StatusIconState::Loading);
+                // Allow reconnecting manually
+                if !self.imp().mpd_port.has_css_class("error") {
+                    self.imp().reconnect.set_sensitive(true);
+                }
+            }
             ConnectionState::Connected => {
                 self.imp().mpd_status.set_subtitle("Connected");
                 self.imp().mpd_status.set_enable_expansion(true);
@@ -394,27 +460,7 @@ impl ClientPreferences {
             &imp.mpd_use_unix_socket.get(),
             "active"
         ).build();
-        imp.mpd_host.set_text(&conn_settings.string("mpd-host"));
-        imp.mpd_unix_socket.set_text(&conn_settings.string("mpd-unix-socket"));
-        imp.mpd_port
-            .set_text(&conn_settings.uint("mpd-port").to_string());
-        let password_field = imp.mpd_password.get();
-        glib::spawn_future_local(async move {
-            match get_mpd_password().await {
-                Ok(maybe_password) => {
-                    // At startup the password entry is disabled with a tooltip stating that
-                    // the credential store is not available.
-                    password_field.set_sensitive(true);
-                    password_field.set_tooltip_text(None);
-                    if let Some(password) = maybe_password {
-                        password_field.set_text(&password);
-                    }
-                }
-                Err(e) => {
-                    println!("{:?}", e);
-                }
-            }
-        });
+        self.load_connection_settings();
 
 
         // TODO: more input validation
@@ -455,6 +501,17 @@ impl ClientPreferences {
             ),
         );
 
+        client_state.connect_notify_local(
+            Some("reconnect-attempt"),
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |cs, _| {
+                    this.on_connection_state_changed(cs);
+                }
+            ),
+        );
+
         self.on_playlists_status_changed(&client_state);
         client_state.connect_notify_local(
             Some("supports-playlists"),
@@ -482,42 +539,58 @@ impl ClientPreferences {
         imp.reconnect.connect_activated(clone!(
             #[weak(rename_to = this)]
             self,
-            #[strong]
-            conn_settings,
             #[weak]
             client,
             move |_| {
-                if this.imp().mpd_use_unix_socket.is_active() {
-                    let _ = conn_settings.set_string("mpd-unix-socket", &this.imp().mpd_unix_socket.text());
-                }
-                else {
-                    let _ = conn_settings.set_string("mpd-host", &this.imp().mpd_host.text());
-                    let _ = conn_settings.set_uint(
-                        "mpd-port",
-                        this.imp().mpd_port.text().parse::<u32>().unwrap(),
-                    );
-                }
+                // Edits made while a profile is active are saved back to that profile.
+                this.save_and_connect(client, profile::get_active_profile_name());
+            }
+        ));
 
-                let password_val = this.imp().mpd_password.text();
-                glib::spawn_future_local(clone!(
-                    #[weak]
-                    client,
-                    async move {
-                        let password: Option<&str> = if password_val.is_empty() { None } else { Some(password_val.as_str()) };
-                        match set_mpd_password(password).await {
-                            Ok(()) => {
-                                client.connect_async().await;
-                            }
-                            Err(msg) => {println!("{}", msg);}
-                        }
-                    }
-                ));
+        // Connection profiles
+        self.update_profiles_list();
+        imp.profile_name.connect_apply(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[weak]
+            client,
+            move |entry| {
+                let name = entry.text().trim().to_owned();
+                if !name.is_empty() && !this.imp().mpd_port.has_css_class("error") {
+                    entry.set_text("");
+                    this.save_and_connect(client, name);
+                }
             }
         ));
+        conn_settings.connect_changed(
+            Some("mpd-profiles"),
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, _| {
+                    this.update_profiles_list();
+                }
+            ),
+        );
+        conn_settings.connect_changed(
+            Some("mpd-active-profile"),
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |_, _| {
+                    // Profile has been copied into the live keys by MpdWrapper by now.
+                    this.load_connection_settings();
+                    this.update_profiles_list();
+                }
+            ),
+        );
         let mpd_download_album_art = imp.mpd_download_album_art.get();
         conn_settings
             .bind("mpd-download-album-art", &mpd_download_album_art, "active")
             .build();
+        conn_settings
+            .bind("mpd-auto-reconnect", &imp.mpd_auto_reconnect.get(), "active")
+            .build();
 
         // Visualiser
         player
@@ -635,6 +708,117 @@ impl ClientPreferences {
         ));
     }
 
+    /// Fill the connection entries with the live connection settings and the
+    /// password of the active profile.
+    fn load_connection_settings(&self) {
+        let imp = self.imp();
+        let conn_settings = utils::settings_manager().child("client");
+        imp.mpd_host.set_text(&conn_settings.string("mpd-host"));
+        imp.mpd_unix_socket.set_text(&conn_settings.string("mpd-unix-socket"));
+        imp.mpd_port
+            .set_text(&conn_settings.uint("mpd-port").to_string());
+        let password_field = imp.mpd_password.get();
+        let profile_name = profile::get_active_profile_name();
+        glib::spawn_future_local(async move {
+            match get_mpd_password(&profile_name).await {
+                Ok(maybe_password) => {
+                    // At startup the password entry is disabled with a tooltip stating that
+                    // the credential store is not available.
+                    password_field.set_sensitive(true);
+                    password_field.set_tooltip_text(None);
+                    password_field.set_text(maybe_password.as_deref().unwrap_or(""));
+                }
+                Err(e) => {
+                    println!("{:?}", e);
+                }
+            }
+        });
+    }
+
+    /// Save the entered connection settings & password, optionally as a named
+    /// profile, then reconnect.
+    fn save_and_connect(&self, client: Rc<MpdWrapper>, profile_name: String) {
+        let imp = self.imp();
+        let conn_settings = utils::settings_manager().child("client");
+        if imp.mpd_use_unix_socket.is_active() {
+            let _ = conn_settings.set_string("mpd-unix-socket", &imp.mpd_unix_socket.text());
+        }
+        else {
+            let _ = conn_settings.set_string("mpd-host", &imp.mpd_host.text());
+            let _ = conn_settings.set_uint(
+                "mpd-port",
+                imp.mpd_port.text().parse::<u32>().unwrap(),
+            );
+        }
+        if !profile_name.is_empty() {
+            profile::save_profile(ConnectionProfile::from_current(&profile_name));
+        }
+
+        let password_val = imp.mpd_password.text();
+        glib::spawn_future_local(clone!(
+            #[weak]
+            client,
+            async move {
+                let password: Option<&str> = if password_val.is_empty() { None } else { Some(password_val.as_str()) };
+                match set_mpd_password(&profile_name, password).await {
+                    Ok(()) => {
+                        if profile::get_active_profile_name() == profile_name {
+                            client.connect_async().await;
+                        } else {
+                            // MpdWrapper reconnects upon seeing the active profile change.
+                            let _ = utils::settings_manager()
+                                .child("client")
+                                .set_string("mpd-active-profile", &profile_name);
+                        }
+                    }
+                    Err(msg) => {println!("{}", msg);}
+                }
+            }
+        ));
+    }
+
+    fn update_profiles_list(&self) {
+        let list = self.imp().profiles_list.get();
+        list.remove_all();
+        let profiles = profile::get_profiles();
+        list.set_visible(!profiles.is_empty());
+        let active_name = profile::get_active_profile_name();
+        for p in profiles.into_iter() {
+            let row = adw::ActionRow::builder()
+                .title(&p.name)
+                .subtitle(p.get_address())
+                .activatable(true)
+                .build();
+            let active_icon = gtk::Image::from_icon_name("object-select-symbolic");
+            active_icon.set_opacity(if p.name == active_name { 1.0 } else { 0.0 });
+            row.add_prefix(&active_icon);
+            let delete_btn = gtk::Button::builder()
+                .icon_name("user-trash-symbolic")
+                .tooltip_text("Delete profile")
+                .valign(gtk::Align::Center)
+                .build();
+            delete_btn.add_css_class("flat");
+            let name = p.name.clone();
+            delete_btn.connect_clicked(move |_| {
+                let name = name.clone();
+                profile::delete_profile(&name);
+                glib::spawn_future_local(async move {
+                    if let Err(msg) = set_mpd_password(&name, None).await {
+                        println!("{}", msg);
+                    }
+                });
+            });
+            row.add_suffix(&delete_btn);
+            let name = p.name;
+            row.connect_activated(move |_| {
+                let _ = utils::settings_manager()
+                    .child("client")
+                    .set_string("mpd-active-profile", &name);
+            });
+            list.append(&row);
+        }
+    }
+
     fn update_pipewire_devices(&self, maybe_devices: Option<Vec<String>>) {
         self.imp().pipewire_devices.set_model(
             maybe_devices.and_then(|devices: Vec<String>| {
@@ -64,8 +64,8 @@ impl Preferences {
     pub fn new(client: Rc<MpdWrapper>, cache: Rc<Cache>, player: &Player) -> Self {
         let res = Self::default();
 
-        res.imp().client_tab.get().setup(client, player);
-        res.imp().library_tab.get().setup();
+        res.imp().client_tab.get().setup(client.clone(), player);
+        res.imp().library_tab.get().setup(client, player);
         res.imp().ui_tab.get().setup(); 
         res.imp().integrations_tab.get().setup(cache);
         
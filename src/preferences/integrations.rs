@@ -1,10 +1,16 @@
 use adw::prelude::*;
 use adw::subclass::prelude::*;
-use gtk::{glib, CompositeTemplate};
-use std::cell::OnceCell;
+use glib::clone;
+use gtk::{gio, glib, CompositeTemplate};
+use std::cell::{OnceCell, RefCell};
 use std::rc::Rc;
 
-use crate::{application::update_xdg_background_request, cache::Cache, utils};
+use crate::{
+    application::update_xdg_background_request,
+    cache::Cache,
+    scrobbler::{lastfm, listenbrainz, set_token},
+    utils,
+};
 
 use super::ProviderRow;
 
@@ -29,7 +35,30 @@ mod imp {
         #[template_child]
         pub lastfm_key: TemplateChild<adw::EntryRow>,
         #[template_child]
+        pub lastfm_secret: TemplateChild<adw::PasswordEntryRow>,
+        #[template_child]
         pub lastfm_download_album_art: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub lastfm_scrobble: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub lastfm_account: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub lastfm_connect: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub lastfm_disconnect: TemplateChild<gtk::Button>,
+        // Token awaiting the user's approval in their browser
+        pub lastfm_token: RefCell<Option<String>>,
+
+        #[template_child]
+        pub listenbrainz_enabled: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub listenbrainz_api_root: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub listenbrainz_token: TemplateChild<adw::PasswordEntryRow>,
+        #[template_child]
+        pub listenbrainz_account: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub listenbrainz_disconnect: TemplateChild<gtk::Button>,
 
         #[template_child]
         pub musicbrainz_download_album_art: TemplateChild<adw::SwitchRow>,
@@ -135,6 +164,51 @@ impl IntegrationsPreferences {
         lastfm_settings
             .bind("download-album-art", &lastfm_download_album_art, "active")
             .build();
+        lastfm_settings
+            .bind("api-secret", &imp.lastfm_secret.get(), "text")
+            .build();
+        lastfm_settings
+            .bind("scrobble", &imp.lastfm_scrobble.get(), "active")
+            .build();
+        self.update_lastfm_account();
+        imp.lastfm_connect.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.on_lastfm_connect_clicked();
+            }
+        ));
+        imp.lastfm_disconnect.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.on_lastfm_disconnect_clicked();
+            }
+        ));
+
+        // Set up ListenBrainz settings
+        let lb_settings = settings.child(listenbrainz::SERVICE_KEY);
+        lb_settings
+            .bind("enabled", &imp.listenbrainz_enabled.get(), "active")
+            .build();
+        lb_settings
+            .bind("api-root", &imp.listenbrainz_api_root.get(), "text")
+            .build();
+        self.update_listenbrainz_account();
+        imp.listenbrainz_token.connect_apply(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.on_listenbrainz_token_applied();
+            }
+        ));
+        imp.listenbrainz_disconnect.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.on_listenbrainz_disconnect_clicked();
+            }
+        ));
 
         // Set up MusicBrainz settings
         let mb_settings = utils::meta_provider_settings("musicbrainz");
@@ -193,6 +267,174 @@ impl IntegrationsPreferences {
         });
     }
 
+    fn update_lastfm_account(&self) {
+        let imp = self.imp();
+        let username = utils::meta_provider_settings(lastfm::SERVICE_KEY).string("username");
+        let connected = !username.is_empty();
+        if connected {
+            imp.lastfm_account.set_subtitle(&format!("Connected as {}", username));
+        } else {
+            imp.lastfm_account.set_subtitle("Not connected");
+        }
+        imp.lastfm_connect.set_label("Connect");
+        imp.lastfm_connect.set_visible(!connected);
+        imp.lastfm_disconnect.set_visible(connected);
+    }
+
+    /// Desktop authentication takes two clicks: the first opens Last.fm's consent page
+    /// in the browser, the second fetches the session once access has been granted there.
+    fn on_lastfm_connect_clicked(&self) {
+        let imp = self.imp();
+        imp.lastfm_connect.set_sensitive(false);
+        let maybe_token = imp.lastfm_token.take();
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = this)]
+            self,
+            async move {
+                let imp = this.imp();
+                if let Some(token) = maybe_token {
+                    let res = gio::spawn_blocking(move || lastfm::finish_auth(&token))
+                        .await
+                        .expect("Last.fm authentication thread panicked");
+                    match res {
+                        Ok((username, key)) => match set_token(lastfm::SERVICE_KEY, Some(&key)).await {
+                            Ok(()) => {
+                                let _ = utils::meta_provider_settings(lastfm::SERVICE_KEY)
+                                    .set_string("username", &username);
+                                this.update_lastfm_account();
+                            }
+                            Err(e) => {
+                                imp.lastfm_account
+                                    .set_subtitle(&format!("Unable to save session: {}", e));
+                                imp.lastfm_connect.set_label("Connect");
+                            }
+                        },
+                        Err(e) => {
+                            imp.lastfm_account
+                                .set_subtitle(&format!("Unable to connect: {}", e));
+                            imp.lastfm_connect.set_label("Connect");
+                        }
+                    }
+                } else {
+                    let res = gio::spawn_blocking(lastfm::begin_auth)
+                        .await
+                        .expect("Last.fm authentication thread panicked");
+                    match res {
+                        Ok((url, token)) => {
+                            imp.lastfm_token.replace(Some(token));
+                            let _ = open::that(url);
+                            imp.lastfm_account
+                                .set_subtitle("Grant access in your browser, then click Finish");
+                            imp.lastfm_connect.set_label("Finish");
+                        }
+                        Err(e) => {
+                            imp.lastfm_account
+                                .set_subtitle(&format!("Unable to connect: {}", e));
+                        }
+                    }
+                }
+                imp.lastfm_connect.set_sensitive(true);
+            }
+        ));
+    }
+
+    fn on_lastfm_disconnect_clicked(&self) {
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = this)]
+            self,
+            async move {
+                match set_token(lastfm::SERVICE_KEY, None).await {
+                    Ok(()) => {
+                        let _ = utils::meta_provider_settings(lastfm::SERVICE_KEY)
+                            .set_string("username", "");
+                        this.update_lastfm_account();
+                    }
+                    Err(e) => {
+                        println!("{:?}", e);
+                    }
+                }
+            }
+        ));
+    }
+
+    fn update_listenbrainz_account(&self) {
+        let imp = self.imp();
+        let username = utils::settings_manager()
+            .child(listenbrainz::SERVICE_KEY)
+            .string("username");
+        let connected = !username.is_empty();
+        if connected {
+            imp.listenbrainz_account
+                .set_subtitle(&format!("Connected as {}", username));
+        } else {
+            imp.listenbrainz_account.set_subtitle("Not connected");
+        }
+        imp.listenbrainz_disconnect.set_visible(connected);
+    }
+
+    /// Tokens are validated against the configured API root before being saved, so
+    /// set that first when using a self-hosted server.
+    fn on_listenbrainz_token_applied(&self) {
+        let imp = self.imp();
+        let token = imp.listenbrainz_token.text().trim().to_owned();
+        if token.is_empty() {
+            return;
+        }
+        imp.listenbrainz_token.set_sensitive(false);
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = this)]
+            self,
+            async move {
+                let imp = this.imp();
+                let to_validate = token.clone();
+                let res = gio::spawn_blocking(move || listenbrainz::validate_token(&to_validate))
+                    .await
+                    .expect("ListenBrainz validation thread panicked");
+                match res {
+                    Ok(username) => match set_token(listenbrainz::SERVICE_KEY, Some(&token)).await {
+                        Ok(()) => {
+                            let _ = utils::settings_manager()
+                                .child(listenbrainz::SERVICE_KEY)
+                                .set_string("username", &username);
+                            // Don't keep the token around in the UI
+                            imp.listenbrainz_token.set_text("");
+                            this.update_listenbrainz_account();
+                        }
+                        Err(e) => {
+                            imp.listenbrainz_account
+                                .set_subtitle(&format!("Unable to save token: {}", e));
+                        }
+                    },
+                    Err(e) => {
+                        imp.listenbrainz_account
+                            .set_subtitle(&format!("Unable to connect: {}", e));
+                    }
+                }
+                imp.listenbrainz_token.set_sensitive(true);
+            }
+        ));
+    }
+
+    fn on_listenbrainz_disconnect_clicked(&self) {
+        glib::spawn_future_local(clone!(
+            #[weak(rename_to = this)]
+            self,
+            async move {
+                match set_token(listenbrainz::SERVICE_KEY, None).await {
+                    Ok(()) => {
+                        let _ = utils::settings_manager()
+                            .child(listenbrainz::SERVICE_KEY)
+                            .set_string("username", "");
+                        this.update_listenbrainz_account();
+                    }
+                    Err(e) => {
+                        println!("{:?}", e);
+                    }
+                }
+            }
+        ));
+    }
+
     fn regen_provider_list(&self) {
         // Priority & key
         let mut new_order: Vec<(i32, String)> = Vec::new();
@@ -1,14 +1,24 @@
 
+use std::{path::PathBuf, rc::Rc};
+
 use adw::prelude::*;
 use adw::subclass::prelude::*;
 use gtk::{glib, gio, CompositeTemplate};
 
-use glib::clone;
+use glib::{clone, closure_local, BoxedAnyObject};
+
+use crate::{
+    cache::{get_doc_cache_path, get_image_cache_path},
+    client::{state::StickersSupportLevel, BackgroundTask, ClientState, MpdWrapper},
+    common::StickerRestoreReport,
+    history::ImportReport,
+    player::Player,
+    utils,
+};
 
-use crate::{cache::{get_doc_cache_path, get_image_cache_path}, utils};
 
 mod imp {
-    use std::cell::Cell;
+    use std::cell::{Cell, RefCell};
 
     use crate::cache::get_app_cache_path;
 
@@ -42,6 +52,41 @@ mod imp {
         #[template_child]
         pub pause_recent: TemplateChild<adw::SwitchRow>,
 
+        #[template_child]
+        pub radio_threshold: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub radio_batch_size: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub radio_no_repeat_window: TemplateChild<adw::SpinRow>,
+
+        #[template_child]
+        pub queue_snapshot_limit: TemplateChild<adw::SpinRow>,
+
+        #[template_child]
+        pub remember_position: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub remember_position_min_length: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub auto_resume: TemplateChild<adw::SwitchRow>,
+
+        #[template_child]
+        pub export_history_json: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub export_history_csv: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub import_history_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub import_history_btn: TemplateChild<gtk::Button>,
+
+        #[template_child]
+        pub backup_stickers_btn: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub restore_stickers_stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub restore_stickers_btn: TemplateChild<gtk::Button>,
+        // Backup file being previewed, to be restored for real once confirmed
+        pub pending_sticker_restore: RefCell<Option<PathBuf>>,
+
         #[template_child]
         pub image_cache_size: TemplateChild<adw::ActionRow>,
         #[template_child]
@@ -104,7 +149,7 @@ impl Default for LibraryPreferences {
 }
 
 impl LibraryPreferences {
-    pub fn setup(&self) {
+    pub fn setup(&self, client: Rc<MpdWrapper>, player: &Player) {
         let imp = self.imp();
         // Populate with current gsettings values
         let settings = utils::settings_manager();
@@ -135,6 +180,42 @@ impl LibraryPreferences {
             .bind("pause-recent", &imp.pause_recent.get(), "active")
             .build();
 
+        // Radio mode itself is toggled from the queue view
+        let player_settings = settings.child("player");
+        player_settings
+            .bind("radio-threshold", &imp.radio_threshold.get(), "value")
+            .build();
+        player_settings
+            .bind("radio-batch-size", &imp.radio_batch_size.get(), "value")
+            .build();
+        player_settings
+            .bind("radio-no-repeat-window", &imp.radio_no_repeat_window.get(), "value")
+            .build();
+        player_settings
+            .bind("queue-snapshot-limit", &imp.queue_snapshot_limit.get(), "value")
+            .build();
+        player_settings
+            .bind("remember-position", &imp.remember_position.get(), "active")
+            .build();
+        player_settings
+            .bind(
+                "remember-position-min-length",
+                &imp.remember_position_min_length.get(),
+                "value",
+            )
+            .build();
+        player_settings
+            .bind("auto-resume", &imp.auto_resume.get(), "active")
+            .build();
+        imp.remember_position
+            .bind_property("active", &imp.remember_position_min_length.get(), "sensitive")
+            .sync_create()
+            .build();
+        imp.remember_position
+            .bind_property("active", &imp.auto_resume.get(), "sensitive")
+            .sync_create()
+            .build();
+
         // Setup artist section
         let artist_delims_buf = imp.artist_delims.buffer();
         let artist_delims_apply = imp.artist_delims_apply.get();
@@ -219,6 +300,370 @@ impl LibraryPreferences {
                 utils::rebuild_artist_delim_exception_automaton();
             }
         ));
+
+        self.setup_history(client.clone(), player);
+        self.setup_stickers(client);
+    }
+
+    fn setup_history(&self, client: Rc<MpdWrapper>, player: &Player) {
+        let imp = self.imp();
+        let client_state = client.get_client_state();
+        for (btn, ext) in [
+            (imp.export_history_json.get(), "json"),
+            (imp.export_history_csv.get(), "csv"),
+        ] {
+            btn.connect_clicked(clone!(
+                #[weak(rename_to = this)]
+                self,
+                #[strong]
+                client,
+                move |_| {
+                    glib::spawn_future_local(clone!(
+                        #[weak]
+                        this,
+                        #[strong]
+                        client,
+                        async move {
+                            let dialog = gtk::FileDialog::builder()
+                                .title("Export listening history")
+                                .modal(true)
+                                .initial_name(format!("euphonica-history.{ext}"))
+                                .build();
+                            if let Ok(file) = dialog
+                                .save_future(this.root().and_downcast_ref::<gtk::Window>())
+                                .await
+                            {
+                                if let Some(path) = file.path() {
+                                    this.set_history_busy(true);
+                                    client.queue_background(
+                                        BackgroundTask::ExportHistory(utils::with_extension(path, ext)),
+                                        true,
+                                    );
+                                }
+                            }
+                        }
+                    ));
+                }
+            ));
+        }
+
+        imp.import_history_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            client,
+            move |_| {
+                glib::spawn_future_local(clone!(
+                    #[weak]
+                    this,
+                    #[strong]
+                    client,
+                    async move {
+                        let filter = gtk::FileFilter::new();
+                        filter.set_name(Some("Listening history"));
+                        for pattern in ["*.json", "*.jsonl", "*.csv"] {
+                            filter.add_pattern(pattern);
+                        }
+                        let filters = gio::ListStore::new::<gtk::FileFilter>();
+                        filters.append(&filter);
+                        let dialog = gtk::FileDialog::builder()
+                            .title("Import listening history")
+                            .modal(true)
+                            .filters(&filters)
+                            .build();
+                        if let Ok(file) = dialog
+                            .open_future(this.root().and_downcast_ref::<gtk::Window>())
+                            .await
+                        {
+                            if let Some(path) = file.path() {
+                                this.set_history_busy(true);
+                                client.queue_background(BackgroundTask::ImportHistory(path), true);
+                            }
+                        }
+                    }
+                ));
+            }
+        ));
+
+        client_state.connect_closure(
+            "history-exported",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |_: ClientState, boxed: BoxedAnyObject| {
+                    this.set_history_busy(false);
+                    let msg = match boxed.borrow::<Result<usize, String>>().as_ref() {
+                        Ok(n) => format!("Exported {n} listen(s)"),
+                        Err(e) => format!("Unable to export listening history: {e}"),
+                    };
+                    this.send_toast(&msg);
+                }
+            ),
+        );
+        client_state.connect_closure(
+            "history-imported",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                #[weak]
+                player,
+                move |_: ClientState, boxed: BoxedAnyObject| {
+                    this.set_history_busy(false);
+                    match boxed.borrow::<Result<ImportReport, String>>().as_ref() {
+                        Ok(report) => {
+                            if report.imported > 0 {
+                                // Refresh the recent & statistics views
+                                player.emit_by_name::<()>("history-changed", &[]);
+                            }
+                            this.show_import_report(report);
+                        }
+                        Err(e) => {
+                            this.send_toast(&format!("Unable to import listening history: {e}"));
+                        }
+                    }
+                }
+            ),
+        );
+    }
+
+    fn set_history_busy(&self, busy: bool) {
+        let imp = self.imp();
+        imp.export_history_json.set_sensitive(!busy);
+        imp.export_history_csv.set_sensitive(!busy);
+        imp.import_history_stack
+            .set_visible_child_name(if busy { "busy" } else { "idle" });
+    }
+
+    fn send_toast(&self, title: &str) {
+        if let Some(dialog) = self.ancestor(adw::PreferencesDialog::static_type()) {
+            dialog
+                .downcast_ref::<adw::PreferencesDialog>()
+                .unwrap()
+                .add_toast(adw::Toast::builder().title(title).timeout(3).build());
+        }
+    }
+
+    fn show_import_report(&self, report: &ImportReport) {
+        let n_unmatched = report.n_unmatched();
+        let diag = adw::AlertDialog::builder()
+            .heading("History imported")
+            .body(format!(
+                "{} listen(s) imported, {} already in your history and {} not found in your library.",
+                report.imported, report.duplicates, n_unmatched
+            ))
+            .build();
+        diag.add_response("close", "_Close");
+        if n_unmatched > 0 {
+            diag.set_extra_child(Some(&utils::report_list(
+                report
+                    .unmatched
+                    .iter()
+                    .map(|(artist, title, count)| (title.as_str(), artist.as_str(), format!("×{count}"))),
+            )));
+        }
+        diag.present(Some(self));
+    }
+
+    fn setup_stickers(&self, client: Rc<MpdWrapper>) {
+        let imp = self.imp();
+        let client_state = client.get_client_state();
+        client_state
+            .bind_property("stickers-support-level", &imp.backup_stickers_btn.get(), "sensitive")
+            .transform_to(|_, lvl: StickersSupportLevel| Some(lvl != StickersSupportLevel::Disabled))
+            .sync_create()
+            .build();
+        client_state
+            .bind_property("stickers-support-level", &imp.restore_stickers_btn.get(), "sensitive")
+            .transform_to(|_, lvl: StickersSupportLevel| Some(lvl != StickersSupportLevel::Disabled))
+            .sync_create()
+            .build();
+
+        imp.backup_stickers_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            client,
+            move |_| {
+                glib::spawn_future_local(clone!(
+                    #[weak]
+                    this,
+                    #[strong]
+                    client,
+                    async move {
+                        let dialog = gtk::FileDialog::builder()
+                            .title("Back up stickers")
+                            .modal(true)
+                            .initial_name("euphonica-stickers.json")
+                            .build();
+                        if let Ok(file) = dialog
+                            .save_future(this.root().and_downcast_ref::<gtk::Window>())
+                            .await
+                        {
+                            if let Some(path) = file.path() {
+                                this.imp().backup_stickers_btn.set_sensitive(false);
+                                client.queue_background(
+                                    BackgroundTask::BackupStickers(utils::with_extension(path, "json")),
+                                    true,
+                                );
+                            }
+                        }
+                    }
+                ));
+            }
+        ));
+
+        imp.restore_stickers_btn.connect_clicked(clone!(
+            #[weak(rename_to = this)]
+            self,
+            #[strong]
+            client,
+            move |_| {
+                glib::spawn_future_local(clone!(
+                    #[weak]
+                    this,
+                    #[strong]
+                    client,
+                    async move {
+                        let filter = gtk::FileFilter::new();
+                        filter.set_name(Some("Sticker backups"));
+                        filter.add_pattern("*.json");
+                        let filters = gio::ListStore::new::<gtk::FileFilter>();
+                        filters.append(&filter);
+                        let dialog = gtk::FileDialog::builder()
+                            .title("Restore stickers")
+                            .modal(true)
+                            .filters(&filters)
+                            .build();
+                        if let Ok(file) = dialog
+                            .open_future(this.root().and_downcast_ref::<gtk::Window>())
+                            .await
+                        {
+                            if let Some(path) = file.path() {
+                                // Preview first
+                                this.imp().restore_stickers_stack.set_visible_child_name("busy");
+                                this.imp().pending_sticker_restore.replace(Some(path.clone()));
+                                client.queue_background(BackgroundTask::RestoreStickers(path, true), true);
+                            }
+                        }
+                    }
+                ));
+            }
+        ));
+
+        client_state.connect_closure(
+            "stickers-backed-up",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                move |state: ClientState, boxed: BoxedAnyObject| {
+                    this.imp().backup_stickers_btn.set_sensitive(
+                        state.get_stickers_support_level() != StickersSupportLevel::Disabled,
+                    );
+                    let msg = match boxed.borrow::<Result<usize, String>>().as_ref() {
+                        Ok(n) => format!("Backed up {n} sticker(s)"),
+                        Err(e) => format!("Unable to back up stickers: {e}"),
+                    };
+                    this.send_toast(&msg);
+                }
+            ),
+        );
+        client_state.connect_closure(
+            "stickers-restored",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                self,
+                #[strong]
+                client,
+                move |_: ClientState, boxed: BoxedAnyObject| {
+                    this.imp().restore_stickers_stack.set_visible_child_name("idle");
+                    match boxed.borrow::<Result<StickerRestoreReport, String>>().as_ref() {
+                        Ok(report) if report.dry_run => {
+                            this.show_sticker_restore_preview(report, client.clone());
+                        }
+                        Ok(report) => {
+                            this.send_toast(&format!("Restored {} sticker(s)", report.n_stickers));
+                        }
+                        Err(e) => {
+                            this.imp().pending_sticker_restore.take();
+                            this.send_toast(&format!("Unable to restore stickers: {e}"));
+                        }
+                    }
+                }
+            ),
+        );
+    }
+
+    fn show_sticker_restore_preview(&self, report: &StickerRestoreReport, client: Rc<MpdWrapper>) {
+        let n_missing =
+            report.songs_missing.len() + report.albums_missing.len() + report.playlists_missing.len();
+        let mut body = format!(
+            "{} sticker(s) will be written to {} song(s) at the same location, {} song(s) found elsewhere, {} album(s) and {} playlist(s). Existing values will be overwritten.",
+            report.n_stickers,
+            report.songs_kept,
+            report.songs_remapped.len(),
+            report.albums_restored,
+            report.playlists_restored
+        );
+        if n_missing > 0 {
+            body.push_str(&format!(
+                "\n\n{} song(s), {} album(s) and {} playlist(s) could not be found and will be skipped.",
+                report.songs_missing.len(),
+                report.albums_missing.len(),
+                report.playlists_missing.len()
+            ));
+        }
+        let diag = adw::AlertDialog::builder()
+            .heading("Restore Stickers?")
+            .body(body)
+            .build();
+        diag.add_response("cancel", "_Cancel");
+        diag.add_response("restore", "_Restore");
+        diag.set_response_appearance("restore", adw::ResponseAppearance::Suggested);
+        diag.set_response_enabled("restore", report.n_stickers > 0);
+        diag.set_default_response(Some("cancel"));
+        diag.set_close_response("cancel");
+        if !report.songs_remapped.is_empty() || n_missing > 0 {
+            let remapped = report
+                .songs_remapped
+                .iter()
+                .map(|(old, new)| (new.as_str(), old.as_str(), "Moved".to_owned()));
+            let missing = report
+                .songs_missing
+                .iter()
+                .map(|uri| (uri.as_str(), "Song", "Not found".to_owned()))
+                .chain(
+                    report
+                        .albums_missing
+                        .iter()
+                        .map(|name| (name.as_str(), "Album", "Not found".to_owned())),
+                )
+                .chain(
+                    report
+                        .playlists_missing
+                        .iter()
+                        .map(|name| (name.as_str(), "Playlist", "Not found".to_owned())),
+                );
+            diag.set_extra_child(Some(&utils::report_list(missing.chain(remapped))));
+        }
+        diag.choose(
+            self,
+            Option::<gio::Cancellable>::None.as_ref(),
+            clone!(
+                #[weak(rename_to = this)]
+                self,
+                move |resp| {
+                    let path = this.imp().pending_sticker_restore.take();
+                    if let (Some(path), "restore") = (path, resp.as_str()) {
+                        this.imp().restore_stickers_stack.set_visible_child_name("busy");
+                        client.queue_background(BackgroundTask::RestoreStickers(path, false), true);
+                    }
+                }
+            ),
+        );
     }
 
     pub fn refresh_cache_stats(&self) {
@@ -0,0 +1,196 @@
+use gtk::{glib, prelude::*};
+use reqwest::{
+    blocking::{Client, Response},
+    header::USER_AGENT,
+};
+use serde::de::DeserializeOwned;
+
+use crate::{
+    config::APPLICATION_USER_AGENT,
+    meta_providers::lastfm::models::{
+        LastfmErrorResponse, LastfmSessionResponse, LastfmTokenResponse,
+    },
+    utils::meta_provider_settings,
+};
+
+use super::{get_token_sync, Scrobble, ScrobbleError, ScrobbleService};
+
+// Shares its API key & settings with the Last.fm metadata provider.
+pub const SERVICE_KEY: &str = "lastfm";
+const API_ROOT: &str = "https://ws.audioscrobbler.com/2.0/";
+const AUTH_URL: &str = "https://www.last.fm/api/auth/";
+const MAX_BATCH: usize = 50;
+
+fn get_credentials() -> Option<(String, String)> {
+    let settings = meta_provider_settings(SERVICE_KEY);
+    let key = settings.string("api-key");
+    let secret = settings.string("api-secret");
+    if key.is_empty() || secret.is_empty() {
+        None
+    } else {
+        Some((key.to_string(), secret.to_string()))
+    }
+}
+
+/// Call an authenticated method. Parameters are signed as described in
+/// https://www.last.fm/api/desktopauth: concatenate them sorted by name,
+/// append the shared secret, then take the MD5 hash.
+fn call(
+    client: &Client,
+    method: &str,
+    mut params: Vec<(String, String)>,
+    post: bool,
+) -> Result<Response, ScrobbleError> {
+    let (key, secret) = get_credentials()
+        .ok_or_else(|| ScrobbleError::Retry("No API key or shared secret".to_owned()))?;
+    params.push(("method".to_owned(), method.to_owned()));
+    params.push(("api_key".to_owned(), key));
+    params.sort_by(|a, b| a.0.cmp(&b.0));
+    let mut raw = String::new();
+    for (name, val) in params.iter() {
+        raw.push_str(name);
+        raw.push_str(val);
+    }
+    raw.push_str(&secret);
+    let sig = glib::compute_checksum_for_string(glib::ChecksumType::Md5, &raw)
+        .expect("Unable to compute MD5 checksum");
+    params.push(("api_sig".to_owned(), sig.to_string()));
+    // Not part of the signature
+    params.push(("format".to_owned(), "json".to_owned()));
+
+    println!("[Last.fm] Calling `{}`", method);
+    let req = if post {
+        client.post(API_ROOT).form(&params)
+    } else {
+        client.get(API_ROOT).query(&params)
+    };
+    req.header(USER_AGENT, APPLICATION_USER_AGENT)
+        .send()
+        .map_err(|e| ScrobbleError::Retry(e.to_string()))
+}
+
+fn parse<T: DeserializeOwned>(resp: Response) -> Result<T, ScrobbleError> {
+    let status = resp.status();
+    let text = resp.text().map_err(|e| ScrobbleError::Retry(e.to_string()))?;
+    if let Ok(err) = serde_json::from_str::<LastfmErrorResponse>(&text) {
+        return Err(match err.error {
+            // Invalid parameters. Other errors (bad session, rate limiting, service
+            // offline, etc.) might go away later.
+            6 => ScrobbleError::Rejected(err.message),
+            code => ScrobbleError::Retry(format!("error {}: {}", code, err.message)),
+        });
+    }
+    if !status.is_success() {
+        return Err(ScrobbleError::Retry(format!("failed with status {:?}", status)));
+    }
+    serde_json::from_str::<T>(&text).map_err(|e| ScrobbleError::Retry(e.to_string()))
+}
+
+/// First step of desktop authentication. Returns the page on which the user should
+/// grant us access, along with the token to pass to `finish_auth` afterwards.
+pub fn begin_auth() -> Result<(String, String), String> {
+    let client = Client::new();
+    let token = call(&client, "auth.getToken", Vec::new(), false)
+        .and_then(parse::<LastfmTokenResponse>)
+        .map_err(|e| e.to_string())?
+        .token;
+    let key = meta_provider_settings(SERVICE_KEY).string("api-key");
+    Ok((format!("{}?api_key={}&token={}", AUTH_URL, key, token), token))
+}
+
+/// Exchange an authorised token for a session, returning the username & session key.
+/// Session keys don't expire, so this only needs to be done once.
+pub fn finish_auth(token: &str) -> Result<(String, String), String> {
+    let client = Client::new();
+    let session = call(
+        &client,
+        "auth.getSession",
+        vec![("token".to_owned(), token.to_owned())],
+        false,
+    )
+    .and_then(parse::<LastfmSessionResponse>)
+    .map_err(|e| e.to_string())?
+    .session;
+    Ok((session.name, session.key))
+}
+
+/// Track parameters, with array notation if an index is given (for batch scrobbling).
+fn track_params(scrobble: &Scrobble, idx: Option<usize>) -> Vec<(String, String)> {
+    let name = |key: &str| match idx {
+        Some(idx) => format!("{}[{}]", key, idx),
+        None => key.to_owned(),
+    };
+    let mut params = vec![
+        (name("artist"), scrobble.artist.clone()),
+        (name("track"), scrobble.title.clone()),
+    ];
+    if let Some(album) = scrobble.album.as_ref() {
+        params.push((name("album"), album.clone()));
+    }
+    if let Some(album_artist) = scrobble.album_artist.as_ref() {
+        params.push((name("albumArtist"), album_artist.clone()));
+    }
+    if let Some(track) = scrobble.track_number {
+        params.push((name("trackNumber"), track.to_string()));
+    }
+    if let Some(duration) = scrobble.duration {
+        params.push((name("duration"), duration.to_string()));
+    }
+    if let Some(mbid) = scrobble.recording_mbid.as_ref() {
+        params.push((name("mbid"), mbid.clone()));
+    }
+    params
+}
+
+pub struct LastfmScrobbler {
+    client: Client,
+}
+
+impl LastfmScrobbler {
+    pub fn new() -> Self {
+        Self {
+            client: Client::new(),
+        }
+    }
+
+    fn session_key(&self) -> Result<String, ScrobbleError> {
+        get_token_sync(SERVICE_KEY)
+            .ok_or_else(|| ScrobbleError::Retry("Not logged in".to_owned()))
+    }
+}
+
+impl ScrobbleService for LastfmScrobbler {
+    fn key(&self) -> &'static str {
+        SERVICE_KEY
+    }
+
+    fn is_enabled(&self) -> bool {
+        meta_provider_settings(SERVICE_KEY).boolean("scrobble")
+    }
+
+    fn max_batch(&self) -> usize {
+        MAX_BATCH
+    }
+
+    fn now_playing(&self, scrobble: &Scrobble) -> Result<(), ScrobbleError> {
+        let mut params = track_params(scrobble, None);
+        params.push(("sk".to_owned(), self.session_key()?));
+        call(&self.client, "track.updateNowPlaying", params, true)
+            .and_then(parse::<serde_json::Value>)
+            .map(|_| ())
+    }
+
+    /// Listens ignored by Last.fm (too old, filtered, etc.) are not reported as errors,
+    /// since resubmitting them won't change anything.
+    fn submit(&self, scrobbles: &[Scrobble]) -> Result<(), ScrobbleError> {
+        let mut params: Vec<(String, String)> = Vec::new();
+        for (idx, scrobble) in scrobbles.iter().enumerate() {
+            params.extend(track_params(scrobble, Some(idx)));
+            params.push((format!("timestamp[{}]", idx), scrobble.timestamp.to_string()));
+        }
+        params.push(("sk".to_owned(), self.session_key()?));
+        call(&self.client, "track.scrobble", params, true)
+            .and_then(parse::<serde_json::Value>)
+            .map(|_| ())
+    }
+}
@@ -0,0 +1,265 @@
+use gtk::{gio, prelude::*};
+use reqwest::{
+    blocking::{Client, RequestBuilder, Response},
+    header::{AUTHORIZATION, USER_AGENT},
+    StatusCode,
+};
+use serde::Deserialize;
+use serde_json::{Map, Value, json};
+
+use crate::{
+    config::{APPLICATION_USER_AGENT, VERSION},
+    utils::settings_manager,
+};
+
+use super::{Scrobble, ScrobbleError, ScrobbleService, get_token_sync};
+
+// Also works with self-hosted servers implementing the same API, such as Maloja or Koito.
+pub const SERVICE_KEY: &str = "listenbrainz";
+// ListenBrainz accepts up to 1000 listens per request, but keep requests reasonably small.
+const MAX_BATCH: usize = 100;
+
+#[derive(Deserialize)]
+struct ValidateTokenResponse {
+    valid: bool,
+    user_name: Option<String>,
+}
+
+fn settings() -> gio::Settings {
+    settings_manager().child(SERVICE_KEY)
+}
+
+/// Configured API root, without trailing slashes.
+fn api_root() -> String {
+    trim_api_root(settings().string("api-root").as_str()).to_owned()
+}
+
+fn trim_api_root(root: &str) -> &str {
+    root.trim_end_matches('/')
+}
+
+fn authorize(req: RequestBuilder, token: &str) -> RequestBuilder {
+    req.header(USER_AGENT, APPLICATION_USER_AGENT)
+        .header(AUTHORIZATION, format!("Token {}", token))
+}
+
+fn check(resp: Response) -> Result<(), ScrobbleError> {
+    let status = resp.status();
+    if status.is_success() {
+        return Ok(());
+    }
+    let msg = resp
+        .json::<Value>()
+        .ok()
+        .and_then(|body| body.get("error").and_then(Value::as_str).map(str::to_owned))
+        .unwrap_or_else(|| format!("failed with status {:?}", status));
+    Err(status_error(status, msg))
+}
+
+fn status_error(status: StatusCode, msg: String) -> ScrobbleError {
+    // Malformed listens are reported as 400. Bad tokens (401), rate limiting (429) and
+    // server errors might go away later.
+    if status == StatusCode::BAD_REQUEST {
+        ScrobbleError::Rejected(msg)
+    } else {
+        ScrobbleError::Retry(msg)
+    }
+}
+
+/// Check a user token against the configured server, returning the username it belongs to.
+pub fn validate_token(token: &str) -> Result<String, String> {
+    let resp = authorize(
+        Client::new().get(format!("{}/1/validate-token", api_root())),
+        token,
+    )
+    .send()
+    .map_err(|e| e.to_string())?;
+    match resp.json::<ValidateTokenResponse>() {
+        Ok(ValidateTokenResponse {
+            valid: true,
+            user_name: Some(name),
+        }) => Ok(name),
+        Ok(_) => Err("Invalid token".to_owned()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Listen payload as described in https://listenbrainz.readthedocs.io/en/latest/users/json.html.
+/// Playing-now payloads must not have a timestamp.
+fn listen_payload(scrobble: &Scrobble, with_timestamp: bool) -> Value {
+    let mut additional_info = Map::new();
+    additional_info.insert("media_player".to_owned(), json!("Euphonica"));
+    additional_info.insert("submission_client".to_owned(), json!("Euphonica"));
+    additional_info.insert("submission_client_version".to_owned(), json!(VERSION));
+    if let Some(mbid) = scrobble.recording_mbid.as_ref() {
+        additional_info.insert("recording_mbid".to_owned(), json!(mbid));
+    }
+    if let Some(mbid) = scrobble.release_mbid.as_ref() {
+        additional_info.insert("release_mbid".to_owned(), json!(mbid));
+    }
+    if let Some(track) = scrobble.track_number {
+        additional_info.insert("tracknumber".to_owned(), json!(track));
+    }
+    if let Some(duration) = scrobble.duration {
+        additional_info.insert("duration_ms".to_owned(), json!(duration * 1000));
+    }
+
+    let mut track_metadata = Map::new();
+    track_metadata.insert("artist_name".to_owned(), json!(scrobble.artist));
+    track_metadata.insert("track_name".to_owned(), json!(scrobble.title));
+    if let Some(album) = scrobble.album.as_ref() {
+        track_metadata.insert("release_name".to_owned(), json!(album));
+    }
+    track_metadata.insert("additional_info".to_owned(), Value::Object(additional_info));
+
+    let mut listen = Map::new();
+    if with_timestamp {
+        listen.insert("listened_at".to_owned(), json!(scrobble.timestamp));
+    }
+    listen.insert("track_metadata".to_owned(), Value::Object(track_metadata));
+    Value::Object(listen)
+}
+
+pub struct ListenBrainzScrobbler {
+    client: Client,
+}
+
+impl ListenBrainzScrobbler {
+    pub fn new() -> Self {
+        Self {
+            client: Client::new(),
+        }
+    }
+
+    fn submit_listens(&self, listen_type: &str, payload: Vec<Value>) -> Result<(), ScrobbleError> {
+        let token = get_token_sync(SERVICE_KEY)
+            .ok_or_else(|| ScrobbleError::Retry("No user token".to_owned()))?;
+        println!(
+            "[ListenBrainz] Submitting {} listen(s) of type {}",
+            payload.len(),
+            listen_type
+        );
+        let resp = authorize(
+            self.client.post(format!("{}/1/submit-listens", api_root())),
+            &token,
+        )
+        .json(&json!({
+            "listen_type": listen_type,
+            "payload": payload,
+        }))
+        .send()
+        .map_err(|e| ScrobbleError::Retry(e.to_string()))?;
+        check(resp)
+    }
+}
+
+impl ScrobbleService for ListenBrainzScrobbler {
+    fn key(&self) -> &'static str {
+        SERVICE_KEY
+    }
+
+    fn is_enabled(&self) -> bool {
+        settings().boolean("enabled")
+    }
+
+    fn max_batch(&self) -> usize {
+        MAX_BATCH
+    }
+
+    fn now_playing(&self, scrobble: &Scrobble) -> Result<(), ScrobbleError> {
+        self.submit_listens("playing_now", vec![listen_payload(scrobble, false)])
+    }
+
+    fn submit(&self, scrobbles: &[Scrobble]) -> Result<(), ScrobbleError> {
+        let listen_type = if scrobbles.len() == 1 {
+            "single"
+        } else {
+            "import"
+        };
+        self.submit_listens(
+            listen_type,
+            scrobbles
+                .iter()
+                .map(|scrobble| listen_payload(scrobble, true))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scrobble() -> Scrobble {
+        Scrobble {
+            artist: "Artist".to_owned(),
+            title: "Title".to_owned(),
+            album: None,
+            album_artist: None,
+            track_number: None,
+            duration: None,
+            recording_mbid: None,
+            release_mbid: None,
+            timestamp: 1700000000,
+        }
+    }
+
+    #[test]
+    fn api_root_trailing_slashes_trimmed() {
+        assert_eq!(trim_api_root("https://api.listenbrainz.org"), "https://api.listenbrainz.org");
+        assert_eq!(trim_api_root("https://api.listenbrainz.org/"), "https://api.listenbrainz.org");
+        assert_eq!(
+            trim_api_root("http://localhost:42010/apis/listenbrainz//"),
+            "http://localhost:42010/apis/listenbrainz"
+        );
+    }
+
+    #[test]
+    fn playing_now_has_no_timestamp() {
+        let listen = listen_payload(&scrobble(), false);
+        assert!(listen.get("listened_at").is_none());
+        let listen = listen_payload(&scrobble(), true);
+        assert_eq!(listen["listened_at"], json!(1700000000));
+    }
+
+    #[test]
+    fn optional_fields_only_when_present() {
+        let listen = listen_payload(&scrobble(), true);
+        let info = &listen["track_metadata"]["additional_info"];
+        assert!(info.get("duration_ms").is_none());
+        assert!(info.get("recording_mbid").is_none());
+        assert!(info.get("release_mbid").is_none());
+        assert!(listen["track_metadata"].get("release_name").is_none());
+
+        let mut full = scrobble();
+        full.album = Some("Album".to_owned());
+        full.duration = Some(215);
+        full.recording_mbid = Some("recording".to_owned());
+        full.release_mbid = Some("release".to_owned());
+        let listen = listen_payload(&full, true);
+        let metadata = &listen["track_metadata"];
+        assert_eq!(metadata["artist_name"], json!("Artist"));
+        assert_eq!(metadata["track_name"], json!("Title"));
+        assert_eq!(metadata["release_name"], json!("Album"));
+        let info = &metadata["additional_info"];
+        assert_eq!(info["duration_ms"], json!(215000));
+        assert_eq!(info["recording_mbid"], json!("recording"));
+        assert_eq!(info["release_mbid"], json!("release"));
+    }
+
+    #[test]
+    fn only_bad_requests_are_rejected() {
+        assert!(matches!(
+            status_error(StatusCode::BAD_REQUEST, String::new()),
+            ScrobbleError::Rejected(_)
+        ));
+        for status in [
+            StatusCode::UNAUTHORIZED,
+            StatusCode::TOO_MANY_REQUESTS,
+            StatusCode::INTERNAL_SERVER_ERROR,
+            StatusCode::SERVICE_UNAVAILABLE,
+        ] {
+            assert!(matches!(status_error(status, String::new()), ScrobbleError::Retry(_)));
+        }
+    }
+}
@@ -0,0 +1,256 @@
+pub mod lastfm;
+pub mod listenbrainz;
+
+use std::collections::HashMap;
+
+use async_channel::{Receiver, Sender};
+use gtk::{gio, glib};
+use libsecret::*;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::{
+    cache::sqlite,
+    common::{sticker, Song},
+    config::APPLICATION_ID,
+};
+
+use lastfm::LastfmScrobbler;
+use listenbrainz::ListenBrainzScrobbler;
+
+// Scrobbling services submit listens from a single background thread. A listen is
+// always written to the submission queue in the local SQLite DB first and only
+// removed once a service has accepted it, so listens survive being offline and
+// application restarts. Failed submissions are retried periodically, in batches.
+
+/// Tracks shorter than this (in seconds) are never scrobbled.
+pub const MIN_DURATION: u64 = 30;
+/// How often to retry queued submissions, in seconds.
+const RETRY_INTERVAL: u32 = 300;
+
+/// Whether a song of the given duration has been played for long enough to be scrobbled.
+/// Past the minimum duration, this follows the same rule as play counts.
+pub fn is_scrobblable(duration: u64, elapsed: f64) -> bool {
+    duration >= MIN_DURATION && elapsed >= sticker::required_playtime(duration)
+}
+
+/// A single listen, as kept in the submission queue.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scrobble {
+    pub artist: String,
+    pub title: String,
+    pub album: Option<String>,
+    pub album_artist: Option<String>,
+    pub track_number: Option<u32>,
+    pub duration: Option<u64>, // Seconds
+    pub recording_mbid: Option<String>,
+    pub release_mbid: Option<String>,
+    pub timestamp: i64, // When playback started, as a UNIX timestamp
+}
+
+impl Scrobble {
+    /// Songs without an artist tag cannot be scrobbled.
+    pub fn from_song(song: &Song, started: OffsetDateTime) -> Option<Self> {
+        let artist = song.get_artist_tag()?.to_owned();
+        let album = song.get_album();
+        Some(Self {
+            artist,
+            title: song.get_name().to_owned(),
+            album: album.map(|album| album.title.clone()),
+            album_artist: album.and_then(|album| album.albumartist.clone()),
+            track_number: u32::try_from(song.get_track()).ok().filter(|track| *track > 0),
+            duration: Some(song.get_duration()).filter(|dur| *dur > 0),
+            recording_mbid: song.get_mbid().map(str::to_owned),
+            release_mbid: album.and_then(|album| album.mbid.clone()),
+            timestamp: started.unix_timestamp(),
+        })
+    }
+}
+
+#[derive(Debug)]
+pub enum ScrobbleError {
+    /// Network problems, rate limiting, missing credentials, etc. Try again later.
+    Retry(String),
+    /// The service refused the submission. Retrying won't help.
+    Rejected(String),
+}
+
+impl std::fmt::Display for ScrobbleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Retry(msg) => write!(f, "{msg} (will retry)"),
+            Self::Rejected(msg) => write!(f, "{msg} (rejected)"),
+        }
+    }
+}
+
+pub trait ScrobbleService {
+    /// Identifies this service's entries in the submission queue and keyring.
+    fn key(&self) -> &'static str;
+    /// Whether the user wants listens to be sent to this service.
+    fn is_enabled(&self) -> bool;
+    /// Maximum number of listens that can be submitted in one go.
+    fn max_batch(&self) -> usize;
+    fn now_playing(&self, scrobble: &Scrobble) -> Result<(), ScrobbleError>;
+    fn submit(&self, scrobbles: &[Scrobble]) -> Result<(), ScrobbleError>;
+}
+
+fn get_services() -> Vec<Box<dyn ScrobbleService>> {
+    vec![
+        Box::new(LastfmScrobbler::new()),
+        Box::new(ListenBrainzScrobbler::new()),
+    ]
+}
+
+enum ScrobbleMessage {
+    NowPlaying(Scrobble),
+    Scrobble(Scrobble),
+    Flush, // Retry queued submissions
+}
+
+pub struct Scrobbler {
+    sender: Sender<ScrobbleMessage>,
+}
+
+impl Scrobbler {
+    pub fn new() -> Self {
+        let (sender, receiver) = async_channel::unbounded::<ScrobbleMessage>();
+        gio::spawn_blocking(move || run(receiver));
+        let flush_sender = sender.clone();
+        glib::timeout_add_seconds_local(RETRY_INTERVAL, move || {
+            if flush_sender.send_blocking(ScrobbleMessage::Flush).is_ok() {
+                glib::ControlFlow::Continue
+            } else {
+                glib::ControlFlow::Break
+            }
+        });
+        // Submit whatever was left over from last time
+        let _ = sender.send_blocking(ScrobbleMessage::Flush);
+        Self { sender }
+    }
+
+    pub fn now_playing(&self, song: &Song) {
+        if let Some(scrobble) = Scrobble::from_song(song, OffsetDateTime::now_utc()) {
+            let _ = self.sender.send_blocking(ScrobbleMessage::NowPlaying(scrobble));
+        }
+    }
+
+    /// Submit a listen that started at the given time.
+    pub fn scrobble(&self, song: &Song, started: OffsetDateTime) {
+        if let Some(scrobble) = Scrobble::from_song(song, started) {
+            let _ = self.sender.send_blocking(ScrobbleMessage::Scrobble(scrobble));
+        }
+    }
+}
+
+fn run(receiver: Receiver<ScrobbleMessage>) {
+    let services = get_services();
+    while let Ok(msg) = receiver.recv_blocking() {
+        match msg {
+            ScrobbleMessage::NowPlaying(scrobble) => {
+                // Not worth retrying as the song will likely have ended by then
+                for service in services.iter().filter(|service| service.is_enabled()) {
+                    if let Err(e) = service.now_playing(&scrobble) {
+                        println!("[Scrobbler] {}: now playing: {}", service.key(), e);
+                    }
+                }
+            }
+            ScrobbleMessage::Scrobble(scrobble) => {
+                for service in services.iter().filter(|service| service.is_enabled()) {
+                    sqlite::queue_scrobble(service.key(), &scrobble).expect("Sqlite DB error");
+                }
+                flush(&services);
+            }
+            ScrobbleMessage::Flush => {
+                flush(&services);
+            }
+        }
+    }
+}
+
+/// Submit queued listens of each enabled service in batches, oldest first, until
+/// either the queue is empty or a submission fails.
+fn flush(services: &[Box<dyn ScrobbleService>]) {
+    for service in services.iter().filter(|service| service.is_enabled()) {
+        loop {
+            let batch = match sqlite::get_queued_scrobbles(service.key(), service.max_batch() as u32) {
+                Ok(batch) => batch,
+                Err(e) => {
+                    println!("[Scrobbler] {}: unable to read queued listens: {:?}", service.key(), e);
+                    break;
+                }
+            };
+            if batch.is_empty() {
+                break;
+            }
+            let (ids, scrobbles): (Vec<i64>, Vec<Scrobble>) = batch.into_iter().unzip();
+            match service.submit(&scrobbles) {
+                Ok(()) => {
+                    sqlite::remove_queued_scrobbles(&ids).expect("Sqlite DB error");
+                }
+                Err(e @ ScrobbleError::Rejected(_)) => {
+                    println!("[Scrobbler] {}: dropping {} listen(s): {}", service.key(), ids.len(), e);
+                    sqlite::remove_queued_scrobbles(&ids).expect("Sqlite DB error");
+                }
+                Err(e) => {
+                    println!("[Scrobbler] {}: {}", service.key(), e);
+                    break;
+                }
+            }
+        }
+    }
+}
+
+// Session keys & user tokens are kept in the keyring, one slot per service.
+fn get_token_schema() -> Schema {
+    let mut attributes = HashMap::new();
+    attributes.insert("type", SchemaAttributeType::String);
+    attributes.insert("service", SchemaAttributeType::String);
+
+    Schema::new(APPLICATION_ID, SchemaFlags::NONE, attributes)
+}
+
+fn get_token_attributes(service: &str) -> HashMap<&str, &str> {
+    let mut attributes = HashMap::new();
+    attributes.insert("type", "scrobbler");
+    attributes.insert("service", service);
+    attributes
+}
+
+/// Blocking. Only call from the scrobbler thread.
+fn get_token_sync(service: &str) -> Option<String> {
+    let schema = get_token_schema();
+    libsecret::password_lookup_sync(
+        Some(&schema),
+        get_token_attributes(service),
+        gio::Cancellable::NONE,
+    )
+        .map_err(|ge| println!("[Scrobbler] {}: cannot read keyring: {:?}", service, ge))
+        .ok()
+        .flatten()
+        .map(|gs| gs.as_str().to_owned())
+}
+
+pub async fn set_token(service: &str, maybe_token: Option<&str>) -> Result<(), String> {
+    let schema = get_token_schema();
+    let attributes = get_token_attributes(service);
+
+    if let Some(token) = maybe_token {
+        libsecret::password_store_future(
+            Some(&schema),
+            attributes,
+            None,
+            &format!("Euphonica scrobbling token ({})", service),
+            token
+        )
+            .await
+            .map_err(|ge| format!("{:?}", ge))
+    } else {
+        libsecret::password_clear_future(
+            Some(&schema),
+            attributes
+        )
+            .await
+            .map_err(|ge| format!("{:?}", ge))
+    }
+}
@@ -17,6 +17,10 @@ mod imp {
         #[template_child]
         pub recent_btn: TemplateChild<SidebarButton>,
         #[template_child]
+        pub search_btn: TemplateChild<SidebarButton>,
+        #[template_child]
+        pub stats_btn: TemplateChild<SidebarButton>,
+        #[template_child]
         pub albums_btn: TemplateChild<SidebarButton>,
         #[template_child]
         pub artists_btn: TemplateChild<SidebarButton>,
@@ -124,6 +128,30 @@ impl Sidebar {
             }
         ));
 
+        let search_view = win.get_search_view();
+        self.imp().search_btn.connect_toggled(clone!(
+            #[weak]
+            stack,
+            #[weak]
+            search_view,
+            move |btn| {
+                if btn.is_active() {
+                    stack.set_visible_child_name("search");
+                    search_view.focus_entry();
+                }
+            }
+        ));
+
+        self.imp().stats_btn.connect_toggled(clone!(
+            #[weak]
+            stack,
+            move |btn| {
+                if btn.is_active() {
+                    stack.set_visible_child_name("stats");
+                }
+            }
+        ));
+
         self.imp().albums_btn.connect_toggled(clone!(
             #[weak]
             stack,
@@ -277,6 +305,7 @@ impl Sidebar {
                 move |_| split_view.set_show_sidebar(!split_view.is_collapsed())
             ));
         for btn in [
+            &self.imp().search_btn.get(),
             &self.imp().albums_btn.get(),
             &self.imp().artists_btn.get(),
             &self.imp().folders_btn.get(),
@@ -301,8 +330,11 @@ impl Sidebar {
     pub fn set_view(&self, view_name: &str) {
         // TODO: something less dumb than this
         match view_name {
+            "search" => self.imp().search_btn.set_active(true),
+            "stats" => self.imp().stats_btn.set_active(true),
             "albums" => self.imp().albums_btn.set_active(true),
             "artists" => self.imp().artists_btn.set_active(true),
+            "playlists" => self.imp().playlists_btn.set_active(true),
             "queue" => self.imp().queue_btn.set_active(true),
             _ => unimplemented!(),
         };
@@ -1,14 +1,14 @@
 use crate::config::APPLICATION_ID;
 use aho_corasick::AhoCorasick;
 use gio::prelude::*;
-use gtk::gio;
+use gtk::{gio, glib};
 use gtk::Ordering;
 use image::{imageops::FilterType, ImageReader, DynamicImage, RgbImage};
 use mpd::status::AudioFormat;
 use once_cell::sync::Lazy;
 use std::sync::OnceLock;
 use std::fmt::Write;
-use std::{io::Cursor, sync::RwLock};
+use std::{io::Cursor, path::PathBuf, sync::RwLock};
 use tokio::runtime::Runtime;
 
 /// Spawn a Tokio runtime on a new thread. This is needed by the zbus dependency.
@@ -273,3 +273,50 @@ pub trait LazyInit {
     fn clear(&self);
     fn populate(&self);
 }
+
+// Only this many entries are listed in import & restore reports.
+const MAX_REPORT_ROWS: usize = 100;
+
+/// Make sure exports get the extension of the chosen format, as that's how it is
+/// decided in the background thread.
+pub fn with_extension(path: PathBuf, ext: &str) -> PathBuf {
+    if path
+        .extension()
+        .is_some_and(|curr| curr.eq_ignore_ascii_case(ext))
+    {
+        path
+    } else {
+        let mut path = path.into_os_string();
+        path.push(".");
+        path.push(ext);
+        PathBuf::from(path)
+    }
+}
+
+/// Scrollable list of titles, subtitles & suffix labels for report dialogs.
+pub fn report_list<'a>(rows: impl Iterator<Item = (&'a str, &'a str, String)>) -> gtk::ScrolledWindow {
+    use adw::prelude::*;
+    let list = gtk::ListBox::builder()
+        .selection_mode(gtk::SelectionMode::None)
+        .css_classes(["boxed-list"])
+        .build();
+    for (title, subtitle, suffix) in rows.take(MAX_REPORT_ROWS) {
+        let row = adw::ActionRow::builder()
+            .title(glib::markup_escape_text(title))
+            .subtitle(glib::markup_escape_text(subtitle))
+            .build();
+        row.add_suffix(
+            &gtk::Label::builder()
+                .label(suffix)
+                .css_classes(["dim-label", "numeric"])
+                .build(),
+        );
+        list.append(&row);
+    }
+    gtk::ScrolledWindow::builder()
+        .hscrollbar_policy(gtk::PolicyType::Never)
+        .propagate_natural_height(true)
+        .max_content_height(300)
+        .child(&list)
+        .build()
+}
@@ -20,12 +20,15 @@
 
 use crate::{
     application::EuphonicaApplication,
-    client::{ClientError, ClientState, ConnectionState},
-    common::{blend_mode::*, paintables::FadePaintable, Album, Artist},
-    library::{AlbumView, ArtistContentView, ArtistView, FolderView, PlaylistView, RecentView},
+    client::{profile, ClientError, ClientState, ConnectionState},
+    common::{blend_mode::*, paintables::FadePaintable, Album, Artist, INode},
+    library::{
+        AlbumView, ArtistContentView, ArtistView, FolderView, PlaylistView, RecentView,
+        SearchView, StatsView,
+    },
     player::{Player, PlayerBar, QueueView},
     sidebar::Sidebar,
-    utils::{self, LazyInit, settings_manager},
+    utils::{self, format_secs_as_duration, LazyInit, settings_manager},
 };
 use adw::{prelude::*, subclass::prelude::*, ColorScheme, StyleManager};
 use glib::signal::SignalHandlerId;
@@ -45,6 +48,7 @@ use std::{
 };
 
 use async_channel::Sender;
+use gettextrs::gettext;
 use glib::Properties;
 use image::ImageReader as Reader;
 
@@ -130,6 +134,10 @@ mod imp {
         #[template_child]
         pub recent_view: TemplateChild<RecentView>,
         #[template_child]
+        pub search_view: TemplateChild<SearchView>,
+        #[template_child]
+        pub stats_view: TemplateChild<StatsView>,
+        #[template_child]
         pub album_view: TemplateChild<AlbumView>,
         #[template_child]
         pub artist_view: TemplateChild<ArtistView>,
@@ -142,6 +150,8 @@ mod imp {
 
         #[template_child]
         pub menu_btn: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub profile_btn: TemplateChild<gtk::MenuButton>,
 
         // Content view stack
         #[template_child]
@@ -198,6 +208,8 @@ mod imp {
         pub fft_data: OnceCell<Arc<Mutex<(Vec<f32>, Vec<f32>)>>>,
         pub accent_color: RefCell<Option<RGB>>,
         pub should_populate_visible: Cell<bool>,
+        // Reused for consecutive edits instead of piling up one toast per edit.
+        pub undo_toast: RefCell<Option<adw::Toast>>,
 
         pub provider: CssProvider,
     }
@@ -364,6 +376,8 @@ mod imp {
             let view = self.split_view.get();
             [
                 self.recent_view.upcast_ref::<gtk::Widget>(),
+                self.search_view.upcast_ref::<gtk::Widget>(),
+                self.stats_view.upcast_ref::<gtk::Widget>(),
                 self.album_view.upcast_ref::<gtk::Widget>(),
                 self.artist_view.upcast_ref::<gtk::Widget>(),
                 self.folder_view.upcast_ref::<gtk::Widget>(),
@@ -853,13 +867,24 @@ impl EuphonicaWindow {
         win.restore_window_state();
         win.imp()
             .queue_view
-            .setup(app.get_player(), app.get_cache(), win.clone());
+            .setup(app.get_player(), app.get_library(), app.get_cache(), win.clone());
         win.imp().recent_view.setup(
             app.get_library(),
             app.get_player(),
             app.get_cache(),
             &win
         );
+        win.imp().search_view.setup(
+            app.get_library(),
+            app.get_cache(),
+            &win
+        );
+        win.imp().stats_view.setup(
+            app.get_library(),
+            app.get_player(),
+            app.get_client().get_client_state(),
+            &win
+        );
         win.imp().album_view.setup(
             app.get_library(),
             app.get_cache(),
@@ -918,6 +943,32 @@ impl EuphonicaWindow {
                 }
             ),
         );
+        client_state.connect_closure(
+            "playlist-file-exported",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                win,
+                move |_: ClientState, boxed: BoxedAnyObject| {
+                    let msg = match boxed.borrow::<Result<usize, String>>().as_ref() {
+                        Ok(n) => format!("Exported {n} song(s)"),
+                        Err(e) => format!("Unable to export playlist: {e}"),
+                    };
+                    this.send_simple_toast(&msg, 3);
+                }
+            ),
+        );
+        client_state.connect_closure(
+            "edit-recorded",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                win,
+                move |_: ClientState, label: String| {
+                    this.send_undo_toast(&label, false);
+                }
+            ),
+        );
         win.handle_connection_state(client_state.get_connection_state());
         client_state.connect_notify_local(
             Some("connection-state"),
@@ -929,6 +980,18 @@ impl EuphonicaWindow {
                 }
             )
         );
+        client_state.connect_notify_local(
+            Some("reconnect-attempt"),
+            clone!(
+                #[weak(rename_to = this)]
+                win,
+                move |state: &ClientState, _| {
+                    if state.get_connection_state() == ConnectionState::Reconnecting {
+                        this.update_reconnecting_subtitle();
+                    }
+                }
+            )
+        );
 
         player.connect_closure(
             "cover-changed",
@@ -941,6 +1004,17 @@ impl EuphonicaWindow {
                 }
             )
         );
+        player.connect_closure(
+            "resume-available",
+            false,
+            closure_local!(
+                #[weak(rename_to = this)]
+                win,
+                move |player: Player, queue_id: u32, position: f64| {
+                    this.offer_resume(&player, queue_id, position);
+                }
+            )
+        );
         let _ = win.imp().player.set(player);
 
         win.imp().stack.connect_visible_child_name_notify(
@@ -977,6 +1051,17 @@ impl EuphonicaWindow {
             ),
         );
 
+        win.update_profile_menu();
+        settings_manager().child("client").connect_changed(
+            Some("mpd-profiles"),
+            clone!(
+                #[weak(rename_to = this)]
+                win,
+                move |_, _| {
+                    this.update_profile_menu();
+                }
+            )
+        );
         win.bind_state();
         win.setup_signals();
 
@@ -997,6 +1082,10 @@ impl EuphonicaWindow {
         self.imp().recent_view.get()
     }
 
+    pub fn get_search_view(&self) -> SearchView {
+        self.imp().search_view.get()
+    }
+
     pub fn get_album_view(&self) -> AlbumView {
         self.imp().album_view.get()
     }
@@ -1017,11 +1106,61 @@ impl EuphonicaWindow {
         self.imp().queue_view.get()
     }
 
+    fn offer_resume(&self, player: &Player, queue_id: u32, position: f64) {
+        let toast = adw::Toast::builder()
+            .title(format!("Continue from {}?", format_secs_as_duration(position)))
+            .button_label("Resume")
+            .timeout(10)
+            .build();
+        toast.connect_button_clicked(clone!(
+            #[weak]
+            player,
+            move |_| {
+                player.resume_at(queue_id, position);
+            }
+        ));
+        self.imp().toast_overlay.add_toast(toast);
+    }
+
     pub fn send_simple_toast(&self, title: &str, timeout: u32) {
         let toast = adw::Toast::builder().title(title).timeout(timeout).build();
         self.imp().toast_overlay.add_toast(toast);
     }
 
+    /// Offer to undo an edit, or to redo one that has just been undone.
+    pub fn send_undo_toast(&self, title: &str, is_undone: bool) {
+        let (button_label, action_name) = if is_undone {
+            (gettext("Redo"), "app.redo")
+        } else {
+            (gettext("Undo"), "app.undo")
+        };
+        let existing = self.imp().undo_toast.borrow().clone();
+        if let Some(toast) = existing {
+            toast.set_title(title);
+            toast.set_button_label(Some(button_label.as_str()));
+            toast.set_action_name(Some(action_name));
+            // Re-adding a shown toast restarts its timeout.
+            self.imp().toast_overlay.add_toast(toast);
+            return;
+        }
+        let toast = adw::Toast::builder()
+            .title(title)
+            .use_markup(false)
+            .button_label(button_label)
+            .action_name(action_name)
+            .timeout(5)
+            .build();
+        toast.connect_dismissed(clone!(
+            #[weak(rename_to = this)]
+            self,
+            move |_| {
+                this.imp().undo_toast.take();
+            }
+        ));
+        self.imp().undo_toast.replace(Some(toast.clone()));
+        self.imp().toast_overlay.add_toast(toast);
+    }
+
     fn show_error_dialog(&self, heading: &str, body: &str, suggest_open_preferences: bool) {
         // Show an alert ONLY IF the preferences dialog is not already open.
         if !self.visible_dialog().is_some() {
@@ -1117,11 +1256,17 @@ impl EuphonicaWindow {
                 imp.should_populate_visible.set(false);
                 // Player clears itself
                 imp.recent_view.clear();
+                imp.search_view.clear();
+                imp.stats_view.clear();
                 imp.album_view.clear();
                 imp.artist_view.clear();
                 imp.folder_view.clear();
                 imp.queue_view.clear();
             }
+            ConnectionState::Reconnecting => {
+                // Keep current contents. They will be resynced once reconnected.
+                self.update_reconnecting_subtitle();
+            }
             ConnectionState::Connected => {
                 let imp = self.imp();
                 imp.title.set_subtitle("Connected");
@@ -1133,6 +1278,15 @@ impl EuphonicaWindow {
         }
     }
 
+    fn update_reconnecting_subtitle(&self) {
+        let attempt = self
+            .downcast_application()
+            .get_client()
+            .get_client_state()
+            .get_reconnect_attempt();
+        self.imp().title.set_subtitle(&format!("Reconnecting (attempt {attempt})"));
+    }
+
     pub fn maybe_populate_visible(&self) {
         let imp = self.imp();
         if imp.should_populate_visible.get() {
@@ -1141,6 +1295,12 @@ impl EuphonicaWindow {
                     "recent" => {
                         imp.recent_view.populate();
                     }
+                    "search" => {
+                        imp.search_view.populate();
+                    }
+                    "stats" => {
+                        imp.stats_view.populate();
+                    }
                     "albums" => {
                         imp.album_view.populate();
                     }
@@ -1215,6 +1375,15 @@ impl EuphonicaWindow {
         }
     }
 
+    pub fn goto_playlist(&self, playlist: &INode) {
+        // Switch first as the Saved Playlists button pops the playlist view
+        self.imp().sidebar.set_view("playlists");
+        self.imp().playlist_view.on_playlist_clicked(playlist);
+        if self.imp().split_view.shows_sidebar() {
+            self.imp().split_view.set_show_sidebar(!self.imp().split_view.is_collapsed());
+        }
+    }
+
     /// Set blurred background to a new image, if enabled. Use thumbnail version to
     /// minimise disk read time.
     fn queue_new_background(&self) {
@@ -1289,6 +1458,29 @@ impl EuphonicaWindow {
             .build();
     }
 
+    fn update_profile_menu(&self) {
+        // Each item targets the GSettings-backed app.mpd-active-profile action, so the
+        // active profile is shown as a checked radio item.
+        let profiles = profile::get_profiles();
+        let btn = self.imp().profile_btn.get();
+        btn.set_visible(!profiles.is_empty());
+        let profiles_section = gio::Menu::new();
+        for p in profiles.iter() {
+            let item = gio::MenuItem::new(Some(&p.name), None);
+            item.set_action_and_target_value(
+                Some("app.mpd-active-profile"),
+                Some(&p.name.to_variant())
+            );
+            profiles_section.append_item(&item);
+        }
+        let menu = gio::Menu::new();
+        menu.append_section(None, &profiles_section);
+        let manage_section = gio::Menu::new();
+        manage_section.append(Some("Manage profiles"), Some("app.preferences"));
+        menu.append_section(None, &manage_section);
+        btn.set_menu_model(Some(&menu));
+    }
+
     fn setup_signals(&self) {
         self.connect_close_request(move |window| {
             let size = window.default_size();